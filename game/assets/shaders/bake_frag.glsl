@@ -4,6 +4,7 @@ precision mediump float;
 
 in vec3 fragPosition;
 in vec2 fragTexCoord;
+in vec2 fragTexCoord1;
 in mat3 TBN;
 
 layout(location = 0) out mediump vec4 Position;
@@ -14,13 +15,31 @@ layout(location = 4) out mediump vec4 Emission;
 
 #define EPSILON 0.0001
 
-uniform struct Material {
+layout(std140) uniform CameraBlock {
+    mat4 projection;
+    mat4 view;
+    vec3 viewPos;
+} camera;
+
+#define MAX_MATERIAL_TEXTURES 10
+#define BASE_COLOR_MAP 0
+#define NORMAL_MAP 1
+#define METALLIC_ROUGHNESS_MAP 2
+#define OCCLUSION_MAP 3
+#define EMISSIVE_MAP 4
+#define CLEARCOAT_MAP 5
+#define SHEEN_MAP 6
+#define SPECULAR_MAP 7
+#define CLEARCOAT_NORMAL_MAP 8
+#define TRANSMISSION_MAP 9
+
+layout(std140) uniform MaterialBlock {
     vec4 baseColorFactor;
+    vec3 emissiveFactor;
     float normalScale;
     float metallicFactor;
     float roughnessFactor;
     float occlusionStrength;
-    vec3 emissiveFactor;
 
     bool hasBaseColorMap;
     bool hasNormalMap;
@@ -28,27 +47,68 @@ uniform struct Material {
     bool hasOcclusionMap;
     bool hasEmissiveMap;
 
-    sampler2D baseColorMap;
-    sampler2D normalMap;
-    sampler2D metallicRoughnessMap;
-    sampler2D occlusionMap;
-    sampler2D emissiveMap;
+    bool alphaMask;
+    float alphaCutoff;
+
+    // two rows of the KHR_texture_transform per map, the first row's w selects the UV set
+    vec4 uvTransforms[MAX_MATERIAL_TEXTURES * 2];
+    float emissiveStrength;
+    bool unlit;
+    float ior;
+    float transmissionFactor;
+    float clearcoatFactor;
+    float clearcoatRoughnessFactor;
+    float sheenRoughnessFactor;
+    vec3 sheenColorFactor;
+    float specularFactor;
+    vec3 specularColorFactor;
+
+    bool hasClearcoatMap;
+    bool hasClearcoatRoughnessMap;
+    bool hasSheenColorMap;
+    bool hasSheenRoughnessMap;
+    bool hasSpecularColorMap;
+    bool hasSpecularMap;
+
+    float clearcoatNormalScale;
+    bool hasClearcoatNormalMap;
+    bool hasTransmissionMap;
 } material;
 
-uniform vec3 viewPos;
+uniform sampler2D baseColorMap;
+uniform sampler2D normalMap;
+uniform sampler2D metallicRoughnessMap;
+uniform sampler2D occlusionMap;
+uniform sampler2D emissiveMap;
+
+vec2 TexCoord(int map)
+{
+    vec4 row0 = material.uvTransforms[map * 2];
+    vec4 row1 = material.uvTransforms[map * 2 + 1];
+    vec3 uv = vec3(row0.w > 0.5 ? fragTexCoord1 : fragTexCoord, 1.0);
+    return vec2(dot(row0.xyz, uv), dot(row1.xyz, uv));
+}
 
 void main()
 {
     vec3 albedo = material.baseColorFactor.rgb;
+    float alpha = material.baseColorFactor.a;
     if (material.hasBaseColorMap)
     {
-        albedo = albedo * texture(material.baseColorMap, fragTexCoord).rgb;
+        vec4 baseColor = texture(baseColorMap, TexCoord(BASE_COLOR_MAP));
+        albedo = albedo * baseColor.rgb;
+        alpha = alpha * baseColor.a;
+    }
+
+    if (material.alphaMask && alpha < material.alphaCutoff)
+    {
+        discard;
     }
 
     vec3 N;
     if (material.hasNormalMap)
     {
-        vec3 normal = texture(material.normalMap, fragTexCoord).rgb;
+        vec3 normal = texture(normalMap, TexCoord(NORMAL_MAP)).rgb;
         normal = normal * 2.0 - 1.0;
         N = normalize(mix(TBN * normal, TBN[2], 1.0 - material.normalScale) + EPSILON);
     }
@@ -56,12 +116,14 @@ void main()
     {
         N = TBN[2];
     }
+    // only double sided materials draw back faces
+    N = gl_FrontFacing ? N : -N;
 
     float metallic = material.metallicFactor;
     float roughness = material.roughnessFactor;
     if (material.hasMetallicRoughnessMap)
     {
-        vec2 metallicRoughness = texture(material.metallicRoughnessMap, fragTexCoord).gb;
+        vec2 metallicRoughness = texture(metallicRoughnessMap, TexCoord(METALLIC_ROUGHNESS_MAP)).gb;
 	    metallic = metallic * metallicRoughness.y;
 	    roughness = roughness * metallicRoughness.x;
     }
@@ -69,16 +131,25 @@ void main()
     float occlusion = 1.0;
     if (material.hasOcclusionMap)
     {
-        occlusion = mix(texture(material.occlusionMap, fragTexCoord).r, 1.0, 1.0 - material.occlusionStrength);
+        occlusion = mix(texture(occlusionMap, TexCoord(OCCLUSION_MAP)).r, 1.0, 1.0 - material.occlusionStrength);
     }
 
-	vec3 emission = vec3(0.0, 0.0, 0.0);
+	vec3 emission = material.emissiveFactor * material.emissiveStrength;
     if (material.hasEmissiveMap)
     {
-        emission = texture(material.emissiveMap, fragTexCoord).rgb * material.emissiveFactor;
+        emission *= texture(emissiveMap, TexCoord(EMISSIVE_MAP)).rgb;
+    }
+
+    // unlit surfaces only emit their base color, clearcoat, sheen, specular and transmission are forward only
+    if (material.unlit)
+    {
+        emission = albedo;
+        albedo = vec3(0.0);
+        metallic = 1.0;
+        roughness = 1.0;
     }
 
-    float d = distance(viewPos, fragPosition);
+    float d = distance(camera.viewPos, fragPosition);
 
     Position = vec4(fragPosition, d);
     BaseColor = vec4(albedo, 1.0);
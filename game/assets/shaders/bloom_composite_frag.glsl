@@ -0,0 +1,16 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 tex_coord;
+
+out vec4 FragColor;
+
+uniform sampler2D bloomMap;
+uniform float strength;
+
+void main()
+{
+    // blended as bloom * strength + scene * (1 - strength)
+    FragColor = vec4(texture(bloomMap, tex_coord).rgb * strength, strength);
+}
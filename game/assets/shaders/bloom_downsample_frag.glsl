@@ -0,0 +1,74 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 tex_coord;
+
+out vec4 FragColor;
+
+uniform sampler2D srcTexture;
+uniform bool karisAverage;
+
+// ----------------------------------------------------------------------------
+float Luminance(vec3 color)
+{
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+// ----------------------------------------------------------------------------
+float KarisWeight(vec3 color)
+{
+    return 1.0 / (1.0 + Luminance(color));
+}
+// ----------------------------------------------------------------------------
+// 13 tap downsample from Call of Duty: Advanced Warfare
+void main()
+{
+    vec2 texel = 1.0 / vec2(textureSize(srcTexture, 0));
+    float x = texel.x;
+    float y = texel.y;
+
+    // a - b - c
+    // - j - k -
+    // d - e - f
+    // - l - m -
+    // g - h - i
+    vec3 a = texture(srcTexture, tex_coord + vec2(-2.0 * x, 2.0 * y)).rgb;
+    vec3 b = texture(srcTexture, tex_coord + vec2(0.0, 2.0 * y)).rgb;
+    vec3 c = texture(srcTexture, tex_coord + vec2(2.0 * x, 2.0 * y)).rgb;
+    vec3 d = texture(srcTexture, tex_coord + vec2(-2.0 * x, 0.0)).rgb;
+    vec3 e = texture(srcTexture, tex_coord).rgb;
+    vec3 f = texture(srcTexture, tex_coord + vec2(2.0 * x, 0.0)).rgb;
+    vec3 g = texture(srcTexture, tex_coord + vec2(-2.0 * x, -2.0 * y)).rgb;
+    vec3 h = texture(srcTexture, tex_coord + vec2(0.0, -2.0 * y)).rgb;
+    vec3 i = texture(srcTexture, tex_coord + vec2(2.0 * x, -2.0 * y)).rgb;
+    vec3 j = texture(srcTexture, tex_coord + vec2(-x, y)).rgb;
+    vec3 k = texture(srcTexture, tex_coord + vec2(x, y)).rgb;
+    vec3 l = texture(srcTexture, tex_coord + vec2(-x, -y)).rgb;
+    vec3 m = texture(srcTexture, tex_coord + vec2(x, -y)).rgb;
+
+    vec3 color;
+    if (karisAverage)
+    {
+        // weigh the five 2x2 blocks by their inverse luminance to suppress fireflies
+        vec3 group0 = (a + b + d + e) * (0.125 / 4.0);
+        vec3 group1 = (b + c + e + f) * (0.125 / 4.0);
+        vec3 group2 = (d + e + g + h) * (0.125 / 4.0);
+        vec3 group3 = (e + f + h + i) * (0.125 / 4.0);
+        vec3 group4 = (j + k + l + m) * (0.5 / 4.0);
+        group0 *= KarisWeight(group0);
+        group1 *= KarisWeight(group1);
+        group2 *= KarisWeight(group2);
+        group3 *= KarisWeight(group3);
+        group4 *= KarisWeight(group4);
+        color = group0 + group1 + group2 + group3 + group4;
+    }
+    else
+    {
+        color = e * 0.125;
+        color += (a + c + g + i) * 0.03125;
+        color += (b + d + f + h) * 0.0625;
+        color += (j + k + l + m) * 0.125;
+    }
+
+    FragColor = vec4(max(color, 0.0001), 1.0);
+}
@@ -0,0 +1,34 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 tex_coord;
+
+out vec4 FragColor;
+
+uniform sampler2D srcTexture;
+uniform float filterRadius;
+
+// 3x3 tent filter, added onto the next larger mip by the blend state
+void main()
+{
+    float x = filterRadius;
+    float y = filterRadius;
+
+    vec3 a = texture(srcTexture, tex_coord + vec2(-x, y)).rgb;
+    vec3 b = texture(srcTexture, tex_coord + vec2(0.0, y)).rgb;
+    vec3 c = texture(srcTexture, tex_coord + vec2(x, y)).rgb;
+    vec3 d = texture(srcTexture, tex_coord + vec2(-x, 0.0)).rgb;
+    vec3 e = texture(srcTexture, tex_coord).rgb;
+    vec3 f = texture(srcTexture, tex_coord + vec2(x, 0.0)).rgb;
+    vec3 g = texture(srcTexture, tex_coord + vec2(-x, -y)).rgb;
+    vec3 h = texture(srcTexture, tex_coord + vec2(0.0, -y)).rgb;
+    vec3 i = texture(srcTexture, tex_coord + vec2(x, -y)).rgb;
+
+    vec3 color = e * 4.0;
+    color += (b + d + f + h) * 2.0;
+    color += (a + c + g + i);
+    color *= 1.0 / 16.0;
+
+    FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,23 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 tex_coord;
+
+out vec4 FragColor;
+
+uniform sampler2D tex;
+uniform sampler3D lut;
+uniform float lutSize;
+uniform float intensity;
+
+void main()
+{
+    vec3 color = clamp(texture(tex, tex_coord).rgb, 0.0, 1.0);
+
+    // sample texel centers so the outer texels map to 0 and 1
+    vec3 coords = color * ((lutSize - 1.0) / lutSize) + 0.5 / lutSize;
+    vec3 graded = texture(lut, coords).rgb;
+
+    FragColor = vec4(mix(color, graded, intensity), 1.0);
+}
@@ -0,0 +1,345 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 tex_coord;
+
+out mediump vec4 FragColor;
+
+layout(std140) uniform CameraBlock {
+    mat4 projection;
+    mat4 view;
+    vec3 viewPos;
+} camera;
+
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+#define MAX_SHADOW_CASCADES 4
+#define MAX_SPOT_SHADOWS 4
+
+// Must match deferred.rs
+#define LIGHT_TEXELS 5
+#define CLUSTER_COUNT_X 16
+#define CLUSTER_COUNT_Y 9
+#define CLUSTER_COUNT_Z 24
+#define CLUSTER_TEXTURE_WIDTH 1024
+
+struct Light {
+    vec3 position;
+    float range;
+    vec3 direction;
+    float intensity;
+    vec3 color;
+    int kind;
+    float angleScale;
+    float angleOffset;
+    int shadowIndex;
+    float shadowBias;
+    float shadowNormalBias;
+};
+
+layout(std140) uniform ShadowBlock {
+    mat4 cascadeMatrices[MAX_SHADOW_CASCADES];
+    vec4 cascadeSplits;
+    mat4 spotMatrices[MAX_SPOT_SHADOWS];
+    int cascadeCount;
+} shadows;
+
+uniform sampler2DArrayShadow cascadeShadowMap;
+uniform sampler2DArrayShadow spotShadowMap;
+
+// G-buffer written by bake_frag.glsl
+uniform sampler2D gPosition;
+uniform sampler2D gBaseColor;
+uniform sampler2D gNormal;
+uniform sampler2D gMRO;
+uniform sampler2D gEmission;
+uniform sampler2D gDepth;
+
+// one row of LIGHT_TEXELS texels per light
+uniform sampler2D lightData;
+// the cluster grid as (offset << 8 | count), followed by the light indices of every cluster
+uniform usampler2D clusterLights;
+uniform float clusterNear;
+uniform float clusterSliceScale;
+
+uniform samplerCube irradianceMap;
+uniform samplerCube prefilterMap;
+uniform sampler2D brdfLUT;
+uniform bool hasEnvironment;
+uniform float environmentIntensity;
+uniform float prefilterMaxLod;
+
+const float PI = 3.14159265359;
+// ----------------------------------------------------------------------------
+float DistributionGGX(vec3 N, vec3 H, float roughness)
+{
+    float a = roughness*roughness;
+    float a2 = a*a;
+    float NdotH = max(dot(N, H), 0.0);
+    float NdotH2 = NdotH*NdotH;
+
+    float nom   = a2;
+    float denom = (NdotH2 * (a2 - 1.0) + 1.0);
+    denom = PI * denom * denom;
+
+    return nom / denom;
+}
+// ----------------------------------------------------------------------------
+float GeometrySchlickGGX(float NdotV, float roughness)
+{
+    float r = (roughness + 1.0);
+    float k = (r*r) / 8.0;
+
+    float nom   = NdotV;
+    float denom = NdotV * (1.0 - k) + k;
+
+    return nom / denom;
+}
+// ----------------------------------------------------------------------------
+float GeometrySmith(vec3 N, vec3 V, vec3 L, float roughness)
+{
+    float NdotV = max(dot(N, V), 0.0);
+    float NdotL = max(dot(N, L), 0.0);
+    float ggx2 = GeometrySchlickGGX(NdotV, roughness);
+    float ggx1 = GeometrySchlickGGX(NdotL, roughness);
+
+    return ggx1 * ggx2;
+}
+// ----------------------------------------------------------------------------
+vec3 fresnelSchlick(float cosTheta, vec3 F0)
+{
+    return F0 + (1.0 - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+// ----------------------------------------------------------------------------
+vec3 fresnelSchlickRoughness(float cosTheta, vec3 F0, float roughness)
+{
+    return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+// ----------------------------------------------------------------------------
+// Attenuation as specified by KHR_lights_punctual, a range of 0 means infinite.
+float RangeAttenuation(float range, float distance)
+{
+    float attenuation = 1.0 / max(distance * distance, 0.0001);
+    if (range <= 0.0)
+    {
+        return attenuation;
+    }
+
+    return clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0) * attenuation;
+}
+// ----------------------------------------------------------------------------
+float SpotAttenuation(Light light, vec3 L)
+{
+    float cd = dot(light.direction, -L);
+    float attenuation = clamp(cd * light.angleScale + light.angleOffset, 0.0, 1.0);
+    return attenuation * attenuation;
+}
+// ----------------------------------------------------------------------------
+// 3x3 PCF over the hardware compared depth
+float SampleCascadeShadow(vec3 coords, int cascade)
+{
+    vec2 texelSize = 1.0 / vec2(textureSize(cascadeShadowMap, 0).xy);
+    float shadow = 0.0;
+    for (int x = -1; x <= 1; ++x)
+    {
+        for (int y = -1; y <= 1; ++y)
+        {
+            shadow += texture(cascadeShadowMap, vec4(coords.xy + vec2(x, y) * texelSize, float(cascade), coords.z));
+        }
+    }
+    return shadow / 9.0;
+}
+// ----------------------------------------------------------------------------
+float SampleSpotShadow(vec3 coords, int index)
+{
+    vec2 texelSize = 1.0 / vec2(textureSize(spotShadowMap, 0).xy);
+    float shadow = 0.0;
+    for (int x = -1; x <= 1; ++x)
+    {
+        for (int y = -1; y <= 1; ++y)
+        {
+            shadow += texture(spotShadowMap, vec4(coords.xy + vec2(x, y) * texelSize, float(index), coords.z));
+        }
+    }
+    return shadow / 9.0;
+}
+// ----------------------------------------------------------------------------
+float ShadowFactor(Light light, vec3 fragPosition, vec3 normal, vec3 L)
+{
+    if (light.shadowIndex < 0)
+    {
+        return 1.0;
+    }
+
+    // push the sample position out along the normal, more so at grazing angles
+    float slope = 1.0 - clamp(dot(normal, L), 0.0, 1.0);
+    vec3 position = fragPosition + normal * light.shadowNormalBias * slope;
+
+    mat4 lightViewProj;
+    int cascade = 0;
+    if (light.kind == LIGHT_DIRECTIONAL)
+    {
+        float viewDepth = -(camera.view * vec4(fragPosition, 1.0)).z;
+        if (viewDepth > shadows.cascadeSplits[shadows.cascadeCount - 1])
+        {
+            return 1.0;
+        }
+
+        while (cascade < shadows.cascadeCount - 1 && viewDepth > shadows.cascadeSplits[cascade])
+        {
+            cascade++;
+        }
+        lightViewProj = shadows.cascadeMatrices[cascade];
+    }
+    else
+    {
+        lightViewProj = shadows.spotMatrices[light.shadowIndex];
+    }
+
+    vec4 lightSpace = lightViewProj * vec4(position, 1.0);
+    vec3 coords = lightSpace.xyz / lightSpace.w * 0.5 + 0.5;
+    if (coords.z > 1.0 || any(lessThan(coords.xy, vec2(0.0))) || any(greaterThan(coords.xy, vec2(1.0))))
+    {
+        return 1.0;
+    }
+    coords.z -= light.shadowBias;
+
+    if (light.kind == LIGHT_DIRECTIONAL)
+    {
+        return SampleCascadeShadow(coords, cascade);
+    }
+    return SampleSpotShadow(coords, light.shadowIndex);
+}
+// ----------------------------------------------------------------------------
+Light FetchLight(int index)
+{
+    vec4 t0 = texelFetch(lightData, ivec2(0, index), 0);
+    vec4 t1 = texelFetch(lightData, ivec2(1, index), 0);
+    vec4 t2 = texelFetch(lightData, ivec2(2, index), 0);
+    vec4 t3 = texelFetch(lightData, ivec2(3, index), 0);
+    vec4 t4 = texelFetch(lightData, ivec2(4, index), 0);
+
+    Light light;
+    light.position = t0.xyz;
+    light.range = t0.w;
+    light.direction = t1.xyz;
+    light.intensity = t1.w;
+    light.color = t2.rgb;
+    light.kind = int(t2.w);
+    light.angleScale = t3.x;
+    light.angleOffset = t3.y;
+    light.shadowIndex = int(t3.z);
+    light.shadowBias = t3.w;
+    light.shadowNormalBias = t4.x;
+    return light;
+}
+// ----------------------------------------------------------------------------
+uint FetchClusterData(int index)
+{
+    return texelFetch(clusterLights, ivec2(index % CLUSTER_TEXTURE_WIDTH, index / CLUSTER_TEXTURE_WIDTH), 0).r;
+}
+// ----------------------------------------------------------------------------
+int ClusterIndex(vec3 fragPosition)
+{
+    float viewDepth = -(camera.view * vec4(fragPosition, 1.0)).z;
+    int x = clamp(int(tex_coord.x * CLUSTER_COUNT_X), 0, CLUSTER_COUNT_X - 1);
+    int y = clamp(int(tex_coord.y * CLUSTER_COUNT_Y), 0, CLUSTER_COUNT_Y - 1);
+    int z = clamp(int(log(max(viewDepth, clusterNear) / clusterNear) * clusterSliceScale), 0, CLUSTER_COUNT_Z - 1);
+    return x + (y + z * CLUSTER_COUNT_Y) * CLUSTER_COUNT_X;
+}
+// ----------------------------------------------------------------------------
+void main()
+{
+    // nothing was drawn here, keep the clear color
+    if (texture(gDepth, tex_coord).r >= 1.0)
+    {
+        discard;
+    }
+
+    vec3 fragPosition = texture(gPosition, tex_coord).xyz;
+    vec3 albedo = texture(gBaseColor, tex_coord).rgb;
+    vec3 N = normalize(texture(gNormal, tex_coord).xyz * 2.0 - 1.0);
+    vec3 mro = texture(gMRO, tex_coord).rgb;
+    vec3 emission = texture(gEmission, tex_coord).rgb;
+
+    float metallic = mro.r;
+    float roughness = mro.g;
+    float occlusion = mro.b;
+
+    vec3 V = normalize(camera.viewPos - fragPosition);
+    float ao = 0.1;
+
+    vec3 F0 = vec3(0.04);
+    F0 = mix(F0, albedo, metallic);
+
+    uint cluster = FetchClusterData(ClusterIndex(fragPosition));
+    int offset = CLUSTER_COUNT_X * CLUSTER_COUNT_Y * CLUSTER_COUNT_Z + int(cluster >> 8u);
+    int count = int(cluster & 255u);
+
+    vec3 Lo = vec3(0.0);
+    for (int i = 0; i < count; ++i)
+    {
+        Light light = FetchLight(int(FetchClusterData(offset + i)));
+
+        vec3 L;
+        float attenuation = 1.0;
+        if (light.kind == LIGHT_DIRECTIONAL)
+        {
+            L = -light.direction;
+        }
+        else
+        {
+            vec3 toLight = light.position - fragPosition;
+            L = normalize(toLight);
+            attenuation = RangeAttenuation(light.range, length(toLight));
+
+            if (light.kind == LIGHT_SPOT)
+            {
+                attenuation *= SpotAttenuation(light, L);
+            }
+        }
+
+        vec3 H = normalize(V + L);
+        // the G-buffer has no geometric normal, shadows are offset along the shading normal
+        vec3 radiance = light.color * light.intensity * attenuation * ShadowFactor(light, fragPosition, N, L);
+
+        // Cook-Torrance BRDF
+        float NDF = DistributionGGX(N, H, roughness);
+        float G   = GeometrySmith(N, V, L, roughness);
+        vec3 F    = fresnelSchlick(clamp(dot(H, V), 0.0, 1.0), F0);
+
+        vec3 numerator    = NDF * G * F;
+        float denominator = 4.0 * max(dot(N, V), 0.0) * max(dot(N, L), 0.0) + 0.0001;
+        vec3 specular = numerator / denominator;
+
+        vec3 kD = (vec3(1.0) - F) * (1.0 - metallic);
+
+        float NdotL = max(dot(N, L), 0.0);
+        Lo += (kD * albedo / PI + specular) * radiance * NdotL;
+    }
+
+    vec3 ambient = vec3(0.03) * albedo * ao;
+    if (hasEnvironment)
+    {
+        // split sum image based lighting
+        float NdotV = max(dot(N, V), 0.0);
+        vec3 F = fresnelSchlickRoughness(NdotV, F0, roughness);
+        vec3 kD = (1.0 - F) * (1.0 - metallic);
+
+        vec3 irradiance = texture(irradianceMap, N).rgb;
+        vec3 diffuse = irradiance * albedo;
+
+        vec3 R = reflect(-V, N);
+        vec3 prefilteredColor = textureLod(prefilterMap, R, roughness * prefilterMaxLod).rgb;
+        vec2 brdf = texture(brdfLUT, vec2(NdotV, roughness)).rg;
+        vec3 specular = prefilteredColor * (F * brdf.x + brdf.y);
+
+        ambient = (kD * diffuse + specular) * environmentIntensity;
+    }
+
+    vec3 color = (ambient + Lo) * occlusion + emission;
+
+    FragColor = vec4(color, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 faceCoord;
+
+out vec4 FragColor;
+
+uniform sampler2D equirectangularMap;
+uniform int face;
+
+// Direction through a texel of the bound cube face, following the GL cube map face layout
+vec3 FaceDirection(vec2 uv)
+{
+    if (face == 0) return normalize(vec3(1.0, -uv.y, -uv.x));
+    if (face == 1) return normalize(vec3(-1.0, -uv.y, uv.x));
+    if (face == 2) return normalize(vec3(uv.x, 1.0, uv.y));
+    if (face == 3) return normalize(vec3(uv.x, -1.0, -uv.y));
+    if (face == 4) return normalize(vec3(uv.x, -uv.y, 1.0));
+    return normalize(vec3(-uv.x, -uv.y, -1.0));
+}
+
+const float PI = 3.14159265359;
+
+void main()
+{
+    vec3 direction = FaceDirection(faceCoord);
+    // the image's first row is the top of the sky
+    vec2 uv = vec2(atan(direction.z, direction.x) / (2.0 * PI) + 0.5, 0.5 - asin(clamp(direction.y, -1.0, 1.0)) / PI);
+
+    FragColor = vec4(texture(equirectangularMap, uv).rgb, 1.0);
+}
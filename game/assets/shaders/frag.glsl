@@ -4,17 +4,36 @@ precision mediump float;
 
 in vec3 fragPosition;
 in vec2 fragTexCoord;
+in vec2 fragTexCoord1;
 in mat3 TBN;
 
 out mediump vec4 FragColor;
 
-uniform struct Material {
+layout(std140) uniform CameraBlock {
+    mat4 projection;
+    mat4 view;
+    vec3 viewPos;
+} camera;
+
+#define MAX_MATERIAL_TEXTURES 10
+#define BASE_COLOR_MAP 0
+#define NORMAL_MAP 1
+#define METALLIC_ROUGHNESS_MAP 2
+#define OCCLUSION_MAP 3
+#define EMISSIVE_MAP 4
+#define CLEARCOAT_MAP 5
+#define SHEEN_MAP 6
+#define SPECULAR_MAP 7
+#define CLEARCOAT_NORMAL_MAP 8
+#define TRANSMISSION_MAP 9
+
+layout(std140) uniform MaterialBlock {
     vec4 baseColorFactor;
+    vec3 emissiveFactor;
     float normalScale;
     float metallicFactor;
     float roughnessFactor;
     float occlusionStrength;
-    vec3 emissiveFactor;
 
     bool hasBaseColorMap;
     bool hasNormalMap;
@@ -22,14 +41,98 @@ uniform struct Material {
     bool hasOcclusionMap;
     bool hasEmissiveMap;
 
-    sampler2D baseColorMap;
-    sampler2D normalMap;
-    sampler2D metallicRoughnessMap;
-    sampler2D occlusionMap;
-    sampler2D emissiveMap;
+    bool alphaMask;
+    float alphaCutoff;
+
+    // two rows of the KHR_texture_transform per map, the first row's w selects the UV set
+    vec4 uvTransforms[MAX_MATERIAL_TEXTURES * 2];
+    float emissiveStrength;
+    bool unlit;
+    float ior;
+    float transmissionFactor;
+    float clearcoatFactor;
+    float clearcoatRoughnessFactor;
+    float sheenRoughnessFactor;
+    vec3 sheenColorFactor;
+    float specularFactor;
+    vec3 specularColorFactor;
+
+    bool hasClearcoatMap;
+    bool hasClearcoatRoughnessMap;
+    bool hasSheenColorMap;
+    bool hasSheenRoughnessMap;
+    bool hasSpecularColorMap;
+    bool hasSpecularMap;
+
+    float clearcoatNormalScale;
+    bool hasClearcoatNormalMap;
+    bool hasTransmissionMap;
 } material;
 
-uniform vec3 viewPos;
+#define MAX_LIGHTS 16
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+#define MAX_SHADOW_CASCADES 4
+#define MAX_SPOT_SHADOWS 4
+
+struct Light {
+    vec3 position;
+    float range;
+    vec3 direction;
+    float intensity;
+    vec3 color;
+    int kind;
+    float angleScale;
+    float angleOffset;
+    int shadowIndex;
+    float shadowBias;
+    float shadowNormalBias;
+};
+
+layout(std140) uniform LightBlock {
+    Light lights[MAX_LIGHTS];
+    int lightCount;
+} lighting;
+
+layout(std140) uniform ShadowBlock {
+    mat4 cascadeMatrices[MAX_SHADOW_CASCADES];
+    vec4 cascadeSplits;
+    mat4 spotMatrices[MAX_SPOT_SHADOWS];
+    int cascadeCount;
+} shadows;
+
+uniform sampler2DArrayShadow cascadeShadowMap;
+uniform sampler2DArrayShadow spotShadowMap;
+
+uniform sampler2D baseColorMap;
+uniform sampler2D normalMap;
+uniform sampler2D metallicRoughnessMap;
+uniform sampler2D occlusionMap;
+uniform sampler2D emissiveMap;
+// R clearcoat, G clearcoat roughness
+uniform sampler2D clearcoatMap;
+// RGB sheen color, A sheen roughness
+uniform sampler2D sheenMap;
+// RGB specular color, A specular
+uniform sampler2D specularMap;
+uniform sampler2D clearcoatNormalMap;
+uniform sampler2D transmissionMap;
+
+vec2 TexCoord(int map)
+{
+    vec4 row0 = material.uvTransforms[map * 2];
+    vec4 row1 = material.uvTransforms[map * 2 + 1];
+    vec3 uv = vec3(row0.w > 0.5 ? fragTexCoord1 : fragTexCoord, 1.0);
+    return vec2(dot(row0.xyz, uv), dot(row1.xyz, uv));
+}
+
+uniform samplerCube irradianceMap;
+uniform samplerCube prefilterMap;
+uniform sampler2D brdfLUT;
+uniform bool hasEnvironment;
+uniform float environmentIntensity;
+uniform float prefilterMaxLod;
 
 const float PI = 3.14159265359;
 // ----------------------------------------------------------------------------
@@ -73,31 +176,170 @@ vec3 fresnelSchlick(float cosTheta, vec3 F0)
     return F0 + (1.0 - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
 }
 // ----------------------------------------------------------------------------
+vec3 fresnelSchlickRoughness(float cosTheta, vec3 F0, float roughness)
+{
+    return F0 + (max(vec3(1.0 - roughness), F0) - F0) * pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+// ----------------------------------------------------------------------------
+// Charlie sheen distribution
+float DistributionCharlie(vec3 N, vec3 H, float roughness)
+{
+    float alpha = max(roughness * roughness, 0.0001);
+    float invAlpha = 1.0 / alpha;
+    float NdotH = max(dot(N, H), 0.0);
+    float sin2h = max(1.0 - NdotH * NdotH, 0.0078125);
+
+    return (2.0 + invAlpha) * pow(sin2h, invAlpha * 0.5) / (2.0 * PI);
+}
+// ----------------------------------------------------------------------------
+// Neubelt sheen visibility
+float VisibilityNeubelt(float NdotV, float NdotL)
+{
+    return 1.0 / (4.0 * (NdotL + NdotV - NdotL * NdotV) + 0.0001);
+}
+// ----------------------------------------------------------------------------
+// Attenuation as specified by KHR_lights_punctual, a range of 0 means infinite.
+float RangeAttenuation(float range, float distance)
+{
+    float attenuation = 1.0 / max(distance * distance, 0.0001);
+    if (range <= 0.0)
+    {
+        return attenuation;
+    }
+
+    return clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0) * attenuation;
+}
+// ----------------------------------------------------------------------------
+float SpotAttenuation(Light light, vec3 L)
+{
+    float cd = dot(light.direction, -L);
+    float attenuation = clamp(cd * light.angleScale + light.angleOffset, 0.0, 1.0);
+    return attenuation * attenuation;
+}
+// ----------------------------------------------------------------------------
+// 3x3 PCF over the hardware compared depth
+float SampleCascadeShadow(vec3 coords, int cascade)
+{
+    vec2 texelSize = 1.0 / vec2(textureSize(cascadeShadowMap, 0).xy);
+    float shadow = 0.0;
+    for (int x = -1; x <= 1; ++x)
+    {
+        for (int y = -1; y <= 1; ++y)
+        {
+            shadow += texture(cascadeShadowMap, vec4(coords.xy + vec2(x, y) * texelSize, float(cascade), coords.z));
+        }
+    }
+    return shadow / 9.0;
+}
+// ----------------------------------------------------------------------------
+float SampleSpotShadow(vec3 coords, int index)
+{
+    vec2 texelSize = 1.0 / vec2(textureSize(spotShadowMap, 0).xy);
+    float shadow = 0.0;
+    for (int x = -1; x <= 1; ++x)
+    {
+        for (int y = -1; y <= 1; ++y)
+        {
+            shadow += texture(spotShadowMap, vec4(coords.xy + vec2(x, y) * texelSize, float(index), coords.z));
+        }
+    }
+    return shadow / 9.0;
+}
+// ----------------------------------------------------------------------------
+float ShadowFactor(Light light, vec3 normal, vec3 L)
+{
+    if (light.shadowIndex < 0)
+    {
+        return 1.0;
+    }
+
+    // push the sample position out along the normal, more so at grazing angles
+    float slope = 1.0 - clamp(dot(normal, L), 0.0, 1.0);
+    vec3 position = fragPosition + normal * light.shadowNormalBias * slope;
+
+    mat4 lightViewProj;
+    int cascade = 0;
+    if (light.kind == LIGHT_DIRECTIONAL)
+    {
+        float viewDepth = -(camera.view * vec4(fragPosition, 1.0)).z;
+        if (viewDepth > shadows.cascadeSplits[shadows.cascadeCount - 1])
+        {
+            return 1.0;
+        }
+
+        while (cascade < shadows.cascadeCount - 1 && viewDepth > shadows.cascadeSplits[cascade])
+        {
+            cascade++;
+        }
+        lightViewProj = shadows.cascadeMatrices[cascade];
+    }
+    else
+    {
+        lightViewProj = shadows.spotMatrices[light.shadowIndex];
+    }
+
+    vec4 lightSpace = lightViewProj * vec4(position, 1.0);
+    vec3 coords = lightSpace.xyz / lightSpace.w * 0.5 + 0.5;
+    if (coords.z > 1.0 || any(lessThan(coords.xy, vec2(0.0))) || any(greaterThan(coords.xy, vec2(1.0))))
+    {
+        return 1.0;
+    }
+    coords.z -= light.shadowBias;
+
+    if (light.kind == LIGHT_DIRECTIONAL)
+    {
+        return SampleCascadeShadow(coords, cascade);
+    }
+    return SampleSpotShadow(coords, light.shadowIndex);
+}
+// ----------------------------------------------------------------------------
 void main()
 {
     vec3 albedo = material.baseColorFactor.rgb;
+    float alpha = material.baseColorFactor.a;
     if (material.hasBaseColorMap)
     {
-        albedo = albedo * texture(material.baseColorMap, fragTexCoord).rgb;
+        vec4 baseColor = texture(baseColorMap, TexCoord(BASE_COLOR_MAP));
+        albedo = albedo * baseColor.rgb;
+        alpha = alpha * baseColor.a;
+    }
+
+    if (material.alphaMask)
+    {
+        if (alpha < material.alphaCutoff)
+        {
+            discard;
+        }
+        alpha = 1.0;
+    }
+
+    if (material.unlit)
+    {
+        FragColor = vec4(albedo, alpha);
+        return;
     }
 
+    // only double sided materials draw back faces
+    vec3 faceNormal = gl_FrontFacing ? TBN[2] : -TBN[2];
+
     vec3 N;
     if (material.hasNormalMap)
     {
-        vec3 normal = texture(material.normalMap, fragTexCoord).rgb;
+        vec3 normal = texture(normalMap, TexCoord(NORMAL_MAP)).rgb;
         normal = normal * 2.0 - 1.0;
         N = normalize(mix(TBN * normal, TBN[2], 1.0 - material.normalScale));
+        N = gl_FrontFacing ? N : -N;
     }
     else
     {
-        N = TBN[2];
+        N = faceNormal;
     }
 
     float metallic = material.metallicFactor;
     float roughness = material.roughnessFactor;
     if (material.hasMetallicRoughnessMap)
     {
-        vec2 metallicRoughness = texture(material.metallicRoughnessMap, fragTexCoord).gb;
+        vec2 metallicRoughness = texture(metallicRoughnessMap, TexCoord(METALLIC_ROUGHNESS_MAP)).gb;
 	    metallic = metallic * metallicRoughness.y;
 	    roughness = roughness * metallicRoughness.x;
     }
@@ -105,31 +347,96 @@ void main()
     float occlusion = 1.0;
     if (material.hasOcclusionMap)
     {
-        occlusion = mix(texture(material.occlusionMap, fragTexCoord).r, 1.0, 1.0 - material.occlusionStrength);
+        occlusion = mix(texture(occlusionMap, TexCoord(OCCLUSION_MAP)).r, 1.0, 1.0 - material.occlusionStrength);
     }
 
-	vec3 emission = vec3(0.0, 0.0, 0.0);
+	vec3 emission = material.emissiveFactor * material.emissiveStrength;
     if (material.hasEmissiveMap)
     {
-        emission = texture(material.emissiveMap, fragTexCoord).rgb * material.emissiveFactor;
+        emission *= texture(emissiveMap, TexCoord(EMISSIVE_MAP)).rgb;
+    }
+
+    float clearcoat = material.clearcoatFactor;
+    float clearcoatRoughness = material.clearcoatRoughnessFactor;
+    if (material.hasClearcoatMap || material.hasClearcoatRoughnessMap)
+    {
+        vec2 clearcoatSample = texture(clearcoatMap, TexCoord(CLEARCOAT_MAP)).rg;
+        clearcoat *= material.hasClearcoatMap ? clearcoatSample.r : 1.0;
+        clearcoatRoughness *= material.hasClearcoatRoughnessMap ? clearcoatSample.g : 1.0;
+    }
+    vec3 clearcoatN = normalize(faceNormal);
+    if (material.hasClearcoatNormalMap)
+    {
+        vec3 clearcoatNormal = texture(clearcoatNormalMap, TexCoord(CLEARCOAT_NORMAL_MAP)).rgb * 2.0 - 1.0;
+        clearcoatNormal = normalize(TBN * (clearcoatNormal * vec3(material.clearcoatNormalScale, material.clearcoatNormalScale, 1.0)));
+        clearcoatN = gl_FrontFacing ? clearcoatNormal : -clearcoatNormal;
     }
 
-    vec3 V = normalize(viewPos - fragPosition);
+    vec3 sheenColor = material.sheenColorFactor;
+    float sheenRoughness = material.sheenRoughnessFactor;
+    if (material.hasSheenColorMap || material.hasSheenRoughnessMap)
+    {
+        vec4 sheenSample = texture(sheenMap, TexCoord(SHEEN_MAP));
+        sheenColor *= material.hasSheenColorMap ? sheenSample.rgb : vec3(1.0);
+        sheenRoughness *= material.hasSheenRoughnessMap ? sheenSample.a : 1.0;
+    }
+    // the base is darkened by the sheen's albedo, approximated by its strongest channel
+    float sheenScaling = 1.0 - max(sheenColor.r, max(sheenColor.g, sheenColor.b)) * 0.25;
+
+    vec3 specularColor = material.specularColorFactor;
+    float specularWeight = material.specularFactor;
+    if (material.hasSpecularColorMap || material.hasSpecularMap)
+    {
+        vec4 specularSample = texture(specularMap, TexCoord(SPECULAR_MAP));
+        specularColor *= material.hasSpecularColorMap ? specularSample.rgb : vec3(1.0);
+        specularWeight *= material.hasSpecularMap ? specularSample.a : 1.0;
+    }
+
+    float transmission = material.transmissionFactor;
+    if (material.hasTransmissionMap)
+    {
+        transmission *= texture(transmissionMap, TexCoord(TRANSMISSION_MAP)).r;
+    }
+
+    vec3 V = normalize(camera.viewPos - fragPosition);
 	float ao = 0.1;
 
     // calculate reflectance at normal incidence; if dia-electric (like plastic) use F0 
-    // of 0.04 and if it's a metal, use the albedo color as F0 (metallic workflow)    
-    vec3 F0 = vec3(0.04); 
+    // from the index of refraction and if it's a metal, use the albedo color as F0 (metallic workflow)    
+    float dielectricF0 = pow((material.ior - 1.0) / (material.ior + 1.0), 2.0);
+    vec3 F0 = min(vec3(dielectricF0) * specularColor, vec3(1.0)) * specularWeight;
     F0 = mix(F0, albedo, metallic);
 
+    float NcdotV = max(dot(clearcoatN, V), 0.0);
+    float clearcoatFresnel = clearcoat * fresnelSchlick(NcdotV, vec3(0.04)).r;
+
     // reflectance equation
     vec3 Lo = vec3(0.0);
-    for(int i = 0; i < 1; ++i) 
+    for(int i = 0; i < lighting.lightCount; ++i) 
     {
+        Light light = lighting.lights[i];
+
         // calculate per-light radiance
-        vec3 L = normalize(-vec3(0.1, -1.0, 0.0));
+        vec3 L;
+        float attenuation = 1.0;
+        if (light.kind == LIGHT_DIRECTIONAL)
+        {
+            L = -light.direction;
+        }
+        else
+        {
+            vec3 toLight = light.position - fragPosition;
+            L = normalize(toLight);
+            attenuation = RangeAttenuation(light.range, length(toLight));
+
+            if (light.kind == LIGHT_SPOT)
+            {
+                attenuation *= SpotAttenuation(light, L);
+            }
+        }
+
         vec3 H = normalize(V + L);
-        vec3 radiance = vec3(1.0, 1.0, 1.0) * 1.1;
+        vec3 radiance = light.color * light.intensity * attenuation * ShadowFactor(light, normalize(faceNormal), L);
 
         // Cook-Torrance BRDF
         float NDF = DistributionGGX(N, H, roughness);   
@@ -150,24 +457,56 @@ void main()
         // have diffuse lighting, or a linear blend if partly metal (pure metals
         // have no diffuse light).
         kD *= 1.0 - metallic;	  
+        // transmitted light leaves through the back instead of scattering diffusely
+        kD *= 1.0 - transmission;
 
         // scale light by NdotL
         float NdotL = max(dot(N, L), 0.0);        
 
+        // note that we already multiplied the BRDF by the Fresnel (kS) so we won't multiply by kS again
+        vec3 base = (kD * albedo / PI + specular) * sheenScaling;
+        base += sheenColor * DistributionCharlie(N, H, sheenRoughness) * VisibilityNeubelt(max(dot(N, V), 0.0), NdotL);
+
+        // clearcoat is a second dielectric specular lobe on top of the base
+        float NcdotL = max(dot(clearcoatN, L), 0.0);
+        float clearcoatSpecular = DistributionGGX(clearcoatN, H, clearcoatRoughness) * GeometrySmith(clearcoatN, V, L, clearcoatRoughness) /
+                                  (4.0 * NcdotV * NcdotL + 0.0001);
+        vec3 coat = clearcoat * fresnelSchlick(clamp(dot(H, V), 0.0, 1.0), vec3(0.04)) * clearcoatSpecular * NcdotL;
+
         // add to outgoing radiance Lo
-        Lo += (kD * albedo / PI + specular) * radiance * NdotL;  // note that we already multiplied the BRDF by the Fresnel (kS) so we won't multiply by kS again
+        Lo += (base * NdotL * (1.0 - clearcoatFresnel) + coat) * radiance;
     }   
     
-    // ambient lighting (note that the next IBL tutorial will replace 
-    // this ambient lighting with environment lighting).
     vec3 ambient = vec3(0.03) * albedo * ao;
+    if (hasEnvironment)
+    {
+        // split sum image based lighting
+        float NdotV = max(dot(N, V), 0.0);
+        vec3 F = fresnelSchlickRoughness(NdotV, F0, roughness);
+        vec3 kD = (1.0 - F) * (1.0 - metallic) * (1.0 - transmission);
 
-    vec3 color = (ambient + Lo) * occlusion + emission;
+        vec3 irradiance = texture(irradianceMap, N).rgb;
+        vec3 diffuse = irradiance * albedo;
 
-    // HDR tonemapping
-    color = color / (color + vec3(1.0));
-    // gamma correct
-    color = pow(color, vec3(1.0/2.2)); 
+        vec3 R = reflect(-V, N);
+        vec3 prefilteredColor = textureLod(prefilterMap, R, roughness * prefilterMaxLod).rgb;
+        vec2 brdf = texture(brdfLUT, vec2(NdotV, roughness)).rg;
+        vec3 specular = prefilteredColor * (F * brdf.x + brdf.y);
+
+        // thin walled transmission, the environment behind the surface refracted through it
+        vec3 T = refract(-V, N, 1.0 / material.ior);
+        vec3 transmitted = textureLod(prefilterMap, T, roughness * prefilterMaxLod).rgb * albedo * (1.0 - F) * (1.0 - metallic) * transmission;
+
+        vec3 Rc = reflect(-V, clearcoatN);
+        vec2 clearcoatBrdf = texture(brdfLUT, vec2(NcdotV, clearcoatRoughness)).rg;
+        vec3 coat = textureLod(prefilterMap, Rc, clearcoatRoughness * prefilterMaxLod).rgb * clearcoat * (0.04 * clearcoatBrdf.x + clearcoatBrdf.y);
+
+        // sheen only responds to punctual lights
+        ambient = ((kD * diffuse + specular) * sheenScaling + transmitted) * (1.0 - clearcoatFresnel) + coat;
+        ambient *= environmentIntensity;
+    }
+
+    vec3 color = (ambient + Lo) * occlusion + emission;
 
-    FragColor = vec4(color, 1.0);
+    FragColor = vec4(color, alpha);
 }
\ No newline at end of file
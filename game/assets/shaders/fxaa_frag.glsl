@@ -0,0 +1,61 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 tex_coord;
+
+out vec4 FragColor;
+
+uniform sampler2D tex;
+uniform float spanMax;
+uniform float reduceMin;
+uniform float reduceMul;
+
+// Source: https://github.com/mattdesl/glsl-fxaa
+void main()
+{
+    vec2 inverseResolution = 1.0 / vec2(textureSize(tex, 0));
+    vec3 luma = vec3(0.299, 0.587, 0.114);
+
+    vec3 rgbNW = texture(tex, tex_coord + vec2(-1.0, -1.0) * inverseResolution).rgb;
+    vec3 rgbNE = texture(tex, tex_coord + vec2(1.0, -1.0) * inverseResolution).rgb;
+    vec3 rgbSW = texture(tex, tex_coord + vec2(-1.0, 1.0) * inverseResolution).rgb;
+    vec3 rgbSE = texture(tex, tex_coord + vec2(1.0, 1.0) * inverseResolution).rgb;
+    vec4 rgbaM = texture(tex, tex_coord);
+    vec3 rgbM = rgbaM.rgb;
+
+    float lumaNW = dot(rgbNW, luma);
+    float lumaNE = dot(rgbNE, luma);
+    float lumaSW = dot(rgbSW, luma);
+    float lumaSE = dot(rgbSE, luma);
+    float lumaM = dot(rgbM, luma);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    // blur along the edge, perpendicular to the luma gradient
+    vec2 dir;
+    dir.x = -((lumaNW + lumaNE) - (lumaSW + lumaSE));
+    dir.y = ((lumaNW + lumaSW) - (lumaNE + lumaSE));
+
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * (0.25 * reduceMul), reduceMin);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = min(vec2(spanMax), max(vec2(-spanMax), dir * rcpDirMin)) * inverseResolution;
+
+    vec3 rgbA = 0.5 * (
+        texture(tex, tex_coord + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture(tex, tex_coord + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (
+        texture(tex, tex_coord + dir * -0.5).rgb +
+        texture(tex, tex_coord + dir * 0.5).rgb);
+
+    // fall back to the narrower blur when the wide one picked up a different surface
+    float lumaB = dot(rgbB, luma);
+    if ((lumaB < lumaMin) || (lumaB > lumaMax))
+    {
+        FragColor = vec4(rgbA, rgbaM.a);
+    }
+    else
+    {
+        FragColor = vec4(rgbB, rgbaM.a);
+    }
+}
@@ -0,0 +1,10 @@
+#version 330 core
+
+precision mediump float;
+
+out vec4 FragColor;
+
+void main()
+{
+    FragColor = vec4(1.0);
+}
@@ -0,0 +1,25 @@
+#version 330 core
+
+precision mediump float;
+
+uniform sampler2D hdrColor;
+// samples are taken on a sampleSize x sampleSize grid
+uniform int sampleSize;
+uniform int binCount;
+uniform float minLogLuminance;
+uniform float logLuminanceRange;
+
+void main()
+{
+    ivec2 cell = ivec2(gl_VertexID % sampleSize, gl_VertexID / sampleSize);
+    vec2 uv = (vec2(cell) + 0.5) / float(sampleSize);
+
+    vec3 color = textureLod(hdrColor, uv, 0.0).rgb;
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+
+    // black pixels go into the first bin
+    float t = luminance < 0.00001 ? 0.0 : clamp((log2(luminance) - minLogLuminance) / logLuminanceRange, 0.0, 1.0);
+    float bin = min(floor(t * float(binCount)), float(binCount - 1));
+
+    gl_Position = vec4((bin + 0.5) / float(binCount) * 2.0 - 1.0, 0.0, 0.0, 1.0);
+}
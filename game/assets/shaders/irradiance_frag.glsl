@@ -0,0 +1,50 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 faceCoord;
+
+out vec4 FragColor;
+
+uniform samplerCube environmentMap;
+uniform int face;
+
+// Direction through a texel of the bound cube face, following the GL cube map face layout
+vec3 FaceDirection(vec2 uv)
+{
+    if (face == 0) return normalize(vec3(1.0, -uv.y, -uv.x));
+    if (face == 1) return normalize(vec3(-1.0, -uv.y, uv.x));
+    if (face == 2) return normalize(vec3(uv.x, 1.0, uv.y));
+    if (face == 3) return normalize(vec3(uv.x, -1.0, -uv.y));
+    if (face == 4) return normalize(vec3(uv.x, -uv.y, 1.0));
+    return normalize(vec3(-uv.x, -uv.y, -1.0));
+}
+
+const float PI = 3.14159265359;
+
+// Cosine weighted convolution of the environment over the hemisphere around N
+void main()
+{
+    vec3 N = FaceDirection(faceCoord);
+    vec3 up = abs(N.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, N));
+    up = normalize(cross(N, right));
+
+    vec3 irradiance = vec3(0.0);
+    float sampleDelta = 0.025;
+    float sampleCount = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += sampleDelta)
+    {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += sampleDelta)
+        {
+            vec3 tangentSample = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 sampleVec = tangentSample.x * right + tangentSample.y * up + tangentSample.z * N;
+
+            // sample a lower mip, the integral is smooth anyway
+            irradiance += textureLod(environmentMap, sampleVec, 2.0).rgb * cos(theta) * sin(theta);
+            sampleCount++;
+        }
+    }
+
+    FragColor = vec4(PI * irradiance / sampleCount, 1.0);
+}
@@ -0,0 +1,59 @@
+#version 330 core
+
+in vec2 fragTexCoord;
+in vec2 fragTexCoord1;
+
+#define MAX_MATERIAL_TEXTURES 10
+#define BASE_COLOR_MAP 0
+
+// The leading members of the forward pass MaterialBlock, only bound for alpha tested casters.
+layout(std140) uniform MaterialBlock {
+    vec4 baseColorFactor;
+    vec3 emissiveFactor;
+    float normalScale;
+    float metallicFactor;
+    float roughnessFactor;
+    float occlusionStrength;
+
+    bool hasBaseColorMap;
+    bool hasNormalMap;
+    bool hasMetallicRoughnessMap;
+    bool hasOcclusionMap;
+    bool hasEmissiveMap;
+
+    bool alphaMask;
+    float alphaCutoff;
+
+    // two rows of the KHR_texture_transform per map, the first row's w selects the UV set
+    vec4 uvTransforms[MAX_MATERIAL_TEXTURES * 2];
+} material;
+
+uniform sampler2D baseColorMap;
+
+// Set for masked and blended casters, both discard below the alpha cutoff.
+uniform bool alphaTest;
+
+vec2 TexCoord(int map)
+{
+    vec4 row0 = material.uvTransforms[map * 2];
+    vec4 row1 = material.uvTransforms[map * 2 + 1];
+    vec3 uv = vec3(row0.w > 0.5 ? fragTexCoord1 : fragTexCoord, 1.0);
+    return vec2(dot(row0.xyz, uv), dot(row1.xyz, uv));
+}
+
+void main()
+{
+    if (alphaTest)
+    {
+        float alpha = material.baseColorFactor.a;
+        if (material.hasBaseColorMap)
+        {
+            alpha *= texture(baseColorMap, TexCoord(BASE_COLOR_MAP)).a;
+        }
+
+        if (alpha < material.alphaCutoff)
+        {
+            discard;
+        }
+    }
+}
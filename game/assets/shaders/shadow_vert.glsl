@@ -0,0 +1,62 @@
+#version 330 core
+
+in layout(location = 0) vec3 positions;
+in layout(location = 2) vec2 texCoords;
+in layout(location = 4) mat4 model;
+in layout(location = 8) vec4 jointIndices;
+in layout(location = 9) vec4 jointWeights;
+in layout(location = 10) vec2 texCoords1;
+
+out vec2 fragTexCoord;
+out vec2 fragTexCoord1;
+
+uniform mat4 lightViewProj;
+
+#define MAX_JOINTS 128
+
+layout(std140) uniform JointBlock {
+    mat4 joints[MAX_JOINTS];
+    bool skinned;
+} skin;
+
+#define MAX_MORPH_TARGETS 8
+#define MORPH_TEXTURE_WIDTH 1024
+
+layout(std140) uniform MorphBlock {
+    vec4 weights[MAX_MORPH_TARGETS / 4];
+    int targetCount;
+    int vertexCount;
+} morph;
+
+// Texel (target * 3 + attribute) * vertexCount + vertex holds a position, normal or tangent displacement.
+uniform sampler2D morphTargets;
+
+vec3 MorphDisplacement(int attribute)
+{
+	vec3 displacement = vec3(0.0);
+	for (int target = 0; target < morph.targetCount; target++)
+	{
+		int texel = (target * 3 + attribute) * morph.vertexCount + gl_VertexID;
+		vec3 targetDisplacement = texelFetch(morphTargets, ivec2(texel % MORPH_TEXTURE_WIDTH, texel / MORPH_TEXTURE_WIDTH), 0).xyz;
+		displacement += morph.weights[target / 4][target % 4] * targetDisplacement;
+	}
+	return displacement;
+}
+
+void main()
+{
+	vec3 position = positions + MorphDisplacement(0);
+
+	mat4 world = model;
+	if (skin.skinned)
+	{
+		world = model * (jointWeights.x * skin.joints[int(jointIndices.x)] +
+		                 jointWeights.y * skin.joints[int(jointIndices.y)] +
+		                 jointWeights.z * skin.joints[int(jointIndices.z)] +
+		                 jointWeights.w * skin.joints[int(jointIndices.w)]);
+	}
+
+	fragTexCoord = texCoords;
+	fragTexCoord1 = texCoords1;
+	gl_Position = lightViewProj * world * vec4(position, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 330 core
+
+precision mediump float;
+
+in vec3 viewDirection;
+
+out vec4 FragColor;
+
+uniform samplerCube environmentMap;
+uniform float intensity;
+
+void main()
+{
+    vec3 color = textureLod(environmentMap, normalize(viewDirection), 0.0).rgb * intensity;
+
+    FragColor = vec4(color, 1.0);
+}
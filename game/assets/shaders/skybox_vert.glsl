@@ -0,0 +1,21 @@
+#version 330 core
+
+precision mediump float;
+
+out vec3 viewDirection;
+
+layout(std140) uniform CameraBlock {
+    mat4 projection;
+    mat4 view;
+    vec3 viewPos;
+} camera;
+
+void main()
+{
+    // fullscreen triangle on the far plane
+    vec2 vertices[3] = vec2[3](vec2(-1, -1), vec2(3, -1), vec2(-1, 3));
+    gl_Position = vec4(vertices[gl_VertexID], 1, 1);
+
+    vec4 viewSpace = inverse(camera.projection) * gl_Position;
+    viewDirection = mat3(transpose(camera.view)) * (viewSpace.xyz / viewSpace.w);
+}
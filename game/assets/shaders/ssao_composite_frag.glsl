@@ -0,0 +1,28 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 tex_coord;
+
+out vec4 FragColor;
+
+uniform sampler2D ssaoMap;
+uniform float intensity;
+
+void main()
+{
+    // 4x4 box blur matching the noise tile size
+    vec2 texelSize = 1.0 / vec2(textureSize(ssaoMap, 0));
+    float ao = 0.0;
+    for (int x = -2; x < 2; ++x)
+    {
+        for (int y = -2; y < 2; ++y)
+        {
+            ao += texture(ssaoMap, tex_coord + vec2(float(x) + 0.5, float(y) + 0.5) * texelSize).r;
+        }
+    }
+    ao /= 16.0;
+
+    // multiplied with the scene color by the blend state
+    FragColor = vec4(vec3(mix(1.0, ao, intensity)), 1.0);
+}
@@ -0,0 +1,64 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 tex_coord;
+
+out float FragColor;
+
+#define KERNEL_SIZE 32
+#define NOISE_SIZE 4.0
+
+uniform sampler2D depthMap;
+uniform sampler2D noiseMap;
+uniform vec3 kernel[KERNEL_SIZE];
+uniform mat4 projection;
+uniform mat4 inverseProjection;
+uniform float radius;
+uniform float bias;
+uniform float power;
+
+// ----------------------------------------------------------------------------
+vec3 ViewPosition(vec2 uv)
+{
+    float depth = texture(depthMap, uv).r;
+    vec4 position = inverseProjection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    return position.xyz / position.w;
+}
+// ----------------------------------------------------------------------------
+void main()
+{
+    if (texture(depthMap, tex_coord).r >= 1.0)
+    {
+        FragColor = 1.0;
+        return;
+    }
+
+    vec3 position = ViewPosition(tex_coord);
+    // reconstructed from depth, faces the camera
+    vec3 normal = normalize(cross(dFdx(position), dFdy(position)));
+
+    vec2 noiseScale = vec2(textureSize(depthMap, 0)) / NOISE_SIZE;
+    vec3 randomVec = normalize(texture(noiseMap, tex_coord * noiseScale).xyz);
+
+    // Gram-Schmidt process to create a randomly rotated tangent space
+    vec3 tangent = normalize(randomVec - normal * dot(randomVec, normal));
+    vec3 bitangent = cross(normal, tangent);
+    mat3 TBN = mat3(tangent, bitangent, normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < KERNEL_SIZE; ++i)
+    {
+        vec3 samplePosition = position + TBN * kernel[i] * radius;
+
+        vec4 offset = projection * vec4(samplePosition, 1.0);
+        offset.xy = offset.xy / offset.w * 0.5 + 0.5;
+
+        float sampleDepth = ViewPosition(offset.xy).z;
+        // fade out occluders far outside the radius
+        float rangeCheck = smoothstep(0.0, 1.0, radius / abs(position.z - sampleDepth));
+        occlusion += (sampleDepth >= samplePosition.z + bias ? 1.0 : 0.0) * rangeCheck;
+    }
+
+    FragColor = pow(1.0 - occlusion / float(KERNEL_SIZE), power);
+}
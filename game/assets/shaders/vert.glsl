@@ -6,26 +6,78 @@ in layout(location = 0) vec3 positions;
 in layout(location = 1) vec3 normals;
 in layout(location = 2) vec2 texCoords;
 in layout(location = 3) vec4 tangents;
+in layout(location = 4) mat4 model;
+in layout(location = 8) vec4 jointIndices;
+in layout(location = 9) vec4 jointWeights;
+in layout(location = 10) vec2 texCoords1;
 
 out vec3 fragPosition;
 out vec2 fragTexCoord;
+out vec2 fragTexCoord1;
 out mat3 TBN;
 
 #define EPSILON 0.0001
 
-uniform mat4 model;
-uniform mat4 view;
-uniform mat4 projection;
+layout(std140) uniform CameraBlock {
+    mat4 projection;
+    mat4 view;
+    vec3 viewPos;
+} camera;
+
+#define MAX_JOINTS 128
+
+layout(std140) uniform JointBlock {
+    mat4 joints[MAX_JOINTS];
+    bool skinned;
+} skin;
+
+#define MAX_MORPH_TARGETS 8
+#define MORPH_TEXTURE_WIDTH 1024
+
+layout(std140) uniform MorphBlock {
+    vec4 weights[MAX_MORPH_TARGETS / 4];
+    int targetCount;
+    int vertexCount;
+} morph;
+
+// Texel (target * 3 + attribute) * vertexCount + vertex holds a position, normal or tangent displacement.
+uniform sampler2D morphTargets;
+
+vec3 MorphDisplacement(int attribute)
+{
+	vec3 displacement = vec3(0.0);
+	for (int target = 0; target < morph.targetCount; target++)
+	{
+		int texel = (target * 3 + attribute) * morph.vertexCount + gl_VertexID;
+		vec3 targetDisplacement = texelFetch(morphTargets, ivec2(texel % MORPH_TEXTURE_WIDTH, texel / MORPH_TEXTURE_WIDTH), 0).xyz;
+		displacement += morph.weights[target / 4][target % 4] * targetDisplacement;
+	}
+	return displacement;
+}
 
 void main()
 {
-	fragPosition = vec3(model * vec4(positions, 1.0));
+	vec3 position = positions + MorphDisplacement(0);
+	vec3 normal = normals + MorphDisplacement(1);
+	vec3 tangent = tangents.xyz + MorphDisplacement(2);
+
+	mat4 world = model;
+	if (skin.skinned)
+	{
+		world = model * (jointWeights.x * skin.joints[int(jointIndices.x)] +
+		                 jointWeights.y * skin.joints[int(jointIndices.y)] +
+		                 jointWeights.z * skin.joints[int(jointIndices.z)] +
+		                 jointWeights.w * skin.joints[int(jointIndices.w)]);
+	}
+
+	fragPosition = vec3(world * vec4(position, 1.0));
 	fragTexCoord = texCoords;
+	fragTexCoord1 = texCoords1;
 
-	vec3 N = normalize((model * vec4(normals, 0.0)) + EPSILON).xyz;
-	vec3 T = normalize((model * vec4(tangents.xyz, 0.0)) + EPSILON).xyz;
+	vec3 N = normalize((world * vec4(normal, 0.0)) + EPSILON).xyz;
+	vec3 T = normalize((world * vec4(tangent, 0.0)) + EPSILON).xyz;
 	vec3 B = normalize(cross(T, N) + EPSILON) * tangents.w;
 	TBN = mat3(T, B, N);
 
-	gl_Position = projection * view * vec4(fragPosition, 1.0);
+	gl_Position = camera.projection * camera.view * vec4(fragPosition, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,24 @@
+#version 330 core
+
+precision mediump float;
+
+in vec2 tex_coord;
+
+out vec4 FragColor;
+
+uniform sampler2D tex;
+uniform float intensity;
+uniform float smoothness;
+uniform float roundness;
+uniform float aspectRatio;
+
+void main()
+{
+    vec3 color = texture(tex, tex_coord).rgb;
+
+    vec2 offset = tex_coord - 0.5;
+    offset.x *= mix(1.0, aspectRatio, roundness);
+    float vignette = 1.0 - smoothstep(0.5 - smoothness * 0.5, 0.5 + smoothness * 0.5, length(offset) * 1.4142);
+
+    FragColor = vec4(color * mix(1.0, vignette, intensity), 1.0);
+}
@@ -4,6 +4,7 @@ extern crate little_bits;
 use little_bits::*;
 
 use std::env;
+use std::path::Path;
 
 fn main() {
     env::set_var("RUST_BACKTRACE", "1");
@@ -13,7 +14,8 @@ fn main() {
 struct Example {
     model: Shared<Model>,
     instance: Shared<ModelInstance>,
-    camera: Shared<Camera>
+    camera: Shared<Camera>,
+    sun: Shared<Light>
 }
 
 impl Game for Example {
@@ -21,7 +23,8 @@ impl Game for Example {
         Box::new(Example {
             model: Shared::empty(),
             instance: Shared::empty(),
-            camera: Shared::empty()
+            camera: Shared::empty(),
+            sun: Shared::empty()
         })
     }
 
@@ -36,17 +39,24 @@ impl Game for Example {
         app().graphics().set_render_camera(self.camera.clone());
         self.camera.as_mut().set_translation(Float3::new(-0.54, 0.0, 0.54));
 
+        self.sun = app().graphics().create_light(LightKind::Directional);
+        {
+            let mut sun = self.sun.as_mut();
+            sun.direction = Float3::new(0.1, -1.0, 0.0).normalized();
+            sun.intensity = 1.1;
+            sun.shadow = Some(ShadowSettings::default());
+        }
+
         self.model = app().resources().get_model(String::from("assets/test_models/DamagedHelmet/glTF/DamagedHelmet.gltf"));
         self.instance = app().graphics().create_dynamic_model_instance(self.model.clone(), None);
 
-        let rotation = Quaternion::from(Float3::new(-90.0, 0.0, 0.0));
         let transform = &mut self.instance.as_mut().transform;
-        transform.set_rotation(rotation);
         transform.set_translation(Float3::new(0.0, 0.0, -1.0));
     }
     
     fn update(&mut self, delta_time: f32) {
-        let rotation = Quaternion::from(Float3::new(-90.0, app().time() * 30.0, 0.0));
+        // The upright orientation comes from the glTF node transform.
+        let rotation = Quaternion::from(Float3::new(0.0, app().time() * 30.0, 0.0));
         self.instance.as_mut().transform.set_rotation(rotation);
 
         // Camera Controller
@@ -80,15 +90,27 @@ impl Game for Example {
         ui.window("PBR Shader")
         .size([400.0, 700.0], imgui::Condition::FirstUseEver)
         .build(|| {
-            let material = &mut self.model.as_mut().materials[0];
-            let mut material = material.as_mut();
-
-            ui.color_picker4("Base Color", &mut material.base_color_factor);
-            ui.slider("Normal Scale", 0.0, 1.0, &mut material.normal_scale);
-            ui.slider("Metallic Factor", 0.0, 1.0, &mut material.metallic_factor);
-            ui.slider("Roughness Factor", 0.0, 1.0, &mut material.roughness_factor);
-            ui.slider("Occlusion Strength", 0.0, 1.0, &mut material.occlusion_strength);
-            ui.color_picker3("Emissive Factor", &mut material.emissive_factor);
+            let mut changed = false;
+            {
+                let material = &mut self.model.as_mut().materials[0];
+                let mut material = material.as_mut();
+
+                changed |= ui.color_picker4("Base Color", &mut material.base_color_factor);
+                changed |= ui.slider("Normal Scale", 0.0, 1.0, &mut material.normal_scale);
+                changed |= ui.slider("Metallic Factor", 0.0, 1.0, &mut material.metallic_factor);
+                changed |= ui.slider("Roughness Factor", 0.0, 1.0, &mut material.roughness_factor);
+                changed |= ui.slider("Occlusion Strength", 0.0, 1.0, &mut material.occlusion_strength);
+                changed |= ui.color_picker3("Emissive Factor", &mut material.emissive_factor);
+            }
+            if changed {
+                app().graphics().update_materials(&self.model);
+            }
+
+            if ui.button("Save Model") {
+                if let Err(error) = write_gltf(Path::new("assets/saved_model.glb"), &self.model.as_ref(), Some(ModelExportSettings::SceneHierarchy)) {
+                    eprintln!("Failed to save model. ({})", error);
+                }
+            }
         });
     }
     
@@ -26,6 +26,14 @@ pub struct GLRBO {
     buffer: GLBuffer
 }
 
+pub struct GLPBO {
+    buffer: GLBuffer
+}
+
+pub struct GLFence {
+    sync: GLsync
+}
+
 /*****************************************************************************
 *                               FUNCS
 ******************************************************************************/
@@ -37,6 +45,13 @@ pub fn gl_vertex_attrib_ptr(index: u32, size: usize, stride: usize, ptr: *const
     }
 }
 
+pub fn gl_vertex_attrib_divisor(index: u32, divisor: u32) {
+    unsafe {
+        gl::VertexAttribDivisor(index, divisor);
+        gl_check();
+    }
+}
+
 pub fn gl_enable_vertex_attrib_array(index: u32) {
     unsafe {
         gl::EnableVertexAttribArray(index);
@@ -46,7 +61,28 @@ pub fn gl_enable_vertex_attrib_array(index: u32) {
 
 pub fn gl_frame_buffer_texture_2d(texture: &GLTexture, attachment: GLenum) {
     unsafe {
-        gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, texture.handle(), 0);
+        gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, texture.target(), texture.handle(), 0);
+        gl_check();
+    }
+}
+
+pub fn gl_frame_buffer_texture_face(texture: &GLTexture, attachment: GLenum, face: u32, level: u32) {
+    unsafe {
+        gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl_cube_face_target(face), texture.handle(), level as i32);
+        gl_check();
+    }
+}
+
+pub fn gl_frame_buffer_texture_layer(texture: &GLTexture, attachment: GLenum, layer: i32, level: u32) {
+    unsafe {
+        gl::FramebufferTextureLayer(gl::FRAMEBUFFER, attachment, texture.handle(), level as i32, layer);
+        gl_check();
+    }
+}
+
+pub fn gl_frame_buffer_texture_layered(texture: &GLTexture, attachment: GLenum, level: u32) {
+    unsafe {
+        gl::FramebufferTexture(gl::FRAMEBUFFER, attachment, texture.handle(), level as i32);
         gl_check();
     }
 }
@@ -58,6 +94,13 @@ pub fn gl_render_buffer_storage(format: GLenum, width: i32, height: i32) {
     }
 }
 
+pub fn gl_render_buffer_storage_multisample(samples: u32, format: GLenum, width: i32, height: i32) {
+    unsafe {
+        gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples as i32, format, width, height);
+        gl_check();
+    }
+}
+
 pub fn gl_frame_buffer_render_buffer(rbo: &GLRBO, attachment: GLenum) {
     unsafe {
         gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, rbo.handle());
@@ -65,6 +108,13 @@ pub fn gl_frame_buffer_render_buffer(rbo: &GLRBO, attachment: GLenum) {
     }
 }
 
+pub fn gl_frame_buffer_detach_render_buffer(attachment: GLenum) {
+    unsafe {
+        gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, 0);
+        gl_check();
+    }
+}
+
 pub fn gl_draw_buffers(count: usize, buffers: *const GLenum) {
     unsafe {
         gl::DrawBuffers(count as i32, buffers);
@@ -72,6 +122,24 @@ pub fn gl_draw_buffers(count: usize, buffers: *const GLenum) {
     }
 }
 
+pub fn gl_bind_default_frame_buffer() {
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl_check();
+    }
+}
+
+// A None frame buffer refers to the default (window) frame buffer.
+pub fn gl_blit_frame_buffer(src: Option<&GLFBO>, dst: Option<&GLFBO>, src_dimensions: Int2, dst_dimensions: Int2, mask: GLenum, filter: GLenum) {
+    unsafe {
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, src.map_or(0, |fbo| fbo.handle()));
+        gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, dst.map_or(0, |fbo| fbo.handle()));
+        gl::BlitFramebuffer(0, 0, src_dimensions.x, src_dimensions.y, 0, 0, dst_dimensions.x, dst_dimensions.y, mask, filter);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl_check();
+    }
+}
+
 /*****************************************************************************
 *                               IMPLEMENTATION
 ******************************************************************************/
@@ -148,6 +216,16 @@ impl IGLBuffer for GLVBO {
     }
 }
 
+impl GLVBO {
+    // For data that is rewritten every frame, such as per-instance attributes.
+    pub fn set_stream_data(&self, size: usize, data: *const c_void) {
+        unsafe {
+            gl::BufferData(gl::ARRAY_BUFFER, size as isize, data, gl::STREAM_DRAW);
+            gl_check();
+        }
+    }
+}
+
 impl Drop for GLVBO {
     fn drop(&mut self) {
         gl_del_buffer(self.buffer);
@@ -216,6 +294,10 @@ impl IGLBuffer for GLFBO {
 }
 
 impl GLFBO {
+    pub fn handle(&self) -> GLBuffer {
+        self.buffer
+    }
+
     pub fn check_status(&self) {
         unsafe {
             assert_eq!(gl::CheckFramebufferStatus(gl::FRAMEBUFFER), gl::FRAMEBUFFER_COMPLETE, "Failed to bind frame buffer.");
@@ -267,6 +349,94 @@ impl Drop for GLRBO {
     }
 }
 
+impl IGLBuffer for GLPBO {
+    fn new() -> Self {
+        GLPBO {
+            buffer: gl_gen_buffer()
+        }
+    }
+
+    fn bind(&self) {
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.buffer);
+            gl_check();
+        }
+    }
+
+    fn unbind(&self) {
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            gl_check();
+        }
+    }
+
+    fn set_data(&self, size: usize, data: *mut c_void) {
+        unsafe {
+            gl::BufferData(gl::PIXEL_PACK_BUFFER, size as isize, data, gl::STREAM_READ);
+            gl_check();
+        }
+    }
+}
+
+impl GLPBO {
+    // Copies the bound pack buffer into memory, the PBO must be bound.
+    pub fn read(&self, size: usize, data: *mut c_void) {
+        unsafe {
+            let mapped = gl::MapBufferRange(gl::PIXEL_PACK_BUFFER, 0, size as isize, gl::MAP_READ_BIT);
+            gl_check();
+            assert!(!mapped.is_null(), "Failed to map pixel buffer.");
+
+            std::ptr::copy_nonoverlapping(mapped as *const u8, data as *mut u8, size);
+
+            gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            gl_check();
+        }
+    }
+}
+
+impl Drop for GLPBO {
+    fn drop(&mut self) {
+        gl_del_buffer(self.buffer);
+    }
+}
+
+impl GLFence {
+    pub fn new() -> Self {
+        unsafe {
+            let sync = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            gl_check();
+
+            GLFence {
+                sync: sync
+            }
+        }
+    }
+
+    pub fn is_signaled(&self) -> bool {
+        unsafe {
+            let result = gl::ClientWaitSync(self.sync, gl::SYNC_FLUSH_COMMANDS_BIT, 0);
+            gl_check();
+            result == gl::ALREADY_SIGNALED || result == gl::CONDITION_SATISFIED
+        }
+    }
+
+    pub fn wait(&self) {
+        unsafe {
+            gl::ClientWaitSync(self.sync, gl::SYNC_FLUSH_COMMANDS_BIT, u64::MAX);
+            gl_check();
+        }
+    }
+}
+
+impl Drop for GLFence {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSync(self.sync);
+            gl_check();
+        }
+    }
+}
+
 /*****************************************************************************
 *                               HELPERS
 ******************************************************************************/
@@ -0,0 +1,272 @@
+use crate::*;
+
+/*****************************************************************************
+*                               STRUCTS
+******************************************************************************/
+
+pub struct GLTextureCube {
+    tex: GLTexture,
+    size: i32,
+    desc: GLTextureDesc
+}
+
+pub struct GLTexture2DArray {
+    tex: GLTexture,
+    width: i32,
+    height: i32,
+    layers: i32,
+    desc: GLTextureDesc
+}
+
+pub struct GLTexture3D {
+    tex: GLTexture,
+    width: i32,
+    height: i32,
+    depth: i32,
+    desc: GLTextureDesc
+}
+
+/*****************************************************************************
+*                               FUNCS
+******************************************************************************/
+
+pub fn gl_tex_image_3d_format(target: GLenum, level: i32, format: GLTextureFormat, width: i32, height: i32, depth: i32, data: *const c_void) {
+    gl_pixel_store_unpack_alignment(format.unpack_alignment(width));
+    unsafe {
+        gl::TexImage3D(target, level, format.internal_format() as i32, width, height, depth, 0, format.format(), format.data_type(), data);
+        gl_check();
+    }
+    gl_pixel_store_unpack_alignment(4);
+}
+
+pub fn gl_tex_sub_image_3d_format(target: GLenum, level: i32, format: GLTextureFormat, offset: Int3, width: i32, height: i32, depth: i32, data: *const c_void) {
+    gl_pixel_store_unpack_alignment(format.unpack_alignment(width));
+    unsafe {
+        gl::TexSubImage3D(target, level, offset.x, offset.y, offset.z, width, height, depth, format.format(), format.data_type(), data);
+        gl_check();
+    }
+    gl_pixel_store_unpack_alignment(4);
+}
+
+pub fn gl_cube_face_target(face: u32) -> GLenum {
+    assert!(face < 6, "Failed to get cube face. (Face must be in 0..6)");
+    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face
+}
+
+pub fn gl_enable_seamless_cubemaps() {
+    unsafe {
+        gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+        gl_check();
+    }
+}
+
+fn mip_dimension(dimension: i32, level: u32) -> i32 {
+    (dimension >> level).max(1)
+}
+
+/*****************************************************************************
+*                               IMPLEMENTATION
+******************************************************************************/
+
+impl GLTextureCube {
+    pub fn new(size: i32, desc: &GLTextureDesc) -> Self {
+        let texture = GLTextureCube {
+            tex: GLTexture::new(gl::TEXTURE_CUBE_MAP),
+            size: size,
+            desc: *desc
+        };
+
+        let mut desc = *desc;
+        desc.sampler.wrap_s = GLWrapMode::ClampToEdge;
+        desc.sampler.wrap_t = GLWrapMode::ClampToEdge;
+        desc.sampler.wrap_r = GLWrapMode::ClampToEdge;
+
+        texture.tex.bind(); {
+            texture.tex.apply_desc(&desc, size, size);
+
+            for level in 0..texture.mip_levels() {
+                let level_size = mip_dimension(size, level);
+                for face in 0..6 {
+                    gl_tex_image_2d_format(gl_cube_face_target(face), level as i32, desc.format, level_size, level_size, std::ptr::null());
+                }
+            }
+        } texture.tex.unbind();
+
+        texture
+    }
+
+    pub fn set_face(&self, face: u32, level: u32, data: *const c_void) {
+        let level_size = mip_dimension(self.size, level);
+
+        self.tex.bind(); {
+            gl_tex_image_2d_format(gl_cube_face_target(face), level as i32, self.desc.format, level_size, level_size, data);
+        } self.tex.unbind();
+    }
+
+    pub fn gen_mips(&self) {
+        self.tex.bind(); {
+            gl_gen_mips(gl::TEXTURE_CUBE_MAP);
+        } self.tex.unbind();
+    }
+
+    pub fn bind(&self, slot: u32) {
+        gl_active_texture(slot);
+        self.tex.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.tex.unbind();
+    }
+
+    pub fn tex(&self) -> &GLTexture {
+        &self.tex
+    }
+
+    pub fn size(&self) -> i32 {
+        self.size
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.desc.mip_level_count(self.size, self.size)
+    }
+
+    pub fn desc(&self) -> &GLTextureDesc {
+        &self.desc
+    }
+}
+
+impl GLTexture2DArray {
+    pub fn new(width: i32, height: i32, layers: i32, desc: &GLTextureDesc) -> Self {
+        let texture = GLTexture2DArray {
+            tex: GLTexture::new(gl::TEXTURE_2D_ARRAY),
+            width: width,
+            height: height,
+            layers: layers,
+            desc: *desc
+        };
+
+        texture.tex.bind(); {
+            texture.tex.apply_desc(desc, width, height);
+
+            for level in 0..texture.mip_levels() {
+                gl_tex_image_3d_format(gl::TEXTURE_2D_ARRAY, level as i32, desc.format, mip_dimension(width, level), mip_dimension(height, level), layers, std::ptr::null());
+            }
+        } texture.tex.unbind();
+
+        texture
+    }
+
+    pub fn set_layer(&self, layer: i32, level: u32, data: *const c_void) {
+        assert!(layer < self.layers, "Failed to set texture array layer. (Layer out of range)");
+
+        self.tex.bind(); {
+            gl_tex_sub_image_3d_format(gl::TEXTURE_2D_ARRAY, level as i32, self.desc.format, Int3::new(0, 0, layer), mip_dimension(self.width, level), mip_dimension(self.height, level), 1, data);
+        } self.tex.unbind();
+    }
+
+    pub fn gen_mips(&self) {
+        self.tex.bind(); {
+            gl_gen_mips(gl::TEXTURE_2D_ARRAY);
+        } self.tex.unbind();
+    }
+
+    pub fn bind(&self, slot: u32) {
+        gl_active_texture(slot);
+        self.tex.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.tex.unbind();
+    }
+
+    pub fn tex(&self) -> &GLTexture {
+        &self.tex
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn layers(&self) -> i32 {
+        self.layers
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.desc.mip_level_count(self.width, self.height)
+    }
+
+    pub fn desc(&self) -> &GLTextureDesc {
+        &self.desc
+    }
+}
+
+impl GLTexture3D {
+    pub fn new(width: i32, height: i32, depth: i32, desc: &GLTextureDesc) -> Self {
+        let texture = GLTexture3D {
+            tex: GLTexture::new(gl::TEXTURE_3D),
+            width: width,
+            height: height,
+            depth: depth,
+            desc: *desc
+        };
+
+        texture.tex.bind(); {
+            texture.tex.apply_desc(desc, width.max(depth), height);
+
+            for level in 0..texture.mip_levels() {
+                gl_tex_image_3d_format(gl::TEXTURE_3D, level as i32, desc.format, mip_dimension(width, level), mip_dimension(height, level), mip_dimension(depth, level), std::ptr::null());
+            }
+        } texture.tex.unbind();
+
+        texture
+    }
+
+    pub fn set_data(&self, level: u32, data: *const c_void) {
+        self.tex.bind(); {
+            gl_tex_sub_image_3d_format(gl::TEXTURE_3D, level as i32, self.desc.format, Int3::new(0, 0, 0), mip_dimension(self.width, level), mip_dimension(self.height, level), mip_dimension(self.depth, level), data);
+        } self.tex.unbind();
+    }
+
+    pub fn gen_mips(&self) {
+        self.tex.bind(); {
+            gl_gen_mips(gl::TEXTURE_3D);
+        } self.tex.unbind();
+    }
+
+    pub fn bind(&self, slot: u32) {
+        gl_active_texture(slot);
+        self.tex.bind();
+    }
+
+    pub fn unbind(&self) {
+        self.tex.unbind();
+    }
+
+    pub fn tex(&self) -> &GLTexture {
+        &self.tex
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn depth(&self) -> i32 {
+        self.depth
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.desc.mip_level_count(self.width.max(self.depth), self.height)
+    }
+
+    pub fn desc(&self) -> &GLTextureDesc {
+        &self.desc
+    }
+}
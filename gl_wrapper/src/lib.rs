@@ -12,6 +12,10 @@ pub mod shaders;
 pub use shaders::*;
 pub mod textures;
 pub use textures::*;
+pub mod layered_textures;
+pub use layered_textures::*;
+pub mod uniform_buffers;
+pub use uniform_buffers::*;
 pub mod imgui_impl;
 pub use imgui_impl::*;
 
@@ -43,6 +47,42 @@ pub fn gl_enable_depth() {
     }
 }
 
+pub fn gl_disable_depth() {
+    unsafe {
+        gl::Disable(gl::DEPTH_TEST);
+        gl_check();
+    }
+}
+
+pub fn gl_enable_blend(src: GLenum, dst: GLenum) {
+    unsafe {
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(src, dst);
+        gl_check();
+    }
+}
+
+pub fn gl_disable_blend() {
+    unsafe {
+        gl::Disable(gl::BLEND);
+        gl_check();
+    }
+}
+
+pub fn gl_depth_mask(write: bool) {
+    unsafe {
+        gl::DepthMask(if write { gl::TRUE } else { gl::FALSE });
+        gl_check();
+    }
+}
+
+pub fn gl_depth_func(func: GLenum) {
+    unsafe {
+        gl::DepthFunc(func);
+        gl_check();
+    }
+}
+
 pub fn gl_cull(mode: GLenum) {
     unsafe {
         gl::Enable(gl::CULL_FACE);
@@ -51,6 +91,13 @@ pub fn gl_cull(mode: GLenum) {
     }
 }
 
+pub fn gl_disable_cull() {
+    unsafe {
+        gl::Disable(gl::CULL_FACE);
+        gl_check();
+    }
+}
+
 pub fn gl_clear_color(color: Float3) {
     unsafe {
         gl::ClearColor(color.x, color.y, color.z, 1.0f32);
@@ -79,6 +126,13 @@ pub fn gl_draw_elems(mode: GLenum, count: usize, index_type: GLenum) {
     }
 }
 
+pub fn gl_draw_elems_instanced(mode: GLenum, count: usize, index_type: GLenum, instance_count: usize) {
+    unsafe {
+        gl::DrawElementsInstanced(mode, count as i32, index_type, std::ptr::null(), instance_count as i32);
+        gl_check();
+    }
+}
+
 pub fn gl_draw_arrays(mode: GLenum, offset: usize, count: usize) {
     unsafe {
         gl::DrawArrays(mode, offset as i32, count as i32);
@@ -86,6 +140,22 @@ pub fn gl_draw_arrays(mode: GLenum, offset: usize, count: usize) {
     }
 }
 
+pub fn gl_read_buffer(mode: GLenum) {
+    unsafe {
+        gl::ReadBuffer(mode);
+        gl_check();
+    }
+}
+
+pub fn gl_read_pixels(offset: Int2, dimensions: Int2, format: GLenum, data_type: GLenum, data: *mut c_void) {
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(offset.x, offset.y, dimensions.x, dimensions.y, format, data_type, data);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        gl_check();
+    }
+}
+
 pub fn gl_finish() {
     unsafe {
         gl::Finish();
@@ -23,14 +23,77 @@ pub enum GLShaderType {
 pub struct GLShaderProgram {
     buffer: GLShaderProgramBuffer,
     uniform_locations: HashMap<String, i32>,
-    uniforms: Option<Vec<GLUniform>>
+    uniforms: Vec<GLUniform>,
+    attributes: Vec<GLAttribute>,
+    uniform_blocks: Vec<GLUniformBlock>
 }
 
-#[derive(Clone)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum GLUniformType {
+    Float,
+    Float2,
+    Float3,
+    Float4,
+    Int,
+    Int2,
+    Int3,
+    Int4,
+    UInt,
+    UInt2,
+    UInt3,
+    UInt4,
+    Bool,
+    Bool2,
+    Bool3,
+    Bool4,
+    Float2x2,
+    Float3x3,
+    Float4x4,
+    Sampler2D,
+    Sampler3D,
+    SamplerCube,
+    Sampler2DArray,
+    Sampler2DShadow,
+    Sampler2DArrayShadow,
+    SamplerCubeShadow,
+    Other(GLenum)
+}
+
+#[derive(Clone, Debug)]
 pub struct GLUniform {
     pub name: String,
-    pub category: u32,
-    pub size: i32
+    pub category: GLUniformType,
+    pub size: i32,
+    pub location: i32,
+    pub block_index: Option<u32>,
+    pub block_offset: Option<usize>,
+    pub array_stride: usize,
+    pub matrix_stride: usize
+}
+
+#[derive(Clone, Debug)]
+pub struct GLAttribute {
+    pub name: String,
+    pub category: GLUniformType,
+    pub size: i32,
+    pub location: i32
+}
+
+#[derive(Clone, Debug)]
+pub struct GLUniformBlock {
+    pub name: String,
+    pub index: u32,
+    pub binding: u32,
+    pub data_size: usize,
+    pub members: Vec<GLUniform>
+}
+
+#[derive(Clone, Debug)]
+pub struct GLSamplerBinding {
+    pub name: String,
+    pub category: GLUniformType,
+    pub location: i32,
+    pub slot: i32
 }
 
 /*****************************************************************************
@@ -65,16 +128,19 @@ impl Drop for GLShader {
 
 impl GLShaderProgram {
     pub fn new(vertex_shader: &GLShader, fragment_shader: &GLShader) -> GLShaderProgram {
-        let program = GLShaderProgram {
+        let mut program = GLShaderProgram {
             buffer: gl_create_program(),
             uniform_locations: HashMap::new(),
-            uniforms: None
+            uniforms: Vec::new(),
+            attributes: Vec::new(),
+            uniform_blocks: Vec::new()
         };
 
         vertex_shader.attach(&program);
         fragment_shader.attach(&program);
 
         gl_link_program(program.buffer);
+        program.reflect();
 
         program
     }
@@ -91,45 +157,162 @@ impl GLShaderProgram {
         gl_use_program(0);
     }
 
-    pub fn uniforms(&mut self) -> Vec<GLUniform> {
-        match &self.uniforms {
-            Some(uniforms) => uniforms.clone(),
-            None => {
-                let mut count: i32 = 0;
-                unsafe {
-                    gl::GetProgramiv(self.buffer, gl::ACTIVE_ATTRIBUTES, &mut count as *mut i32);
-                    gl_check();
-                }
+    pub fn uniforms(&self) -> &Vec<GLUniform> {
+        &self.uniforms
+    }
 
-                let mut uniforms: Vec<GLUniform> = Vec::new();
+    pub fn attributes(&self) -> &Vec<GLAttribute> {
+        &self.attributes
+    }
 
-                for i in 0..count {
-                    let mut name_data: [u8; 1024] = [0; 1024];
-                    let mut name_length: i32 = 0;
+    pub fn uniform_blocks(&self) -> &Vec<GLUniformBlock> {
+        &self.uniform_blocks
+    }
 
-                    let mut uniform_size: i32 = 0;
-                    let mut uniform_type: u32 = 0;
+    pub fn uniform_block(&self, name: &str) -> Option<&GLUniformBlock> {
+        self.uniform_blocks.iter().find(|block| block.name == name)
+    }
 
-                    unsafe {
-                        gl::GetActiveUniform(self.buffer, i as u32, 1024, (&mut name_length) as *mut i32, (&mut uniform_size) as *mut i32, (&mut uniform_type) as *mut u32, name_data.as_mut_ptr() as *mut c_char);
-                        gl_check();
-                    }
+    pub fn sampler_bindings(&self) -> Vec<GLSamplerBinding> {
+        let mut samplers = Vec::new();
 
-                    let mut name_str: String = String::new();
-                    for i in 0..name_length {
-                        name_str.push(name_data[i as usize] as char);
-                    }
+        for uniform in self.uniforms.iter().filter(|uniform| uniform.category.is_sampler()) {
+            let mut slot: i32 = 0;
+            unsafe {
+                gl::GetUniformiv(self.buffer, uniform.location, &mut slot);
+                gl_check();
+            }
 
-                    uniforms.push(GLUniform {
-                        name: name_str,
-                        category: uniform_type,
-                        size: uniform_size
-                    })
+            samplers.push(GLSamplerBinding {
+                name: uniform.name.clone(),
+                category: uniform.category,
+                location: uniform.location,
+                slot: slot
+            });
+        }
+
+        samplers
+    }
+
+    fn reflect(&mut self) {
+        self.uniforms.clear();
+        self.attributes.clear();
+        self.uniform_blocks.clear();
+
+        let mut uniform_count: i32 = 0;
+        let mut attribute_count: i32 = 0;
+        let mut block_count: i32 = 0;
+        unsafe {
+            gl::GetProgramiv(self.buffer, gl::ACTIVE_UNIFORMS, &mut uniform_count);
+            gl::GetProgramiv(self.buffer, gl::ACTIVE_ATTRIBUTES, &mut attribute_count);
+            gl::GetProgramiv(self.buffer, gl::ACTIVE_UNIFORM_BLOCKS, &mut block_count);
+            gl_check();
+        }
+
+        let mut block_members: Vec<Vec<GLUniform>> = vec![Vec::new(); block_count as usize];
+
+        for i in 0..uniform_count as u32 {
+            let mut name_data: [u8; 1024] = [0; 1024];
+            let mut name_length: i32 = 0;
+
+            let mut uniform_size: i32 = 0;
+            let mut uniform_type: u32 = 0;
+
+            let mut block_index: i32 = -1;
+            let mut block_offset: i32 = -1;
+            let mut array_stride: i32 = 0;
+            let mut matrix_stride: i32 = 0;
+
+            unsafe {
+                gl::GetActiveUniform(self.buffer, i, 1024, &mut name_length, &mut uniform_size, &mut uniform_type, name_data.as_mut_ptr() as *mut c_char);
+                gl::GetActiveUniformsiv(self.buffer, 1, &i, gl::UNIFORM_BLOCK_INDEX, &mut block_index);
+                gl::GetActiveUniformsiv(self.buffer, 1, &i, gl::UNIFORM_OFFSET, &mut block_offset);
+                gl::GetActiveUniformsiv(self.buffer, 1, &i, gl::UNIFORM_ARRAY_STRIDE, &mut array_stride);
+                gl::GetActiveUniformsiv(self.buffer, 1, &i, gl::UNIFORM_MATRIX_STRIDE, &mut matrix_stride);
+                gl_check();
+            }
+
+            let name = String::from_utf8_lossy(&name_data[0..name_length as usize]).into_owned();
+
+            let mut uniform = GLUniform {
+                name: name,
+                category: GLUniformType::from_gl(uniform_type),
+                size: uniform_size,
+                location: -1,
+                block_index: None,
+                block_offset: None,
+                array_stride: array_stride.max(0) as usize,
+                matrix_stride: matrix_stride.max(0) as usize
+            };
+
+            if block_index >= 0 {
+                uniform.block_index = Some(block_index as u32);
+                uniform.block_offset = Some(block_offset.max(0) as usize);
+                block_members[block_index as usize].push(uniform);
+            } else {
+                uniform.location = gl_get_uniform_location(self.buffer, &uniform.name);
+                self.uniform_locations.insert(uniform.name.clone(), uniform.location);
+                if let Some(base_name) = uniform.name.strip_suffix("[0]") {
+                    self.uniform_locations.insert(String::from(base_name), uniform.location);
                 }
+                self.uniforms.push(uniform);
+            }
+        }
 
-                self.uniforms = Some(uniforms);
-                self.uniforms.clone().unwrap()
+        for i in 0..attribute_count as u32 {
+            let mut name_data: [u8; 1024] = [0; 1024];
+            let mut name_length: i32 = 0;
+
+            let mut attribute_size: i32 = 0;
+            let mut attribute_type: u32 = 0;
+
+            unsafe {
+                gl::GetActiveAttrib(self.buffer, i, 1024, &mut name_length, &mut attribute_size, &mut attribute_type, name_data.as_mut_ptr() as *mut c_char);
+                gl_check();
             }
+
+            let name = String::from_utf8_lossy(&name_data[0..name_length as usize]).into_owned();
+            let mut cname = name.clone();
+            cname.push('\0');
+
+            let location = unsafe {
+                let location = gl::GetAttribLocation(self.buffer, cname.as_ptr() as *const i8);
+                gl_check();
+                location
+            };
+
+            self.attributes.push(GLAttribute {
+                name: name,
+                category: GLUniformType::from_gl(attribute_type),
+                size: attribute_size,
+                location: location
+            });
+        }
+        self.attributes.sort_by_key(|attribute| attribute.location);
+
+        for (i, mut members) in block_members.into_iter().enumerate() {
+            let mut name_data: [u8; 1024] = [0; 1024];
+            let mut name_length: i32 = 0;
+
+            let mut binding: i32 = 0;
+            let mut data_size: i32 = 0;
+
+            unsafe {
+                gl::GetActiveUniformBlockName(self.buffer, i as u32, 1024, &mut name_length, name_data.as_mut_ptr() as *mut c_char);
+                gl::GetActiveUniformBlockiv(self.buffer, i as u32, gl::UNIFORM_BLOCK_BINDING, &mut binding);
+                gl::GetActiveUniformBlockiv(self.buffer, i as u32, gl::UNIFORM_BLOCK_DATA_SIZE, &mut data_size);
+                gl_check();
+            }
+
+            members.sort_by_key(|member| member.block_offset);
+
+            self.uniform_blocks.push(GLUniformBlock {
+                name: String::from_utf8_lossy(&name_data[0..name_length as usize]).into_owned(),
+                index: i as u32,
+                binding: binding as u32,
+                data_size: data_size as usize,
+                members: members
+            });
         }
     }
 
@@ -179,32 +362,105 @@ impl GLShaderProgram {
         self.set_int(name, value);
     }
 
+    pub fn set_uniform_block_binding(&mut self, name: &String, binding: u32) {
+        unsafe {
+            let mut cname = name.clone();
+            cname.push('\0');
+
+            let index = gl::GetUniformBlockIndex(self.buffer, cname.as_ptr() as *const i8);
+            gl_check();
+            if index == gl::INVALID_INDEX {
+                eprintln!("Failed to get uniform block index. (Name: '{}')", name);
+                return;
+            }
+
+            gl::UniformBlockBinding(self.buffer, index, binding);
+            gl_check();
+        }
+
+        if let Some(block) = self.uniform_blocks.iter_mut().find(|block| &block.name == name) {
+            block.binding = binding;
+        }
+    }
+
     fn uniform_location(&mut self, name: &String) -> i32 {
         match self.uniform_locations.get(name) {
             Some(location) => location.clone(),
             None => {
-                unsafe {
-                    let mut cname = name.clone();
-                    cname.push('\0');
-                    
-                    let location: i32 = gl::GetUniformLocation(self.buffer, cname.as_ptr() as *const i8);
-                    gl_check();
-                    if location < 0 {
-                        eprintln!("Failed to get uniform location. (Name: '{}')", name);
-                    }
-
-                    self.uniform_locations.insert(name.clone(), location);
-                    location
+                let location = gl_get_uniform_location(self.buffer, name);
+                if location < 0 {
+                    eprintln!("Failed to get uniform location. (Name: '{}')", name);
                 }
+
+                self.uniform_locations.insert(name.clone(), location);
+                location
             }
         }
     }
 }
 
+impl GLUniformType {
+    pub fn from_gl(gl_type: GLenum) -> Self {
+        match gl_type {
+            gl::FLOAT => GLUniformType::Float,
+            gl::FLOAT_VEC2 => GLUniformType::Float2,
+            gl::FLOAT_VEC3 => GLUniformType::Float3,
+            gl::FLOAT_VEC4 => GLUniformType::Float4,
+            gl::INT => GLUniformType::Int,
+            gl::INT_VEC2 => GLUniformType::Int2,
+            gl::INT_VEC3 => GLUniformType::Int3,
+            gl::INT_VEC4 => GLUniformType::Int4,
+            gl::UNSIGNED_INT => GLUniformType::UInt,
+            gl::UNSIGNED_INT_VEC2 => GLUniformType::UInt2,
+            gl::UNSIGNED_INT_VEC3 => GLUniformType::UInt3,
+            gl::UNSIGNED_INT_VEC4 => GLUniformType::UInt4,
+            gl::BOOL => GLUniformType::Bool,
+            gl::BOOL_VEC2 => GLUniformType::Bool2,
+            gl::BOOL_VEC3 => GLUniformType::Bool3,
+            gl::BOOL_VEC4 => GLUniformType::Bool4,
+            gl::FLOAT_MAT2 => GLUniformType::Float2x2,
+            gl::FLOAT_MAT3 => GLUniformType::Float3x3,
+            gl::FLOAT_MAT4 => GLUniformType::Float4x4,
+            gl::SAMPLER_2D => GLUniformType::Sampler2D,
+            gl::SAMPLER_3D => GLUniformType::Sampler3D,
+            gl::SAMPLER_CUBE => GLUniformType::SamplerCube,
+            gl::SAMPLER_2D_ARRAY => GLUniformType::Sampler2DArray,
+            gl::SAMPLER_2D_SHADOW => GLUniformType::Sampler2DShadow,
+            gl::SAMPLER_2D_ARRAY_SHADOW => GLUniformType::Sampler2DArrayShadow,
+            gl::SAMPLER_CUBE_SHADOW => GLUniformType::SamplerCubeShadow,
+            _ => GLUniformType::Other(gl_type)
+        }
+    }
+
+    pub fn is_sampler(&self) -> bool {
+        match self {
+            GLUniformType::Sampler2D |
+            GLUniformType::Sampler3D |
+            GLUniformType::SamplerCube |
+            GLUniformType::Sampler2DArray |
+            GLUniformType::Sampler2DShadow |
+            GLUniformType::Sampler2DArrayShadow |
+            GLUniformType::SamplerCubeShadow => true,
+            _ => false
+        }
+    }
+}
+
 /*****************************************************************************
 *                               HELPERS
 ******************************************************************************/
 
+fn gl_get_uniform_location(shader_program: GLShaderProgramBuffer, name: &String) -> i32 {
+    let mut cname = name.clone();
+    cname.push('\0');
+
+    unsafe {
+        let location: i32 = gl::GetUniformLocation(shader_program, cname.as_ptr() as *const i8);
+        gl_check();
+        location
+    }
+}
+
 fn gl_create_vert_shader() -> GLShaderBuffer {
     unsafe {
         let shader: GLShaderBuffer = gl::CreateShader(gl::VERTEX_SHADER);
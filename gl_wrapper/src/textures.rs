@@ -12,6 +12,58 @@ pub struct GLTexture {
     target: GLTextureType
 }
 
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum GLTextureFormat {
+    R8,
+    RG8,
+    RGB8,
+    RGBA8,
+    SRGB8,
+    SRGB8Alpha8,
+    R16F,
+    RG16F,
+    RGBA16F,
+    R32F,
+    RG32F,
+    RGB32F,
+    RGBA32F,
+    R32UI,
+    Depth24,
+    Depth32F,
+    Depth24Stencil8
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum GLWrapMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum GLFilterMode {
+    Nearest,
+    Linear
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct GLSamplerDesc {
+    pub wrap_s: GLWrapMode,
+    pub wrap_t: GLWrapMode,
+    pub wrap_r: GLWrapMode,
+    pub min_filter: GLFilterMode,
+    pub mag_filter: GLFilterMode,
+    pub mip_filter: Option<GLFilterMode>,
+    pub anisotropy: f32
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct GLTextureDesc {
+    pub format: GLTextureFormat,
+    pub sampler: GLSamplerDesc,
+    pub mip_levels: Option<u32>
+}
+
 /*****************************************************************************
 *                               FUNCS
 ******************************************************************************/
@@ -44,6 +96,62 @@ pub fn gl_tex_image_2df(internal_format: u32, width: i32, height: i32, format: u
     }
 }
 
+pub fn gl_tex_image_2d_format(target: GLTextureType, level: i32, format: GLTextureFormat, width: i32, height: i32, data: *const c_void) {
+    gl_pixel_store_unpack_alignment(format.unpack_alignment(width));
+    unsafe {
+        gl::TexImage2D(target, level, format.internal_format() as i32, width, height, 0, format.format(), format.data_type(), data);
+        gl_check();
+    }
+    gl_pixel_store_unpack_alignment(4);
+}
+
+pub fn gl_tex_image_2d_multisample(samples: u32, format: GLTextureFormat, width: i32, height: i32) {
+    unsafe {
+        gl::TexImage2DMultisample(gl::TEXTURE_2D_MULTISAMPLE, samples as i32, format.internal_format(), width, height, gl::TRUE);
+        gl_check();
+    }
+}
+
+pub fn gl_max_samples() -> u32 {
+    let mut max_samples: i32 = 1;
+    unsafe {
+        gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+        gl_check();
+    }
+    max_samples.max(1) as u32
+}
+
+pub fn gl_pixel_store_unpack_alignment(alignment: i32) {
+    unsafe {
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment);
+        gl_check();
+    }
+}
+
+pub fn gl_max_anisotropy() -> f32 {
+    let mut max_anisotropy: f32 = 1.0;
+    unsafe {
+        gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+        if gl::GetError() != gl::NO_ERROR {
+            max_anisotropy = 1.0;
+        }
+    }
+    max_anisotropy
+}
+
+pub fn gl_mip_level_count(width: i32, height: i32) -> u32 {
+    32 - (width.max(height).max(1) as u32).leading_zeros()
+}
+
+pub fn gl_get_tex_image(target: GLTextureType, level: i32, format: GLenum, data_type: GLenum, data: *mut c_void) {
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::GetTexImage(target, level, format, data_type, data);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+        gl_check();
+    }
+}
+
 pub fn gl_active_texture(slot: u32) {
     unsafe {
         gl::ActiveTexture(gl::TEXTURE0 + slot);
@@ -84,6 +192,198 @@ impl GLTexture {
     pub fn target(&self) -> GLTextureType {
         self.target
     }
+
+    // Applies the sampler and mip range to the bound texture.
+    pub fn apply_desc(&self, desc: &GLTextureDesc, width: i32, height: i32) {
+        let sampler = &desc.sampler;
+
+        gl_tex_parami(self.target, gl::TEXTURE_WRAP_S, sampler.wrap_s.to_gl());
+        gl_tex_parami(self.target, gl::TEXTURE_WRAP_T, sampler.wrap_t.to_gl());
+        gl_tex_parami(self.target, gl::TEXTURE_WRAP_R, sampler.wrap_r.to_gl());
+        gl_tex_parami(self.target, gl::TEXTURE_MIN_FILTER, sampler.min_filter_gl());
+        gl_tex_parami(self.target, gl::TEXTURE_MAG_FILTER, sampler.mag_filter.to_gl());
+
+        let mip_levels = desc.mip_level_count(width, height);
+        gl_tex_parami(self.target, gl::TEXTURE_BASE_LEVEL, 0);
+        gl_tex_parami(self.target, gl::TEXTURE_MAX_LEVEL, mip_levels - 1);
+
+        if sampler.anisotropy > 1.0 {
+            let anisotropy = sampler.anisotropy.min(gl_max_anisotropy());
+            unsafe {
+                gl::TexParameterf(self.target, TEXTURE_MAX_ANISOTROPY, anisotropy);
+                gl::GetError();
+            }
+        }
+    }
+}
+
+impl GLTextureFormat {
+    pub fn from_channel_count(channel_count: i32, srgb: bool) -> Self {
+        match (channel_count, srgb) {
+            (1, _) => GLTextureFormat::R8,
+            (2, _) => GLTextureFormat::RG8,
+            (3, false) => GLTextureFormat::RGB8,
+            (3, true) => GLTextureFormat::SRGB8,
+            (_, false) => GLTextureFormat::RGBA8,
+            (_, true) => GLTextureFormat::SRGB8Alpha8
+        }
+    }
+
+    pub fn internal_format(&self) -> GLenum {
+        match self {
+            GLTextureFormat::R8 => gl::R8,
+            GLTextureFormat::RG8 => gl::RG8,
+            GLTextureFormat::RGB8 => gl::RGB8,
+            GLTextureFormat::RGBA8 => gl::RGBA8,
+            GLTextureFormat::SRGB8 => gl::SRGB8,
+            GLTextureFormat::SRGB8Alpha8 => gl::SRGB8_ALPHA8,
+            GLTextureFormat::R16F => gl::R16F,
+            GLTextureFormat::RG16F => gl::RG16F,
+            GLTextureFormat::RGBA16F => gl::RGBA16F,
+            GLTextureFormat::R32F => gl::R32F,
+            GLTextureFormat::RG32F => gl::RG32F,
+            GLTextureFormat::RGB32F => gl::RGB32F,
+            GLTextureFormat::RGBA32F => gl::RGBA32F,
+            GLTextureFormat::R32UI => gl::R32UI,
+            GLTextureFormat::Depth24 => gl::DEPTH_COMPONENT24,
+            GLTextureFormat::Depth32F => gl::DEPTH_COMPONENT32F,
+            GLTextureFormat::Depth24Stencil8 => gl::DEPTH24_STENCIL8
+        }
+    }
+
+    pub fn format(&self) -> GLenum {
+        match self {
+            GLTextureFormat::R32UI => return gl::RED_INTEGER,
+            GLTextureFormat::Depth24 | GLTextureFormat::Depth32F => return gl::DEPTH_COMPONENT,
+            GLTextureFormat::Depth24Stencil8 => return gl::DEPTH_STENCIL,
+            _ => ()
+        }
+
+        match self.channel_count() {
+            1 => gl::RED,
+            2 => gl::RG,
+            3 => gl::RGB,
+            _ => gl::RGBA
+        }
+    }
+
+    pub fn data_type(&self) -> GLenum {
+        match self {
+            GLTextureFormat::R8 |
+            GLTextureFormat::RG8 |
+            GLTextureFormat::RGB8 |
+            GLTextureFormat::RGBA8 |
+            GLTextureFormat::SRGB8 |
+            GLTextureFormat::SRGB8Alpha8 => gl::UNSIGNED_BYTE,
+            GLTextureFormat::R32UI | GLTextureFormat::Depth24 => gl::UNSIGNED_INT,
+            GLTextureFormat::Depth24Stencil8 => gl::UNSIGNED_INT_24_8,
+            _ => gl::FLOAT
+        }
+    }
+
+    pub fn channel_count(&self) -> i32 {
+        match self {
+            GLTextureFormat::R8 | GLTextureFormat::R16F | GLTextureFormat::R32F | GLTextureFormat::R32UI => 1,
+            GLTextureFormat::Depth24 | GLTextureFormat::Depth32F | GLTextureFormat::Depth24Stencil8 => 1,
+            GLTextureFormat::RG8 | GLTextureFormat::RG16F | GLTextureFormat::RG32F => 2,
+            GLTextureFormat::RGB8 | GLTextureFormat::SRGB8 | GLTextureFormat::RGB32F => 3,
+            _ => 4
+        }
+    }
+
+    // Size of one uploaded pixel, float formats are uploaded as f32.
+    pub fn pixel_size(&self) -> usize {
+        let component_size = match self.data_type() {
+            gl::UNSIGNED_BYTE => 1,
+            _ => 4
+        };
+        component_size * self.channel_count() as usize
+    }
+
+    pub fn is_depth(&self) -> bool {
+        matches!(self, GLTextureFormat::Depth24 | GLTextureFormat::Depth32F | GLTextureFormat::Depth24Stencil8)
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        *self == GLTextureFormat::Depth24Stencil8
+    }
+
+    pub fn is_integer(&self) -> bool {
+        *self == GLTextureFormat::R32UI
+    }
+
+    pub fn unpack_alignment(&self, width: i32) -> i32 {
+        let row_size = self.pixel_size() * width.max(0) as usize;
+        if row_size % 4 == 0 { 4 } else if row_size % 2 == 0 { 2 } else { 1 }
+    }
+}
+
+impl GLWrapMode {
+    pub fn to_gl(&self) -> GLenum {
+        match self {
+            GLWrapMode::Repeat => gl::REPEAT,
+            GLWrapMode::MirroredRepeat => gl::MIRRORED_REPEAT,
+            GLWrapMode::ClampToEdge => gl::CLAMP_TO_EDGE
+        }
+    }
+}
+
+impl GLFilterMode {
+    pub fn to_gl(&self) -> GLenum {
+        match self {
+            GLFilterMode::Nearest => gl::NEAREST,
+            GLFilterMode::Linear => gl::LINEAR
+        }
+    }
+}
+
+impl GLSamplerDesc {
+    pub fn min_filter_gl(&self) -> GLenum {
+        match (self.min_filter, self.mip_filter) {
+            (GLFilterMode::Nearest, None) => gl::NEAREST,
+            (GLFilterMode::Linear, None) => gl::LINEAR,
+            (GLFilterMode::Nearest, Some(GLFilterMode::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+            (GLFilterMode::Nearest, Some(GLFilterMode::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+            (GLFilterMode::Linear, Some(GLFilterMode::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+            (GLFilterMode::Linear, Some(GLFilterMode::Linear)) => gl::LINEAR_MIPMAP_LINEAR
+        }
+    }
+}
+
+impl Default for GLSamplerDesc {
+    fn default() -> Self {
+        GLSamplerDesc {
+            wrap_s: GLWrapMode::Repeat,
+            wrap_t: GLWrapMode::Repeat,
+            wrap_r: GLWrapMode::Repeat,
+            min_filter: GLFilterMode::Linear,
+            mag_filter: GLFilterMode::Linear,
+            mip_filter: Some(GLFilterMode::Linear),
+            anisotropy: 1.0
+        }
+    }
+}
+
+impl GLTextureDesc {
+    pub fn new(format: GLTextureFormat) -> Self {
+        GLTextureDesc {
+            format: format,
+            sampler: GLSamplerDesc::default(),
+            mip_levels: None
+        }
+    }
+
+    pub fn mip_level_count(&self, width: i32, height: i32) -> u32 {
+        if self.sampler.mip_filter.is_none() {
+            return 1;
+        }
+
+        let full_chain = gl_mip_level_count(width, height);
+        match self.mip_levels {
+            Some(mip_levels) => mip_levels.clamp(1, full_chain),
+            None => full_chain
+        }
+    }
 }
 
 impl Drop for GLTexture {
@@ -96,6 +396,10 @@ impl Drop for GLTexture {
 *                               HELPERS
 ******************************************************************************/
 
+// EXT_texture_filter_anisotropic, core since 4.6.
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
 fn gl_gen_texture() -> GLTextureBuffer {
     unsafe {
         let mut buffer: GLTextureBuffer = 0;
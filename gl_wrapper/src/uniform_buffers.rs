@@ -0,0 +1,250 @@
+use crate::*;
+
+use std::marker::PhantomData;
+
+/*****************************************************************************
+*                               STRUCTS
+******************************************************************************/
+
+pub struct GLUniformBuffer<T: Std140> {
+    buffer: GLBuffer,
+    binding: u32,
+    _marker: PhantomData<T>
+}
+
+pub struct Std140Writer {
+    data: Vec<u8>
+}
+
+// Types that can be written to a uniform block using the std140 layout rules.
+pub trait Std140 {
+    const ALIGN: usize;
+    const SIZE: usize;
+
+    fn write_std140(&self, writer: &mut Std140Writer);
+}
+
+/*****************************************************************************
+*                               FUNCS
+******************************************************************************/
+
+pub const fn std140_align_up(offset: usize, align: usize) -> usize {
+    (offset + align - 1) / align * align
+}
+
+pub fn gl_bind_uniform_buffer_base(binding: u32, buffer: GLBuffer) {
+    unsafe {
+        gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, buffer);
+        gl_check();
+    }
+}
+
+/*****************************************************************************
+*                               IMPLEMENTATION
+******************************************************************************/
+
+impl<T: Std140> GLUniformBuffer<T> {
+    pub fn new(binding: u32) -> Self {
+        let mut buffer: GLBuffer = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut buffer);
+            gl_check();
+
+            gl::BindBuffer(gl::UNIFORM_BUFFER, buffer);
+            gl::BufferData(gl::UNIFORM_BUFFER, T::SIZE as isize, std::ptr::null(), gl::DYNAMIC_DRAW);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl_check();
+        }
+
+        gl_bind_uniform_buffer_base(binding, buffer);
+
+        GLUniformBuffer {
+            buffer: buffer,
+            binding: binding,
+            _marker: PhantomData
+        }
+    }
+
+    pub fn set_data(&self, value: &T) {
+        let mut writer = Std140Writer::new();
+        writer.write(value);
+        writer.align(16);
+
+        let data = writer.data();
+        assert!(data.len() <= T::SIZE, "Failed to set uniform buffer data. (Std140 size mismatch)");
+
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.buffer);
+            gl::BufferSubData(gl::UNIFORM_BUFFER, 0, data.len() as isize, data.as_ptr() as *const c_void);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl_check();
+        }
+    }
+
+    pub fn bind(&self) {
+        gl_bind_uniform_buffer_base(self.binding, self.buffer);
+    }
+
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+
+    pub fn handle(&self) -> GLBuffer {
+        self.buffer
+    }
+}
+
+impl<T: Std140> Drop for GLUniformBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.buffer);
+            gl_check();
+        }
+    }
+}
+
+impl Std140Writer {
+    pub fn new() -> Self {
+        Std140Writer {
+            data: Vec::new()
+        }
+    }
+
+    pub fn write<T: Std140>(&mut self, value: &T) {
+        self.align(T::ALIGN);
+        value.write_std140(self);
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn align(&mut self, align: usize) {
+        let aligned = std140_align_up(self.data.len(), align);
+        self.data.resize(aligned, 0);
+    }
+
+    pub fn data(&self) -> &Vec<u8> {
+        &self.data
+    }
+}
+
+macro_rules! std140_scalar_impl {
+    ($t: ty) => {
+        impl Std140 for $t {
+            const ALIGN: usize = 4;
+            const SIZE: usize = 4;
+
+            fn write_std140(&self, writer: &mut Std140Writer) {
+                writer.write_bytes(&self.to_ne_bytes());
+            }
+        }
+    };
+}
+
+macro_rules! std140_vector_impl {
+    ($t: ty, $align: expr, $($field: ident),+) => {
+        impl Std140 for $t {
+            const ALIGN: usize = $align;
+            const SIZE: usize = 4 * [$(stringify!($field)),+].len();
+
+            fn write_std140(&self, writer: &mut Std140Writer) {
+                $(writer.write_bytes(&self.$field.to_ne_bytes());)+
+            }
+        }
+    };
+}
+
+std140_scalar_impl!(f32);
+std140_scalar_impl!(i32);
+std140_scalar_impl!(u32);
+
+impl Std140 for bool {
+    const ALIGN: usize = 4;
+    const SIZE: usize = 4;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_bytes(&(*self as u32).to_ne_bytes());
+    }
+}
+
+std140_vector_impl!(Float2, 8, x, y);
+std140_vector_impl!(Float3, 16, x, y, z);
+std140_vector_impl!(Float4, 16, x, y, z, w);
+std140_vector_impl!(Int2, 8, x, y);
+std140_vector_impl!(Int3, 16, x, y, z);
+std140_vector_impl!(Int4, 16, x, y, z, w);
+std140_vector_impl!(UInt2, 8, x, y);
+std140_vector_impl!(UInt3, 16, x, y, z);
+std140_vector_impl!(UInt4, 16, x, y, z, w);
+
+impl Std140 for Float4x4 {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 64;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        for elem in self.elems.iter() {
+            writer.write_bytes(&elem.to_ne_bytes());
+        }
+    }
+}
+
+// Array elements are padded to a multiple of a vec4.
+impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGN: usize = std140_align_up(T::ALIGN, 16);
+    const SIZE: usize = std140_align_up(T::SIZE, 16) * N;
+
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        for elem in self.iter() {
+            writer.align(16);
+            elem.write_std140(writer);
+        }
+        writer.align(16);
+    }
+}
+
+// Declares a struct and derives its std140 layout from the field order.
+//
+// std140_struct! {
+//     pub struct CameraBlock {
+//         pub projection: Float4x4,
+//         pub view_pos: Float3
+//     }
+// }
+#[macro_export]
+macro_rules! std140_struct {
+    ($(#[$meta: meta])* $vis: vis struct $name: ident { $($field_vis: vis $field: ident: $t: ty),* $(,)? }) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($field_vis $field: $t),*
+        }
+
+        impl $crate::Std140 for $name {
+            const ALIGN: usize = 16;
+            const SIZE: usize = {
+                let mut offset = 0;
+                $(offset = $crate::std140_align_up(offset, <$t as $crate::Std140>::ALIGN) + <$t as $crate::Std140>::SIZE;)*
+                $crate::std140_align_up(offset, 16)
+            };
+
+            fn write_std140(&self, writer: &mut $crate::Std140Writer) {
+                $(writer.write(&self.$field);)*
+                writer.align(16);
+            }
+        }
+
+        impl $name {
+            pub fn std140_offsets() -> Vec<(&'static str, usize)> {
+                let mut offsets = Vec::new();
+                let mut offset = 0;
+                $(
+                    offset = $crate::std140_align_up(offset, <$t as $crate::Std140>::ALIGN);
+                    offsets.push((stringify!($field), offset));
+                    offset += <$t as $crate::Std140>::SIZE;
+                )*
+                let _ = offset;
+                offsets
+            }
+        }
+    };
+}
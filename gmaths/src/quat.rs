@@ -136,6 +136,48 @@ impl<T: Float + Default + AddAssign<T> + DivAssign<T>> Quaternion<T> {
     }
 }
 
+/*****************************************************************************
+*                               INTERPOLATION
+******************************************************************************/
+
+impl<T: Float> Quaternion<T> {
+    #[inline]
+    pub fn dot(&self, other: Quaternion<T>) -> T {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    #[inline]
+    pub fn normalized(&self) -> Quaternion<T> {
+        let length = self.dot(*self).sqrt();
+        Quaternion::new(self.x / length, self.y / length, self.z / length, self.w / length)
+    }
+
+    // Interpolates along the shorter arc, nearly equal rotations fall back to a normalized lerp.
+    pub fn slerp(&self, other: Quaternion<T>, t: T) -> Quaternion<T> {
+        let mut other = other;
+        let mut cos_angle = self.dot(other);
+        if cos_angle < t!(0.0) {
+            other = Quaternion::new(-other.x, -other.y, -other.z, -other.w);
+            cos_angle = -cos_angle;
+        }
+
+        let (a, b) = if cos_angle > t!(0.9995) {
+            (t!(1.0) - t, t)
+        } else {
+            let angle = cos_angle.acos();
+            let sin_angle = angle.sin();
+            (((t!(1.0) - t) * angle).sin() / sin_angle, (t * angle).sin() / sin_angle)
+        };
+
+        Quaternion::new(
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b,
+            self.w * a + other.w * b
+        ).normalized()
+    }
+}
+
 /*****************************************************************************
 *                               MODIFIERS
 ******************************************************************************/
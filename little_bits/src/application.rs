@@ -31,6 +31,8 @@ pub trait Game {
     fn start(&mut self);
     fn update(&mut self, delta_time: f32);
     fn debug_ui(&mut self, _: &mut DebugUI) {}
+    // Adds custom passes to the frame graph, called once per stage every frame.
+    fn render_passes(&mut self, _: RenderStage, _: &mut FrameGraph, _: &FrameTextures) {}
     fn stop(&mut self);
 }
 
@@ -67,7 +69,9 @@ impl Application {
             delta_timer.reset();
 
             self.game.update(delta_time);
-            self.game.debug_ui(app().graphics().debug_ui());
+            let ui = app().graphics().debug_ui();
+            self.game.debug_ui(ui);
+            app().graphics().draw_debug_ui(ui);
 
             self.input().update();
             self.graphics().update();
@@ -97,6 +101,10 @@ impl Application {
         self.input.as_mut().expect("Failed to get input.").as_mut()
     }
 
+    pub(crate) fn game(&mut self) -> &mut dyn Game {
+        self.game.as_mut()
+    }
+
     pub fn time(&self) -> f32 {
         self.timer.elapsed() as f32
     }
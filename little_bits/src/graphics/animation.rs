@@ -0,0 +1,300 @@
+use crate::gmaths::*;
+use crate::graphics::opengl::*;
+use crate::resources::{Model, AnimationChannel, AnimationPath, Interpolation};
+use crate::Shared;
+
+// Playback position of one clip.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct AnimationPlayback {
+    pub clip: usize,
+    pub time: f32,
+    pub looping: bool
+}
+
+// Pose of a node, rotations are stored like node rotations.
+#[derive(PartialEq, Clone, Debug)]
+struct NodePose {
+    translation: Float3,
+    rotation: Quat,
+    scale: Float3,
+    weights: Vec<f32>
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+struct CrossFade {
+    from: AnimationPlayback,
+    elapsed: f32,
+    duration: f32
+}
+
+// Animation state of one model instance, samples the model's clips into node and joint matrices.
+#[derive(Clone)]
+pub struct Animator {
+    model: Shared<Model>,
+    // Multiplies the delta time of every playing clip.
+    pub speed: f32,
+    current: Option<AnimationPlayback>,
+    fade: Option<CrossFade>,
+
+    rest_pose: Vec<NodePose>,
+    node_matrices: Vec<Float4x4>,
+    joint_matrices: Vec<Vec<Float4x4>>,
+    morph_weights: Vec<Vec<f32>>
+}
+
+impl Animator {
+    pub fn new(model: &Shared<Model>) -> Self {
+        let rest_pose = model.as_ref().nodes.iter().map(|node| NodePose {
+            translation: node.transform.get_translation(),
+            rotation: node.transform.get_rotation(),
+            scale: node.transform.get_scale(),
+            weights: node.weights.clone()
+        }).collect();
+
+        let mut animator = Animator {
+            model: model.clone(),
+            speed: 1.0,
+            current: None,
+            fade: None,
+            rest_pose: rest_pose,
+            node_matrices: Vec::new(),
+            joint_matrices: Vec::new(),
+            morph_weights: Vec::new()
+        };
+        animator.update(0.0);
+
+        animator
+    }
+
+    pub fn play(&mut self, clip: usize, looping: bool) {
+        assert!(clip < self.model.as_ref().animations.len(), "Failed to play animation. (Clip index out of range)");
+
+        self.current = Some(AnimationPlayback { clip: clip, time: 0.0, looping: looping });
+        self.fade = None;
+    }
+
+    // Blends from the current pose to the clip over the duration in seconds.
+    pub fn cross_fade(&mut self, clip: usize, duration: f32, looping: bool) {
+        let from = match self.current {
+            Some(current) if duration > 0.0 => current,
+            _ => return self.play(clip, looping)
+        };
+
+        self.play(clip, looping);
+        self.fade = Some(CrossFade { from: from, elapsed: 0.0, duration: duration });
+    }
+
+    // Returns to the rest pose.
+    pub fn stop(&mut self) {
+        self.current = None;
+        self.fade = None;
+    }
+
+    pub fn playback(&self) -> Option<AnimationPlayback> {
+        self.current
+    }
+
+    // Non-looping clips stop playing once they reach their end, holding the last pose.
+    pub fn is_playing(&self) -> bool {
+        let model = self.model.as_ref();
+        match self.current {
+            Some(current) => current.looping || current.time < model.animations[current.clip].duration,
+            None => false
+        }
+    }
+
+    // Model space matrix of every node in the current pose.
+    pub fn node_matrices(&self) -> &[Float4x4] {
+        &self.node_matrices
+    }
+
+    // Joint matrices of the skin in model space, including the inverse bind matrices.
+    pub fn joint_matrices(&self, skin: usize) -> &[Float4x4] {
+        &self.joint_matrices[skin]
+    }
+
+    // Morph target weights of the node's meshes in the current pose.
+    pub fn morph_weights(&self, node: usize) -> &[f32] {
+        &self.morph_weights[node]
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        let model = self.model.clone();
+        let model = model.as_ref();
+
+        let delta_time = delta_time * self.speed;
+        if let Some(current) = self.current.as_mut() {
+            let duration = model.animations[current.clip].duration;
+            Self::advance(current, duration, delta_time);
+        }
+        if let Some(fade) = self.fade.as_mut() {
+            let duration = model.animations[fade.from.clip].duration;
+            Self::advance(&mut fade.from, duration, delta_time);
+            fade.elapsed += delta_time;
+            if fade.elapsed >= fade.duration {
+                self.fade = None;
+            }
+        }
+
+        let mut pose = self.rest_pose.clone();
+        if let Some(current) = self.current {
+            Self::sample(&model, &current, &mut pose);
+        }
+        if let Some(fade) = self.fade {
+            let mut from_pose = self.rest_pose.clone();
+            Self::sample(&model, &fade.from, &mut from_pose);
+
+            let weight = (fade.elapsed / fade.duration).clamp(0.0, 1.0);
+            for (node_pose, from) in pose.iter_mut().zip(from_pose.iter()) {
+                *node_pose = NodePose {
+                    translation: from.translation.lerp(node_pose.translation, weight),
+                    rotation: from.rotation.slerp(node_pose.rotation, weight),
+                    scale: from.scale.lerp(node_pose.scale, weight),
+                    weights: from.weights.iter().zip(node_pose.weights.iter()).map(|(from, to)| from + (to - from) * weight).collect()
+                };
+            }
+        }
+
+        self.node_matrices = vec![Float4x4::identity(); model.nodes.len()];
+        let mut stack: Vec<(usize, Float4x4)> = model.root_nodes.iter().map(|node_idx| (*node_idx, Float4x4::identity())).collect();
+        while let Some((node_idx, parent_matrix)) = stack.pop() {
+            let node_pose = &pose[node_idx];
+            let local = Float4x4::translation(node_pose.translation) * Float4x4::from(node_pose.rotation) * Float4x4::scale(node_pose.scale);
+            self.node_matrices[node_idx] = parent_matrix * local;
+
+            for child in model.nodes[node_idx].children.iter() {
+                stack.push((*child, self.node_matrices[node_idx]));
+            }
+        }
+
+        self.joint_matrices = model.skins.iter().map(|skin| {
+            skin.joints.iter().map(|joint| self.node_matrices[joint.node] * joint.inverse_bind_matrix).collect()
+        }).collect();
+        self.morph_weights = pose.into_iter().map(|node_pose| node_pose.weights).collect();
+    }
+
+    fn advance(playback: &mut AnimationPlayback, duration: f32, delta_time: f32) {
+        playback.time += delta_time;
+        if playback.looping && duration > 0.0 {
+            playback.time = playback.time.rem_euclid(duration);
+        } else {
+            playback.time = playback.time.clamp(0.0, duration);
+        }
+    }
+
+    fn sample(model: &Model, playback: &AnimationPlayback, pose: &mut [NodePose]) {
+        for channel in model.animations[playback.clip].channels.iter() {
+            let node_pose = &mut pose[channel.node];
+
+            if channel.path == AnimationPath::Weights {
+                for (i, weight) in node_pose.weights.iter_mut().enumerate() {
+                    let value = Self::sample_channel(channel, i / 4, playback.time);
+                    *weight = [value.x, value.y, value.z, value.w][i % 4];
+                }
+                continue;
+            }
+
+            let value = Self::sample_channel(channel, 0, playback.time);
+            match channel.path {
+                AnimationPath::Translation => node_pose.translation = Float3::new(value.x, value.y, value.z),
+                AnimationPath::Rotation => node_pose.rotation = Quat::new(value.x, value.y, value.z, value.w).normalized(),
+                AnimationPath::Scale => node_pose.scale = Float3::new(value.x, value.y, value.z),
+                AnimationPath::Weights => {}
+            }
+        }
+    }
+
+    // Samples one of the channel's values per keyframe, element is below the channel's stride.
+    fn sample_channel(channel: &AnimationChannel, element: usize, time: f32) -> Float4 {
+        if element >= channel.stride {
+            return Float4::default();
+        }
+
+        let stride = channel.stride;
+        let cubic = channel.interpolation == Interpolation::CubicSpline;
+        // Cubic spline keyframes are (in-tangent, value, out-tangent) triples.
+        let output = |key: usize, part: usize| -> Float4 {
+            if cubic { channel.values[(key * 3 + part) * stride + element] } else { channel.values[key * stride + element] }
+        };
+        let value = |key: usize| -> Float4 { output(key, 1) };
+
+        let last = channel.times.len() - 1;
+        if time <= channel.times[0] {
+            return value(0);
+        }
+        if time >= channel.times[last] {
+            return value(last);
+        }
+
+        let key = channel.times.partition_point(|key_time| *key_time <= time) - 1;
+        let key_duration = channel.times[key + 1] - channel.times[key];
+        let t = (time - channel.times[key]) / key_duration;
+
+        match channel.interpolation {
+            Interpolation::Step => value(key),
+            Interpolation::Linear if channel.path == AnimationPath::Rotation => {
+                let (a, b) = (value(key), value(key + 1));
+                let rotation = Quat::new(a.x, a.y, a.z, a.w).slerp(Quat::new(b.x, b.y, b.z, b.w), t);
+                Float4::new(rotation.x, rotation.y, rotation.z, rotation.w)
+            },
+            Interpolation::Linear => value(key).lerp(value(key + 1), t),
+            Interpolation::CubicSpline => {
+                // Hermite spline, tangents are scaled by the keyframe duration.
+                let t2 = t * t;
+                let t3 = t2 * t;
+                let out_tangent = output(key, 2) * key_duration;
+                let in_tangent = output(key + 1, 0) * key_duration;
+
+                value(key) * (2.0 * t3 - 3.0 * t2 + 1.0) + out_tangent * (t3 - 2.0 * t2 + t) + value(key + 1) * (-2.0 * t3 + 3.0 * t2) + in_tangent * (t3 - t2)
+            }
+        }
+    }
+}
+
+// Uniform buffers deforming the meshes of one instance, with the joints of its skin and its morph target weights.
+pub struct DeformationBuffers {
+    joint_block: GLUniformBuffer<JointBlock>,
+    morph_block: GLUniformBuffer<MorphBlock>
+}
+
+impl DeformationBuffers {
+    pub fn new() -> Self {
+        let deformation = DeformationBuffers {
+            joint_block: GLUniformBuffer::new(JOINT_BLOCK_BINDING),
+            morph_block: GLUniformBuffer::new(MORPH_BLOCK_BINDING)
+        };
+        deformation.reset();
+
+        deformation
+    }
+
+    pub fn setup_program(shader_program: &mut GLShaderProgram) {
+        JointBlock::setup_program(shader_program);
+        MorphBlock::setup_program(shader_program);
+        shader_program.set_sampler_slot(&String::from("morphTargets"), MORPH_TARGETS_SLOT as i32);
+    }
+
+    // Deformed meshes can't share a draw between instances.
+    pub fn is_deformed(model: &GLModel, mesh_idx: usize) -> bool {
+        model.mesh_skin(mesh_idx).is_some() || model.meshes[mesh_idx].morph_target_count() > 0
+    }
+
+    // Instances without an animator are drawn in their bind pose with the default weights.
+    pub fn set(&self, model: &GLModel, mesh_idx: usize, animator: Option<&Animator>) {
+        match (model.mesh_skin(mesh_idx), animator) {
+            (Some(skin), Some(animator)) => self.joint_block.set_data(&JointBlock::skinned(animator.joint_matrices(skin))),
+            _ => self.joint_block.set_data(&JointBlock::unskinned())
+        }
+
+        let weights = match (model.mesh_node(mesh_idx), animator) {
+            (Some(node), Some(animator)) => animator.morph_weights(node),
+            _ => model.mesh_weights(mesh_idx)
+        };
+        self.morph_block.set_data(&model.meshes[mesh_idx].bind_morph_targets(weights));
+    }
+
+    pub fn reset(&self) {
+        self.joint_block.set_data(&JointBlock::unskinned());
+        self.morph_block.set_data(&MorphBlock::none());
+    }
+}
@@ -1,6 +1,16 @@
 use crate::gmaths::*;
+use crate::graphics::culling::Frustum;
 use crate::app;
 
+// How the camera's view is lit.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RenderPath {
+    // Every light is evaluated while drawing, limited to MAX_LIGHTS.
+    Forward,
+    // Opaque materials are written to a G-buffer and lit per cluster, transparent materials are drawn forward.
+    Deferred
+}
+
 #[derive(Clone)]
 pub struct Camera {
     translation: Float3,
@@ -10,6 +20,7 @@ pub struct Camera {
     fov: f32,
     near: f32,
     far: f32,
+    render_path: RenderPath,
 
     proj_dirty: bool,
     proj_matrix: Float4x4,
@@ -34,6 +45,7 @@ impl Camera {
             fov: 60.0,
             near: 0.1,
             far: 300.0,
+            render_path: RenderPath::Forward,
             proj_dirty: true,
             proj_matrix: Float4x4::identity(),
             view_dirty: false,
@@ -68,16 +80,29 @@ impl Camera {
         self.proj_dirty = true;
     }
 
+    pub fn get_render_path(&self) -> RenderPath {
+        self.render_path
+    }
+
+    pub fn set_render_path(&mut self, render_path: RenderPath) {
+        self.render_path = render_path;
+    }
+
     pub fn set_aspect_ratio(&mut self, aspect_ratio: Option<f32>) {
         self.aspect_ratio = aspect_ratio;
     }
 
+    // Falls back to the window's aspect ratio when none is set.
+    pub fn get_aspect_ratio(&self) -> f32 {
+        self.aspect_ratio.unwrap_or_else(|| -> f32 {
+            let dimensions = app().graphics().dimensions();
+            dimensions.x as f32 / dimensions.y as f32
+        })
+    }
+
     pub fn get_proj_matrix(&mut self) -> Float4x4 {
         if self.proj_dirty {
-            let aspect_ratio = self.aspect_ratio.unwrap_or_else(|| -> f32 {
-                let dimensions = app().graphics().dimensions();
-                dimensions.x as f32 / dimensions.y as f32
-            });
+            let aspect_ratio = self.get_aspect_ratio();
             
             self.proj_matrix = Float4x4::perspective(self.fov, aspect_ratio, self.near, self.far);
         }
@@ -118,6 +143,14 @@ impl Camera {
 
         self.view_matrix
     }
+
+    pub fn get_view_proj_matrix(&mut self) -> Float4x4 {
+        self.get_proj_matrix() * self.get_view_matrix()
+    }
+
+    pub fn get_frustum(&mut self) -> Frustum {
+        Frustum::from_view_proj(&self.get_view_proj_matrix())
+    }
 }
 
 impl CLCamera {
@@ -0,0 +1,130 @@
+use crate::gmaths::*;
+
+use std::ops::AddAssign;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct BoundingBox {
+    pub min: Float3,
+    pub max: Float3
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct BoundingSphere {
+    pub center: Float3,
+    pub radius: f32
+}
+
+// Planes are stored as (normal, distance) with normals pointing inwards.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [Float4; 6]
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub drawn_instances: usize,
+    pub culled_instances: usize,
+    pub draw_calls: usize
+}
+
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, other: RenderStats) {
+        self.drawn_instances += other.drawn_instances;
+        self.culled_instances += other.culled_instances;
+        self.draw_calls += other.draw_calls;
+    }
+}
+
+impl BoundingBox {
+    pub fn new(min: Float3, max: Float3) -> Self {
+        BoundingBox {
+            min: min,
+            max: max
+        }
+    }
+
+    pub fn center(&self) -> Float3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extents(&self) -> Float3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn merge(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    // Transforms the box and refits it around the result, the box stays axis aligned.
+    pub fn transformed(&self, matrix: &Float4x4) -> BoundingBox {
+        let center = self.center();
+        let extents = self.extents();
+
+        let mut world_center = [0.0f32; 3];
+        let mut world_extents = [0.0f32; 3];
+        for row in 0..3 {
+            world_center[row] = matrix.at(0, row) * center.x + matrix.at(1, row) * center.y + matrix.at(2, row) * center.z + matrix.at(3, row);
+            world_extents[row] = matrix.at(0, row).abs() * extents.x + matrix.at(1, row).abs() * extents.y + matrix.at(2, row).abs() * extents.z;
+        }
+
+        let world_center = Float3::new(world_center[0], world_center[1], world_center[2]);
+        let world_extents = Float3::new(world_extents[0], world_extents[1], world_extents[2]);
+        BoundingBox::new(world_center - world_extents, world_center + world_extents)
+    }
+
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere {
+            center: self.center(),
+            radius: self.extents().magnitude()
+        }
+    }
+}
+
+impl Frustum {
+    // Gribb-Hartmann plane extraction, works for any projection * view matrix.
+    pub fn from_view_proj(view_proj: &Float4x4) -> Self {
+        let row = |y: usize| Float4::new(view_proj.at(0, y), view_proj.at(1, y), view_proj.at(2, y), view_proj.at(3, y));
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+
+        let mut planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r3 + r2, r3 - r2];
+        for plane in planes.iter_mut() {
+            let length = Float3::new(plane.x, plane.y, plane.z).magnitude();
+            if length > 0.0 {
+                *plane = *plane * (1.0 / length);
+            }
+        }
+
+        Frustum {
+            planes: planes
+        }
+    }
+
+    pub fn planes(&self) -> &[Float4; 6] {
+        &self.planes
+    }
+
+    fn distance(plane: &Float4, point: Float3) -> f32 {
+        plane.x * point.x + plane.y * point.y + plane.z * point.z + plane.w
+    }
+
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| Self::distance(plane, sphere.center) >= -sphere.radius)
+    }
+
+    // Tests the corner furthest along each plane normal.
+    pub fn intersects_box(&self, bounds: &BoundingBox) -> bool {
+        self.planes.iter().all(|plane| {
+            let corner = Float3::new(
+                if plane.x >= 0.0 { bounds.max.x } else { bounds.min.x },
+                if plane.y >= 0.0 { bounds.max.y } else { bounds.min.y },
+                if plane.z >= 0.0 { bounds.max.z } else { bounds.min.z }
+            );
+            Self::distance(plane, corner) >= 0.0
+        })
+    }
+
+    // Sphere test first as a cheap early out, then the tighter box test.
+    pub fn is_visible(&self, bounds: &BoundingBox) -> bool {
+        self.intersects_sphere(&bounds.bounding_sphere()) && self.intersects_box(bounds)
+    }
+}
@@ -0,0 +1,298 @@
+use crate::gmaths::*;
+use crate::app;
+use crate::graphics::opengl::*;
+use crate::graphics::animation::DeformationBuffers;
+use crate::graphics::render_graph::*;
+use crate::graphics::shadows::{ShadowRenderer, transform_point};
+use crate::graphics::environment::EnvironmentRenderer;
+use crate::graphics::culling::Frustum;
+use crate::graphics::{FrameGraph, FrameTextures, MaterialFilter, draw_models};
+
+// Lights uploaded for the deferred lighting pass, forward passes only see the first MAX_LIGHTS.
+pub const MAX_DEFERRED_LIGHTS: usize = 1024;
+
+// Must match the defines in deferred_lighting_frag.glsl.
+const LIGHT_TEXELS: usize = 5;
+const CLUSTER_COUNT_X: usize = 16;
+const CLUSTER_COUNT_Y: usize = 9;
+const CLUSTER_COUNT_Z: usize = 24;
+const CLUSTER_TEXTURE_WIDTH: usize = 1024;
+// Counts are packed into the low 8 bits of a cluster.
+const MAX_LIGHTS_PER_CLUSTER: usize = 255;
+
+const GBUFFER_SLOTS: [(&str, u32); 6] = [
+    ("gPosition", 0),
+    ("gBaseColor", 1),
+    ("gNormal", 2),
+    ("gMRO", 3),
+    ("gEmission", 4),
+    ("gDepth", 15)
+];
+const LIGHT_DATA_SLOT: u32 = 13;
+const CLUSTER_LIGHTS_SLOT: u32 = 14;
+
+// Same layout as the baker, see bake_frag.glsl.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct GBuffer {
+    pub position: RenderTextureId,
+    pub base_color: RenderTextureId,
+    pub normal: RenderTextureId,
+    pub mro: RenderTextureId,
+    pub emission: RenderTextureId
+}
+
+// View the lights are assigned to clusters of, slices are exponential in depth.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ClusterView {
+    pub view: Float4x4,
+    pub projection: Float4x4,
+    pub near: f32,
+    pub far: f32
+}
+
+// Lights opaque materials from a G-buffer, lights are assigned to view space clusters on the CPU.
+pub struct DeferredRenderer {
+    gbuffer_program: GLShaderProgram,
+    lighting_program: GLShaderProgram,
+    light_texture: GLTexture2D,
+    cluster_texture: GLTexture2D,
+    clusters: Vec<Vec<u32>>,
+    cluster_view: Option<ClusterView>,
+
+    vao: GLVAO
+}
+
+impl DeferredRenderer {
+    pub fn new() -> Self {
+        let mut gbuffer_program = Self::create_program("vert.glsl", "bake_frag.glsl");
+        gbuffer_program.bind(); {
+            CameraBlock::setup_program(&mut gbuffer_program);
+            DeformationBuffers::setup_program(&mut gbuffer_program);
+            GLMaterial::setup_program(&mut gbuffer_program);
+        } gbuffer_program.unbind();
+
+        let mut lighting_program = Self::create_program("quad_vert.glsl", "deferred_lighting_frag.glsl");
+        lighting_program.bind(); {
+            CameraBlock::setup_program(&mut lighting_program);
+            ShadowRenderer::setup_program(&mut lighting_program);
+            EnvironmentRenderer::setup_program(&mut lighting_program);
+            for (name, slot) in GBUFFER_SLOTS.iter() {
+                lighting_program.set_sampler_slot(&String::from(*name), *slot as i32);
+            }
+            lighting_program.set_sampler_slot(&String::from("lightData"), LIGHT_DATA_SLOT as i32);
+            lighting_program.set_sampler_slot(&String::from("clusterLights"), CLUSTER_LIGHTS_SLOT as i32);
+        } lighting_program.unbind();
+
+        DeferredRenderer {
+            gbuffer_program: gbuffer_program,
+            lighting_program: lighting_program,
+            light_texture: Self::create_data_texture(GLTextureFormat::RGBA32F),
+            cluster_texture: Self::create_data_texture(GLTextureFormat::R32UI),
+            clusters: vec![Vec::new(); CLUSTER_COUNT_X * CLUSTER_COUNT_Y * CLUSTER_COUNT_Z],
+            cluster_view: None,
+            vao: GLVAO::new()
+        }
+    }
+
+    fn create_program(vertex_shader_name: &str, fragment_shader_name: &str) -> GLShaderProgram {
+        let vertex_shader_src = app().resources().get_text(format!("assets/shaders/{}", vertex_shader_name));
+        let vertex_shader = GLShader::new(GLShaderType::VERTEX, &vertex_shader_src.as_ref());
+        let fragment_shader_src = app().resources().get_text(format!("assets/shaders/{}", fragment_shader_name));
+        let fragment_shader = GLShader::new(GLShaderType::FRAGMENT, &fragment_shader_src.as_ref());
+        GLShaderProgram::new(&vertex_shader, &fragment_shader)
+    }
+
+    // Read with texelFetch only.
+    fn create_data_texture(format: GLTextureFormat) -> GLTexture2D {
+        let mut desc = GLTextureDesc::new(format);
+        desc.sampler.wrap_s = GLWrapMode::ClampToEdge;
+        desc.sampler.wrap_t = GLWrapMode::ClampToEdge;
+        desc.sampler.min_filter = GLFilterMode::Nearest;
+        desc.sampler.mag_filter = GLFilterMode::Nearest;
+        desc.sampler.mip_filter = None;
+
+        let zeros = [0u32; LIGHT_TEXELS * 4];
+        GLTexture2D::from_data(Int2::new(1, 1), zeros.as_ptr() as *const c_void, &desc)
+    }
+
+    // Adds the G-buffer and lighting passes, the lit opaque scene ends up in the scene color and depth.
+    pub fn add_passes(graph: &mut FrameGraph, textures: &FrameTextures, shadow_maps: RenderTextureId, frustum: Frustum) -> GBuffer {
+        let textures = *textures;
+        let dimensions = graph.dimensions(textures.scene_color).unwrap();
+
+        let mut pass = graph.add_pass("G-Buffer");
+        let gbuffer = GBuffer {
+            position: pass.create("gbuffer_position", RenderTextureDesc::new(dimensions, GLTextureFormat::RGBA32F)),
+            base_color: pass.create("gbuffer_base_color", RenderTextureDesc::new(dimensions, GLTextureFormat::RGBA16F)),
+            normal: pass.create("gbuffer_normal", RenderTextureDesc::new(dimensions, GLTextureFormat::RGBA16F)),
+            mro: pass.create("gbuffer_mro", RenderTextureDesc::new(dimensions, GLTextureFormat::RGBA16F)),
+            emission: pass.create("gbuffer_emission", RenderTextureDesc::new(dimensions, GLTextureFormat::RGBA16F))
+        };
+        pass.write(textures.scene_depth);
+        pass.execute(move |graphics, context| {
+            context.bind_target(&[gbuffer.position, gbuffer.base_color, gbuffer.normal, gbuffer.mro, gbuffer.emission], Some(textures.scene_depth)); {
+                gl_clear();
+
+                graphics.deferred.gbuffer_program.bind(); {
+                    graphics.stats = draw_models(&graphics.static_models, &mut graphics.dynamic_models, &graphics.scene, &graphics.deformation, &frustum, MaterialFilter::Opaque);
+                } graphics.deferred.gbuffer_program.unbind();
+            } context.unbind_target();
+        });
+
+        let mut pass = graph.add_pass("Deferred Lighting");
+        for texture in [gbuffer.position, gbuffer.base_color, gbuffer.normal, gbuffer.mro, gbuffer.emission, textures.scene_depth, shadow_maps] {
+            pass.read(texture);
+        }
+        pass.write(textures.scene_color);
+        pass.execute(move |graphics, context| {
+            gl_disable_depth();
+            context.bind_target(&[textures.scene_color], None); {
+                gl_clear();
+
+                let inputs = [gbuffer.position, gbuffer.base_color, gbuffer.normal, gbuffer.mro, gbuffer.emission, textures.scene_depth];
+                for (input, (_, slot)) in inputs.iter().zip(GBUFFER_SLOTS.iter()) {
+                    context.texture(*input).bind(*slot);
+                }
+                graphics.deferred.draw_lighting(&graphics.shadows, &graphics.environment);
+            } context.unbind_target();
+            gl_enable_depth();
+        });
+
+        gbuffer
+    }
+
+    // Light data must be in world space with shadows assigned, lights past MAX_LIGHTS_PER_CLUSTER in a cluster are dropped.
+    pub fn assign_lights(&mut self, lights: &[LightData], cluster_view: &ClusterView) {
+        for cluster in self.clusters.iter_mut() {
+            cluster.clear();
+        }
+
+        let slice_scale = CLUSTER_COUNT_Z as f32 / (cluster_view.far / cluster_view.near).ln();
+        let slice = |depth: f32| -> usize {
+            ((depth.max(cluster_view.near) / cluster_view.near).ln() * slice_scale).clamp(0.0, (CLUSTER_COUNT_Z - 1) as f32) as usize
+        };
+
+        for (light_idx, light) in lights.iter().enumerate() {
+            // Directional lights and lights without a range reach every cluster.
+            let bounds = if light.kind == 0 || light.range <= 0.0 {
+                Some(((0, 0, 0), (CLUSTER_COUNT_X - 1, CLUSTER_COUNT_Y - 1, CLUSTER_COUNT_Z - 1)))
+            } else {
+                Self::light_cluster_bounds(light.position, light.range, cluster_view, &slice)
+            };
+
+            let ((min_x, min_y, min_z), (max_x, max_y, max_z)) = match bounds {
+                Some(bounds) => bounds,
+                None => continue
+            };
+
+            for z in min_z..=max_z {
+                for y in min_y..=max_y {
+                    for x in min_x..=max_x {
+                        let cluster = &mut self.clusters[x + (y + z * CLUSTER_COUNT_Y) * CLUSTER_COUNT_X];
+                        if cluster.len() < MAX_LIGHTS_PER_CLUSTER {
+                            cluster.push(light_idx as u32);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.upload_lights(lights);
+        self.upload_clusters();
+        self.cluster_view = Some(*cluster_view);
+    }
+
+    // Clusters overlapping the view space bounding box of the light's sphere, None when it is outside the view.
+    fn light_cluster_bounds(position: Float3, range: f32, cluster_view: &ClusterView, slice: &dyn Fn(f32) -> usize) -> Option<((usize, usize, usize), (usize, usize, usize))> {
+        let center = transform_point(&cluster_view.view, position);
+        let min_depth = -center.z - range;
+        let max_depth = -center.z + range;
+        if max_depth < cluster_view.near || min_depth > cluster_view.far {
+            return None;
+        }
+
+        let (mut min_ndc, mut max_ndc) = (Float2::new(-1.0, -1.0), Float2::new(1.0, 1.0));
+        // Corners behind the near plane don't project, the light then covers the whole screen.
+        if min_depth > cluster_view.near {
+            min_ndc = Float2::new(f32::MAX, f32::MAX);
+            max_ndc = Float2::new(f32::MIN, f32::MIN);
+            for corner in 0..8 {
+                let offset = Float3::new(
+                    if corner & 1 == 0 { -range } else { range },
+                    if corner & 2 == 0 { -range } else { range },
+                    if corner & 4 == 0 { -range } else { range }
+                );
+                let ndc = transform_point(&cluster_view.projection, center + offset);
+                min_ndc = Float2::new(min_ndc.x.min(ndc.x), min_ndc.y.min(ndc.y));
+                max_ndc = Float2::new(max_ndc.x.max(ndc.x), max_ndc.y.max(ndc.y));
+            }
+
+            if min_ndc.x > 1.0 || min_ndc.y > 1.0 || max_ndc.x < -1.0 || max_ndc.y < -1.0 {
+                return None;
+            }
+        }
+
+        let tile = |ndc: f32, count: usize| -> usize {
+            ((ndc * 0.5 + 0.5) * count as f32).clamp(0.0, (count - 1) as f32) as usize
+        };
+
+        Some((
+            (tile(min_ndc.x, CLUSTER_COUNT_X), tile(min_ndc.y, CLUSTER_COUNT_Y), slice(min_depth)),
+            (tile(max_ndc.x, CLUSTER_COUNT_X), tile(max_ndc.y, CLUSTER_COUNT_Y), slice(max_depth))
+        ))
+    }
+
+    fn upload_lights(&self, lights: &[LightData]) {
+        let mut data = Vec::with_capacity(lights.len().max(1) * LIGHT_TEXELS * 4);
+        for light in lights.iter() {
+            data.extend_from_slice(&[
+                light.position.x, light.position.y, light.position.z, light.range,
+                light.direction.x, light.direction.y, light.direction.z, light.intensity,
+                light.color.x, light.color.y, light.color.z, light.kind as f32,
+                light.angle_scale, light.angle_offset, light.shadow_index as f32, light.shadow_bias,
+                light.shadow_normal_bias, 0.0, 0.0, 0.0
+            ]);
+        }
+        if lights.is_empty() {
+            data.resize(LIGHT_TEXELS * 4, 0.0);
+        }
+
+        let rows = lights.len().max(1) as i32;
+        self.light_texture.set_data(Int2::new(LIGHT_TEXELS as i32, rows), data.as_ptr() as *const c_void);
+    }
+
+    // The grid comes first, every cluster stores the offset of its light indices after the grid and their count.
+    fn upload_clusters(&self) {
+        let mut data = Vec::with_capacity(self.clusters.len() * 2);
+        let mut offset = 0;
+        for cluster in self.clusters.iter() {
+            data.push((offset << 8) as u32 | cluster.len() as u32);
+            offset += cluster.len();
+        }
+        for cluster in self.clusters.iter() {
+            data.extend_from_slice(cluster);
+        }
+
+        let rows = (data.len() + CLUSTER_TEXTURE_WIDTH - 1) / CLUSTER_TEXTURE_WIDTH;
+        data.resize(rows * CLUSTER_TEXTURE_WIDTH, 0);
+        self.cluster_texture.set_data(Int2::new(CLUSTER_TEXTURE_WIDTH as i32, rows as i32), data.as_ptr() as *const c_void);
+    }
+
+    // Draws the lit G-buffer into the bound target, expects the G-buffer and camera block to be bound.
+    fn draw_lighting(&mut self, shadows: &ShadowRenderer, environment: &EnvironmentRenderer) {
+        let cluster_view = self.cluster_view.expect("Failed to draw deferred lighting. (Lights were not assigned)");
+
+        self.lighting_program.bind(); {
+            shadows.bind();
+            environment.bind(&mut self.lighting_program);
+            self.light_texture.bind(LIGHT_DATA_SLOT);
+            self.cluster_texture.bind(CLUSTER_LIGHTS_SLOT);
+            self.lighting_program.set_float(&String::from("clusterNear"), cluster_view.near);
+            self.lighting_program.set_float(&String::from("clusterSliceScale"), CLUSTER_COUNT_Z as f32 / (cluster_view.far / cluster_view.near).ln());
+
+            self.vao.bind(); {
+                gl_draw_arrays(gl::TRIANGLES, 0, 3);
+            } self.vao.unbind();
+        } self.lighting_program.unbind();
+    }
+}
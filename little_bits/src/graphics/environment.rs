@@ -0,0 +1,267 @@
+use crate::app;
+use crate::resources::HdrImage;
+use crate::graphics::opengl::*;
+use crate::Shared;
+
+// Texture slots after the shadow maps.
+const IRRADIANCE_SLOT: u32 = 10;
+const PREFILTER_SLOT: u32 = 11;
+const BRDF_LUT_SLOT: u32 = 12;
+const SKYBOX_SLOT: u32 = 0;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct EnvironmentSettings {
+    pub cubemap_size: u32,
+    pub irradiance_size: u32,
+    pub prefilter_size: u32,
+    // Roughness 0 is stored in the first mip, roughness 1 in the last.
+    pub prefilter_mip_levels: u32,
+    pub intensity: f32,
+    pub draw_skybox: bool
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        EnvironmentSettings {
+            cubemap_size: 512,
+            irradiance_size: 32,
+            prefilter_size: 128,
+            prefilter_mip_levels: 5,
+            intensity: 1.0,
+            draw_skybox: true
+        }
+    }
+}
+
+// Baked image based lighting of a single environment.
+pub struct Environment {
+    settings: EnvironmentSettings,
+    cubemap: GLTextureCube,
+    irradiance_map: GLTextureCube,
+    prefilter_map: GLTextureCube
+}
+
+pub struct EnvironmentRenderer {
+    equirect_program: GLShaderProgram,
+    irradiance_program: GLShaderProgram,
+    prefilter_program: GLShaderProgram,
+    skybox_program: GLShaderProgram,
+    brdf_lut: GLRenderTarget,
+    target: GLRenderTarget,
+    vao: GLVAO,
+    environment: Option<Environment>
+}
+
+impl Environment {
+    pub fn settings(&self) -> &EnvironmentSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut EnvironmentSettings {
+        &mut self.settings
+    }
+
+    pub fn cubemap(&self) -> &GLTextureCube {
+        &self.cubemap
+    }
+
+    pub fn irradiance_map(&self) -> &GLTextureCube {
+        &self.irradiance_map
+    }
+
+    pub fn prefilter_map(&self) -> &GLTextureCube {
+        &self.prefilter_map
+    }
+}
+
+impl EnvironmentRenderer {
+    pub fn new() -> Self {
+        let equirect_program = Self::create_program("cubemap_vert.glsl", "equirect_to_cube_frag.glsl");
+        let irradiance_program = Self::create_program("cubemap_vert.glsl", "irradiance_frag.glsl");
+        let prefilter_program = Self::create_program("cubemap_vert.glsl", "prefilter_frag.glsl");
+        let mut skybox_program = Self::create_program("skybox_vert.glsl", "skybox_frag.glsl");
+        skybox_program.bind(); {
+            CameraBlock::setup_program(&mut skybox_program);
+            skybox_program.set_sampler_slot(&String::from("environmentMap"), SKYBOX_SLOT as i32);
+        } skybox_program.unbind();
+
+        let mut target_desc = GLRenderTargetDesc::new(1, 1);
+        target_desc.depth = GLDepthAttachment::None;
+
+        let vao = GLVAO::new();
+        let brdf_lut = Self::bake_brdf_lut(&vao, 512);
+
+        EnvironmentRenderer {
+            equirect_program: equirect_program,
+            irradiance_program: irradiance_program,
+            prefilter_program: prefilter_program,
+            skybox_program: skybox_program,
+            brdf_lut: brdf_lut,
+            target: GLRenderTarget::from_desc(&target_desc),
+            vao: vao,
+            environment: None
+        }
+    }
+
+    fn create_program(vertex_shader_name: &str, fragment_shader_name: &str) -> GLShaderProgram {
+        let vertex_shader_src = app().resources().get_text(format!("assets/shaders/{}", vertex_shader_name));
+        let vertex_shader = GLShader::new(GLShaderType::VERTEX, &vertex_shader_src.as_ref());
+        let fragment_shader_src = app().resources().get_text(format!("assets/shaders/{}", fragment_shader_name));
+        let fragment_shader = GLShader::new(GLShaderType::FRAGMENT, &fragment_shader_src.as_ref());
+        GLShaderProgram::new(&vertex_shader, &fragment_shader)
+    }
+
+    pub fn setup_program(shader_program: &mut GLShaderProgram) {
+        shader_program.set_sampler_slot(&String::from("irradianceMap"), IRRADIANCE_SLOT as i32);
+        shader_program.set_sampler_slot(&String::from("prefilterMap"), PREFILTER_SLOT as i32);
+        shader_program.set_sampler_slot(&String::from("brdfLUT"), BRDF_LUT_SLOT as i32);
+    }
+
+    // Split sum scale and bias of the specular BRDF, indexed by (NdotV, roughness).
+    fn bake_brdf_lut(vao: &GLVAO, size: usize) -> GLRenderTarget {
+        let program = Self::create_program("quad_vert.glsl", "brdf_lut_frag.glsl");
+
+        let mut desc = GLRenderTargetDesc::new(size, size);
+        desc.color_formats = vec![GLTextureFormat::RG16F];
+        desc.depth = GLDepthAttachment::None;
+        let target = GLRenderTarget::from_desc(&desc);
+
+        target.bind(); {
+            gl_viewport(target.dimensions());
+            gl_clear();
+
+            program.bind(); {
+                Self::draw_fullscreen(vao);
+            } program.unbind();
+        } target.unbind();
+
+        target
+    }
+
+    fn draw_fullscreen(vao: &GLVAO) {
+        vao.bind(); {
+            gl_draw_arrays(gl::TRIANGLES, 0, 3);
+        } vao.unbind();
+    }
+
+    // Renders every face of every mip with the program, which receives the face index.
+    fn render_cube(target: &mut GLRenderTarget, vao: &GLVAO, cubemap: &GLTextureCube, program: &mut GLShaderProgram, levels: u32, mut per_level: impl FnMut(&mut GLShaderProgram, u32)) {
+        for level in 0..levels {
+            let level_size = (cubemap.size() >> level).max(1) as usize;
+            target.resize(level_size, level_size);
+
+            program.bind(); {
+                per_level(program, level);
+
+                for face in 0..6 {
+                    target.set_cube_face(GLRenderAttachment::Color(0), cubemap, face, level);
+                    target.bind(); {
+                        gl_viewport(target.dimensions());
+                        program.set_int(&String::from("face"), face as i32);
+                        Self::draw_fullscreen(vao);
+                    } target.unbind();
+                }
+            } program.unbind();
+        }
+    }
+
+    // Converts an equirectangular image to a cubemap and bakes its irradiance and prefiltered specular maps.
+    pub fn set_environment(&mut self, image: &Shared<HdrImage>, settings: EnvironmentSettings) {
+        let image = image.as_ref();
+
+        let source_format = match image.channel_count {
+            3 => GLTextureFormat::RGB32F,
+            4 => GLTextureFormat::RGBA32F,
+            _ => panic!("Failed to set environment. (Image must have 3 or 4 channels)")
+        };
+        let mut source_desc = GLTextureDesc::new(source_format);
+        source_desc.sampler.wrap_t = GLWrapMode::ClampToEdge;
+        source_desc.sampler.mip_filter = None;
+        let source = GLTexture2D::from_f32(image.dimensions, &image.data, &source_desc);
+
+        let cubemap = GLTextureCube::new(settings.cubemap_size as i32, &GLTextureDesc::new(GLTextureFormat::RGBA16F));
+
+        let mut irradiance_desc = GLTextureDesc::new(GLTextureFormat::RGBA16F);
+        irradiance_desc.sampler.mip_filter = None;
+        let irradiance_map = GLTextureCube::new(settings.irradiance_size as i32, &irradiance_desc);
+
+        let mut prefilter_desc = GLTextureDesc::new(GLTextureFormat::RGBA16F);
+        prefilter_desc.mip_levels = Some(settings.prefilter_mip_levels);
+        let prefilter_map = GLTextureCube::new(settings.prefilter_size as i32, &prefilter_desc);
+
+        source.bind(0);
+        Self::render_cube(&mut self.target, &self.vao, &cubemap, &mut self.equirect_program, 1, |program, _| {
+            program.set_sampler_slot(&String::from("equirectangularMap"), 0);
+        });
+        // Mips of the environment are sampled while convolving to reduce noise.
+        cubemap.gen_mips();
+
+        cubemap.bind(0);
+        Self::render_cube(&mut self.target, &self.vao, &irradiance_map, &mut self.irradiance_program, 1, |program, _| {
+            program.set_sampler_slot(&String::from("environmentMap"), 0);
+        });
+
+        let prefilter_levels = prefilter_map.mip_levels();
+        let source_size = settings.cubemap_size as f32;
+        cubemap.bind(0);
+        Self::render_cube(&mut self.target, &self.vao, &prefilter_map, &mut self.prefilter_program, prefilter_levels, |program, level| {
+            let roughness = if prefilter_levels > 1 { level as f32 / (prefilter_levels - 1) as f32 } else { 0.0 };
+            program.set_sampler_slot(&String::from("environmentMap"), 0);
+            program.set_float(&String::from("roughness"), roughness);
+            program.set_float(&String::from("resolution"), source_size);
+        });
+
+        self.environment = Some(Environment {
+            settings: settings,
+            cubemap: cubemap,
+            irradiance_map: irradiance_map,
+            prefilter_map: prefilter_map
+        });
+    }
+
+    pub fn clear_environment(&mut self) {
+        self.environment = None;
+    }
+
+    pub fn environment(&self) -> Option<&Environment> {
+        self.environment.as_ref()
+    }
+
+    pub fn environment_mut(&mut self) -> Option<&mut Environment> {
+        self.environment.as_mut()
+    }
+
+    // Binds the maps and sets the lighting uniforms of the PBR program, which has to be bound.
+    pub fn bind(&self, shader_program: &mut GLShaderProgram) {
+        match &self.environment {
+            Some(environment) => {
+                environment.irradiance_map.bind(IRRADIANCE_SLOT);
+                environment.prefilter_map.bind(PREFILTER_SLOT);
+                self.brdf_lut.get_texture_ref(GLRenderAttachment::Color(0)).unwrap().bind(BRDF_LUT_SLOT);
+
+                shader_program.set_bool(&String::from("hasEnvironment"), true);
+                shader_program.set_float(&String::from("environmentIntensity"), environment.settings.intensity);
+                shader_program.set_float(&String::from("prefilterMaxLod"), (environment.prefilter_map.mip_levels() - 1) as f32);
+            },
+            None => {
+                shader_program.set_bool(&String::from("hasEnvironment"), false);
+            }
+        }
+    }
+
+    // Draws the environment behind all geometry, expects the camera block to be bound.
+    pub fn draw_skybox(&mut self) {
+        let environment = match &self.environment {
+            Some(environment) if environment.settings.draw_skybox => environment,
+            _ => return
+        };
+
+        gl_depth_func(gl::LEQUAL);
+        self.skybox_program.bind(); {
+            environment.cubemap.bind(SKYBOX_SLOT);
+            self.skybox_program.set_float(&String::from("intensity"), environment.settings.intensity);
+            Self::draw_fullscreen(&self.vao);
+        } self.skybox_program.unbind();
+        gl_depth_func(gl::LESS);
+    }
+}
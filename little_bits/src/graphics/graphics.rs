@@ -2,14 +2,17 @@ extern crate glfw;
 use glfw::{Action, Context, Key, Glfw, Window, WindowEvent};
 
 use crate::system::*;
-use crate::resources::Model;
+use crate::resources::{Model, LightKind};
 use crate::application::*;
 use crate::app;
 use crate::Shared;
+use crate::Timer;
 
 use std::mem;
+use std::path::Path;
 use std::sync::mpsc::Receiver;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::cmp::Ordering;
 
 pub extern crate imgui;
 
@@ -23,11 +26,102 @@ mod nn;
 pub mod camera;
 pub use camera::*;
 
+pub mod culling;
+pub use culling::*;
+
+pub mod scene;
+pub use scene::*;
+
+pub mod light;
+pub use light::*;
+
+pub mod shadows;
+pub use shadows::*;
+
+pub mod environment;
+pub use environment::*;
+
+pub mod tonemapping;
+pub use tonemapping::*;
+
+pub mod post;
+pub use post::*;
+
+pub mod render_graph;
+pub use render_graph::*;
+
+pub mod deferred;
+pub use deferred::*;
+
+pub mod animation;
+pub use animation::*;
+
 use self::nn::BakeParameters;
 
-#[derive(PartialEq, Clone, Debug, Copy)]
+// The graph of a rendered frame, passes get access to the graphics system.
+pub type FrameGraph = RenderGraph<'static, Graphics>;
+
+// Points in the frame where games can add their own passes.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RenderStage {
+    // After the scene is resolved, the scene color is still linear HDR.
+    BeforePostProcess,
+    // After tonemapping and the post effects, before the debug UI.
+    AfterPostProcess
+}
+
+// Textures of the frame graph passes can read and write.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct FrameTextures {
+    pub scene_color: RenderTextureId,
+    pub scene_depth: RenderTextureId,
+    pub backbuffer: RenderTextureId
+}
+
+#[derive(Clone)]
 pub struct ModelInstance {
-    pub transform: Transform
+    pub transform: Transform,
+    pub parent: Option<SceneNodeId>,
+    // Set for models with skins or animations.
+    pub animator: Option<Animator>
+}
+
+// Which meshes a draw includes, depth only draws include the opaque meshes without binding their material.
+// Alpha tested draws include the masked and blended meshes, blended shadow casters are cut off like masked ones.
+// Transparent meshes are drawn separately, see draw_sorted_models.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum MaterialFilter {
+    DepthOnly,
+    AlphaTested,
+    Opaque
+}
+
+impl MaterialFilter {
+    fn includes(&self, material: &GLMaterial) -> bool {
+        match self {
+            MaterialFilter::DepthOnly => !material.is_alpha_tested(),
+            MaterialFilter::AlphaTested => material.is_alpha_tested(),
+            MaterialFilter::Opaque => !material.is_transparent()
+        }
+    }
+}
+
+struct StaticModel {
+    model: GLModel,
+    // Cached world matrix and bounds of every placement, per mesh. Rebuilt when the scene changes.
+    mesh_instances: Vec<Vec<(Float4x4, BoundingBox)>>
+}
+
+impl StaticModel {
+    fn new(model: &Shared<Model>) -> Self {
+        let gl_model = GLModel::new(model);
+        let mesh_count = gl_model.meshes.len();
+
+        StaticModel {
+            model: gl_model,
+            mesh_instances: vec![Vec::new(); mesh_count]
+        }
+    }
 }
 
 pub struct Graphics {
@@ -40,7 +134,26 @@ pub struct Graphics {
 
     render_camera: Shared<Camera>,
     dynamic_models: HashMap<*const Model, (GLModel, Vec<Shared<ModelInstance>>)>,
-    shader_program: GLShaderProgram
+    static_models: HashMap<*const Model, StaticModel>,
+    scene: Scene,
+    lights: Vec<Shared<Light>>,
+    // Lights imported with static model instances, kept alive as long as their node.
+    scene_lights: Vec<(SceneNodeId, Shared<Light>)>,
+    shader_program: GLShaderProgram,
+    camera_block: GLUniformBuffer<CameraBlock>,
+    light_block: GLUniformBuffer<LightBlock>,
+    deformation: DeformationBuffers,
+    animation_timer: Timer,
+    shadows: ShadowRenderer,
+    environment: EnvironmentRenderer,
+    hdr: HdrRenderer,
+    deferred: DeferredRenderer,
+    post: PostProcessRenderer,
+    post_stack: PostProcessStack,
+    graph_resources: RenderGraphResources,
+    pending_screenshot: Option<String>,
+    stats: RenderStats,
+    graph_stats: RenderGraphStats
 }
 
 impl System for Graphics {
@@ -64,6 +177,7 @@ impl System for Graphics {
         gl_init(&mut window);
         gl_enable_depth();
         gl_cull(gl::BACK);
+        gl_enable_seamless_cubemaps();
 
         let nn_baker = nn::Baker::new(&mut window);
 
@@ -72,11 +186,25 @@ impl System for Graphics {
         let fragment_shader_src = app().resources().get_text(String::from("assets/shaders/frag.glsl"));
         let fragment_shader = GLShader::new(GLShaderType::FRAGMENT, &fragment_shader_src.as_ref());
 
-        let shader_program = GLShaderProgram::new(&vertex_shader, &fragment_shader);
+        let mut shader_program = GLShaderProgram::new(&vertex_shader, &fragment_shader);
+        shader_program.bind(); {
+            CameraBlock::setup_program(&mut shader_program);
+            LightBlock::setup_program(&mut shader_program);
+            DeformationBuffers::setup_program(&mut shader_program);
+            ShadowRenderer::setup_program(&mut shader_program);
+            EnvironmentRenderer::setup_program(&mut shader_program);
+            GLMaterial::setup_program(&mut shader_program);
+        } shader_program.unbind();
+
+        let camera_block = GLUniformBuffer::new(CAMERA_BLOCK_BINDING);
+        let light_block = GLUniformBuffer::new(LIGHT_BLOCK_BINDING);
 
         let mut imgui = ImGui::new();
         imgui.resize(default_dimensions);
 
+        let (framebuffer_width, framebuffer_height) = window.get_framebuffer_size();
+        let hdr = HdrRenderer::new(Int2::new(framebuffer_width, framebuffer_height), 4);
+
         Box::new(Graphics {
             glfw: glfw,
             window: window,
@@ -85,7 +213,25 @@ impl System for Graphics {
             nn_baker: nn_baker,
             render_camera: Shared::empty(),
             dynamic_models: HashMap::new(),
-            shader_program: shader_program
+            static_models: HashMap::new(),
+            scene: Scene::new(),
+            lights: Vec::new(),
+            scene_lights: Vec::new(),
+            shader_program: shader_program,
+            camera_block: camera_block,
+            light_block: light_block,
+            deformation: DeformationBuffers::new(),
+            animation_timer: Timer::new(),
+            shadows: ShadowRenderer::new(),
+            environment: EnvironmentRenderer::new(),
+            hdr: hdr,
+            deferred: DeferredRenderer::new(),
+            post: PostProcessRenderer::new(),
+            post_stack: PostProcessStack::default(),
+            graph_resources: RenderGraphResources::new(),
+            pending_screenshot: None,
+            stats: RenderStats::default(),
+            graph_stats: RenderGraphStats::default()
         })
     }
 
@@ -133,6 +279,39 @@ impl Graphics {
         Int2::new(x, y)
     }
 
+    // Reads the back buffer of the window, call before the frame is presented.
+    pub fn read_pixels(&self) -> Image {
+        gl_read_buffer(gl::BACK);
+        gl_read_image(self.framebuffer_dimensions())
+    }
+
+    // Reads the linear scene color before exposure and tonemapping.
+    pub fn read_pixels_f32(&self) -> HdrImage {
+        match self.graph_resources.persistent_texture("scene_color") {
+            Some(scene_color) => scene_color.read_pixels_f32(),
+            None => panic!("Failed to read pixels. (No frame was rendered yet)")
+        }
+    }
+
+    // Writes the next presented frame to a .png, .hdr or .exr file.
+    pub fn capture_screenshot(&mut self, path: &str) {
+        self.pending_screenshot = Some(String::from(path));
+    }
+
+    fn framebuffer_dimensions(&self) -> Int2 {
+        let (x, y) = self.window.get_framebuffer_size();
+        Int2::new(x, y)
+    }
+
+    // Counts of the last rendered frame.
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    pub fn graph_stats(&self) -> RenderGraphStats {
+        self.graph_stats
+    }
+
     pub fn should_close(&self) -> bool {
         self.window.should_close()
     }
@@ -141,6 +320,43 @@ impl Graphics {
         self.imgui.new_frame()
     }
 
+    pub(crate) fn draw_debug_ui(&mut self, ui: &DebugUI) {
+        ui.window("Graphics")
+        .size([300.0, 300.0], imgui::Condition::FirstUseEver)
+        .collapsed(true, imgui::Condition::FirstUseEver)
+        .build(|| {
+            if ui.collapsing_header("Tonemapping", imgui::TreeNodeFlags::DEFAULT_OPEN) {
+                self.hdr.settings().debug_ui(ui);
+                ui.text(format!("Exposure: {:.2} EV", self.hdr.exposure()));
+            }
+
+            if ui.collapsing_header("Post Processing", imgui::TreeNodeFlags::empty()) {
+                self.post_stack.debug_ui(ui);
+            }
+
+            if ui.collapsing_header("Stats", imgui::TreeNodeFlags::empty()) {
+                ui.text(format!("Draw calls: {}", self.stats.draw_calls));
+                ui.text(format!("Drawn instances: {}", self.stats.drawn_instances));
+                ui.text(format!("Culled instances: {}", self.stats.culled_instances));
+                ui.text(format!("Passes: {} ({} culled)", self.graph_stats.passes, self.graph_stats.culled_passes));
+                ui.text(format!("Transient textures: {} ({} allocated)", self.graph_stats.transient_textures, self.graph_stats.physical_textures));
+            }
+        });
+    }
+
+    pub fn post_stack(&mut self) -> &mut PostProcessStack {
+        &mut self.post_stack
+    }
+
+    pub fn tonemap_settings(&mut self) -> &mut TonemapSettings {
+        self.hdr.settings()
+    }
+
+    // Exposure in stops applied to the last frame, follows the metered exposure in auto mode.
+    pub fn exposure(&self) -> f32 {
+        self.hdr.exposure()
+    }
+
     pub fn create_camera(&mut self) -> Shared<Camera> {
         let camera = Shared::new(Camera::new());
         camera
@@ -158,8 +374,19 @@ impl Graphics {
             None => Transform::new()
         };
 
+        let animator = {
+            let model_ref = model.as_ref();
+            let has_morph_targets = model_ref.meshes.iter().any(|mesh| !mesh.morph_targets.is_empty());
+            match model_ref.skins.is_empty() && model_ref.animations.is_empty() && !has_morph_targets {
+                true => None,
+                false => Some(Animator::new(&model))
+            }
+        };
+
         let model_instance = Shared::new(ModelInstance {
-            transform: transform
+            transform: transform,
+            parent: None,
+            animator: animator
         });
 
         match self.dynamic_models.get_mut(&model_ptr) {
@@ -178,6 +405,66 @@ impl Graphics {
         model_instance
     }
 
+    pub fn scene(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    // Bakes the image based lighting of an equirectangular HDR image, replacing the current environment.
+    pub fn set_environment(&mut self, image: &Shared<HdrImage>, settings: EnvironmentSettings) {
+        self.environment.set_environment(image, settings);
+    }
+
+    pub fn clear_environment(&mut self) {
+        self.environment.clear_environment();
+    }
+
+    pub fn environment(&mut self) -> Option<&mut Environment> {
+        self.environment.environment_mut()
+    }
+
+    // Uploads the materials of a model again, needed after its material properties change.
+    pub fn update_materials(&mut self, model: &Shared<Model>) {
+        let model_ptr = model.as_ptr();
+        let static_model = self.static_models.get(&model_ptr).map(|static_model| &static_model.model);
+        let dynamic_model = self.dynamic_models.get(&model_ptr).map(|(gl_model, _)| gl_model);
+
+        for gl_model in static_model.into_iter().chain(dynamic_model) {
+            for material in gl_model.materials.iter() {
+                material.update();
+            }
+        }
+    }
+
+    // Static instances are placed in the scene graph, their world matrices are only recomputed when the scene changes.
+    pub fn create_static_model_instance(&mut self, model: Shared<Model>, transform: Option<Transform>, parent: Option<SceneNodeId>) -> SceneNodeId {
+        let (root, nodes) = self.scene.instantiate_model_nodes(&model, transform.unwrap_or(Transform::new()), parent);
+
+        let model_ref = model.as_ref();
+        for (node_idx, node) in model_ref.nodes.iter().enumerate() {
+            if let (Some(light_idx), Some(scene_node)) = (node.light, nodes[node_idx]) {
+                let light = self.create_light(model_ref.lights[light_idx].kind);
+                *light.as_mut() = Light {
+                    parent: Some(scene_node),
+                    ..Light::from_punctual(&model_ref.lights[light_idx])
+                };
+                self.scene_lights.push((scene_node, light));
+            }
+        }
+
+        root
+    }
+
+    // The light is removed once the returned handle is dropped.
+    pub fn create_light(&mut self, kind: LightKind) -> Shared<Light> {
+        let light = Shared::new(Light::new(kind));
+        self.lights.push(light.clone());
+        light
+    }
+
+    pub fn remove_static_model_instance(&mut self, instance: SceneNodeId) {
+        self.scene.remove_node(instance);
+    }
+
     pub fn bake_nemo(&mut self, model: Shared<Model>, params: &BakeParameters) {
         let gl_model = GLModel::new(&model);
         self.nn_baker.bake(&gl_model, params, &mut self.window, &mut self.glfw);
@@ -212,19 +499,63 @@ impl Graphics {
     fn resize(&mut self, dimensions: Int2) {
         gl_viewport(dimensions);
         self.imgui.resize(dimensions);
+        self.hdr.resize(dimensions);
     }
 
     fn pre_render(&mut self) {
+        self.scene.update();
+
+        let delta_time = self.animation_timer.elapsed() as f32;
+        self.animation_timer.reset();
+        for (_, (_, instances)) in self.dynamic_models.iter() {
+            for instance in instances.iter() {
+                if let Some(animator) = instance.as_mut().animator.as_mut() {
+                    animator.update(delta_time);
+                }
+            }
+        }
+
+        if self.scene.take_changed() {
+            for (_, static_model) in self.static_models.iter_mut() {
+                for mesh_instances in static_model.mesh_instances.iter_mut() {
+                    mesh_instances.clear();
+                }
+            }
 
+            let mut referenced_models: HashSet<*const Model> = HashSet::new();
+            for (_, node) in self.scene.nodes() {
+                if let Some(scene_model) = node.model() {
+                    referenced_models.insert(scene_model.model.as_ptr());
+                    // Models placed through the scene directly are uploaded the first time they are seen.
+                    let static_model = self.static_models.entry(scene_model.model.as_ptr()).or_insert_with(|| StaticModel::new(&scene_model.model));
+                    for mesh_idx in scene_model.meshes.iter() {
+                        let mesh = &static_model.model.meshes[*mesh_idx];
+                        let bounds = mesh.bounding_box().transformed(&node.world_matrix());
+                        static_model.mesh_instances[*mesh_idx].push((node.world_matrix(), bounds));
+                    }
+                }
+            }
+
+            // Models whose nodes were all removed release their GL resources.
+            self.static_models.retain(|model_ptr, _| referenced_models.contains(model_ptr));
+        }
     }
 
     fn render(&mut self) {
         gl_clear_color(Float3::new(0.1, 0.1, 0.1));
         gl_clear();
 
-        let (proj, view, view_pos) = match self.render_camera.try_as_mut() {
+        let (proj, view, view_pos, shadow_camera, render_path) = match self.render_camera.try_as_mut() {
             Some(mut camera) => {
-                (camera.get_proj_matrix(), camera.get_view_matrix(), camera.get_translation())
+                let shadow_camera = ShadowCamera {
+                    view: camera.get_view_matrix(),
+                    fov: camera.get_fov(),
+                    aspect_ratio: camera.get_aspect_ratio(),
+                    near: camera.get_near(),
+                    far: camera.get_far()
+                };
+
+                (camera.get_proj_matrix(), camera.get_view_matrix(), camera.get_translation(), shadow_camera, camera.get_render_path())
             },
             None => {
                 let aspect_ratio: f32 = self.dimensions().x as f32 / self.dimensions().y as f32;
@@ -232,37 +563,259 @@ impl Graphics {
                 let view = Float4x4::identity();
                 let view_pos = Float3::default();
 
-                (proj, view, view_pos)
+                let shadow_camera = ShadowCamera {
+                    view: view,
+                    fov: 60.0,
+                    aspect_ratio: aspect_ratio,
+                    near: 0.01,
+                    far: 1000.0
+                };
+
+                (proj, view, view_pos, shadow_camera, RenderPath::Forward)
             }
         };
 
-        for (_, models) in self.dynamic_models.iter_mut() {
-            for model_transform in models.1.iter_mut() {
-                let materials = &models.0.materials;
-
-                for mesh in models.0.meshes.iter() {
-                    self.shader_program.bind(); {
-                        let mut model_transform = model_transform.as_mut();
-                        self.shader_program.set_float4x4(&String::from("model"), model_transform.transform.get_matrix());
-                        self.shader_program.set_float4x4(&String::from("projection"), proj);
-                        self.shader_program.set_float4x4(&String::from("view"), view);
-
-                        self.shader_program.set_float3(&String::from("viewPos"), view_pos);
-                    
-                        let material = &materials[mesh.material_idx()];
-                        material.bind(&mut self.shader_program);
-
-                        mesh.draw();
-                    } self.shader_program.unbind();
-                }
+        let frustum = Frustum::from_view_proj(&(proj * view));
+        let cluster_view = ClusterView {
+            view: view,
+            projection: proj,
+            near: shadow_camera.near,
+            far: shadow_camera.far
+        };
+
+        let mut graph = FrameGraph::new();
+        let textures = FrameTextures {
+            scene_color: graph.persistent("scene_color", RenderTextureDesc::new(self.hdr.dimensions(), GLTextureFormat::RGBA16F)),
+            // Depth is kept for screen space effects.
+            scene_depth: graph.persistent("scene_depth", RenderTextureDesc::new(self.hdr.dimensions(), GLTextureFormat::Depth24)),
+            backbuffer: graph.import_backbuffer(self.framebuffer_dimensions())
+        };
+        // Owned by their renderers, only used to order the passes.
+        let shadow_maps = graph.external("shadow_maps");
+        let hdr_target = graph.external("hdr_target");
+        let exposure = graph.external("exposure");
+
+        let mut pass = graph.add_pass("Shadows");
+        pass.write(shadow_maps);
+        pass.execute(move |graphics, _| {
+            let max_lights = if render_path == RenderPath::Deferred { MAX_DEFERRED_LIGHTS } else { MAX_LIGHTS };
+            let light_data = graphics.render_shadows_and_upload_lights(&shadow_camera, max_lights);
+            if render_path == RenderPath::Deferred {
+                graphics.deferred.assign_lights(&light_data, &cluster_view);
             }
+
+            graphics.camera_block.set_data(&CameraBlock {
+                projection: proj,
+                view: view,
+                view_pos: view_pos
+            });
+            graphics.camera_block.bind();
+        });
+
+        match render_path {
+            RenderPath::Forward => {
+                let mut pass = graph.add_pass("Opaque");
+                pass.read(shadow_maps);
+                pass.write(hdr_target);
+                pass.execute(move |graphics, _| {
+                    graphics.hdr.begin(); {
+                        graphics.shader_program.bind(); {
+                            graphics.shadows.bind();
+                            graphics.environment.bind(&mut graphics.shader_program);
+                            graphics.stats = draw_models(&graphics.static_models, &mut graphics.dynamic_models, &graphics.scene, &graphics.deformation, &frustum, MaterialFilter::Opaque);
+                        } graphics.shader_program.unbind();
+                    } graphics.hdr.unbind();
+                });
+
+                let mut pass = graph.add_pass("Skybox");
+                pass.read_write(hdr_target);
+                pass.execute(|graphics, _| {
+                    graphics.hdr.bind(); {
+                        graphics.environment.draw_skybox();
+                    } graphics.hdr.unbind();
+                });
+
+                let mut pass = graph.add_pass("Transparent");
+                pass.read(shadow_maps);
+                pass.read_write(hdr_target);
+                pass.execute(move |graphics, _| {
+                    graphics.hdr.bind(); {
+                        graphics.draw_transparent(&frustum, view_pos);
+                    } graphics.hdr.unbind();
+                });
+
+                let mut pass = graph.add_pass("Resolve");
+                pass.read(hdr_target);
+                pass.write(textures.scene_color);
+                pass.write(textures.scene_depth);
+                pass.execute(move |graphics, context| {
+                    graphics.hdr.resolve(context.target(&[textures.scene_color], Some(textures.scene_depth)));
+                    // Everything after the resolve draws fullscreen.
+                    gl_disable_depth();
+                });
+            },
+            RenderPath::Deferred => {
+                DeferredRenderer::add_passes(&mut graph, &textures, shadow_maps, frustum);
+
+                let mut pass = graph.add_pass("Skybox");
+                pass.read(textures.scene_depth);
+                pass.read_write(textures.scene_color);
+                pass.execute(move |graphics, context| {
+                    context.bind_target(&[textures.scene_color], Some(textures.scene_depth)); {
+                        graphics.environment.draw_skybox();
+                    } context.unbind_target();
+                });
+
+                let mut pass = graph.add_pass("Transparent");
+                pass.read(shadow_maps);
+                pass.read(textures.scene_depth);
+                pass.read_write(textures.scene_color);
+                pass.execute(move |graphics, context| {
+                    context.bind_target(&[textures.scene_color], Some(textures.scene_depth)); {
+                        graphics.draw_transparent(&frustum, view_pos);
+                    } context.unbind_target();
+                    // Everything after the scene draws fullscreen.
+                    gl_disable_depth();
+                });
+            }
+        }
+
+        app().game().render_passes(RenderStage::BeforePostProcess, &mut graph, &textures);
+
+        PostProcessRenderer::add_hdr_passes(&mut graph, &self.post_stack.stage_effects(PostStage::Hdr), textures.scene_color, textures.scene_depth, proj);
+
+        let mut pass = graph.add_pass("Exposure");
+        pass.read(textures.scene_color);
+        pass.write(exposure);
+        pass.execute(move |graphics, context| {
+            graphics.hdr.update_exposure(context.texture(textures.scene_color));
+        });
+
+        let ldr_effects = self.post_stack.stage_effects(PostStage::Ldr);
+        let mut pass = graph.add_pass("Tonemap");
+        pass.read(textures.scene_color);
+        pass.read(exposure);
+        let tonemapped = if ldr_effects.is_empty() {
+            pass.write(textures.backbuffer)
+        } else {
+            pass.create("tonemapped", RenderTextureDesc::new(self.framebuffer_dimensions(), GLTextureFormat::RGBA8))
+        };
+        pass.execute(move |graphics, context| {
+            context.bind_target(&[tonemapped], None); {
+                graphics.hdr.tonemap(context.texture(textures.scene_color));
+            } context.unbind_target();
+        });
+
+        if !ldr_effects.is_empty() {
+            PostProcessRenderer::add_ldr_passes(&mut graph, &ldr_effects, tonemapped, textures.backbuffer);
+        }
+
+        app().game().render_passes(RenderStage::AfterPostProcess, &mut graph, &textures);
+
+        let mut pass = graph.add_pass("Debug UI");
+        pass.read_write(textures.backbuffer);
+        pass.execute(|graphics, _| {
+            graphics.imgui.render();
+        });
+
+        let mut graph_resources = mem::take(&mut self.graph_resources);
+        self.graph_stats = graph.execute(self, &mut graph_resources, None);
+        self.graph_resources = graph_resources;
+
+        gl_enable_depth();
+
+        if let Some(path) = self.pending_screenshot.take() {
+            self.write_screenshot(&path);
         }
 
-        self.imgui.render();
         self.window.swap_buffers();
     }
 
+    // Shadows are assigned to the first max_lights enabled lights, only the first MAX_LIGHTS are uploaded to the light block.
+    fn render_shadows_and_upload_lights(&mut self, shadow_camera: &ShadowCamera, max_lights: usize) -> Vec<LightData> {
+        let mut lights = Vec::with_capacity(max_lights);
+        let mut light_data = Vec::with_capacity(max_lights);
+        for light in self.lights.iter() {
+            let light = light.as_ref();
+            if !light.enabled {
+                continue;
+            }
+
+            let parent_matrix = match light.parent {
+                Some(parent) if !self.scene.contains(parent) => continue,
+                Some(parent) => self.scene.world_matrix(parent),
+                None => Float4x4::identity()
+            };
+
+            lights.push(*light);
+            light_data.push(light.light_data(&parent_matrix));
+            if light_data.len() == max_lights {
+                break;
+            }
+        }
+
+        let view_count = self.shadows.prepare(&lights, &mut light_data, shadow_camera);
+        for view in 0..view_count {
+            let frustum = self.shadows.view_frustum(view);
+
+            self.shadows.begin_view(view); {
+                draw_models(&self.static_models, &mut self.dynamic_models, &self.scene, &self.deformation, &frustum, MaterialFilter::DepthOnly);
+                self.shadows.begin_alpha_tested();
+                draw_models(&self.static_models, &mut self.dynamic_models, &self.scene, &self.deformation, &frustum, MaterialFilter::AlphaTested);
+            } self.shadows.end_view();
+        }
+        gl_viewport(self.framebuffer_dimensions());
+
+        let light_count = light_data.len().min(MAX_LIGHTS);
+        let mut block_lights = light_data.iter();
+        self.light_block.set_data(&LightBlock {
+            lights: std::array::from_fn(|_| block_lights.next().copied().unwrap_or(Light::new(LightKind::Point).light_data(&Float4x4::identity()))),
+            light_count: light_count as i32
+        });
+        self.light_block.bind();
+
+        light_data
+    }
+
+    // Blends transparent materials over the bound target, depth is tested but not written.
+    fn draw_transparent(&mut self, frustum: &Frustum, view_pos: Float3) {
+        gl_enable_blend(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        gl_depth_mask(false);
+
+        self.shader_program.bind(); {
+            self.shadows.bind();
+            self.environment.bind(&mut self.shader_program);
+            self.stats += draw_sorted_models(&self.static_models, &self.dynamic_models, &self.scene, &self.deformation, frustum, view_pos);
+        } self.shader_program.unbind();
+
+        gl_depth_mask(true);
+        gl_disable_blend();
+    }
+
+    fn write_screenshot(&self, path: &String) {
+        let path = Path::new(path);
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("").to_lowercase();
+
+        let result = match extension.as_str() {
+            "png" => write_png(path, &self.read_pixels()),
+            "hdr" => write_hdr(path, &self.read_pixels_f32()),
+            "exr" => write_exr(path, &self.read_pixels_f32()),
+            _ => {
+                eprintln!("Failed to capture screenshot. (Unsupported extension '{}')", extension);
+                return;
+            }
+        };
+
+        if let Err(error) = result {
+            eprintln!("Failed to capture screenshot. ({})", error);
+        }
+    }
+
     fn post_render(&mut self) {
+        let scene = &self.scene;
+        self.scene_lights.retain(|(node, _)| scene.contains(*node));
+        self.lights.retain(|light| light.strong_count() > 1);
+
         for (_, models) in self.dynamic_models.iter_mut() {
             let mut indices = Vec::new();
 
@@ -277,6 +830,187 @@ impl Graphics {
     }
 }
 
+// Draws every static and dynamic model instance inside the frustum whose material passes the filter.
+// Dynamic models are posed by their animators, deformed meshes are drawn one instance at a time.
+fn draw_models(static_models: &HashMap<*const Model, StaticModel>, dynamic_models: &mut HashMap<*const Model, (GLModel, Vec<Shared<ModelInstance>>)>, scene: &Scene, deformation: &DeformationBuffers, frustum: &Frustum, filter: MaterialFilter) -> RenderStats {
+    let mut stats = RenderStats::default();
+
+    for (_, static_model) in static_models.iter() {
+        for (mesh, mesh_instances) in static_model.model.meshes.iter().zip(static_model.mesh_instances.iter()) {
+            let material = &static_model.model.materials[mesh.material_idx()];
+            if !filter.includes(material) {
+                continue;
+            }
+
+            let transforms: Vec<Float4x4> = mesh_instances.iter()
+                .filter(|(_, bounds)| frustum.is_visible(bounds))
+                .map(|(transform, _)| *transform)
+                .collect();
+
+            stats.drawn_instances += transforms.len();
+            stats.culled_instances += mesh_instances.len() - transforms.len();
+            if transforms.is_empty() {
+                continue;
+            }
+
+            mesh.set_instances(&transforms);
+            material.apply_culling();
+            if filter != MaterialFilter::DepthOnly {
+                material.bind();
+            }
+
+            mesh.draw_instanced(transforms.len());
+            stats.draw_calls += 1;
+        }
+    }
+
+    for (_, (model, instances)) in dynamic_models.iter_mut() {
+        if !model.meshes.iter().any(|mesh| filter.includes(&model.materials[mesh.material_idx()])) {
+            continue;
+        }
+
+        let mut transforms: Vec<Float4x4> = Vec::with_capacity(instances.len());
+        let mut visible_instances: Vec<&Shared<ModelInstance>> = Vec::with_capacity(instances.len());
+        for instance_ref in instances.iter() {
+            let mut instance = instance_ref.as_mut();
+            let transform = match instance.parent {
+                Some(parent) => scene.world_matrix(parent) * instance.transform.get_matrix(),
+                None => instance.transform.get_matrix()
+            };
+
+            let bounds = model.posed_bounding_box(instance.animator.as_ref()).transformed(&transform);
+            if frustum.is_visible(&bounds) {
+                transforms.push(transform);
+                visible_instances.push(instance_ref);
+            }
+        }
+
+        stats.drawn_instances += transforms.len();
+        stats.culled_instances += instances.len() - transforms.len();
+        if transforms.is_empty() {
+            continue;
+        }
+
+        for (mesh_idx, mesh) in model.meshes.iter().enumerate() {
+            let material = &model.materials[mesh.material_idx()];
+            if !filter.includes(material) {
+                continue;
+            }
+
+            material.apply_culling();
+            if filter != MaterialFilter::DepthOnly {
+                material.bind();
+            }
+
+            if DeformationBuffers::is_deformed(model, mesh_idx) {
+                for (instance, transform) in visible_instances.iter().zip(transforms.iter()) {
+                    let instance = instance.as_ref();
+                    let animator = instance.animator.as_ref();
+
+                    deformation.set(model, mesh_idx, animator);
+                    mesh.set_instances(&[*transform * model.posed_mesh_matrix(mesh_idx, animator)]);
+                    mesh.draw_instanced(1);
+                    stats.draw_calls += 1;
+                }
+                deformation.reset();
+            } else {
+                let mesh_transforms: Vec<Float4x4> = visible_instances.iter().zip(transforms.iter())
+                    .map(|(instance, transform)| *transform * model.posed_mesh_matrix(mesh_idx, instance.as_ref().animator.as_ref()))
+                    .collect();
+
+                mesh.set_instances(&mesh_transforms);
+                mesh.draw_instanced(mesh_transforms.len());
+                stats.draw_calls += 1;
+            }
+        }
+    }
+    gl_cull(gl::BACK);
+
+    stats
+}
+
+// Draws the transparent meshes one instance at a time, farthest from the camera first.
+fn draw_sorted_models(static_models: &HashMap<*const Model, StaticModel>, dynamic_models: &HashMap<*const Model, (GLModel, Vec<Shared<ModelInstance>>)>, scene: &Scene, deformation: &DeformationBuffers, frustum: &Frustum, view_pos: Float3) -> RenderStats {
+    let mut stats = RenderStats::default();
+    // Deformed draws keep their model, mesh and instance to set the deformation with.
+    let mut draws: Vec<(f32, &GLMesh, &GLMaterial, Float4x4, Option<(&GLModel, usize, &Shared<ModelInstance>)>)> = Vec::new();
+
+    for (_, static_model) in static_models.iter() {
+        for (mesh, mesh_instances) in static_model.model.meshes.iter().zip(static_model.mesh_instances.iter()) {
+            let material = &static_model.model.materials[mesh.material_idx()];
+            if !material.is_transparent() {
+                continue;
+            }
+
+            for (transform, bounds) in mesh_instances.iter() {
+                if frustum.is_visible(bounds) {
+                    draws.push((distance(bounds.center(), view_pos), mesh, material, *transform, None));
+                } else {
+                    stats.culled_instances += 1;
+                }
+            }
+        }
+    }
+
+    for (_, (model, instances)) in dynamic_models.iter() {
+        if !model.meshes.iter().any(|mesh| model.materials[mesh.material_idx()].is_transparent()) {
+            continue;
+        }
+
+        let transforms: Vec<Float4x4> = instances.iter().map(|instance| {
+            let mut instance = instance.as_mut();
+            match instance.parent {
+                Some(parent) => scene.world_matrix(parent) * instance.transform.get_matrix(),
+                None => instance.transform.get_matrix()
+            }
+        }).collect();
+
+        for (mesh_idx, mesh) in model.meshes.iter().enumerate() {
+            let material = &model.materials[mesh.material_idx()];
+            if !material.is_transparent() {
+                continue;
+            }
+
+            for (instance, transform) in instances.iter().zip(transforms.iter()) {
+                let instance_ref = instance.as_ref();
+                let animator = instance_ref.animator.as_ref();
+                let mesh_transform = *transform * model.posed_mesh_matrix(mesh_idx, animator);
+                let bounds = model.posed_mesh_bounding_box(mesh_idx, animator).transformed(transform);
+                if frustum.is_visible(&bounds) {
+                    let deformed = match DeformationBuffers::is_deformed(model, mesh_idx) {
+                        true => Some((model, mesh_idx, instance)),
+                        false => None
+                    };
+                    draws.push((distance(bounds.center(), view_pos), mesh, material, mesh_transform, deformed));
+                } else {
+                    stats.culled_instances += 1;
+                }
+            }
+        }
+    }
+
+    draws.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+    for (_, mesh, material, transform, deformed) in draws {
+        material.apply_culling();
+        material.bind();
+        if let Some((model, mesh_idx, instance)) = deformed {
+            deformation.set(model, mesh_idx, instance.as_ref().animator.as_ref());
+        }
+        mesh.set_instances(&[transform]);
+        mesh.draw_instanced(1);
+        if deformed.is_some() {
+            deformation.reset();
+        }
+
+        stats.drawn_instances += 1;
+        stats.draw_calls += 1;
+    }
+    gl_cull(gl::BACK);
+
+    stats
+}
+
 fn vec_remove_multiple<T>(vec: &mut Vec<T>, indices: &mut Vec<usize>) {
     indices.sort();    
 
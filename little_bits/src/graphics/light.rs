@@ -0,0 +1,82 @@
+use crate::gmaths::*;
+use crate::resources::{LightKind, PunctualLight};
+use crate::graphics::scene::SceneNodeId;
+use crate::graphics::opengl::LightData;
+use crate::graphics::shadows::ShadowSettings;
+
+#[derive(PartialEq, Clone, Debug, Copy)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Float3,
+    // Lux for directional lights, candela for point and spot lights.
+    pub intensity: f32,
+    // Distance where the light reaches zero, None falls off with the inverse square only.
+    pub range: Option<f32>,
+    pub position: Float3,
+    pub direction: Float3,
+    // Position and direction are relative to the parent node when set.
+    pub parent: Option<SceneNodeId>,
+    // Point lights don't cast shadows.
+    pub shadow: Option<ShadowSettings>,
+    pub enabled: bool
+}
+
+impl Light {
+    pub fn new(kind: LightKind) -> Self {
+        Light {
+            kind: kind,
+            color: Float3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            range: None,
+            position: Float3::default(),
+            direction: Float3::new(0.0, 0.0, -1.0),
+            parent: None,
+            shadow: None,
+            enabled: true
+        }
+    }
+
+    pub fn from_punctual(light: &PunctualLight) -> Self {
+        Light {
+            color: light.color,
+            intensity: light.intensity,
+            range: light.range,
+            ..Light::new(light.kind)
+        }
+    }
+
+    pub(crate) fn light_data(&self, parent_matrix: &Float4x4) -> LightData {
+        let transform = |v: Float3, w: f32| -> Float3 {
+            Float3::new(
+                parent_matrix.at(0, 0) * v.x + parent_matrix.at(1, 0) * v.y + parent_matrix.at(2, 0) * v.z + parent_matrix.at(3, 0) * w,
+                parent_matrix.at(0, 1) * v.x + parent_matrix.at(1, 1) * v.y + parent_matrix.at(2, 1) * v.z + parent_matrix.at(3, 1) * w,
+                parent_matrix.at(0, 2) * v.x + parent_matrix.at(1, 2) * v.y + parent_matrix.at(2, 2) * v.z + parent_matrix.at(3, 2) * w
+            )
+        };
+
+        let (kind, angle_scale, angle_offset) = match self.kind {
+            LightKind::Directional => (0, 0.0, 0.0),
+            LightKind::Point => (1, 0.0, 0.0),
+            LightKind::Spot { inner_cone_angle, outer_cone_angle } => {
+                // Precomputed so the shader only needs a multiply-add, see KHR_lights_punctual.
+                let cos_outer = outer_cone_angle.cos();
+                let angle_scale = 1.0 / (inner_cone_angle.cos() - cos_outer).max(0.001);
+                (2, angle_scale, -cos_outer * angle_scale)
+            }
+        };
+
+        LightData {
+            position: transform(self.position, 1.0),
+            range: self.range.unwrap_or(0.0),
+            direction: transform(self.direction, 0.0).normalized(),
+            intensity: self.intensity,
+            color: self.color,
+            kind: kind,
+            angle_scale: angle_scale,
+            angle_offset: angle_offset,
+            shadow_index: -1,
+            shadow_bias: self.shadow.map_or(0.0, |shadow| shadow.bias),
+            shadow_normal_bias: self.shadow.map_or(0.0, |shadow| shadow.normal_bias)
+        }
+    }
+}
@@ -4,6 +4,8 @@ extern crate cl_wrapper;
 use cl_wrapper::*;
 
 use crate::graphics::opengl::*;
+use crate::graphics::animation::DeformationBuffers;
+use crate::graphics::render_graph::*;
 use rand::Rng;
 
 use crate::{app, Timer};
@@ -184,6 +186,7 @@ pub struct Baker {
     program: CLProgram,
     kernel: CLKernel,
     shader_program: GLShaderProgram,
+    camera_block: GLUniformBuffer<CameraBlock>,
 
     display_shader_program: GLShaderProgram,
     display_vao: GLVAO
@@ -220,7 +223,7 @@ impl Baker {
         let program = CLProgram::new(&context, &program_src.as_ref(), Some(&String::from("assets/cl/")));
         let kernel = CLKernel::new(&program, &String::from("render"));
 
-        let shader_program;
+        let mut shader_program;
         {
             let vertex_shader_src = app().resources().get_text(String::from("assets/shaders/vert.glsl"));
             let vertex_shader = GLShader::new(GLShaderType::VERTEX, &vertex_shader_src.as_ref());
@@ -228,6 +231,12 @@ impl Baker {
             let fragment_shader = GLShader::new(GLShaderType::FRAGMENT, &fragment_shader_src.as_ref());
             shader_program = GLShaderProgram::new(&vertex_shader, &fragment_shader);
         }
+        shader_program.bind(); {
+            CameraBlock::setup_program(&mut shader_program);
+            DeformationBuffers::setup_program(&mut shader_program);
+            GLMaterial::setup_program(&mut shader_program);
+        } shader_program.unbind();
+        let camera_block = GLUniformBuffer::new(CAMERA_BLOCK_BINDING);
 
         let display_shader_program;
         let display_vao = GLVAO::new();
@@ -245,6 +254,7 @@ impl Baker {
             program: program,
             kernel: kernel,
             shader_program: shader_program,
+            camera_block: camera_block,
             display_shader_program: display_shader_program,
             display_vao: display_vao
         }
@@ -312,27 +322,7 @@ impl Baker {
 
         //let camera_points = vec![Float3::new(radius * 1.5, 0.0, 0.0)];
 
-        let position_rt = GLRenderTexture::new(params.sample_resolution, params.sample_resolution);
-        let cl_position = CLGLTexture2D::new(&self.context, position_rt.tex(), CLBufferMode::Read);
-        let base_color_rt = GLRenderTexture::new(params.sample_resolution, params.sample_resolution);
-        let cl_base_color = CLGLTexture2D::new(&self.context, base_color_rt.tex(), CLBufferMode::Read);
-        let normal_rt = GLRenderTexture::new(params.sample_resolution, params.sample_resolution);
-        let cl_normal = CLGLTexture2D::new(&self.context, normal_rt.tex(), CLBufferMode::Read);
-        let mro_rt = GLRenderTexture::new(params.sample_resolution, params.sample_resolution);
-        let cl_mro = CLGLTexture2D::new(&self.context, mro_rt.tex(), CLBufferMode::Read);
-        let emission_rt = GLRenderTexture::new(params.sample_resolution, params.sample_resolution);
-        let cl_emission = CLGLTexture2D::new(&self.context, emission_rt.tex(), CLBufferMode::Read);
-
-        let mut render_target = GLRenderTarget::new(params.sample_resolution, params.sample_resolution);
-        render_target.set_texture(GLRenderAttachment::Color(0), position_rt);
-        render_target.set_texture(GLRenderAttachment::Color(1), base_color_rt);
-        render_target.set_texture(GLRenderAttachment::Color(2), normal_rt);
-        render_target.set_texture(GLRenderAttachment::Color(3), mro_rt);
-        render_target.set_texture(GLRenderAttachment::Color(4), emission_rt);
-        render_target.check();
-
-        let display_target = GLRenderTexture::new(params.sample_resolution, params.sample_resolution);
-        let cl_display_target = CLGLTexture2D::new(&self.context, display_target.tex(), CLBufferMode::Write);
+        let sample_dimensions = Int2::new(params.sample_resolution as i32, params.sample_resolution as i32);
 
         let cl_camera = CLBuffer::new(&self.context, CLBufferMode::Read, std::mem::size_of::<CLCamera>());
 
@@ -354,119 +344,137 @@ impl Baker {
         let cl_errors = CLBuffer::new(&self.context, CLBufferMode::ReadWrite, std::mem::size_of::<f32>() * (multi_hash_grid.required_nn_inputs() + 1));
 
         let mut timer = Timer::new();
+        // Sample targets are kept alive between the views.
+        let mut graph_resources = RenderGraphResources::new();
 
         for e in 0..params.epochs {
             for camera_point in &camera_points {
                 for _ in 0..2 {
                     glfw.poll_events();
 
+                    let mut graph: RenderGraph<()> = RenderGraph::new();
+                    let backbuffer = graph.import_backbuffer(app().graphics().dimensions());
+
                     // Render inputs to rt's
-                    gl_viewport(Int2::new(params.sample_resolution as i32, params.sample_resolution as i32));
-                    {
-                        render_target.bind(); {
+                    let mut pass = graph.add_pass("Bake Inputs");
+                    let position = pass.create("position", RenderTextureDesc::new(sample_dimensions, GLTextureFormat::RGBA32F));
+                    let base_color = pass.create("base_color", RenderTextureDesc::new(sample_dimensions, GLTextureFormat::RGBA16F));
+                    let normal = pass.create("normal", RenderTextureDesc::new(sample_dimensions, GLTextureFormat::RGBA16F));
+                    let mro = pass.create("mro", RenderTextureDesc::new(sample_dimensions, GLTextureFormat::RGBA16F));
+                    let emission = pass.create("emission", RenderTextureDesc::new(sample_dimensions, GLTextureFormat::RGBA16F));
+                    let depth = pass.create("depth", RenderTextureDesc::new(sample_dimensions, GLTextureFormat::Depth24));
+                    let shader_program = &mut self.shader_program;
+                    let camera_block = &self.camera_block;
+                    let projection = camera.get_proj_matrix();
+                    pass.execute(move |_, context| {
+                        context.bind_target(&[position, base_color, normal, mro, emission], Some(depth)); {
                             gl_clear_color(Float3::new(0.0, 0.0, 0.0));
                             gl_clear();
 
                             let materials = &model.materials;
 
-                            for mesh in model.meshes.iter() {
-                                self.shader_program.bind(); {
-                                    self.shader_program.set_float4x4(&String::from("model"), Float4x4::identity());
-                                    self.shader_program.set_float4x4(&String::from("projection"), camera.get_proj_matrix());
-                                    self.shader_program.set_float4x4(&String::from("view"), Float4x4::look_at(camera_point.clone(), center, Float3::up()));
-                                    self.shader_program.set_float3(&String::from("viewPos"), camera_point.clone());
+                            camera_block.set_data(&CameraBlock {
+                                projection: projection,
+                                view: Float4x4::look_at(camera_point.clone(), center, Float3::up()),
+                                view_pos: camera_point.clone()
+                            });
+                            camera_block.bind();
 
-                                    let material = &materials[mesh.material_idx()];
-                                    material.bind(&mut self.shader_program);
+                            shader_program.bind(); {
+                                model.set_instances(&[Float4x4::identity()]);
 
-                                    mesh.draw();
-                                } self.shader_program.unbind();
-                            }
-                        }  render_target.unbind();
+                                for mesh in model.meshes.iter() {
+                                    let material = &materials[mesh.material_idx()];
+                                    material.bind();
+
+                                    mesh.draw_instanced(1);
+                                }
+                            } shader_program.unbind();
+                        } context.unbind_target();
+                    });
+
+                    // Train nemo, the graph acquires the shared textures for the kernel
+                    let mut pass = graph.add_compute_pass("Train");
+                    for input in [position, base_color, normal, mro, emission] {
+                        pass.read(input);
                     }
-
-                    let mut cl_camera_rep = CLCamera::new(camera_point.clone(), (camera_point - center).normalized(), 60.0, 1.0);
-                    let mut loss = 0.0f32;
-                    // Train nemo
-                    gl_finish();
-                    {
-                        // Acquire gl resources
-                        self.command_queue.acquire_gl_texture(&cl_position);
-                        self.command_queue.acquire_gl_texture(&cl_base_color);
-                        self.command_queue.acquire_gl_texture(&cl_normal);
-                        self.command_queue.acquire_gl_texture(&cl_mro);
-                        self.command_queue.acquire_gl_texture(&cl_emission);
-                        self.command_queue.acquire_gl_texture(&cl_display_target);
-
-                        self.command_queue.write_buffer(&cl_camera, &mut cl_camera_rep as *mut CLCamera as *mut c_void);
-                        self.command_queue.write_buffer(&cl_neural_network, &mut cl_nn_rep as *mut CLNeuralNetwork as *mut c_void);
-                        self.command_queue.write_buffer(&cl_in_weights, neural_network.weights.as_mut_ptr() as *mut c_void);
-                        self.command_queue.write_buffer(&cl_out_weights, neural_network.weights.as_mut_ptr() as *mut c_void);
-                        self.command_queue.write_buffer(&cl_in_momentum, momentum.as_mut_ptr() as *mut c_void);
-                        self.command_queue.write_buffer(&cl_out_momentum, momentum.as_mut_ptr() as *mut c_void);
-                        multi_hash_grid.write(&self.command_queue);
-                        self.command_queue.write_buffer(&cl_aabb, &mut aabb as *mut AABB as *mut c_void);
+                    let display_target = pass.create("display", RenderTextureDesc::new(sample_dimensions, GLTextureFormat::RGBA16F));
+                    let kernel = &self.kernel;
+                    let (cl_camera, cl_neural_network, cl_in_weights, cl_out_weights) = (&cl_camera, &cl_neural_network, &cl_in_weights, &cl_out_weights);
+                    let (cl_in_momentum, cl_out_momentum, cl_aabb, cl_loss, cl_errors) = (&cl_in_momentum, &cl_out_momentum, &cl_aabb, &cl_loss, &cl_errors);
+                    let (neural_network, cl_nn_rep, momentum, multi_hash_grid, aabb, timer) = (&mut neural_network, &mut cl_nn_rep, &mut momentum, &mut multi_hash_grid, &mut aabb, &mut timer);
+                    pass.execute(move |_, context| {
+                        let command_queue = context.command_queue();
+                        let mut cl_camera_rep = CLCamera::new(camera_point.clone(), (camera_point - center).normalized(), 60.0, 1.0);
+
+                        command_queue.write_buffer(cl_camera, &mut cl_camera_rep as *mut CLCamera as *mut c_void);
+                        command_queue.write_buffer(cl_neural_network, cl_nn_rep as *mut CLNeuralNetwork as *mut c_void);
+                        command_queue.write_buffer(cl_in_weights, neural_network.weights.as_mut_ptr() as *mut c_void);
+                        command_queue.write_buffer(cl_out_weights, neural_network.weights.as_mut_ptr() as *mut c_void);
+                        command_queue.write_buffer(cl_in_momentum, momentum.as_mut_ptr() as *mut c_void);
+                        command_queue.write_buffer(cl_out_momentum, momentum.as_mut_ptr() as *mut c_void);
+                        multi_hash_grid.write(command_queue);
+                        command_queue.write_buffer(cl_aabb, aabb as *mut AABB as *mut c_void);
                         let mut zero = 0.0f32;
-                        self.command_queue.write_buffer(&cl_loss, &mut zero as *mut f32 as *mut c_void);
+                        command_queue.write_buffer(cl_loss, &mut zero as *mut f32 as *mut c_void);
                         let mut zeros = vec![0.0f32; multi_hash_grid.required_nn_inputs() + 1];
-                        self.command_queue.write_buffer(&cl_errors, zeros.as_mut_ptr() as *mut c_void);
-
-                        self.kernel.set_arg_buffer(0, &cl_display_target);
-                        self.kernel.set_arg_buffer(1, &cl_position);
-                        self.kernel.set_arg_buffer(2, &cl_base_color);
-                        self.kernel.set_arg_buffer(3, &cl_normal);
-                        self.kernel.set_arg_buffer(4, &cl_mro);
-                        self.kernel.set_arg_buffer(5, &cl_emission);
-                        self.kernel.set_arg_buffer(6, &cl_camera);
-                        self.kernel.set_arg_buffer(7, &cl_neural_network);
-                        self.kernel.set_arg_buffer(8, &cl_in_weights);
-                        self.kernel.set_arg_buffer(9, &cl_out_weights);
-                        self.kernel.set_arg_buffer(10, &cl_in_momentum);
-                        self.kernel.set_arg_buffer(11, &cl_out_momentum);
-                        //self.kernel.set_arg_empty(12, neural_network.required_cache_size());
-                        multi_hash_grid.set_kernel_arg(&self.kernel, 12);
-                        self.kernel.set_arg_buffer(15, &cl_aabb);
-                        self.kernel.set_arg_buffer(16, &cl_loss);
-                        self.kernel.set_arg_buffer(17, &cl_errors);
+                        command_queue.write_buffer(cl_errors, zeros.as_mut_ptr() as *mut c_void);
+
+                        kernel.set_arg_buffer(0, context.cl_texture(display_target));
+                        kernel.set_arg_buffer(1, context.cl_texture(position));
+                        kernel.set_arg_buffer(2, context.cl_texture(base_color));
+                        kernel.set_arg_buffer(3, context.cl_texture(normal));
+                        kernel.set_arg_buffer(4, context.cl_texture(mro));
+                        kernel.set_arg_buffer(5, context.cl_texture(emission));
+                        kernel.set_arg_buffer(6, cl_camera);
+                        kernel.set_arg_buffer(7, cl_neural_network);
+                        kernel.set_arg_buffer(8, cl_in_weights);
+                        kernel.set_arg_buffer(9, cl_out_weights);
+                        kernel.set_arg_buffer(10, cl_in_momentum);
+                        kernel.set_arg_buffer(11, cl_out_momentum);
+                        //kernel.set_arg_empty(12, neural_network.required_cache_size());
+                        multi_hash_grid.set_kernel_arg(kernel, 12);
+                        kernel.set_arg_buffer(15, cl_aabb);
+                        kernel.set_arg_buffer(16, cl_loss);
+                        kernel.set_arg_buffer(17, cl_errors);
 
                         timer.reset();
                         let local_work_dims = vec![16, 16];
-                        self.command_queue.execute(&self.kernel, &vec![display_target.width() as usize, display_target.height() as usize], Some(&local_work_dims));
-                        self.command_queue.finish();
+                        let display_dimensions = context.dimensions(display_target);
+                        command_queue.execute(kernel, &vec![display_dimensions.x as usize, display_dimensions.y as usize], Some(&local_work_dims));
+                        command_queue.finish();
                         println!("elapsed: {}ms", (timer.elapsed() as f32 * 10000.0) as i32 as f32 * 0.1);
 
-                        self.command_queue.read_buffer(&cl_out_weights, neural_network.weights.as_mut_ptr());
-                        self.command_queue.read_buffer(&cl_out_momentum, momentum.as_mut_ptr());
-                        loss = 0.0f32;
-                        self.command_queue.read_buffer(&cl_loss, &mut loss as *mut f32);
+                        command_queue.read_buffer(cl_out_weights, neural_network.weights.as_mut_ptr());
+                        command_queue.read_buffer(cl_out_momentum, momentum.as_mut_ptr());
+                        let mut loss = 0.0f32;
+                        command_queue.read_buffer(cl_loss, &mut loss as *mut f32);
                         println!("loss: {}", loss);
-                        
-                        multi_hash_grid.read(&self.command_queue);
-
-                        // Release gl resources
-                        self.command_queue.release_gl_texture(&cl_display_target);
-                        self.command_queue.release_gl_texture(&cl_emission);
-                        self.command_queue.release_gl_texture(&cl_mro);
-                        self.command_queue.release_gl_texture(&cl_normal);
-                        self.command_queue.release_gl_texture(&cl_base_color);
-                        self.command_queue.release_gl_texture(&cl_position);
-                    }
+
+                        multi_hash_grid.read(command_queue);
+                    });
 
                     // Display rt result
-                    gl_viewport(app().graphics().dimensions());
-                    {
-                        gl_clear();
-
-                        self.display_shader_program.bind(); {
-                            let t = &display_target;//render_target.get_texture(GLRenderAttachment::Color(0)).unwrap();
-                            t.bind(0);
-                            self.display_shader_program.set_sampler_slot(&String::from("tex"), 0);
-
-                            self.display_vao.bind(); {
-                                gl_draw_arrays(gl::TRIANGLES, 0, 6);
-                            } self.display_vao.unbind();
-                        } self.display_shader_program.unbind();
-                    }
+                    let mut pass = graph.add_pass("Display");
+                    pass.read(display_target);
+                    pass.write(backbuffer);
+                    let (display_shader_program, display_vao) = (&mut self.display_shader_program, &self.display_vao);
+                    pass.execute(move |_, context| {
+                        context.bind_target(&[backbuffer], None); {
+                            gl_clear();
+
+                            display_shader_program.bind(); {
+                                context.texture(display_target).bind(0);
+                                display_shader_program.set_sampler_slot(&String::from("tex"), 0);
+
+                                display_vao.bind(); {
+                                    gl_draw_arrays(gl::TRIANGLES, 0, 3);
+                                } display_vao.unbind();
+                            } display_shader_program.unbind();
+                        } context.unbind_target();
+                    });
+
+                    graph.execute(&mut (), &mut graph_resources, Some((&self.context, &self.command_queue)));
 
                     window.swap_buffers();
                 }
@@ -1,73 +1,313 @@
 use crate::graphics::opengl::*;
-use crate::resources::Material;
+use crate::resources::{Material, Image, Sampler, AlphaMode, TextureTransform};
 use crate::Shared;
 
+const TEXTURE_ANISOTROPY: f32 = 8.0;
+
+// Extension maps pack two glTF textures each and are bound past the fixed pipeline slots.
+const SAMPLER_SLOTS: [(&str, i32); 10] = [
+    ("baseColorMap", 0),
+    ("normalMap", 1),
+    ("metallicRoughnessMap", 2),
+    ("occlusionMap", 3),
+    ("emissiveMap", 4),
+    ("clearcoatMap", 17),
+    ("sheenMap", 18),
+    ("specularMap", 19),
+    ("clearcoatNormalMap", 20),
+    ("transmissionMap", 21)
+];
+
+// One texture of an extension map, sampling the channels of both of its glTF textures.
+struct PackedTexture {
+    texture: GLTexture2D,
+    transform: TextureTransform,
+    first: bool,
+    second: bool
+}
+
 pub struct GLMaterial {
     pub base_color_texture: Option<GLTexture2D>,
     pub normal_texture: Option<GLTexture2D>,
     pub metallic_roughness_texture: Option<GLTexture2D>,
     pub occlusion_texture: Option<GLTexture2D>,
     pub emissive_texture: Option<GLTexture2D>,
-    material_properties: Shared<Material>
+    clearcoat_texture: Option<PackedTexture>,
+    sheen_texture: Option<PackedTexture>,
+    specular_texture: Option<PackedTexture>,
+    clearcoat_normal_texture: Option<GLTexture2D>,
+    transmission_texture: Option<GLTexture2D>,
+    material_properties: Shared<Material>,
+    uniform_buffer: GLUniformBuffer<MaterialBlock>
 }
 
 impl GLMaterial {
     pub fn new(material: Shared<Material>) -> Self {
-        GLMaterial {
-            base_color_texture: GLTexture2D::new(&material.as_ref().base_color_texture),
-            normal_texture: GLTexture2D::new(&material.as_ref().normal_texture),
-            metallic_roughness_texture: GLTexture2D::new(&material.as_ref().metallic_roughness_texture),
-            occlusion_texture: GLTexture2D::new(&material.as_ref().occlusion_texture),
-            emissive_texture: GLTexture2D::new(&material.as_ref().emissive_texture),
-            material_properties: material.clone()
+        let properties = material.as_ref();
+
+        let gl_material = GLMaterial {
+            base_color_texture: GLTexture2D::new(&properties.base_color_texture, &Self::sampler_desc(&properties.base_color_sampler), true),
+            normal_texture: GLTexture2D::new(&properties.normal_texture, &Self::sampler_desc(&properties.normal_sampler), false),
+            metallic_roughness_texture: GLTexture2D::new(&properties.metallic_roughness_texture, &Self::sampler_desc(&properties.metallic_roughness_sampler), false),
+            occlusion_texture: GLTexture2D::new(&properties.occlusion_texture, &Self::sampler_desc(&properties.occlusion_sampler), false),
+            emissive_texture: GLTexture2D::new(&properties.emissive_texture, &Self::sampler_desc(&properties.emissive_sampler), true),
+            clearcoat_texture: Self::packed_texture(&properties.name,
+                (&properties.clearcoat_texture, &properties.clearcoat_sampler, properties.clearcoat_transform),
+                (&properties.clearcoat_roughness_texture, &properties.clearcoat_roughness_sampler, properties.clearcoat_roughness_transform), 1, false),
+            // sRGB decoding leaves the alpha channel linear.
+            sheen_texture: Self::packed_texture(&properties.name,
+                (&properties.sheen_color_texture, &properties.sheen_color_sampler, properties.sheen_color_transform),
+                (&properties.sheen_roughness_texture, &properties.sheen_roughness_sampler, properties.sheen_roughness_transform), 3, true),
+            specular_texture: Self::packed_texture(&properties.name,
+                (&properties.specular_color_texture, &properties.specular_color_sampler, properties.specular_color_transform),
+                (&properties.specular_texture, &properties.specular_sampler, properties.specular_transform), 3, true),
+            clearcoat_normal_texture: GLTexture2D::new(&properties.clearcoat_normal_texture, &Self::sampler_desc(&properties.clearcoat_normal_sampler), false),
+            transmission_texture: GLTexture2D::new(&properties.transmission_texture, &Self::sampler_desc(&properties.transmission_sampler), false),
+            material_properties: material.clone(),
+            uniform_buffer: GLUniformBuffer::new(MATERIAL_BLOCK_BINDING)
+        };
+        gl_material.update();
+        gl_material
+    }
+
+    fn sampler_desc(sampler: &Sampler) -> GLSamplerDesc {
+        let mut desc = GLSamplerDesc::from(sampler);
+        desc.anisotropy = TEXTURE_ANISOTROPY;
+        desc
+    }
+
+    // Only one texture unit is left per extension. Textures in different images are packed into a new image, the second
+    // one's channel replacing the first's, unless they are sampled with different transforms.
+    fn packed_texture(name: &String, first: (&Shared<Image>, &Sampler, TextureTransform), second: (&Shared<Image>, &Sampler, TextureTransform), second_channel: usize, srgb: bool) -> Option<PackedTexture> {
+        let (image, sampler, transform, has_first, has_second) = match (first.0.is_empty(), second.0.is_empty()) {
+            (true, true) => return None,
+            (false, true) => (first.0.clone(), first.1, first.2, true, false),
+            (true, false) => (second.0.clone(), second.1, second.2, false, true),
+            (false, false) if first.0.as_ptr() == second.0.as_ptr() => (first.0.clone(), first.1, first.2, true, true),
+            (false, false) if first.2 == second.2 => {
+                let image = Self::pack_images(&first.0.as_ref(), &second.0.as_ref(), second_channel);
+                (Shared::new(image), first.1, first.2, true, true)
+            },
+            (false, false) => {
+                eprintln!("Failed to pack extension textures. (Material '{}' samples them with different transforms, ignoring the second)", name);
+                (first.0.clone(), first.1, first.2, true, false)
+            }
+        };
+
+        GLTexture2D::new(&image, &Self::sampler_desc(sampler), srgb).map(|texture| PackedTexture {
+            texture: texture,
+            transform: transform,
+            first: has_first,
+            second: has_second
+        })
+    }
+
+    // RGBA image at the larger size of both, nearest sampled.
+    fn pack_images(first: &Image, second: &Image, second_channel: usize) -> Image {
+        let dimensions = Int2::new(first.dimensions.x.max(second.dimensions.x), first.dimensions.y.max(second.dimensions.y));
+        let mut data = Vec::with_capacity((dimensions.x * dimensions.y * 4) as usize);
+
+        for y in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                let mut pixel = Self::image_pixel(first, x * first.dimensions.x / dimensions.x, y * first.dimensions.y / dimensions.y);
+                pixel[second_channel] = Self::image_pixel(second, x * second.dimensions.x / dimensions.x, y * second.dimensions.y / dimensions.y)[second_channel];
+                data.extend_from_slice(&pixel);
+            }
+        }
+
+        Image {
+            data: data,
+            dimensions: dimensions,
+            channel_count: 4
         }
     }
 
-    pub fn bind(&self, shader_program: &mut GLShaderProgram) {
-        shader_program.set_float4(&String::from("material.baseColorFactor"), self.material_properties.as_ref().base_color_factor);
-        if let Some(base_color_texture) = &self.base_color_texture {
-            base_color_texture.bind(0);
-            shader_program.set_sampler_slot(&String::from("material.baseColorMap"), 0);
-            shader_program.set_bool(&String::from("material.hasBaseColorMap"), true);
+    // Grey images spread to rgb, a missing alpha channel is opaque.
+    fn image_pixel(image: &Image, x: i32, y: i32) -> [u8; 4] {
+        let channel_count = image.channel_count as usize;
+        let offset = (y * image.dimensions.x + x) as usize * channel_count;
+        let p = &image.data[offset..offset + channel_count];
+        match channel_count {
+            1 => [p[0], p[0], p[0], 255],
+            2 => [p[0], p[0], p[0], p[1]],
+            3 => [p[0], p[1], p[2], 255],
+            _ => [p[0], p[1], p[2], p[3]]
+        }
+    }
+
+    // Two rows of the affine UV transform, the first row's w holds the UV set.
+    fn uv_transform(transform: &TextureTransform) -> [Float4; 2] {
+        let (sin, cos) = transform.rotation.sin_cos();
+        let (scale, offset) = (transform.scale, transform.offset);
+        [
+            Float4::new(cos * scale.x, sin * scale.y, offset.x, transform.tex_coord as f32),
+            Float4::new(-sin * scale.x, cos * scale.y, offset.y, 0.0)
+        ]
+    }
+
+    fn uv_transforms(&self, material: &Material) -> [Float4; MAX_MATERIAL_TEXTURES * 2] {
+        let packed = |texture: &Option<PackedTexture>| texture.as_ref().map(|texture| texture.transform).unwrap_or_default();
+        let transforms = [
+            material.base_color_transform,
+            material.normal_transform,
+            material.metallic_roughness_transform,
+            material.occlusion_transform,
+            material.emissive_transform,
+            packed(&self.clearcoat_texture),
+            packed(&self.sheen_texture),
+            packed(&self.specular_texture),
+            material.clearcoat_normal_transform,
+            material.transmission_transform
+        ];
+
+        let mut uv_transforms = [Float4::default(); MAX_MATERIAL_TEXTURES * 2];
+        for (i, transform) in transforms.iter().enumerate() {
+            let rows = Self::uv_transform(transform);
+            uv_transforms[i * 2] = rows[0];
+            uv_transforms[i * 2 + 1] = rows[1];
+        }
+        uv_transforms
+    }
+
+    // Binds the material block and sampler slots, the program must be bound.
+    // Must come after the other renderers' setup, samplers they assigned to their own slots are not material inputs.
+    pub fn setup_program(shader_program: &mut GLShaderProgram) {
+        shader_program.set_uniform_block_binding(&String::from("MaterialBlock"), MATERIAL_BLOCK_BINDING);
+        for (name, slot) in SAMPLER_SLOTS.iter() {
+            shader_program.set_sampler_slot(&String::from(*name), *slot);
+        }
+
+        if cfg!(debug_assertions) {
+            for input in Self::missing_inputs(shader_program) {
+                eprintln!("Material does not supply shader input. (Name: '{}')", input);
+            }
+        }
+    }
+
+    // Returns the material inputs a program reads that GLMaterial does not supply.
+    // Samplers bound outside the material's slots belong to other renderers, unassigned ones stay at slot 0.
+    pub fn missing_inputs(shader_program: &GLShaderProgram) -> Vec<String> {
+        let mut missing = Vec::new();
+
+        if let Some(block) = shader_program.uniform_block("MaterialBlock") {
+            let offsets = MaterialBlock::std140_offsets();
+            for member in block.members.iter() {
+                if !offsets.iter().any(|(_, offset)| Some(*offset) == member.block_offset) {
+                    missing.push(member.name.clone());
+                }
+            }
+
+            if block.data_size > MaterialBlock::SIZE {
+                missing.push(format!("{} ({}B, supplied {}B)", block.name, block.data_size, MaterialBlock::SIZE));
+            }
+        }
+
+        let is_material_slot = |slot: i32| slot == 0 || SAMPLER_SLOTS.iter().any(|(_, material_slot)| *material_slot == slot);
+        for sampler in shader_program.sampler_bindings().into_iter().filter(|sampler| is_material_slot(sampler.slot)) {
+            match SAMPLER_SLOTS.iter().find(|(name, _)| *name == sampler.name) {
+                Some((_, slot)) if *slot == sampler.slot => {},
+                _ => missing.push(sampler.name)
+            }
+        }
+
+        missing
+    }
+
+    // Blended materials are drawn sorted in the forward pass after the opaque scene.
+    pub fn is_transparent(&self) -> bool {
+        self.material_properties.as_ref().alpha_mode == AlphaMode::Blend
+    }
+
+    // Masked and blended materials, their depth only draws discard fragments below the alpha cutoff.
+    pub fn is_alpha_tested(&self) -> bool {
+        self.material_properties.as_ref().alpha_mode != AlphaMode::Opaque
+    }
+
+    pub fn is_double_sided(&self) -> bool {
+        self.material_properties.as_ref().double_sided
+    }
+
+    // Back face culling is disabled for double sided materials.
+    pub fn apply_culling(&self) {
+        if self.is_double_sided() {
+            gl_disable_cull();
         } else {
-            shader_program.set_bool(&String::from("material.hasBaseColorMap"), false);
+            gl_cull(gl::BACK);
         }
+    }
+
+    // Uploads the material block, the properties are only read again after they change.
+    pub fn update(&self) {
+        let material = self.material_properties.as_ref();
+        self.uniform_buffer.set_data(&MaterialBlock {
+            base_color_factor: material.base_color_factor,
+            emissive_factor: material.emissive_factor,
+            normal_scale: material.normal_scale,
+            metallic_factor: material.metallic_factor,
+            roughness_factor: material.roughness_factor,
+            occlusion_strength: material.occlusion_strength,
+            has_base_color_map: self.base_color_texture.is_some(),
+            has_normal_map: self.normal_texture.is_some(),
+            has_metallic_roughness_map: self.metallic_roughness_texture.is_some(),
+            has_occlusion_map: self.occlusion_texture.is_some(),
+            has_emissive_map: self.emissive_texture.is_some(),
+            alpha_mask: material.alpha_mode == AlphaMode::Mask,
+            alpha_cutoff: material.alpha_cutoff,
+            uv_transforms: self.uv_transforms(&material),
+            emissive_strength: material.emissive_strength,
+            unlit: material.unlit,
+            ior: material.ior,
+            transmission_factor: material.transmission_factor,
+            clearcoat_factor: material.clearcoat_factor,
+            clearcoat_roughness_factor: material.clearcoat_roughness_factor,
+            sheen_roughness_factor: material.sheen_roughness_factor,
+            sheen_color_factor: material.sheen_color_factor,
+            specular_factor: material.specular_factor,
+            specular_color_factor: material.specular_color_factor,
+            has_clearcoat_map: self.clearcoat_texture.as_ref().map_or(false, |texture| texture.first),
+            has_clearcoat_roughness_map: self.clearcoat_texture.as_ref().map_or(false, |texture| texture.second),
+            has_sheen_color_map: self.sheen_texture.as_ref().map_or(false, |texture| texture.first),
+            has_sheen_roughness_map: self.sheen_texture.as_ref().map_or(false, |texture| texture.second),
+            has_specular_color_map: self.specular_texture.as_ref().map_or(false, |texture| texture.first),
+            has_specular_map: self.specular_texture.as_ref().map_or(false, |texture| texture.second),
+            clearcoat_normal_scale: material.clearcoat_normal_scale,
+            has_clearcoat_normal_map: self.clearcoat_normal_texture.is_some(),
+            has_transmission_map: self.transmission_texture.is_some()
+        });
+    }
+
+    pub fn bind(&self) {
+        self.uniform_buffer.bind();
 
-        shader_program.set_float(&String::from("material.normalScale"), self.material_properties.as_ref().normal_scale);
+        if let Some(base_color_texture) = &self.base_color_texture {
+            base_color_texture.bind(0);
+        }
         if let Some(normal_texture) = &self.normal_texture {
             normal_texture.bind(1);
-            shader_program.set_sampler_slot(&String::from("material.normalMap"), 1);
-            shader_program.set_bool(&String::from("material.hasNormalMap"), true);
-        } else {
-            shader_program.set_bool(&String::from("material.hasNormalMap"), false);
         }
-
-        shader_program.set_float(&String::from("material.metallicFactor"), self.material_properties.as_ref().metallic_factor);
-        shader_program.set_float(&String::from("material.roughnessFactor"), self.material_properties.as_ref().roughness_factor);
         if let Some(mr_texture) = &self.metallic_roughness_texture {
             mr_texture.bind(2);
-            shader_program.set_sampler_slot(&String::from("material.metallicRoughnessMap"), 2);
-            shader_program.set_bool(&String::from("material.hasMetallicRoughnessMap"), true);
-        } else {
-            shader_program.set_bool(&String::from("material.hasMetallicRoughnessMap"), false);
         }
-
-        shader_program.set_float(&String::from("material.occlusionStrength"), self.material_properties.as_ref().occlusion_strength);
         if let Some(occlusion_texture) = &self.occlusion_texture {
             occlusion_texture.bind(3);
-            shader_program.set_sampler_slot(&String::from("material.occlusionMap"), 3);
-            shader_program.set_bool(&String::from("material.hasOcclusionMap"), true);
-        } else {
-            shader_program.set_bool(&String::from("material.hasOcclusionMap"), false);
         }
-
-        shader_program.set_float3(&String::from("material.emissiveFactor"), self.material_properties.as_ref().emissive_factor);
         if let Some(emissive_texture) = &self.emissive_texture {
             emissive_texture.bind(4);
-            shader_program.set_sampler_slot(&String::from("material.emissiveMap"), 4);
-            shader_program.set_bool(&String::from("material.hasEmissiveMap"), true);
-        } else {
-            shader_program.set_bool(&String::from("material.hasEmissiveMap"), false);
+        }
+        if let Some(clearcoat_texture) = &self.clearcoat_texture {
+            clearcoat_texture.texture.bind(17);
+        }
+        if let Some(sheen_texture) = &self.sheen_texture {
+            sheen_texture.texture.bind(18);
+        }
+        if let Some(specular_texture) = &self.specular_texture {
+            specular_texture.texture.bind(19);
+        }
+        if let Some(clearcoat_normal_texture) = &self.clearcoat_normal_texture {
+            clearcoat_normal_texture.bind(20);
+        }
+        if let Some(transmission_texture) = &self.transmission_texture {
+            transmission_texture.bind(21);
         }
     }
-}
\ No newline at end of file
+}
@@ -4,19 +4,34 @@ pub use gl_wrapper::*;
 use std::mem;
 use memoffset::offset_of;
 
-use crate::resources::Mesh;
+use crate::resources::{Mesh, PrimitiveType};
 use crate::graphics::opengl::*;
+use crate::graphics::culling::BoundingBox;
 
 use crate::resources::Model;
+use crate::graphics::Animator;
 use crate::Shared;
 
 pub struct GLModel {
     pub meshes: Vec<GLMesh>,
     pub materials: Vec<GLMaterial>,
+    mesh_matrices: Vec<Float4x4>,
+    mesh_skins: Vec<Option<usize>>,
+    mesh_nodes: Vec<Option<usize>>,
+    mesh_weights: Vec<Vec<f32>>
 }
 
+// Model matrices are passed per instance in attribute locations 4 to 7.
+const INSTANCE_ATTRIB_LOCATION: u32 = 4;
+
 impl GLModel {
     pub fn new(model: &Shared<Model>) -> Self {
+        for skin in model.as_ref().skins.iter() {
+            if skin.joints.len() > MAX_JOINTS {
+                eprintln!("Skin has more joints than supported. (Count: {}, ignoring joints past {})", skin.joints.len(), MAX_JOINTS);
+            }
+        }
+
         let mut meshes: Vec<GLMesh> = Vec::new();
         for mesh in model.as_ref().meshes.iter() {
             meshes.push(GLMesh::new(mesh));
@@ -27,22 +42,104 @@ impl GLModel {
             materials.push(GLMaterial::new(material.clone()));
         }
 
-        GLModel {
+        let gl_model = GLModel {
             meshes: meshes,
-            materials: materials
+            materials: materials,
+            mesh_matrices: model.as_ref().mesh_matrices(),
+            mesh_skins: model.as_ref().mesh_skins(),
+            mesh_nodes: model.as_ref().mesh_nodes(),
+            mesh_weights: model.as_ref().mesh_weights()
+        };
+        gl_model.set_instances(&[Float4x4::identity()]);
+
+        gl_model
+    }
+
+    // Uploads the instance matrices of every mesh, combined with the mesh's node matrix.
+    pub fn set_instances(&self, transforms: &[Float4x4]) {
+        for mesh_idx in 0..self.meshes.len() {
+            let mesh_matrix = self.mesh_matrix(mesh_idx);
+            let mesh_transforms: Vec<Float4x4> = transforms.iter().map(|transform| *transform * mesh_matrix).collect();
+            self.meshes[mesh_idx].set_instances(&mesh_transforms);
         }
     }
 
+    // Model space matrix of a mesh from the glTF node hierarchy, skinned meshes are placed by their joints instead.
+    pub fn mesh_matrix(&self, mesh_idx: usize) -> Float4x4 {
+        match self.mesh_skins[mesh_idx] {
+            Some(_) => Float4x4::identity(),
+            None => self.mesh_matrices[mesh_idx]
+        }
+    }
+
+    // Mesh matrix in the animator's pose, skinned meshes stay at the identity.
+    pub fn posed_mesh_matrix(&self, mesh_idx: usize, animator: Option<&Animator>) -> Float4x4 {
+        match (self.mesh_skins[mesh_idx], self.mesh_nodes[mesh_idx], animator) {
+            (None, Some(node), Some(animator)) => animator.node_matrices()[node],
+            _ => self.mesh_matrix(mesh_idx)
+        }
+    }
+
+    pub fn mesh_skin(&self, mesh_idx: usize) -> Option<usize> {
+        self.mesh_skins[mesh_idx]
+    }
+
+    pub fn has_skins(&self) -> bool {
+        self.mesh_skins.iter().any(|skin| skin.is_some())
+    }
+
+    pub fn mesh_node(&self, mesh_idx: usize) -> Option<usize> {
+        self.mesh_nodes[mesh_idx]
+    }
+
+    // Morph target weights of the mesh's node, used when no animator poses the mesh.
+    pub fn mesh_weights(&self, mesh_idx: usize) -> &[f32] {
+        &self.mesh_weights[mesh_idx]
+    }
+
     pub fn bounds(&self) -> (Float3, Float3) {
-        let mut min = self.meshes[0].min;
-        let mut max = self.meshes[0].max;
+        let bounds = self.bounding_box();
+        (bounds.min, bounds.max)
+    }
 
-        for mesh in &self.meshes {
-            min = min.min(mesh.min);
-            max = max.max(mesh.max);
+    pub fn bounding_box(&self) -> BoundingBox {
+        let mut bounds = self.mesh_bounding_box(0);
+        for mesh_idx in 1..self.meshes.len() {
+            bounds = bounds.merge(&self.mesh_bounding_box(mesh_idx));
         }
 
-        (min, max)
+        bounds
+    }
+
+    pub fn mesh_bounding_box(&self, mesh_idx: usize) -> BoundingBox {
+        self.meshes[mesh_idx].bounding_box().transformed(&self.mesh_matrix(mesh_idx))
+    }
+
+    // Bounds of the meshes in the animator's pose, in model space.
+    pub fn posed_bounding_box(&self, animator: Option<&Animator>) -> BoundingBox {
+        let mut bounds = self.posed_mesh_bounding_box(0, animator);
+        for mesh_idx in 1..self.meshes.len() {
+            bounds = bounds.merge(&self.posed_mesh_bounding_box(mesh_idx, animator));
+        }
+
+        bounds
+    }
+
+    // Skinned vertices blend their joint matrices, so they stay inside the bind pose box moved by each joint they use.
+    pub fn posed_mesh_bounding_box(&self, mesh_idx: usize, animator: Option<&Animator>) -> BoundingBox {
+        let mesh = &self.meshes[mesh_idx];
+        let bounds = mesh.bounding_box();
+        match (self.mesh_skins[mesh_idx], animator) {
+            (Some(skin), Some(animator)) => {
+                let joint_matrices = animator.joint_matrices(skin);
+                mesh.used_joints().iter()
+                    .filter_map(|joint| joint_matrices.get(*joint))
+                    .map(|joint_matrix| bounds.transformed(joint_matrix))
+                    .reduce(|posed, joint_bounds| posed.merge(&joint_bounds))
+                    .unwrap_or(bounds)
+            },
+            _ => bounds.transformed(&self.posed_mesh_matrix(mesh_idx, animator))
+        }
     }
 }
 
@@ -50,9 +147,18 @@ pub struct GLMesh {
     vao: Option<GLVAO>,
     vbo: Option<GLVBO>,
     ebo: Option<GLEBO>,
+    instance_vbo: Option<GLVBO>,
 
     index_count: usize,
     material_idx: usize,
+    primitive_mode: GLenum,
+
+    // Displacements of every target, see create_morph_texture.
+    morph_targets: Option<GLTexture2D>,
+    morph_target_count: usize,
+    vertex_count: usize,
+    // Joints with weight on any vertex, sorted.
+    used_joints: Vec<usize>,
 
     min: Float3,
     max: Float3
@@ -63,9 +169,20 @@ pub struct GLVertex {
     position: Float3,
     normal: Float3,
     tex_coord: Float2,
-    tangent: Float4
+    tangent: Float4,
+    joints: Float4,
+    weights: Float4,
+    tex_coord_1: Float2
 }
 
+// Skinning attributes follow the instance matrices.
+const JOINTS_ATTRIB_LOCATION: u32 = 8;
+const WEIGHTS_ATTRIB_LOCATION: u32 = 9;
+const TEX_COORD_1_ATTRIB_LOCATION: u32 = 10;
+
+pub const MORPH_TARGETS_SLOT: u32 = 16;
+const MORPH_TEXTURE_WIDTH: usize = 1024;
+
 impl GLMesh {
     pub fn new(mesh: &Mesh) -> Self {
         let vao = GLVAO::new();
@@ -73,15 +190,27 @@ impl GLMesh {
         let ebo = GLEBO::new();
 
         let mut vertices: Vec<GLVertex> = Vec::with_capacity(mesh.vertices.len());
+        let mut used_joints: Vec<usize> = Vec::new();
         for i in 0..mesh.vertices.len() {
+            let (joints, weights) = Self::supported_joints(mesh.vertices[i].joints, mesh.vertices[i].weights);
+            for (joint, weight) in [(joints.x, weights.x), (joints.y, weights.y), (joints.z, weights.z), (joints.w, weights.w)] {
+                if weight > 0.0 {
+                    used_joints.push(joint as usize);
+                }
+            }
             vertices.push(GLVertex {
                 position: mesh.vertices[i].position,
                 normal: mesh.vertices[i].normal,
                 tex_coord: mesh.vertices[i].tex_coord,
-                tangent: mesh.vertices[i].tangent
+                tangent: mesh.vertices[i].tangent,
+                joints: joints,
+                weights: weights,
+                tex_coord_1: mesh.vertices[i].tex_coord_1
             });
         }
         let mut indices: Vec<u32> = mesh.indices.clone();
+        used_joints.sort();
+        used_joints.dedup();
 
         vao.bind(); {
             let vertex_size = mem::size_of::<GLVertex>();
@@ -93,22 +222,41 @@ impl GLMesh {
             gl_vertex_attrib_ptr(1, 3, vertex_size, offset_of!(GLVertex, normal) as *const c_void);
             gl_vertex_attrib_ptr(2, 2, vertex_size, offset_of!(GLVertex, tex_coord) as *const c_void);
             gl_vertex_attrib_ptr(3, 4, vertex_size, offset_of!(GLVertex, tangent) as *const c_void);
+            gl_vertex_attrib_ptr(JOINTS_ATTRIB_LOCATION, 4, vertex_size, offset_of!(GLVertex, joints) as *const c_void);
+            gl_vertex_attrib_ptr(WEIGHTS_ATTRIB_LOCATION, 4, vertex_size, offset_of!(GLVertex, weights) as *const c_void);
+            gl_vertex_attrib_ptr(TEX_COORD_1_ATTRIB_LOCATION, 2, vertex_size, offset_of!(GLVertex, tex_coord_1) as *const c_void);
 
             gl_enable_vertex_attrib_array(0);
             gl_enable_vertex_attrib_array(1);
             gl_enable_vertex_attrib_array(2);
             gl_enable_vertex_attrib_array(3);
+            gl_enable_vertex_attrib_array(JOINTS_ATTRIB_LOCATION);
+            gl_enable_vertex_attrib_array(WEIGHTS_ATTRIB_LOCATION);
+            gl_enable_vertex_attrib_array(TEX_COORD_1_ATTRIB_LOCATION);
 
             ebo.bind();
             ebo.set_data(mem::size_of::<u32>() * mesh.indices.len(), indices.as_mut_ptr() as *mut c_void);
         } vao.unbind();
 
+        let instance_vbo = GLVBO::new();
+        Self::set_instance_buffer(&vao, &instance_vbo);
+
         GLMesh {
             vao: Some(vao),
             vbo: Some(vbo),
             ebo: Some(ebo),
+            instance_vbo: Some(instance_vbo),
             index_count: indices.len(),
             material_idx: mesh.material_idx,
+            primitive_mode: match mesh.primitive_type {
+                PrimitiveType::Triangles => gl::TRIANGLES,
+                PrimitiveType::Lines => gl::LINES,
+                PrimitiveType::Points => gl::POINTS
+            },
+            morph_targets: Self::create_morph_texture(mesh),
+            morph_target_count: mesh.morph_targets.len(),
+            vertex_count: mesh.vertices.len(),
+            used_joints: used_joints,
             min: mesh.min,
             max: mesh.max
         }
@@ -118,13 +266,133 @@ impl GLMesh {
         self.vao.as_ref().unwrap()
     }
 
+    pub fn bounding_box(&self) -> BoundingBox {
+        BoundingBox::new(self.min, self.max)
+    }
+
     pub fn material_idx(&self) -> usize {
         self.material_idx
     }
 
+    pub fn used_joints(&self) -> &[usize] {
+        &self.used_joints
+    }
+
+    pub fn morph_target_count(&self) -> usize {
+        self.morph_target_count
+    }
+
+    // Joints past MAX_JOINTS lose their weight and point at the first joint, the rest are renormalized.
+    // Vertices left without weight follow the first joint.
+    fn supported_joints(joints: [u16; 4], weights: Float4) -> (Float4, Float4) {
+        let mut joints = joints.map(|joint| joint as f32);
+        let mut weights = [weights.x, weights.y, weights.z, weights.w];
+        for (joint, weight) in joints.iter_mut().zip(weights.iter_mut()) {
+            if *joint as usize >= MAX_JOINTS {
+                *joint = 0.0;
+                *weight = 0.0;
+            }
+        }
+
+        let total: f32 = weights.iter().sum();
+        let weights = match total > 0.0 {
+            true => weights.map(|weight| weight / total),
+            false => [1.0, 0.0, 0.0, 0.0]
+        };
+
+        (Float4::new(joints[0], joints[1], joints[2], joints[3]), Float4::new(weights[0], weights[1], weights[2], weights[3]))
+    }
+
+    // Binds the displacements and returns the block describing them.
+    pub fn bind_morph_targets(&self, weights: &[f32]) -> MorphBlock {
+        if let Some(morph_targets) = self.morph_targets.as_ref() {
+            morph_targets.bind(MORPH_TARGETS_SLOT);
+        }
+
+        let target_count = weights.len().min(self.morph_target_count);
+        MorphBlock::new(&weights[..target_count], self.vertex_count)
+    }
+
+    // Texel (target * 3 + attribute) * vertex_count + vertex holds the position, normal or tangent displacement.
+    // Rows wrap at MORPH_TEXTURE_WIDTH texels.
+    fn create_morph_texture(mesh: &Mesh) -> Option<GLTexture2D> {
+        if mesh.morph_targets.is_empty() {
+            return None;
+        }
+        if mesh.morph_targets.len() > MAX_MORPH_TARGETS {
+            eprintln!("Mesh has more morph targets than supported. (Count: {}, ignoring targets past {})", mesh.morph_targets.len(), MAX_MORPH_TARGETS);
+        }
+
+        let target_count = mesh.morph_targets.len().min(MAX_MORPH_TARGETS);
+        let vertex_count = mesh.vertices.len();
+        let texel_count = target_count * 3 * vertex_count;
+        let rows = (texel_count + MORPH_TEXTURE_WIDTH - 1) / MORPH_TEXTURE_WIDTH;
+
+        let mut data = vec![0.0f32; rows.max(1) * MORPH_TEXTURE_WIDTH * 4];
+        for (target_idx, target) in mesh.morph_targets.iter().take(target_count).enumerate() {
+            for (attribute, displacements) in [&target.positions, &target.normals, &target.tangents].iter().enumerate() {
+                let first_texel = (target_idx * 3 + attribute) * vertex_count;
+                for (vertex, displacement) in displacements.iter().take(vertex_count).enumerate() {
+                    let texel = (first_texel + vertex) * 4;
+                    data[texel] = displacement.x;
+                    data[texel + 1] = displacement.y;
+                    data[texel + 2] = displacement.z;
+                }
+            }
+        }
+
+        let mut desc = GLTextureDesc::new(GLTextureFormat::RGBA32F);
+        desc.sampler.wrap_s = GLWrapMode::ClampToEdge;
+        desc.sampler.wrap_t = GLWrapMode::ClampToEdge;
+        desc.sampler.min_filter = GLFilterMode::Nearest;
+        desc.sampler.mag_filter = GLFilterMode::Nearest;
+        desc.sampler.mip_filter = None;
+
+        Some(GLTexture2D::from_data(Int2::new(MORPH_TEXTURE_WIDTH as i32, rows.max(1) as i32), data.as_ptr() as *const c_void, &desc))
+    }
+
+    fn set_instance_buffer(vao: &GLVAO, instance_vbo: &GLVBO) {
+        let matrix_size = mem::size_of::<f32>() * 16;
+        let column_size = mem::size_of::<f32>() * 4;
+
+        vao.bind(); {
+            instance_vbo.bind();
+
+            for column in 0..4 {
+                let location = INSTANCE_ATTRIB_LOCATION + column as u32;
+                gl_vertex_attrib_ptr(location, 4, matrix_size, (column * column_size) as *const c_void);
+                gl_vertex_attrib_divisor(location, 1);
+                gl_enable_vertex_attrib_array(location);
+            }
+        } vao.unbind();
+    }
+
+    // Uploads the model matrices used by the next draw_instanced call.
+    pub fn set_instances(&self, transforms: &[Float4x4]) {
+        let mut data: Vec<f32> = Vec::with_capacity(transforms.len() * 16);
+        for transform in transforms {
+            data.extend_from_slice(&transform.elems);
+        }
+
+        let instance_vbo = self.instance_vbo.as_ref().unwrap();
+        instance_vbo.bind(); {
+            instance_vbo.set_stream_data(mem::size_of::<f32>() * data.len(), data.as_ptr() as *const c_void);
+        } instance_vbo.unbind();
+    }
+
     pub fn draw(&self) {
         self.vao().bind(); {
-            gl_draw_elems(gl::TRIANGLES, self.index_count, gl::UNSIGNED_INT);
+            gl_draw_elems(self.primitive_mode, self.index_count, gl::UNSIGNED_INT);
+        } self.vao().unbind();
+    }
+
+    pub fn draw_instanced(&self, instance_count: usize) {
+        if instance_count == 0 {
+            return;
+        }
+
+        self.vao().bind(); {
+            gl_draw_elems_instanced(self.primitive_mode, self.index_count, gl::UNSIGNED_INT, instance_count);
         } self.vao().unbind();
     }
 }
@@ -133,6 +401,8 @@ impl Drop for GLMesh {
     fn drop(&mut self) {
         self.vbo = None;
         self.ebo = None;
+        self.instance_vbo = None;
+        self.morph_targets = None;
         self.vao = None;
     }
 }
\ No newline at end of file
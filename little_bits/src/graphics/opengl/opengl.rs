@@ -10,5 +10,11 @@ pub use render_texture::*;
 pub mod render_target;
 pub use render_target::*;
 
+pub mod readback;
+pub use readback::*;
+
 pub mod material;
-pub use material::*;
\ No newline at end of file
+pub use material::*;
+
+pub mod uniform_blocks;
+pub use uniform_blocks::*;
\ No newline at end of file
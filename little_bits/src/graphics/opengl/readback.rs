@@ -0,0 +1,167 @@
+extern crate gl_wrapper;
+pub use gl_wrapper::*;
+
+use crate::resources::{Image, HdrImage};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum GLReadbackFormat {
+    RGBA8,
+    RGBA32F,
+    Depth32F
+}
+
+// Reads pixels into a pixel buffer object so the copy does not stall the frame.
+pub struct GLPixelReadback {
+    pbo: GLPBO,
+    fence: Option<GLFence>,
+    dimensions: Int2,
+    format: GLReadbackFormat
+}
+
+impl GLReadbackFormat {
+    pub fn channel_count(&self) -> i32 {
+        match self {
+            GLReadbackFormat::Depth32F => 1,
+            _ => 4
+        }
+    }
+
+    pub fn component_size(&self) -> usize {
+        match self {
+            GLReadbackFormat::RGBA8 => 1,
+            _ => 4
+        }
+    }
+
+    pub fn format(&self) -> GLenum {
+        match self {
+            GLReadbackFormat::Depth32F => gl::DEPTH_COMPONENT,
+            _ => gl::RGBA
+        }
+    }
+
+    pub fn data_type(&self) -> GLenum {
+        match self {
+            GLReadbackFormat::RGBA8 => gl::UNSIGNED_BYTE,
+            _ => gl::FLOAT
+        }
+    }
+
+    pub fn size(&self, dimensions: Int2) -> usize {
+        (dimensions.x * dimensions.y * self.channel_count()) as usize * self.component_size()
+    }
+}
+
+// Reads the read buffer of the bound framebuffer into a top-down image.
+pub fn gl_read_image(dimensions: Int2) -> Image {
+    let mut image = Image {
+        data: vec![0; GLReadbackFormat::RGBA8.size(dimensions)],
+        dimensions: dimensions,
+        channel_count: 4
+    };
+
+    gl_read_pixels(Int2::new(0, 0), dimensions, gl::RGBA, gl::UNSIGNED_BYTE, image.data.as_mut_ptr() as *mut c_void);
+    image.flip_vertical();
+    image
+}
+
+// Reads the read buffer of the bound framebuffer into a top-down float image.
+pub fn gl_read_hdr_image(dimensions: Int2, format: GLReadbackFormat) -> HdrImage {
+    assert_ne!(format, GLReadbackFormat::RGBA8, "Failed to read hdr image. (Format must be a float format)");
+
+    let mut image = HdrImage {
+        data: vec![0.0; (dimensions.x * dimensions.y * format.channel_count()) as usize],
+        dimensions: dimensions,
+        channel_count: format.channel_count()
+    };
+
+    gl_read_pixels(Int2::new(0, 0), dimensions, format.format(), gl::FLOAT, image.data.as_mut_ptr() as *mut c_void);
+    image.flip_vertical();
+    image
+}
+
+impl GLPixelReadback {
+    pub fn new(dimensions: Int2, format: GLReadbackFormat) -> Self {
+        let pbo = GLPBO::new();
+        pbo.bind(); {
+            pbo.set_data(format.size(dimensions), std::ptr::null_mut());
+        } pbo.unbind();
+
+        GLPixelReadback {
+            pbo: pbo,
+            fence: None,
+            dimensions: dimensions,
+            format: format
+        }
+    }
+
+    // Starts a read from the read buffer of the bound framebuffer.
+    pub fn begin(&mut self) {
+        self.pbo.bind(); {
+            gl_read_pixels(Int2::new(0, 0), self.dimensions, self.format.format(), self.format.data_type(), std::ptr::null_mut());
+        } self.pbo.unbind();
+
+        self.fence = Some(GLFence::new());
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.fence.is_some()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        match &self.fence {
+            Some(fence) => fence.is_signaled(),
+            None => false
+        }
+    }
+
+    pub fn dimensions(&self) -> Int2 {
+        self.dimensions
+    }
+
+    pub fn format(&self) -> GLReadbackFormat {
+        self.format
+    }
+
+    pub fn try_image(&mut self) -> Option<Image> {
+        assert_eq!(self.format, GLReadbackFormat::RGBA8, "Failed to read image. (Readback format must be RGBA8)");
+        if !self.is_ready() {
+            return None;
+        }
+
+        let mut image = Image {
+            data: vec![0; self.format.size(self.dimensions)],
+            dimensions: self.dimensions,
+            channel_count: self.format.channel_count()
+        };
+        self.read_into(image.data.as_mut_ptr() as *mut c_void);
+        image.flip_vertical();
+
+        Some(image)
+    }
+
+    pub fn try_hdr_image(&mut self) -> Option<HdrImage> {
+        assert_ne!(self.format, GLReadbackFormat::RGBA8, "Failed to read hdr image. (Readback format must be a float format)");
+        if !self.is_ready() {
+            return None;
+        }
+
+        let mut image = HdrImage {
+            data: vec![0.0; (self.dimensions.x * self.dimensions.y * self.format.channel_count()) as usize],
+            dimensions: self.dimensions,
+            channel_count: self.format.channel_count()
+        };
+        self.read_into(image.data.as_mut_ptr() as *mut c_void);
+        image.flip_vertical();
+
+        Some(image)
+    }
+
+    fn read_into(&mut self, data: *mut c_void) {
+        self.pbo.bind(); {
+            self.pbo.read(self.format.size(self.dimensions), data);
+        } self.pbo.unbind();
+
+        self.fence = None;
+    }
+}
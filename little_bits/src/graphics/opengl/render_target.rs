@@ -2,19 +2,43 @@ extern crate gl_wrapper;
 pub use gl_wrapper::*;
 
 use crate::graphics::opengl::render_texture::GLRenderTexture;
+use crate::graphics::opengl::readback::*;
+use crate::resources::{Image, HdrImage};
 
 use std::collections::HashMap;
 
 pub struct GLRenderTarget {
     fbo: GLFBO,
-    rbo: GLRBO,
-    textures: HashMap<GLRenderAttachment, GLRenderTexture>
+    rbo: Option<(GLRBO, GLTextureFormat)>,
+    textures: HashMap<GLRenderAttachment, GLRenderTexture>,
+    external_attachments: Vec<GLRenderAttachment>,
+    samples: u32,
+    width: usize,
+    height: usize
 }
 
-#[derive(std::cmp::Eq, std::cmp::PartialEq, Hash)]
+#[derive(std::cmp::Eq, std::cmp::PartialEq, Hash, Clone, Copy, Debug)]
 pub enum GLRenderAttachment {
     Color(u32),
-    Depth
+    Depth,
+    DepthStencil
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum GLDepthAttachment {
+    None,
+    // Not sampleable, cheapest option when depth is only needed for testing.
+    Renderbuffer(GLTextureFormat),
+    Texture(GLTextureFormat)
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct GLRenderTargetDesc {
+    pub width: usize,
+    pub height: usize,
+    pub samples: u32,
+    pub color_formats: Vec<GLTextureFormat>,
+    pub depth: GLDepthAttachment
 }
 
 impl GLRenderAttachment {
@@ -27,35 +51,121 @@ impl GLRenderAttachment {
                     std::mem::transmute(slot0 + slot)
                 }
             },
-            GLRenderAttachment::Depth => gl::DEPTH_ATTACHMENT
+            GLRenderAttachment::Depth => gl::DEPTH_ATTACHMENT,
+            GLRenderAttachment::DepthStencil => gl::DEPTH_STENCIL_ATTACHMENT
+        }
+    }
+
+    pub fn for_depth_format(format: GLTextureFormat) -> Self {
+        assert!(format.is_depth(), "Failed to create depth attachment. (Not a depth format)");
+        if format.has_stencil() { GLRenderAttachment::DepthStencil } else { GLRenderAttachment::Depth }
+    }
+
+    pub fn is_depth(&self) -> bool {
+        !matches!(self, GLRenderAttachment::Color(_))
+    }
+}
+
+impl GLRenderTargetDesc {
+    pub fn new(width: usize, height: usize) -> Self {
+        GLRenderTargetDesc {
+            width: width,
+            height: height,
+            samples: 1,
+            color_formats: Vec::new(),
+            depth: GLDepthAttachment::Renderbuffer(GLTextureFormat::Depth24)
         }
     }
 }
 
 impl GLRenderTarget {
     pub fn new(width: usize, height: usize) -> Self {
-        let fbo = GLFBO::new();
-        let rbo = GLRBO::new();
-        let textures = HashMap::new();
+        Self::from_desc(&GLRenderTargetDesc::new(width, height))
+    }
+
+    pub fn from_desc(desc: &GLRenderTargetDesc) -> Self {
+        let samples = desc.samples.clamp(1, gl_max_samples());
 
-        fbo.bind(); {
-            rbo.bind(); {
-                gl_render_buffer_storage(gl::DEPTH_COMPONENT, width as i32, height as i32);
-            } rbo.unbind();
+        let mut target = GLRenderTarget {
+            fbo: GLFBO::new(),
+            rbo: None,
+            textures: HashMap::new(),
+            external_attachments: Vec::new(),
+            samples: samples,
+            width: desc.width,
+            height: desc.height
+        };
 
-            gl_frame_buffer_render_buffer(&rbo, gl::DEPTH_ATTACHMENT);
+        for (slot, format) in desc.color_formats.iter().enumerate() {
+            let texture = GLRenderTexture::multisampled(desc.width, desc.height, *format, samples);
+            target.set_texture(GLRenderAttachment::Color(slot as u32), texture);
+        }
 
-            fbo.check_status();
-        } fbo.unbind();
+        match desc.depth {
+            GLDepthAttachment::None => (),
+            GLDepthAttachment::Renderbuffer(format) => {
+                assert!(format.is_depth(), "Failed to create GLRenderTarget. (Depth renderbuffer needs a depth format)");
+                target.rbo = Some((GLRBO::new(), format));
+                target.allocate_render_buffer();
+            },
+            GLDepthAttachment::Texture(format) => {
+                let texture = GLRenderTexture::multisampled(desc.width, desc.height, format, samples);
+                target.set_texture(GLRenderAttachment::for_depth_format(format), texture);
+            }
+        }
 
-        GLRenderTarget {
-            fbo: fbo,
-            rbo: rbo,
-            textures: textures
+        if target.rbo.is_some() || !target.textures.is_empty() {
+            target.bind(); {
+                target.check();
+            } target.unbind();
         }
+
+        target
+    }
+
+    fn allocate_render_buffer(&self) {
+        if let Some((rbo, format)) = &self.rbo {
+            self.bind(); {
+                rbo.bind(); {
+                    if self.samples > 1 {
+                        gl_render_buffer_storage_multisample(self.samples, format.internal_format(), self.width as i32, self.height as i32);
+                    } else {
+                        gl_render_buffer_storage(format.internal_format(), self.width as i32, self.height as i32);
+                    }
+                } rbo.unbind();
+
+                gl_frame_buffer_render_buffer(rbo, GLRenderAttachment::for_depth_format(*format).to_gl());
+            } self.unbind();
+        }
+    }
+
+    // Owned attachments are reallocated, external attachments have to be reattached by the caller.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if self.width == width && self.height == height {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+
+        self.bind(); {
+            for (attachment, texture) in self.textures.iter_mut() {
+                texture.resize(width, height);
+                gl_frame_buffer_texture_2d(texture.tex(), attachment.to_gl());
+            }
+        } self.unbind();
+
+        self.allocate_render_buffer();
     }
 
     pub fn set_texture(&mut self, attachment: GLRenderAttachment, texture: GLRenderTexture) {
+        assert_eq!(texture.samples(), self.samples, "Failed to set GLRenderTexture. (Sample count mismatch)");
+        assert_eq!(attachment.is_depth(), texture.format().is_depth(), "Failed to set GLRenderTexture. (Attachment and format mismatch)");
+
+        if attachment.is_depth() {
+            self.detach_render_buffer();
+        }
+
         self.bind(); {
             let glattachment = attachment.to_gl();
             gl_frame_buffer_texture_2d(&texture.tex(), glattachment);
@@ -69,14 +179,78 @@ impl GLRenderTarget {
                 self.textures.insert(attachment, texture);
             }
         }
+        self.external_attachments.retain(|external| *external != attachment);
+
+        self.set_active_buffers();
+    }
+
+    // Attaches a render texture owned elsewhere, e.g. by a render graph.
+    pub fn set_external_texture(&mut self, attachment: GLRenderAttachment, texture: &GLRenderTexture) {
+        assert_eq!(texture.samples(), self.samples, "Failed to set GLRenderTexture. (Sample count mismatch)");
+        assert_eq!(attachment.is_depth(), texture.format().is_depth(), "Failed to set GLRenderTexture. (Attachment and format mismatch)");
+
+        self.bind(); {
+            gl_frame_buffer_texture_2d(texture.tex(), attachment.to_gl());
+        } self.unbind();
+
+        self.set_external(attachment);
+    }
+
+    // Attaches a single face of a cubemap, the texture is not owned by the target.
+    pub fn set_cube_face(&mut self, attachment: GLRenderAttachment, texture: &GLTextureCube, face: u32, level: u32) {
+        self.bind(); {
+            gl_frame_buffer_texture_face(texture.tex(), attachment.to_gl(), face, level);
+        } self.unbind();
+
+        self.set_external(attachment);
+    }
+
+    // Attaches a single layer of an array or 3D texture, the texture is not owned by the target.
+    pub fn set_texture_layer(&mut self, attachment: GLRenderAttachment, texture: &GLTexture, layer: i32, level: u32) {
+        self.bind(); {
+            gl_frame_buffer_texture_layer(texture, attachment.to_gl(), layer, level);
+        } self.unbind();
+
+        self.set_external(attachment);
+    }
+
+    // Attaches all layers or faces for layered rendering with gl_Layer, the texture is not owned by the target.
+    pub fn set_texture_layered(&mut self, attachment: GLRenderAttachment, texture: &GLTexture, level: u32) {
+        self.bind(); {
+            gl_frame_buffer_texture_layered(texture, attachment.to_gl(), level);
+        } self.unbind();
+
+        self.set_external(attachment);
+    }
+
+    // A depth texture replaces the depth renderbuffer.
+    fn detach_render_buffer(&mut self) {
+        if let Some((_, format)) = self.rbo.take() {
+            self.bind(); {
+                gl_frame_buffer_detach_render_buffer(GLRenderAttachment::for_depth_format(format).to_gl());
+            } self.unbind();
+        }
+    }
+
+    fn set_external(&mut self, attachment: GLRenderAttachment) {
+        if attachment.is_depth() {
+            self.detach_render_buffer();
+        }
+
+        self.textures.remove(&attachment);
+        if !self.external_attachments.contains(&attachment) {
+            self.external_attachments.push(attachment);
+        }
 
         self.set_active_buffers();
     }
 
     fn set_active_buffers(&self) {
         let mut attachments = Vec::new();
-        for (attachment, _) in &self.textures {
-            attachments.push(attachment.to_gl());
+        for attachment in self.textures.keys().chain(self.external_attachments.iter()) {
+            if let GLRenderAttachment::Color(_) = attachment {
+                attachments.push(attachment.to_gl());
+            }
         }
 
         attachments.sort();
@@ -93,6 +267,126 @@ impl GLRenderTarget {
         }
     }
 
+    pub fn get_texture_ref(&self, attachment: GLRenderAttachment) -> Option<&GLRenderTexture> {
+        self.textures.get(&attachment)
+    }
+
+    // Copies one attachment into another target, a None target is the window frame buffer.
+    pub fn blit(&self, attachment: GLRenderAttachment, target: Option<&GLRenderTarget>, target_attachment: GLRenderAttachment, filter: GLFilterMode) {
+        assert_eq!(attachment.is_depth(), target_attachment.is_depth(), "Failed to blit. (Color and depth attachments can't be mixed)");
+
+        let target_dimensions = target.map_or(self.dimensions(), |target| target.dimensions());
+        if self.samples > 1 {
+            assert_eq!(self.dimensions(), target_dimensions, "Failed to blit. (Multisampled targets can't be scaled)");
+        }
+
+        let (mask, filter) = if attachment.is_depth() {
+            let mask = if attachment == GLRenderAttachment::DepthStencil { gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT } else { gl::DEPTH_BUFFER_BIT };
+            (mask, gl::NEAREST)
+        } else {
+            self.bind(); {
+                gl_read_buffer(attachment.to_gl());
+            } self.unbind();
+
+            if let Some(target) = target {
+                let draw_buffer = target_attachment.to_gl();
+                target.bind(); {
+                    gl_draw_buffers(1, &draw_buffer);
+                } target.unbind();
+            }
+
+            (gl::COLOR_BUFFER_BIT, filter.to_gl())
+        };
+
+        gl_blit_frame_buffer(Some(&self.fbo), target.map(|target| &target.fbo), self.dimensions(), target_dimensions, mask, filter);
+
+        if let Some(target) = target {
+            target.set_active_buffers();
+        }
+    }
+
+    pub fn blit_to_screen(&self, attachment: GLRenderAttachment) {
+        self.blit(attachment, None, attachment, GLFilterMode::Nearest);
+    }
+
+    // Resolves every attachment both targets share, typically from a multisampled into a single sampled target.
+    pub fn resolve(&self, target: &GLRenderTarget) {
+        let mut attachments: Vec<GLRenderAttachment> = self.textures.keys().cloned().collect();
+        if let Some((_, format)) = &self.rbo {
+            attachments.push(GLRenderAttachment::for_depth_format(*format));
+        }
+        attachments.sort_by_key(|attachment| attachment.to_gl());
+
+        for attachment in attachments {
+            if target.has_attachment(attachment) {
+                self.blit(attachment, Some(target), attachment, GLFilterMode::Nearest);
+            }
+        }
+    }
+
+    pub fn has_attachment(&self, attachment: GLRenderAttachment) -> bool {
+        if self.textures.contains_key(&attachment) || self.external_attachments.contains(&attachment) {
+            return true;
+        }
+
+        match &self.rbo {
+            Some((_, format)) => GLRenderAttachment::for_depth_format(*format) == attachment,
+            None => false
+        }
+    }
+
+    pub fn read_pixels(&self, attachment: GLRenderAttachment) -> Image {
+        assert!(!attachment.is_depth(), "Failed to read pixels. (Use read_pixels_f32 for depth)");
+        assert_eq!(self.samples, 1, "Failed to read pixels. (Target is multisampled, resolve it first)");
+
+        let image;
+        self.bind(); {
+            gl_read_buffer(attachment.to_gl());
+            image = gl_read_image(self.dimensions());
+        } self.unbind();
+
+        image
+    }
+
+    pub fn read_pixels_f32(&self, attachment: GLRenderAttachment) -> HdrImage {
+        assert_eq!(self.samples, 1, "Failed to read pixels. (Target is multisampled, resolve it first)");
+
+        let image;
+        self.bind(); {
+            let format = match attachment {
+                GLRenderAttachment::Depth | GLRenderAttachment::DepthStencil => GLReadbackFormat::Depth32F,
+                GLRenderAttachment::Color(_) => {
+                    gl_read_buffer(attachment.to_gl());
+                    GLReadbackFormat::RGBA32F
+                }
+            };
+
+            image = gl_read_hdr_image(self.dimensions(), format);
+        } self.unbind();
+
+        image
+    }
+
+    // Starts an asynchronous read, poll the readback for the result in a later frame.
+    pub fn read_pixels_async(&self, attachment: GLRenderAttachment, readback: &mut GLPixelReadback) {
+        assert_eq!(readback.dimensions(), self.dimensions(), "Failed to read pixels. (Readback dimensions mismatch)");
+
+        self.bind(); {
+            if let GLRenderAttachment::Color(_) = attachment {
+                gl_read_buffer(attachment.to_gl());
+            }
+            readback.begin();
+        } self.unbind();
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn dimensions(&self) -> Int2 {
+        Int2::new(self.width as i32, self.height as i32)
+    }
+
     pub fn bind(&self) {
         self.fbo.bind();
     }
@@ -1,28 +1,94 @@
 extern crate gl_wrapper;
 pub use gl_wrapper::*;
 
+use crate::resources::HdrImage;
+
 pub struct GLRenderTexture {
     tex: GLTexture,
+    format: GLTextureFormat,
+    samples: u32,
     width: usize,
     height: usize
 }
 
 impl GLRenderTexture {
     pub fn new(width: usize, height: usize) -> Self {
-        let tex = GLTexture::new(gl::TEXTURE_2D);
+        Self::with_format(width, height, GLTextureFormat::RGBA16F)
+    }
+
+    pub fn with_format(width: usize, height: usize, format: GLTextureFormat) -> Self {
+        Self::multisampled(width, height, format, 1)
+    }
 
-        tex.bind(); {
-            gl_tex_image_2df(gl::RGBA16F, width as i32, height as i32, gl::RGBA, std::ptr::null());
-            gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR);
-            gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR);
-        } tex.unbind();
+    // Multisampled textures can't be sampled directly, resolve them into a single sampled target first.
+    pub fn multisampled(width: usize, height: usize, format: GLTextureFormat, samples: u32) -> Self {
+        let samples = samples.clamp(1, gl_max_samples());
+        let target = if samples > 1 { gl::TEXTURE_2D_MULTISAMPLE } else { gl::TEXTURE_2D };
 
-        GLRenderTexture {
-            tex: tex,
+        let mut texture = GLRenderTexture {
+            tex: GLTexture::new(target),
+            format: format,
+            samples: samples,
             width: width,
             height: height
+        };
+
+        texture.allocate();
+
+        if samples == 1 {
+            let filter = if format.is_integer() || format.is_depth() { gl::NEAREST } else { gl::LINEAR };
+
+            texture.tex.bind(); {
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter);
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter);
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE);
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE);
+            } texture.tex.unbind();
         }
+
+        texture
+    }
+
+    fn allocate(&mut self) {
+        self.tex.bind(); {
+            if self.samples > 1 {
+                gl_tex_image_2d_multisample(self.samples, self.format, self.width as i32, self.height as i32);
+            } else {
+                gl_tex_image_2d_format(gl::TEXTURE_2D, 0, self.format, self.width as i32, self.height as i32, std::ptr::null());
+            }
+        } self.tex.unbind();
+    }
+
+    // Storage is reallocated in place, so frame buffer attachments keep referring to this texture.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if self.width == width && self.height == height {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.allocate();
     }
+
+    // Enables hardware depth comparison so the texture can be bound to a sampler2DShadow.
+    pub fn set_compare_mode(&self, enabled: bool) {
+        assert!(self.format.is_depth(), "Failed to set compare mode. (Not a depth texture)");
+        assert_eq!(self.samples, 1, "Failed to set compare mode. (Texture is multisampled)");
+
+        self.tex.bind(); {
+            if enabled {
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE);
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL);
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR);
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR);
+            } else {
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_MODE, gl::NONE);
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST);
+                gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST);
+            }
+        } self.tex.unbind();
+    }
+
     pub fn bind(&self, slot: u32) {
         gl_active_texture(slot);
         self.tex.bind();
@@ -32,10 +98,37 @@ impl GLRenderTexture {
         self.tex.unbind();
     }
 
+    pub fn read_pixels_f32(&self) -> HdrImage {
+        assert_eq!(self.samples, 1, "Failed to read pixels. (Texture is multisampled, resolve it first)");
+        assert!(!self.format.is_integer(), "Failed to read pixels. (Integer textures can't be read as floats)");
+
+        let (format, channel_count) = if self.format.is_depth() { (gl::DEPTH_COMPONENT, 1) } else { (gl::RGBA, 4) };
+        let mut image = HdrImage {
+            data: vec![0.0; self.width * self.height * channel_count],
+            dimensions: Int2::new(self.width as i32, self.height as i32),
+            channel_count: channel_count as i32
+        };
+
+        self.tex.bind(); {
+            gl_get_tex_image(gl::TEXTURE_2D, 0, format, gl::FLOAT, image.data.as_mut_ptr() as *mut c_void);
+        } self.tex.unbind();
+
+        image.flip_vertical();
+        image
+    }
+
     pub fn tex(&self) -> &GLTexture {
         &self.tex
     }
 
+    pub fn format(&self) -> GLTextureFormat {
+        self.format
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
     pub fn width(&self) -> usize {
         self.width
     }
@@ -43,4 +136,4 @@ impl GLRenderTexture {
     pub fn height(&self) -> usize {
         self.height
     }
-}
\ No newline at end of file
+}
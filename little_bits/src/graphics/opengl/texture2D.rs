@@ -3,43 +3,62 @@
 extern crate gl_wrapper;
 pub use gl_wrapper::*;
 
-use crate::resources::Image;
+use crate::resources::{Image, Sampler, WrapMode, FilterMode};
 use crate::Shared;
 
 pub struct GLTexture2D {
-    tex: GLTexture
+    tex: GLTexture,
+    desc: GLTextureDesc
 }
 
 impl GLTexture2D {
-    pub fn new(image: &Shared<Image>) -> Option<Self> {
+    pub fn new(image: &Shared<Image>, sampler: &GLSamplerDesc, srgb: bool) -> Option<Self> {
         match image.try_as_ref() {
             Some(image) => {
-                let texture = GLTexture2D {
-                    tex: GLTexture::new(gl::TEXTURE_2D)
-                };
-        
-                texture.bind_slotless(); {
-                    gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT);
-                    gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT);
-                    gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR);
-                    gl_tex_parami(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR);
-        
-                    let format = match image.channel_count {
-                        1 => gl::RED,
-                        3 => gl::RGB,
-                        _ => gl::RGBA
-                    };
-        
-                    gl_tex_image_2d(gl::RGBA, image.dimensions.x, image.dimensions.y, format, image.data.as_ptr() as *const c_void);
-                    gl_gen_mips(gl::TEXTURE_2D);
-                } texture.unbind();
-        
-                Some(texture)
+                let mut desc = GLTextureDesc::new(GLTextureFormat::from_channel_count(image.channel_count, srgb));
+                desc.sampler = *sampler;
+
+                Some(Self::from_data(image.dimensions, image.data.as_ptr() as *const c_void, &desc))
             },
             None => None
         }
     }
 
+    pub fn from_f32(dimensions: Int2, data: &[f32], desc: &GLTextureDesc) -> Self {
+        assert_eq!(desc.format.data_type(), gl::FLOAT, "Failed to create GLTexture2D. (Format must be a float format)");
+        assert_eq!(data.len(), (dimensions.x * dimensions.y * desc.format.channel_count()) as usize, "Failed to create GLTexture2D. (Data size mismatch)");
+
+        Self::from_data(dimensions, data.as_ptr() as *const c_void, desc)
+    }
+
+    pub fn from_data(dimensions: Int2, data: *const c_void, desc: &GLTextureDesc) -> Self {
+        let texture = GLTexture2D {
+            tex: GLTexture::new(gl::TEXTURE_2D),
+            desc: *desc
+        };
+
+        texture.bind_slotless(); {
+            texture.tex.apply_desc(desc, dimensions.x, dimensions.y);
+
+            gl_tex_image_2d_format(gl::TEXTURE_2D, 0, desc.format, dimensions.x, dimensions.y, data);
+            if desc.mip_level_count(dimensions.x, dimensions.y) > 1 {
+                gl_gen_mips(gl::TEXTURE_2D);
+            }
+        } texture.unbind();
+
+        texture
+    }
+
+    // Replaces the contents, the dimensions may change.
+    pub fn set_data(&self, dimensions: Int2, data: *const c_void) {
+        self.bind_slotless(); {
+            gl_tex_image_2d_format(gl::TEXTURE_2D, 0, self.desc.format, dimensions.x, dimensions.y, data);
+            if self.desc.mip_level_count(dimensions.x, dimensions.y) > 1 {
+                gl_gen_mips(gl::TEXTURE_2D);
+            }
+        } self.unbind();
+    }
+
     pub fn bind(&self, slot: u32) {
         gl_active_texture(slot);
         self.tex.bind();
@@ -56,4 +75,37 @@ impl GLTexture2D {
     pub fn tex(&self) -> &GLTexture {
         &self.tex
     }
-}
\ No newline at end of file
+
+    pub fn desc(&self) -> &GLTextureDesc {
+        &self.desc
+    }
+}
+
+impl From<&Sampler> for GLSamplerDesc {
+    fn from(sampler: &Sampler) -> Self {
+        let wrap_mode = |mode: WrapMode| -> GLWrapMode {
+            match mode {
+                WrapMode::Repeat => GLWrapMode::Repeat,
+                WrapMode::MirroredRepeat => GLWrapMode::MirroredRepeat,
+                WrapMode::ClampToEdge => GLWrapMode::ClampToEdge
+            }
+        };
+
+        let filter_mode = |mode: FilterMode| -> GLFilterMode {
+            match mode {
+                FilterMode::Nearest => GLFilterMode::Nearest,
+                FilterMode::Linear => GLFilterMode::Linear
+            }
+        };
+
+        GLSamplerDesc {
+            wrap_s: wrap_mode(sampler.wrap_s),
+            wrap_t: wrap_mode(sampler.wrap_t),
+            wrap_r: GLWrapMode::Repeat,
+            min_filter: filter_mode(sampler.min_filter),
+            mag_filter: filter_mode(sampler.mag_filter),
+            mip_filter: sampler.mip_filter.map(filter_mode),
+            anisotropy: 1.0
+        }
+    }
+}
@@ -0,0 +1,181 @@
+extern crate gl_wrapper;
+pub use gl_wrapper::*;
+
+pub const CAMERA_BLOCK_BINDING: u32 = 0;
+pub const MATERIAL_BLOCK_BINDING: u32 = 1;
+pub const LIGHT_BLOCK_BINDING: u32 = 2;
+pub const SHADOW_BLOCK_BINDING: u32 = 3;
+pub const JOINT_BLOCK_BINDING: u32 = 4;
+pub const MORPH_BLOCK_BINDING: u32 = 5;
+
+// Must match the defines in the shaders.
+pub const MAX_LIGHTS: usize = 16;
+pub const MAX_SHADOW_CASCADES: usize = 4;
+pub const MAX_SPOT_SHADOWS: usize = 4;
+pub const MAX_JOINTS: usize = 128;
+pub const MAX_MORPH_TARGETS: usize = 8;
+pub const MAX_MATERIAL_TEXTURES: usize = 10;
+
+std140_struct! {
+    pub struct CameraBlock {
+        pub projection: Float4x4,
+        pub view: Float4x4,
+        pub view_pos: Float3
+    }
+}
+
+std140_struct! {
+    pub struct MaterialBlock {
+        pub base_color_factor: Float4,
+        pub emissive_factor: Float3,
+        pub normal_scale: f32,
+        pub metallic_factor: f32,
+        pub roughness_factor: f32,
+        pub occlusion_strength: f32,
+
+        pub has_base_color_map: bool,
+        pub has_normal_map: bool,
+        pub has_metallic_roughness_map: bool,
+        pub has_occlusion_map: bool,
+        pub has_emissive_map: bool,
+
+        pub alpha_mask: bool,
+        pub alpha_cutoff: f32,
+
+        // Two rows per texture, in sampler slot order.
+        pub uv_transforms: [Float4; MAX_MATERIAL_TEXTURES * 2],
+        pub emissive_strength: f32,
+        pub unlit: bool,
+        pub ior: f32,
+        pub transmission_factor: f32,
+        pub clearcoat_factor: f32,
+        pub clearcoat_roughness_factor: f32,
+        pub sheen_roughness_factor: f32,
+        pub sheen_color_factor: Float3,
+        pub specular_factor: f32,
+        pub specular_color_factor: Float3,
+
+        pub has_clearcoat_map: bool,
+        pub has_clearcoat_roughness_map: bool,
+        pub has_sheen_color_map: bool,
+        pub has_sheen_roughness_map: bool,
+        pub has_specular_color_map: bool,
+        pub has_specular_map: bool,
+
+        pub clearcoat_normal_scale: f32,
+        pub has_clearcoat_normal_map: bool,
+        pub has_transmission_map: bool
+    }
+}
+
+std140_struct! {
+    #[derive(Clone, Copy)]
+    pub struct LightData {
+        pub position: Float3,
+        pub range: f32,
+        pub direction: Float3,
+        pub intensity: f32,
+        pub color: Float3,
+        pub kind: i32,
+        pub angle_scale: f32,
+        pub angle_offset: f32,
+        // Cascade index 0 for directional lights, spot shadow map index for spot lights, -1 without shadows.
+        pub shadow_index: i32,
+        pub shadow_bias: f32,
+        pub shadow_normal_bias: f32
+    }
+}
+
+std140_struct! {
+    pub struct LightBlock {
+        pub lights: [LightData; MAX_LIGHTS],
+        pub light_count: i32
+    }
+}
+
+std140_struct! {
+    pub struct ShadowBlock {
+        pub cascade_matrices: [Float4x4; MAX_SHADOW_CASCADES],
+        // View space far distance of every cascade.
+        pub cascade_splits: Float4,
+        pub spot_matrices: [Float4x4; MAX_SPOT_SHADOWS],
+        pub cascade_count: i32
+    }
+}
+
+std140_struct! {
+    pub struct JointBlock {
+        pub joints: [Float4x4; MAX_JOINTS],
+        pub skinned: bool
+    }
+}
+
+// Weights are packed four to a vector.
+std140_struct! {
+    pub struct MorphBlock {
+        pub weights: [Float4; MAX_MORPH_TARGETS / 4],
+        pub target_count: i32,
+        pub vertex_count: i32
+    }
+}
+
+impl CameraBlock {
+    pub fn setup_program(shader_program: &mut GLShaderProgram) {
+        shader_program.set_uniform_block_binding(&String::from("CameraBlock"), CAMERA_BLOCK_BINDING);
+    }
+}
+
+impl LightBlock {
+    pub fn setup_program(shader_program: &mut GLShaderProgram) {
+        shader_program.set_uniform_block_binding(&String::from("LightBlock"), LIGHT_BLOCK_BINDING);
+    }
+}
+
+impl JointBlock {
+    pub fn setup_program(shader_program: &mut GLShaderProgram) {
+        shader_program.set_uniform_block_binding(&String::from("JointBlock"), JOINT_BLOCK_BINDING);
+    }
+
+    // Joints past MAX_JOINTS are ignored.
+    pub fn skinned(joint_matrices: &[Float4x4]) -> Self {
+        let mut joints = [Float4x4::identity(); MAX_JOINTS];
+        for (joint, matrix) in joints.iter_mut().zip(joint_matrices.iter()) {
+            *joint = *matrix;
+        }
+
+        JointBlock {
+            joints: joints,
+            skinned: true
+        }
+    }
+
+    pub fn unskinned() -> Self {
+        JointBlock {
+            joints: [Float4x4::identity(); MAX_JOINTS],
+            skinned: false
+        }
+    }
+}
+
+impl MorphBlock {
+    pub fn setup_program(shader_program: &mut GLShaderProgram) {
+        shader_program.set_uniform_block_binding(&String::from("MorphBlock"), MORPH_BLOCK_BINDING);
+    }
+
+    // Targets past MAX_MORPH_TARGETS are ignored.
+    pub fn new(weights: &[f32], vertex_count: usize) -> Self {
+        let target_count = weights.len().min(MAX_MORPH_TARGETS);
+        let mut packed = [0.0; MAX_MORPH_TARGETS];
+        packed[..target_count].copy_from_slice(&weights[..target_count]);
+
+        MorphBlock {
+            weights: std::array::from_fn(|i| Float4::new(packed[i * 4], packed[i * 4 + 1], packed[i * 4 + 2], packed[i * 4 + 3])),
+            target_count: target_count as i32,
+            vertex_count: vertex_count as i32
+        }
+    }
+
+    pub fn none() -> Self {
+        Self::new(&[], 0)
+    }
+}
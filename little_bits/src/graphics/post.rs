@@ -0,0 +1,631 @@
+extern crate rand;
+use rand::Rng;
+
+use crate::gmaths::*;
+use crate::app;
+use crate::resources::Image;
+use crate::graphics::opengl::*;
+use crate::graphics::render_graph::*;
+use crate::graphics::FrameGraph;
+use crate::Shared;
+
+const SSAO_KERNEL_SIZE: usize = 32;
+const SSAO_NOISE_SIZE: i32 = 4;
+
+// Effects run on the linear scene color before tonemapping, or on the display color after it.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PostStage {
+    Hdr,
+    Ldr
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct SsaoSettings {
+    pub enabled: bool,
+    // View space sample radius.
+    pub radius: f32,
+    pub bias: f32,
+    pub power: f32,
+    pub intensity: f32
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    // Blend between the scene and the bloom, energy is kept so values stay small.
+    pub strength: f32,
+    // Upsample tent filter radius in uv space.
+    pub filter_radius: f32,
+    pub mip_count: u32
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct FxaaSettings {
+    pub enabled: bool,
+    pub span_max: f32,
+    pub reduce_min: f32,
+    pub reduce_mul: f32
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct VignetteSettings {
+    pub enabled: bool,
+    pub intensity: f32,
+    pub smoothness: f32,
+    // 1.0 is circular, 0.0 follows the aspect ratio of the screen.
+    pub roundness: f32
+}
+
+// The lut is a horizontal strip of size x size slices, blue increases per slice and green downwards.
+#[derive(Clone)]
+pub struct ColorGradingSettings {
+    pub enabled: bool,
+    pub lut: Option<Shared<Image>>,
+    pub intensity: f32
+}
+
+#[derive(Clone)]
+pub enum PostEffect {
+    Ssao(SsaoSettings),
+    Bloom(BloomSettings),
+    Fxaa(FxaaSettings),
+    Vignette(VignetteSettings),
+    ColorGrading(ColorGradingSettings)
+}
+
+// Ordered list of effects, HDR effects always run before tonemapping and LDR effects after it.
+#[derive(Clone)]
+pub struct PostProcessStack {
+    effects: Vec<PostEffect>
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        SsaoSettings {
+            enabled: true,
+            radius: 0.5,
+            bias: 0.025,
+            power: 1.5,
+            intensity: 1.0
+        }
+    }
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        BloomSettings {
+            enabled: true,
+            strength: 0.04,
+            filter_radius: 0.005,
+            mip_count: 6
+        }
+    }
+}
+
+impl Default for FxaaSettings {
+    fn default() -> Self {
+        FxaaSettings {
+            enabled: true,
+            span_max: 8.0,
+            reduce_min: 1.0 / 128.0,
+            reduce_mul: 1.0 / 8.0
+        }
+    }
+}
+
+impl Default for VignetteSettings {
+    fn default() -> Self {
+        VignetteSettings {
+            enabled: true,
+            intensity: 0.3,
+            smoothness: 0.5,
+            roundness: 1.0
+        }
+    }
+}
+
+impl Default for ColorGradingSettings {
+    fn default() -> Self {
+        ColorGradingSettings {
+            enabled: true,
+            lut: None,
+            intensity: 1.0
+        }
+    }
+}
+
+impl PostEffect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostEffect::Ssao(_) => "SSAO",
+            PostEffect::Bloom(_) => "Bloom",
+            PostEffect::Fxaa(_) => "FXAA",
+            PostEffect::Vignette(_) => "Vignette",
+            PostEffect::ColorGrading(_) => "Color Grading"
+        }
+    }
+
+    pub fn stage(&self) -> PostStage {
+        match self {
+            PostEffect::Ssao(_) | PostEffect::Bloom(_) => PostStage::Hdr,
+            _ => PostStage::Ldr
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        match self {
+            PostEffect::Ssao(settings) => settings.enabled,
+            PostEffect::Bloom(settings) => settings.enabled,
+            PostEffect::Fxaa(settings) => settings.enabled,
+            PostEffect::Vignette(settings) => settings.enabled,
+            PostEffect::ColorGrading(settings) => settings.enabled && settings.lut.is_some()
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        match self {
+            PostEffect::Ssao(settings) => settings.enabled = enabled,
+            PostEffect::Bloom(settings) => settings.enabled = enabled,
+            PostEffect::Fxaa(settings) => settings.enabled = enabled,
+            PostEffect::Vignette(settings) => settings.enabled = enabled,
+            PostEffect::ColorGrading(settings) => settings.enabled = enabled
+        }
+    }
+
+    fn debug_ui(&mut self, ui: &DebugUI) {
+        match self {
+            PostEffect::Ssao(settings) => {
+                ui.checkbox("Enabled", &mut settings.enabled);
+                ui.slider("Radius", 0.01, 4.0, &mut settings.radius);
+                ui.slider("Bias", 0.0, 0.2, &mut settings.bias);
+                ui.slider("Power", 0.1, 4.0, &mut settings.power);
+                ui.slider("Intensity", 0.0, 1.0, &mut settings.intensity);
+            },
+            PostEffect::Bloom(settings) => {
+                ui.checkbox("Enabled", &mut settings.enabled);
+                ui.slider("Strength", 0.0, 1.0, &mut settings.strength);
+                ui.slider("Filter Radius", 0.0, 0.05, &mut settings.filter_radius);
+                ui.slider("Mip Count", 1, 10, &mut settings.mip_count);
+            },
+            PostEffect::Fxaa(settings) => {
+                ui.checkbox("Enabled", &mut settings.enabled);
+                ui.slider("Span Max", 1.0, 16.0, &mut settings.span_max);
+                ui.slider("Reduce Min", 0.0, 0.1, &mut settings.reduce_min);
+                ui.slider("Reduce Mul", 0.0, 0.5, &mut settings.reduce_mul);
+            },
+            PostEffect::Vignette(settings) => {
+                ui.checkbox("Enabled", &mut settings.enabled);
+                ui.slider("Intensity", 0.0, 1.0, &mut settings.intensity);
+                ui.slider("Smoothness", 0.01, 1.0, &mut settings.smoothness);
+                ui.slider("Roundness", 0.0, 1.0, &mut settings.roundness);
+            },
+            PostEffect::ColorGrading(settings) => {
+                ui.checkbox("Enabled", &mut settings.enabled);
+                ui.slider("Intensity", 0.0, 1.0, &mut settings.intensity);
+                if settings.lut.is_none() {
+                    ui.text("No lut set.");
+                }
+            }
+        }
+    }
+}
+
+impl PostProcessStack {
+    pub fn new() -> Self {
+        PostProcessStack {
+            effects: Vec::new()
+        }
+    }
+
+    pub fn effects(&self) -> &Vec<PostEffect> {
+        &self.effects
+    }
+
+    pub fn effect_mut(&mut self, index: usize) -> Option<&mut PostEffect> {
+        self.effects.get_mut(index)
+    }
+
+    pub fn push(&mut self, effect: PostEffect) -> usize {
+        self.effects.push(effect);
+        self.effects.len() - 1
+    }
+
+    pub fn insert(&mut self, index: usize, effect: PostEffect) {
+        self.effects.insert(index, effect);
+    }
+
+    pub fn remove(&mut self, index: usize) -> PostEffect {
+        self.effects.remove(index)
+    }
+
+    // Moves an effect to a new position, shifting the effects in between.
+    pub fn move_effect(&mut self, from: usize, to: usize) {
+        let effect = self.effects.remove(from);
+        self.effects.insert(to.min(self.effects.len()), effect);
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    // Enabled effects of a stage, in stack order.
+    pub fn stage_effects(&self, stage: PostStage) -> Vec<&PostEffect> {
+        self.effects.iter().filter(|effect| effect.stage() == stage && effect.enabled()).collect()
+    }
+
+    pub fn debug_ui(&mut self, ui: &DebugUI) {
+        let mut move_up = None;
+        for (i, effect) in self.effects.iter_mut().enumerate() {
+            if let Some(_node) = ui.tree_node(format!("{}##post{}", effect.name(), i)) {
+                effect.debug_ui(ui);
+                if i > 0 && ui.small_button("Move Up") {
+                    move_up = Some(i);
+                }
+            }
+        }
+
+        if let Some(i) = move_up {
+            self.move_effect(i, i - 1);
+        }
+    }
+}
+
+impl Default for PostProcessStack {
+    fn default() -> Self {
+        PostProcessStack {
+            effects: vec![
+                PostEffect::Ssao(SsaoSettings::default()),
+                PostEffect::Bloom(BloomSettings::default()),
+                PostEffect::Fxaa(FxaaSettings::default()),
+                PostEffect::Vignette(VignetteSettings::default()),
+                PostEffect::ColorGrading(ColorGradingSettings::default())
+            ]
+        }
+    }
+}
+
+// GPU resources of the effects, every effect draws a fullscreen triangle.
+// Intermediate targets are transient textures of the frame graph.
+pub struct PostProcessRenderer {
+    ssao_program: GLShaderProgram,
+    ssao_composite_program: GLShaderProgram,
+    ssao_noise: GLTexture2D,
+    ssao_kernel: Vec<Float3>,
+
+    bloom_downsample_program: GLShaderProgram,
+    bloom_upsample_program: GLShaderProgram,
+    bloom_composite_program: GLShaderProgram,
+
+    fxaa_program: GLShaderProgram,
+    vignette_program: GLShaderProgram,
+    color_grading_program: GLShaderProgram,
+    lut: Option<(*const Image, GLTexture3D)>,
+
+    vao: GLVAO
+}
+
+impl PostProcessRenderer {
+    pub fn new() -> Self {
+        PostProcessRenderer {
+            ssao_program: Self::create_program("ssao_frag.glsl"),
+            ssao_composite_program: Self::create_program("ssao_composite_frag.glsl"),
+            ssao_noise: Self::create_ssao_noise(),
+            ssao_kernel: Self::create_ssao_kernel(),
+            bloom_downsample_program: Self::create_program("bloom_downsample_frag.glsl"),
+            bloom_upsample_program: Self::create_program("bloom_upsample_frag.glsl"),
+            bloom_composite_program: Self::create_program("bloom_composite_frag.glsl"),
+            fxaa_program: Self::create_program("fxaa_frag.glsl"),
+            vignette_program: Self::create_program("vignette_frag.glsl"),
+            color_grading_program: Self::create_program("color_grading_frag.glsl"),
+            lut: None,
+            vao: GLVAO::new()
+        }
+    }
+
+    fn create_program(fragment_shader_name: &str) -> GLShaderProgram {
+        let vertex_shader_src = app().resources().get_text(String::from("assets/shaders/quad_vert.glsl"));
+        let vertex_shader = GLShader::new(GLShaderType::VERTEX, &vertex_shader_src.as_ref());
+        let fragment_shader_src = app().resources().get_text(format!("assets/shaders/{}", fragment_shader_name));
+        let fragment_shader = GLShader::new(GLShaderType::FRAGMENT, &fragment_shader_src.as_ref());
+        GLShaderProgram::new(&vertex_shader, &fragment_shader)
+    }
+
+    // Hemisphere samples, denser towards the center.
+    fn create_ssao_kernel() -> Vec<Float3> {
+        let mut rng = rand::thread_rng();
+
+        let mut kernel = Vec::with_capacity(SSAO_KERNEL_SIZE);
+        for i in 0..SSAO_KERNEL_SIZE {
+            let sample = Float3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(0.0, 1.0)).normalized();
+
+            let scale = i as f32 / SSAO_KERNEL_SIZE as f32;
+            let scale = 0.1 + scale * scale * 0.9;
+            kernel.push(sample * rng.gen_range(0.0, 1.0) * scale);
+        }
+
+        kernel
+    }
+
+    // Random rotations around the normal, tiled over the screen.
+    fn create_ssao_noise() -> GLTexture2D {
+        let mut rng = rand::thread_rng();
+
+        let mut noise = Vec::with_capacity((SSAO_NOISE_SIZE * SSAO_NOISE_SIZE * 3) as usize);
+        for _ in 0..SSAO_NOISE_SIZE * SSAO_NOISE_SIZE {
+            noise.push(rng.gen_range(-1.0, 1.0));
+            noise.push(rng.gen_range(-1.0, 1.0));
+            noise.push(0.0);
+        }
+
+        let mut desc = GLTextureDesc::new(GLTextureFormat::RGB32F);
+        desc.sampler.min_filter = GLFilterMode::Nearest;
+        desc.sampler.mag_filter = GLFilterMode::Nearest;
+        desc.sampler.mip_filter = None;
+        GLTexture2D::from_f32(Int2::new(SSAO_NOISE_SIZE, SSAO_NOISE_SIZE), &noise, &desc)
+    }
+
+    fn draw_fullscreen(&self) {
+        self.vao.bind(); {
+            gl_draw_arrays(gl::TRIANGLES, 0, 3);
+        } self.vao.unbind();
+    }
+
+    // Adds the HDR effects, which modify the scene color in place.
+    pub fn add_hdr_passes(graph: &mut FrameGraph, effects: &[&PostEffect], scene_color: RenderTextureId, scene_depth: RenderTextureId, projection: Float4x4) {
+        for effect in effects {
+            match effect {
+                PostEffect::Ssao(settings) => Self::add_ssao_passes(graph, *settings, scene_color, scene_depth, projection),
+                PostEffect::Bloom(settings) => Self::add_bloom_passes(graph, *settings, scene_color),
+                _ => ()
+            }
+        }
+    }
+
+    fn add_ssao_passes(graph: &mut FrameGraph, settings: SsaoSettings, scene_color: RenderTextureId, scene_depth: RenderTextureId, projection: Float4x4) {
+        let dimensions = graph.dimensions(scene_depth).unwrap();
+
+        let mut pass = graph.add_pass("SSAO");
+        pass.read(scene_depth);
+        let ssao = pass.create("ssao", RenderTextureDesc::new(dimensions, GLTextureFormat::R8));
+        pass.execute(move |graphics, context| {
+            context.bind_target(&[ssao], None); {
+                graphics.post.draw_ssao(&settings, context.texture(scene_depth), projection);
+            } context.unbind_target();
+        });
+
+        // Blurs away the noise pattern and multiplies the scene color.
+        let mut pass = graph.add_pass("SSAO Composite");
+        pass.read(ssao);
+        pass.read_write(scene_color);
+        pass.execute(move |graphics, context| {
+            context.bind_target(&[scene_color], None); {
+                graphics.post.composite_ssao(&settings, context.texture(ssao));
+            } context.unbind_target();
+        });
+    }
+
+    fn draw_ssao(&mut self, settings: &SsaoSettings, depth: &GLRenderTexture, projection: Float4x4) {
+        let mut inverse_projection = projection;
+        inverse_projection.invert();
+
+        self.ssao_program.bind(); {
+            depth.bind(0);
+            self.ssao_noise.bind(1);
+            self.ssao_program.set_sampler_slot(&String::from("depthMap"), 0);
+            self.ssao_program.set_sampler_slot(&String::from("noiseMap"), 1);
+            self.ssao_program.set_float4x4(&String::from("projection"), projection);
+            self.ssao_program.set_float4x4(&String::from("inverseProjection"), inverse_projection);
+            self.ssao_program.set_float(&String::from("radius"), settings.radius);
+            self.ssao_program.set_float(&String::from("bias"), settings.bias);
+            self.ssao_program.set_float(&String::from("power"), settings.power);
+            for (i, sample) in self.ssao_kernel.iter().enumerate() {
+                self.ssao_program.set_float3(&format!("kernel[{}]", i), *sample);
+            }
+
+            self.draw_fullscreen();
+        } self.ssao_program.unbind();
+    }
+
+    fn composite_ssao(&mut self, settings: &SsaoSettings, ssao: &GLRenderTexture) {
+        gl_enable_blend(gl::ZERO, gl::SRC_COLOR);
+        self.ssao_composite_program.bind(); {
+            ssao.bind(0);
+            self.ssao_composite_program.set_sampler_slot(&String::from("ssaoMap"), 0);
+            self.ssao_composite_program.set_float(&String::from("intensity"), settings.intensity);
+
+            self.draw_fullscreen();
+        } self.ssao_composite_program.unbind();
+        gl_disable_blend();
+    }
+
+    // Source: https://learnopengl.com/Guest-Articles/2022/Phys.-Based-Bloom
+    fn add_bloom_passes(graph: &mut FrameGraph, settings: BloomSettings, scene_color: RenderTextureId) {
+        let mut mips = Vec::new();
+        let mut size = graph.dimensions(scene_color).unwrap();
+        let mut source = scene_color;
+        for mip in 0..settings.mip_count {
+            size = Int2::new(size.x / 2, size.y / 2);
+            if size.x < 2 || size.y < 2 {
+                break;
+            }
+
+            let mut pass = graph.add_pass("Bloom Downsample");
+            pass.read(source);
+            let target = pass.create(&format!("bloom_mip{}", mip), RenderTextureDesc::new(size, GLTextureFormat::RGBA16F));
+            pass.execute(move |graphics, context| {
+                context.bind_target(&[target], None); {
+                    // The Karis average on the first downsample keeps fireflies from flickering.
+                    graphics.post.downsample_bloom(context.texture(source), mip == 0);
+                } context.unbind_target();
+            });
+
+            mips.push(target);
+            source = target;
+        }
+
+        if mips.is_empty() {
+            return;
+        }
+
+        for mip in (1..mips.len()).rev() {
+            let (source, target) = (mips[mip], mips[mip - 1]);
+
+            let mut pass = graph.add_pass("Bloom Upsample");
+            pass.read(source);
+            pass.read_write(target);
+            pass.execute(move |graphics, context| {
+                context.bind_target(&[target], None); {
+                    graphics.post.upsample_bloom(&settings, context.texture(source));
+                } context.unbind_target();
+            });
+        }
+
+        let bloom = mips[0];
+        let mut pass = graph.add_pass("Bloom Composite");
+        pass.read(bloom);
+        pass.read_write(scene_color);
+        pass.execute(move |graphics, context| {
+            context.bind_target(&[scene_color], None); {
+                graphics.post.composite_bloom(&settings, context.texture(bloom));
+            } context.unbind_target();
+        });
+    }
+
+    fn downsample_bloom(&mut self, source: &GLRenderTexture, karis_average: bool) {
+        self.bloom_downsample_program.bind(); {
+            source.bind(0);
+            self.bloom_downsample_program.set_sampler_slot(&String::from("srcTexture"), 0);
+            self.bloom_downsample_program.set_bool(&String::from("karisAverage"), karis_average);
+            self.draw_fullscreen();
+        } self.bloom_downsample_program.unbind();
+    }
+
+    // Adds the tent filtered smaller mip onto the bound one.
+    fn upsample_bloom(&mut self, settings: &BloomSettings, source: &GLRenderTexture) {
+        gl_enable_blend(gl::ONE, gl::ONE);
+        self.bloom_upsample_program.bind(); {
+            source.bind(0);
+            self.bloom_upsample_program.set_sampler_slot(&String::from("srcTexture"), 0);
+            self.bloom_upsample_program.set_float(&String::from("filterRadius"), settings.filter_radius);
+            self.draw_fullscreen();
+        } self.bloom_upsample_program.unbind();
+        gl_disable_blend();
+    }
+
+    // Outputs (bloom * strength, strength) so the blend lerps between scene and bloom.
+    fn composite_bloom(&mut self, settings: &BloomSettings, bloom: &GLRenderTexture) {
+        gl_enable_blend(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+        self.bloom_composite_program.bind(); {
+            bloom.bind(0);
+            self.bloom_composite_program.set_sampler_slot(&String::from("bloomMap"), 0);
+            self.bloom_composite_program.set_float(&String::from("strength"), settings.strength);
+            self.draw_fullscreen();
+        } self.bloom_composite_program.unbind();
+        gl_disable_blend();
+    }
+
+    // Adds the LDR effects reading the tonemapped input, the last effect writes the output.
+    // Intermediate results ping-pong between two aliased transient textures.
+    pub fn add_ldr_passes(graph: &mut FrameGraph, effects: &[&PostEffect], input: RenderTextureId, output: RenderTextureId) {
+        let dimensions = graph.dimensions(input).unwrap();
+
+        let mut source = input;
+        for (i, effect) in effects.iter().enumerate() {
+            let effect = (*effect).clone();
+
+            let mut pass = graph.add_pass(effect.name());
+            pass.read(source);
+            let target = if i == effects.len() - 1 {
+                pass.write(output)
+            } else {
+                pass.create(&format!("ldr{}", i), RenderTextureDesc::new(dimensions, GLTextureFormat::RGBA8))
+            };
+            pass.execute(move |graphics, context| {
+                let aspect_ratio = dimensions.x as f32 / dimensions.y.max(1) as f32;
+
+                context.bind_target(&[target], None); {
+                    context.texture(source).bind(0);
+                    match &effect {
+                        PostEffect::Fxaa(settings) => graphics.post.draw_fxaa(settings),
+                        PostEffect::Vignette(settings) => graphics.post.draw_vignette(settings, aspect_ratio),
+                        PostEffect::ColorGrading(settings) => graphics.post.draw_color_grading(settings),
+                        _ => ()
+                    }
+                } context.unbind_target();
+            });
+
+            source = target;
+        }
+    }
+
+    fn draw_fxaa(&mut self, settings: &FxaaSettings) {
+        self.fxaa_program.bind(); {
+            self.fxaa_program.set_sampler_slot(&String::from("tex"), 0);
+            self.fxaa_program.set_float(&String::from("spanMax"), settings.span_max);
+            self.fxaa_program.set_float(&String::from("reduceMin"), settings.reduce_min);
+            self.fxaa_program.set_float(&String::from("reduceMul"), settings.reduce_mul);
+            self.draw_fullscreen();
+        } self.fxaa_program.unbind();
+    }
+
+    fn draw_vignette(&mut self, settings: &VignetteSettings, aspect_ratio: f32) {
+        self.vignette_program.bind(); {
+            self.vignette_program.set_sampler_slot(&String::from("tex"), 0);
+            self.vignette_program.set_float(&String::from("intensity"), settings.intensity);
+            self.vignette_program.set_float(&String::from("smoothness"), settings.smoothness);
+            self.vignette_program.set_float(&String::from("roundness"), settings.roundness);
+            self.vignette_program.set_float(&String::from("aspectRatio"), aspect_ratio);
+            self.draw_fullscreen();
+        } self.vignette_program.unbind();
+    }
+
+    fn draw_color_grading(&mut self, settings: &ColorGradingSettings) {
+        let lut = match &settings.lut {
+            Some(lut) => lut,
+            None => return
+        };
+        if self.lut.as_ref().map(|(ptr, _)| *ptr) != Some(lut.as_ptr()) {
+            self.lut = Some((lut.as_ptr(), Self::create_lut(&lut.as_ref())));
+        }
+        let (_, lut_texture) = self.lut.as_ref().unwrap();
+
+        self.color_grading_program.bind(); {
+            lut_texture.bind(1);
+            self.color_grading_program.set_sampler_slot(&String::from("tex"), 0);
+            self.color_grading_program.set_sampler_slot(&String::from("lut"), 1);
+            self.color_grading_program.set_float(&String::from("intensity"), settings.intensity);
+            self.color_grading_program.set_float(&String::from("lutSize"), lut_texture.depth() as f32);
+            self.draw_fullscreen();
+        } self.color_grading_program.unbind();
+    }
+
+    // Rearranges the strip into a size^3 volume.
+    fn create_lut(image: &Image) -> GLTexture3D {
+        let size = image.dimensions.y;
+        assert_eq!(image.dimensions.x, size * size, "Failed to create lut. (Image must be a strip of size x size slices)");
+        assert!(image.channel_count >= 3, "Failed to create lut. (Image needs rgb channels)");
+
+        let channels = image.channel_count as usize;
+        let mut data = Vec::with_capacity((size * size * size * 3) as usize);
+        for blue in 0..size {
+            for green in 0..size {
+                for red in 0..size {
+                    let x = (blue * size + red) as usize;
+                    let y = green as usize;
+                    let pixel = (y * image.dimensions.x as usize + x) * channels;
+                    data.extend_from_slice(&image.data[pixel..pixel + 3]);
+                }
+            }
+        }
+
+        let mut desc = GLTextureDesc::new(GLTextureFormat::RGB8);
+        desc.sampler.wrap_s = GLWrapMode::ClampToEdge;
+        desc.sampler.wrap_t = GLWrapMode::ClampToEdge;
+        desc.sampler.wrap_r = GLWrapMode::ClampToEdge;
+        desc.sampler.mip_filter = None;
+
+        let lut = GLTexture3D::new(size, size, size, &desc);
+        lut.set_data(0, data.as_ptr() as *const c_void);
+        lut
+    }
+}
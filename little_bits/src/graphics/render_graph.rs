@@ -0,0 +1,634 @@
+extern crate cl_wrapper;
+use cl_wrapper::{CLContext, CLCommandQueue, CLGLTexture2D, CLBufferMode};
+
+use crate::gmaths::*;
+use crate::graphics::opengl::*;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct RenderTextureId(usize);
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct RenderTextureDesc {
+    pub width: usize,
+    pub height: usize,
+    pub format: GLTextureFormat,
+    pub samples: u32
+}
+
+// Compute passes run OpenCL kernels on textures shared with GL.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PassQueue {
+    Graphics,
+    Compute
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct RenderGraphStats {
+    pub passes: usize,
+    pub culled_passes: usize,
+    pub transient_textures: usize,
+    // Transient textures after aliasing.
+    pub physical_textures: usize
+}
+
+enum TextureSource<'a> {
+    Transient(RenderTextureDesc),
+    // Kept by the graph resources between frames under the texture's name, released after a frame that doesn't use it.
+    Persistent(RenderTextureDesc),
+    // Owned outside of the graph, borrowed until the graph is executed.
+    Imported(&'a GLRenderTexture),
+    // The window frame buffer.
+    Backbuffer(Int2),
+    // Only orders passes, e.g. for textures a renderer keeps to itself.
+    External
+}
+
+struct TextureNode<'a> {
+    name: String,
+    source: TextureSource<'a>
+}
+
+struct PassNode<'a, T> {
+    name: String,
+    queue: PassQueue,
+    reads: Vec<RenderTextureId>,
+    writes: Vec<RenderTextureId>,
+    execute: Box<dyn FnMut(&mut T, &mut RenderPassContext) + 'a>
+}
+
+// Passes of a single frame. Passes declare the textures they read and write, the graph culls passes
+// nobody depends on, orders the rest and allocates the transient textures right before executing them.
+pub struct RenderGraph<'a, T> {
+    textures: Vec<TextureNode<'a>>,
+    passes: Vec<PassNode<'a, T>>
+}
+
+pub struct RenderPassBuilder<'g, 'a, T> {
+    graph: &'g mut RenderGraph<'a, T>,
+    name: String,
+    queue: PassQueue,
+    reads: Vec<RenderTextureId>,
+    writes: Vec<RenderTextureId>
+}
+
+struct PooledTexture {
+    desc: RenderTextureDesc,
+    texture: GLRenderTexture,
+    // Persistent textures are never aliased.
+    name: Option<String>,
+    // Last pass position of the current frame the texture is in use.
+    busy_until: Option<usize>
+}
+
+// Textures, frame buffers and OpenCL images the graph keeps between frames.
+pub struct RenderGraphResources {
+    pool: Vec<PooledTexture>,
+    targets: Vec<(Vec<(GLRenderAttachment, GLTextureBuffer)>, Int2, GLRenderTarget, bool)>,
+    cl_textures: Vec<(GLTextureBuffer, Int2, CLGLTexture2D, bool)>
+}
+
+pub struct RenderPassContext<'r> {
+    textures: &'r [TextureNode<'r>],
+    physical: &'r [Option<usize>],
+    resources: &'r mut RenderGraphResources,
+    command_queue: Option<&'r CLCommandQueue>,
+    bound_target: Option<usize>
+}
+
+impl RenderTextureDesc {
+    pub fn new(dimensions: Int2, format: GLTextureFormat) -> Self {
+        RenderTextureDesc {
+            width: dimensions.x.max(1) as usize,
+            height: dimensions.y.max(1) as usize,
+            format: format,
+            samples: 1
+        }
+    }
+
+    pub fn dimensions(&self) -> Int2 {
+        Int2::new(self.width as i32, self.height as i32)
+    }
+}
+
+impl<'a, T> RenderGraph<'a, T> {
+    pub fn new() -> Self {
+        RenderGraph {
+            textures: Vec::new(),
+            passes: Vec::new()
+        }
+    }
+
+    pub fn import(&mut self, name: &str, texture: &'a GLRenderTexture) -> RenderTextureId {
+        self.add_texture(name, TextureSource::Imported(texture))
+    }
+
+    // A texture the graph resources keep between frames, e.g. to read it after the frame. Same names share the texture.
+    pub fn persistent(&mut self, name: &str, desc: RenderTextureDesc) -> RenderTextureId {
+        self.add_texture(name, TextureSource::Persistent(desc))
+    }
+
+    pub fn import_backbuffer(&mut self, dimensions: Int2) -> RenderTextureId {
+        self.add_texture("backbuffer", TextureSource::Backbuffer(dimensions))
+    }
+
+    // A resource without a texture the graph can hand out, used to order passes around it.
+    pub fn external(&mut self, name: &str) -> RenderTextureId {
+        self.add_texture(name, TextureSource::External)
+    }
+
+    fn add_texture(&mut self, name: &str, source: TextureSource<'a>) -> RenderTextureId {
+        self.textures.push(TextureNode {
+            name: String::from(name),
+            source: source
+        });
+
+        RenderTextureId(self.textures.len() - 1)
+    }
+
+    pub fn add_pass(&mut self, name: &str) -> RenderPassBuilder<'_, 'a, T> {
+        RenderPassBuilder {
+            graph: self,
+            name: String::from(name),
+            queue: PassQueue::Graphics,
+            reads: Vec::new(),
+            writes: Vec::new()
+        }
+    }
+
+    pub fn add_compute_pass(&mut self, name: &str) -> RenderPassBuilder<'_, 'a, T> {
+        let mut builder = self.add_pass(name);
+        builder.queue = PassQueue::Compute;
+        builder
+    }
+
+    // Dimensions of a texture, None for external resources.
+    pub fn dimensions(&self, texture: RenderTextureId) -> Option<Int2> {
+        match &self.textures[texture.0].source {
+            TextureSource::Transient(desc) | TextureSource::Persistent(desc) => Some(desc.dimensions()),
+            TextureSource::Imported(texture) => Some(Int2::new(texture.width() as i32, texture.height() as i32)),
+            TextureSource::Backbuffer(dimensions) => Some(*dimensions),
+            TextureSource::External => None
+        }
+    }
+
+    pub fn pass_count(&self) -> usize {
+        self.passes.len()
+    }
+
+    fn is_transient(&self, texture: RenderTextureId) -> bool {
+        matches!(self.textures[texture.0].source, TextureSource::Transient(_))
+    }
+
+    // Execution order of the passes that contribute to a non transient texture.
+    fn schedule(&self) -> Vec<usize> {
+        let pass_count = self.passes.len();
+
+        // Reads wait for the last write, writes wait for the earlier reads and writes of the same texture.
+        let mut dependencies: Vec<Vec<usize>> = vec![Vec::new(); pass_count];
+        let mut producers: Vec<Vec<usize>> = vec![Vec::new(); pass_count];
+        let mut last_writer: Vec<Option<usize>> = vec![None; self.textures.len()];
+        let mut readers: Vec<Vec<usize>> = vec![Vec::new(); self.textures.len()];
+        for (i, pass) in self.passes.iter().enumerate() {
+            for read in pass.reads.iter() {
+                match last_writer[read.0] {
+                    Some(writer) => {
+                        dependencies[i].push(writer);
+                        producers[i].push(writer);
+                    },
+                    None => {
+                        assert!(!self.is_transient(*read), "Failed to compile render graph. (Pass '{}' reads '{}' before it is written)", pass.name, self.textures[read.0].name);
+                    }
+                }
+                readers[read.0].push(i);
+            }
+
+            for write in pass.writes.iter() {
+                dependencies[i].extend(last_writer[write.0]);
+                dependencies[i].extend(readers[write.0].drain(..).filter(|reader| *reader != i));
+                last_writer[write.0] = Some(i);
+            }
+        }
+
+        // Passes writing outside of the graph are kept, together with every pass whose output they read.
+        let mut alive = vec![false; pass_count];
+        let mut stack: Vec<usize> = (0..pass_count)
+            .filter(|i| self.passes[*i].writes.iter().any(|write| !self.is_transient(*write)))
+            .collect();
+        while let Some(i) = stack.pop() {
+            if alive[i] {
+                continue;
+            }
+
+            alive[i] = true;
+            stack.extend(producers[i].iter().cloned());
+        }
+
+        // Among the ready passes, staying on the same queue saves a synchronization between GL and CL.
+        let mut scheduled = vec![false; pass_count];
+        let mut order = Vec::with_capacity(pass_count);
+        let mut queue = PassQueue::Graphics;
+        loop {
+            let ready: Vec<usize> = (0..pass_count)
+                .filter(|i| alive[*i] && !scheduled[*i])
+                .filter(|i| dependencies[*i].iter().all(|dependency| scheduled[*dependency] || !alive[*dependency]))
+                .collect();
+
+            let next = match ready.iter().find(|i| self.passes[**i].queue == queue).or(ready.first()) {
+                Some(next) => *next,
+                None => break
+            };
+
+            scheduled[next] = true;
+            queue = self.passes[next].queue;
+            order.push(next);
+        }
+
+        order
+    }
+
+    // First and last position in the order every transient texture is used at.
+    fn lifetimes(&self, order: &[usize]) -> Vec<Option<(usize, usize)>> {
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.textures.len()];
+        for (position, pass) in order.iter().enumerate() {
+            let pass = &self.passes[*pass];
+            for texture in pass.reads.iter().chain(pass.writes.iter()) {
+                if !self.is_transient(*texture) {
+                    continue;
+                }
+
+                let lifetime = lifetimes[texture.0].get_or_insert((position, position));
+                lifetime.1 = position;
+            }
+        }
+
+        lifetimes
+    }
+
+    // Runs the passes. Compute passes need an OpenCL context that shares textures with the GL context.
+    pub fn execute(mut self, state: &mut T, resources: &mut RenderGraphResources, cl: Option<(&CLContext, &CLCommandQueue)>) -> RenderGraphStats {
+        let order = self.schedule();
+        let lifetimes = self.lifetimes(&order);
+
+        let descs: Vec<Option<RenderTextureDesc>> = self.textures.iter().map(|texture| match texture.source {
+            TextureSource::Transient(desc) => Some(desc),
+            _ => None
+        }).collect();
+        let mut physical = resources.allocate(&descs, &lifetimes);
+        for (i, texture) in self.textures.iter().enumerate() {
+            if let TextureSource::Persistent(desc) = texture.source {
+                physical[i] = Some(resources.allocate_persistent(&texture.name, desc));
+            }
+        }
+
+        let stats = RenderGraphStats {
+            passes: order.len(),
+            culled_passes: self.passes.len() - order.len(),
+            transient_textures: lifetimes.iter().filter(|lifetime| lifetime.is_some()).count(),
+            physical_textures: resources.pool.len()
+        };
+
+        let mut queue = PassQueue::Graphics;
+        for i in order {
+            let pass = &mut self.passes[i];
+
+            let mut cl_textures = Vec::new();
+            let command_queue = match pass.queue {
+                PassQueue::Graphics => {
+                    if queue == PassQueue::Compute {
+                        // GL may only use shared textures again once CL is done with them.
+                        cl.unwrap().1.finish();
+                    }
+                    None
+                },
+                PassQueue::Compute => {
+                    let (context, command_queue) = cl.unwrap_or_else(|| panic!("Failed to execute render graph. (Compute pass '{}' needs an OpenCL context)", pass.name));
+                    if queue == PassQueue::Graphics {
+                        gl_finish();
+                    }
+
+                    for texture in pass.reads.iter().chain(pass.writes.iter()) {
+                        let gl_texture = node_texture(&self.textures[texture.0], physical[texture.0], &resources.pool);
+                        let cl_texture = get_cl_texture(&mut resources.cl_textures, context, gl_texture);
+                        command_queue.acquire_gl_texture(&resources.cl_textures[cl_texture].2);
+                        cl_textures.push(cl_texture);
+                    }
+                    Some(command_queue)
+                }
+            };
+            queue = pass.queue;
+
+            let mut context = RenderPassContext {
+                textures: &self.textures,
+                physical: &physical,
+                resources: resources,
+                command_queue: command_queue,
+                bound_target: None
+            };
+            (pass.execute)(state, &mut context);
+            context.unbind_target();
+
+            if let Some(command_queue) = command_queue {
+                for cl_texture in cl_textures {
+                    command_queue.release_gl_texture(&resources.cl_textures[cl_texture].2);
+                }
+            }
+        }
+
+        if queue == PassQueue::Compute {
+            cl.unwrap().1.finish();
+        }
+
+        resources.release_unused();
+        stats
+    }
+}
+
+impl<'g, 'a, T> RenderPassBuilder<'g, 'a, T> {
+    // Creates a transient texture, which is written by this pass.
+    pub fn create(&mut self, name: &str, desc: RenderTextureDesc) -> RenderTextureId {
+        let texture = self.graph.add_texture(name, TextureSource::Transient(desc));
+        self.write(texture)
+    }
+
+    pub fn read(&mut self, texture: RenderTextureId) -> RenderTextureId {
+        if !self.reads.contains(&texture) {
+            self.reads.push(texture);
+        }
+        texture
+    }
+
+    pub fn write(&mut self, texture: RenderTextureId) -> RenderTextureId {
+        if !self.writes.contains(&texture) {
+            self.writes.push(texture);
+        }
+        texture
+    }
+
+    // Blending into a texture or drawing over it reads and writes it.
+    pub fn read_write(&mut self, texture: RenderTextureId) -> RenderTextureId {
+        self.read(texture);
+        self.write(texture)
+    }
+
+    pub fn dimensions(&self, texture: RenderTextureId) -> Option<Int2> {
+        self.graph.dimensions(texture)
+    }
+
+    pub fn execute(self, execute: impl FnMut(&mut T, &mut RenderPassContext) + 'a) {
+        self.graph.passes.push(PassNode {
+            name: self.name,
+            queue: self.queue,
+            reads: self.reads,
+            writes: self.writes,
+            execute: Box::new(execute)
+        });
+    }
+}
+
+impl RenderGraphResources {
+    pub fn new() -> Self {
+        RenderGraphResources {
+            pool: Vec::new(),
+            targets: Vec::new(),
+            cl_textures: Vec::new()
+        }
+    }
+
+    // Textures whose lifetimes don't overlap share the same pooled texture if size and format match.
+    fn allocate(&mut self, descs: &[Option<RenderTextureDesc>], lifetimes: &[Option<(usize, usize)>]) -> Vec<Option<usize>> {
+        for pooled in self.pool.iter_mut() {
+            pooled.busy_until = None;
+        }
+
+        let mut textures: Vec<usize> = (0..descs.len()).filter(|texture| lifetimes[*texture].is_some()).collect();
+        textures.sort_by_key(|texture| lifetimes[*texture].unwrap().0);
+
+        let mut physical = vec![None; descs.len()];
+        for texture in textures {
+            let desc = descs[texture].unwrap();
+            let (first, last) = lifetimes[texture].unwrap();
+
+            let free = self.pool.iter().position(|pooled| pooled.name.is_none() && pooled.desc == desc && pooled.busy_until.map_or(true, |busy_until| busy_until < first));
+            let index = match free {
+                Some(index) => index,
+                None => {
+                    self.pool.push(PooledTexture {
+                        desc: desc,
+                        texture: GLRenderTexture::multisampled(desc.width, desc.height, desc.format, desc.samples),
+                        name: None,
+                        busy_until: None
+                    });
+                    self.pool.len() - 1
+                }
+            };
+
+            self.pool[index].busy_until = Some(last);
+            physical[texture] = Some(index);
+        }
+
+        physical
+    }
+
+    // Persistent textures keep their contents, a new size reallocates the storage in place.
+    fn allocate_persistent(&mut self, name: &str, desc: RenderTextureDesc) -> usize {
+        let existing = self.pool.iter().position(|pooled| pooled.name.as_deref() == Some(name));
+        let index = match existing {
+            Some(index) if self.pool[index].desc.format == desc.format && self.pool[index].desc.samples == desc.samples => {
+                self.pool[index].texture.resize(desc.width, desc.height);
+                self.pool[index].desc = desc;
+                index
+            },
+            _ => {
+                // A texture with another format is released with the unused ones at the end of the frame.
+                if let Some(index) = existing {
+                    self.pool[index].name = None;
+                }
+
+                self.pool.push(PooledTexture {
+                    desc: desc,
+                    texture: GLRenderTexture::multisampled(desc.width, desc.height, desc.format, desc.samples),
+                    name: Some(String::from(name)),
+                    busy_until: None
+                });
+                self.pool.len() - 1
+            }
+        };
+
+        self.pool[index].busy_until = Some(usize::MAX);
+        index
+    }
+
+    // Persistent texture of the last executed graph.
+    pub fn persistent_texture(&self, name: &str) -> Option<&GLRenderTexture> {
+        self.pool.iter()
+            .find(|pooled| pooled.name.as_deref() == Some(name))
+            .map(|pooled| &pooled.texture)
+    }
+
+    // Drops pooled textures, frame buffers and OpenCL images the last frame didn't use.
+    fn release_unused(&mut self) {
+        let released: Vec<GLTextureBuffer> = self.pool.iter()
+            .filter(|pooled| pooled.busy_until.is_none())
+            .map(|pooled| pooled.texture.tex().handle())
+            .collect();
+
+        // Texture indices of this frame are no longer needed, so the pool can be compacted.
+        self.pool.retain(|pooled| pooled.busy_until.is_some());
+        self.targets.retain(|(attachments, _, _, used)| *used && !attachments.iter().any(|(_, handle)| released.contains(handle)));
+        self.cl_textures.retain(|(handle, _, _, used)| *used && !released.contains(handle));
+
+        for (_, _, _, used) in self.targets.iter_mut() {
+            *used = false;
+        }
+        for (_, _, _, used) in self.cl_textures.iter_mut() {
+            *used = false;
+        }
+    }
+}
+
+impl Default for RenderGraphResources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'r> RenderPassContext<'r> {
+    pub fn texture(&self, texture: RenderTextureId) -> &GLRenderTexture {
+        node_texture(&self.textures[texture.0], self.physical[texture.0], &self.resources.pool)
+    }
+
+    pub fn dimensions(&self, texture: RenderTextureId) -> Int2 {
+        match self.textures[texture.0].source {
+            TextureSource::Backbuffer(dimensions) => dimensions,
+            _ => {
+                let texture = self.texture(texture);
+                Int2::new(texture.width() as i32, texture.height() as i32)
+            }
+        }
+    }
+
+    // Binds a frame buffer with the textures attached and sets the viewport. The backbuffer can only be bound on its own.
+    pub fn bind_target(&mut self, colors: &[RenderTextureId], depth: Option<RenderTextureId>) {
+        self.unbind_target();
+
+        match self.target_index(colors, depth) {
+            Some(index) => {
+                let (_, dimensions, target, _) = &self.resources.targets[index];
+                target.bind();
+                gl_viewport(*dimensions);
+                self.bound_target = Some(index);
+            },
+            None => {
+                gl_bind_default_frame_buffer();
+                gl_viewport(self.dimensions(colors[0]));
+            }
+        }
+    }
+
+    // Frame buffer with the textures attached without binding it, e.g. to blit into.
+    pub fn target(&mut self, colors: &[RenderTextureId], depth: Option<RenderTextureId>) -> &GLRenderTarget {
+        match self.target_index(colors, depth) {
+            Some(index) => &self.resources.targets[index].2,
+            None => panic!("Failed to get target. (The backbuffer has no frame buffer object)")
+        }
+    }
+
+    // Cached frame buffer of the attachments, None for the backbuffer.
+    fn target_index(&mut self, colors: &[RenderTextureId], depth: Option<RenderTextureId>) -> Option<usize> {
+        let first = match colors.first().or(depth.as_ref()) {
+            Some(first) => *first,
+            None => panic!("Failed to bind target. (No attachments)")
+        };
+
+        if let TextureSource::Backbuffer(_) = self.textures[first.0].source {
+            assert!(colors.len() == 1 && depth.is_none(), "Failed to bind target. (The backbuffer can't be combined with other textures)");
+            return None;
+        }
+
+        let mut attachments: Vec<(GLRenderAttachment, RenderTextureId)> = colors.iter().enumerate()
+            .map(|(slot, color)| (GLRenderAttachment::Color(slot as u32), *color))
+            .collect();
+        if let Some(depth) = depth {
+            attachments.push((GLRenderAttachment::for_depth_format(self.texture(depth).format()), depth));
+        }
+
+        let key: Vec<(GLRenderAttachment, GLTextureBuffer)> = attachments.iter()
+            .map(|(attachment, texture)| (*attachment, self.texture(*texture).tex().handle()))
+            .collect();
+        let dimensions = self.dimensions(first);
+
+        let index = match self.resources.targets.iter().position(|(target_key, target_dimensions, _, _)| *target_key == key && *target_dimensions == dimensions) {
+            Some(index) => index,
+            None => {
+                let mut desc = GLRenderTargetDesc::new(dimensions.x as usize, dimensions.y as usize);
+                desc.samples = self.texture(first).samples();
+                desc.depth = GLDepthAttachment::None;
+
+                let mut target = GLRenderTarget::from_desc(&desc);
+                for (attachment, texture) in attachments.iter() {
+                    target.set_external_texture(*attachment, self.texture(*texture));
+                }
+                target.bind(); {
+                    target.check();
+                } target.unbind();
+
+                self.resources.targets.push((key, dimensions, target, false));
+                self.resources.targets.len() - 1
+            }
+        };
+
+        self.resources.targets[index].3 = true;
+        Some(index)
+    }
+
+    pub fn unbind_target(&mut self) {
+        if let Some(index) = self.bound_target.take() {
+            self.resources.targets[index].2.unbind();
+        }
+    }
+
+    // OpenCL image of a texture the compute pass declared, acquired for the duration of the pass.
+    pub fn cl_texture(&self, texture: RenderTextureId) -> &CLGLTexture2D {
+        &self.resources.cl_textures[self.cl_texture_index(texture)].2
+    }
+
+    fn cl_texture_index(&self, texture: RenderTextureId) -> usize {
+        let gl_texture = self.texture(texture);
+        let handle = gl_texture.tex().handle();
+        let dimensions = Int2::new(gl_texture.width() as i32, gl_texture.height() as i32);
+
+        match self.resources.cl_textures.iter().position(|(cl_handle, cl_dimensions, _, _)| *cl_handle == handle && *cl_dimensions == dimensions) {
+            Some(index) => index,
+            None => panic!("Failed to get OpenCL texture. ('{}' isn't used by a compute pass)", self.textures[texture.0].name)
+        }
+    }
+
+    pub fn command_queue(&self) -> &CLCommandQueue {
+        self.command_queue.expect("Failed to get command queue. (Not a compute pass)")
+    }
+}
+
+fn node_texture<'r>(node: &TextureNode<'r>, physical: Option<usize>, pool: &'r [PooledTexture]) -> &'r GLRenderTexture {
+    match node.source {
+        TextureSource::Transient(_) | TextureSource::Persistent(_) => &pool[physical.unwrap()].texture,
+        TextureSource::Imported(texture) => texture,
+        _ => panic!("Failed to get texture. ('{}' has no texture)", node.name)
+    }
+}
+
+fn get_cl_texture(cl_textures: &mut Vec<(GLTextureBuffer, Int2, CLGLTexture2D, bool)>, context: &CLContext, texture: &GLRenderTexture) -> usize {
+    let handle = texture.tex().handle();
+    let dimensions = Int2::new(texture.width() as i32, texture.height() as i32);
+
+    let index = match cl_textures.iter().position(|(cl_handle, cl_dimensions, _, _)| *cl_handle == handle && *cl_dimensions == dimensions) {
+        Some(index) => index,
+        None => {
+            // Kernels declare the access themselves, the same texture may be read in one pass and written in another.
+            cl_textures.push((handle, dimensions, CLGLTexture2D::new(context, texture.tex(), CLBufferMode::ReadWrite), false));
+            cl_textures.len() - 1
+        }
+    };
+
+    cl_textures[index].3 = true;
+    index
+}
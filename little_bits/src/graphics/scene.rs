@@ -0,0 +1,276 @@
+use crate::gmaths::*;
+use crate::resources::Model;
+use crate::Shared;
+
+// Generational handle, a removed node's id never resolves to a node created later in its slot.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub struct SceneNodeId {
+    index: usize,
+    generation: u32
+}
+
+pub struct SceneNode {
+    pub name: String,
+    transform: Transform,
+    parent: Option<SceneNodeId>,
+    children: Vec<SceneNodeId>,
+    model: Option<SceneModel>,
+    world_matrix: Float4x4,
+    dirty: bool
+}
+
+// Meshes of a model drawn at a node, used for static geometry.
+#[derive(Clone)]
+pub struct SceneModel {
+    pub model: Shared<Model>,
+    pub meshes: Vec<usize>
+}
+
+struct SceneSlot {
+    generation: u32,
+    node: Option<SceneNode>
+}
+
+pub struct Scene {
+    slots: Vec<SceneSlot>,
+    free_slots: Vec<usize>,
+    roots: Vec<SceneNodeId>,
+    changed: bool
+}
+
+impl SceneNode {
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn parent(&self) -> Option<SceneNodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &Vec<SceneNodeId> {
+        &self.children
+    }
+
+    pub fn model(&self) -> Option<&SceneModel> {
+        self.model.as_ref()
+    }
+
+    // Only valid after Scene::update.
+    pub fn world_matrix(&self) -> Float4x4 {
+        self.world_matrix
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene {
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            roots: Vec::new(),
+            changed: false
+        }
+    }
+
+    pub fn create_node(&mut self, name: &str, transform: Transform, parent: Option<SceneNodeId>) -> SceneNodeId {
+        let node = SceneNode {
+            name: String::from(name),
+            transform: transform,
+            parent: None,
+            children: Vec::new(),
+            model: None,
+            world_matrix: Float4x4::identity(),
+            dirty: true
+        };
+
+        let id = match self.free_slots.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.node = Some(node);
+                SceneNodeId { index: index, generation: slot.generation }
+            },
+            None => {
+                self.slots.push(SceneSlot { generation: 0, node: Some(node) });
+                SceneNodeId { index: self.slots.len() - 1, generation: 0 }
+            }
+        };
+
+        self.roots.push(id);
+        if parent.is_some() {
+            self.set_parent(id, parent);
+        }
+
+        self.changed = true;
+        id
+    }
+
+    // Removes the node together with its whole subtree.
+    pub fn remove_node(&mut self, id: SceneNodeId) {
+        if !self.contains(id) {
+            return;
+        }
+
+        self.detach(id);
+        self.roots.retain(|root| *root != id);
+
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.slots[id.index];
+            if let Some(node) = slot.node.take() {
+                stack.extend(node.children);
+            }
+            slot.generation += 1;
+            self.free_slots.push(id.index);
+        }
+
+        self.changed = true;
+    }
+
+    pub fn contains(&self, id: SceneNodeId) -> bool {
+        match self.slots.get(id.index) {
+            Some(slot) => slot.generation == id.generation && slot.node.is_some(),
+            None => false
+        }
+    }
+
+    pub fn node(&self, id: SceneNodeId) -> Option<&SceneNode> {
+        match self.slots.get(id.index) {
+            Some(slot) if slot.generation == id.generation => slot.node.as_ref(),
+            _ => None
+        }
+    }
+
+    fn node_mut(&mut self, id: SceneNodeId) -> Option<&mut SceneNode> {
+        match self.slots.get_mut(id.index) {
+            Some(slot) if slot.generation == id.generation => slot.node.as_mut(),
+            _ => None
+        }
+    }
+
+    fn expect_node_mut(&mut self, id: SceneNodeId) -> &mut SceneNode {
+        self.node_mut(id).expect("Failed to get scene node. (Node was removed)")
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (SceneNodeId, &SceneNode)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.node.as_ref().map(|node| (SceneNodeId { index: index, generation: slot.generation }, node))
+        })
+    }
+
+    pub fn roots(&self) -> &Vec<SceneNodeId> {
+        &self.roots
+    }
+
+    pub fn set_transform(&mut self, id: SceneNodeId, transform: Transform) {
+        let node = self.expect_node_mut(id);
+        node.transform = transform;
+        node.dirty = true;
+    }
+
+    pub fn set_model(&mut self, id: SceneNodeId, model: Option<SceneModel>) {
+        self.expect_node_mut(id).model = model;
+        self.changed = true;
+    }
+
+    // A None parent makes the node a root, the local transform is kept as is.
+    pub fn set_parent(&mut self, id: SceneNodeId, parent: Option<SceneNodeId>) {
+        if let Some(parent) = parent {
+            assert!(self.contains(parent), "Failed to set parent. (Parent was removed)");
+            assert!(!self.is_ancestor(id, parent), "Failed to set parent. (Would create a cycle)");
+        }
+
+        self.detach(id);
+        self.roots.retain(|root| *root != id);
+
+        match parent {
+            Some(parent) => self.expect_node_mut(parent).children.push(id),
+            None => self.roots.push(id)
+        }
+
+        let node = self.expect_node_mut(id);
+        node.parent = parent;
+        node.dirty = true;
+        self.changed = true;
+    }
+
+    fn detach(&mut self, id: SceneNodeId) {
+        if let Some(parent) = self.node(id).and_then(|node| node.parent) {
+            if let Some(parent) = self.node_mut(parent) {
+                parent.children.retain(|child| *child != id);
+            }
+        }
+    }
+
+    fn is_ancestor(&self, ancestor: SceneNodeId, id: SceneNodeId) -> bool {
+        let mut current = Some(id);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.node(id).and_then(|node| node.parent);
+        }
+        false
+    }
+
+    pub fn world_matrix(&self, id: SceneNodeId) -> Float4x4 {
+        self.node(id).map(|node| node.world_matrix).unwrap_or(Float4x4::identity())
+    }
+
+    // Propagates world matrices down from every dirty node.
+    pub fn update(&mut self) {
+        let mut stack: Vec<(SceneNodeId, Float4x4, bool)> = self.roots.iter().rev().map(|root| (*root, Float4x4::identity(), false)).collect();
+
+        while let Some((id, parent_matrix, parent_dirty)) = stack.pop() {
+            let node = self.expect_node_mut(id);
+            let dirty = node.dirty || parent_dirty;
+            if dirty {
+                node.world_matrix = parent_matrix * node.transform.get_matrix();
+                node.dirty = false;
+            }
+
+            let world_matrix = node.world_matrix;
+            for child in node.children.iter().rev() {
+                stack.push((*child, world_matrix, dirty));
+            }
+
+            self.changed |= dirty;
+        }
+    }
+
+    // Returns whether any world matrix or model changed since the last call.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::replace(&mut self.changed, false)
+    }
+
+    // Instantiates the glTF node hierarchy of a model below a new root node.
+    pub fn instantiate_model(&mut self, model: &Shared<Model>, transform: Transform, parent: Option<SceneNodeId>) -> SceneNodeId {
+        self.instantiate_model_nodes(model, transform, parent).0
+    }
+
+    // Also returns the scene node created for every model node, None for nodes outside the model's scene.
+    pub fn instantiate_model_nodes(&mut self, model: &Shared<Model>, transform: Transform, parent: Option<SceneNodeId>) -> (SceneNodeId, Vec<Option<SceneNodeId>>) {
+        let root = self.create_node("Model", transform, parent);
+        let mut nodes = vec![None; model.as_ref().nodes.len()];
+
+        let model_ref = model.as_ref();
+        let mut stack: Vec<(usize, SceneNodeId)> = model_ref.root_nodes.iter().rev().map(|node_idx| (*node_idx, root)).collect();
+
+        while let Some((node_idx, parent)) = stack.pop() {
+            let model_node = &model_ref.nodes[node_idx];
+            let id = self.create_node(&model_node.name, model_node.transform, Some(parent));
+            nodes[node_idx] = Some(id);
+
+            if !model_node.meshes.is_empty() {
+                self.set_model(id, Some(SceneModel {
+                    model: model.clone(),
+                    meshes: model_node.meshes.clone()
+                }));
+            }
+
+            for child in model_node.children.iter().rev() {
+                stack.push((*child, id));
+            }
+        }
+
+        (root, nodes)
+    }
+}
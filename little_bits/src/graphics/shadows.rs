@@ -0,0 +1,304 @@
+use crate::gmaths::*;
+use crate::app;
+use crate::resources::LightKind;
+use crate::graphics::light::Light;
+use crate::graphics::culling::Frustum;
+use crate::graphics::opengl::*;
+use crate::graphics::animation::DeformationBuffers;
+
+// Texture slots after the material's slots.
+const CASCADE_SHADOW_SLOT: u32 = 5;
+const SPOT_SHADOW_SLOT: u32 = 6;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ShadowSettings {
+    pub resolution: u32,
+    // Depth bias in light clip space, applied when comparing.
+    pub bias: f32,
+    // World space offset along the surface normal, applied before projecting.
+    pub normal_bias: f32,
+    // Cascade settings, only used by directional lights.
+    pub cascade_count: u32,
+    // Blend between uniform (0.0) and logarithmic (1.0) cascade splits.
+    pub cascade_split_lambda: f32,
+    pub max_distance: f32,
+    // How far behind a cascade casters are still rendered.
+    pub caster_distance: f32
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            resolution: 2048,
+            bias: 0.0005,
+            normal_bias: 0.02,
+            cascade_count: 4,
+            cascade_split_lambda: 0.75,
+            max_distance: 100.0,
+            caster_distance: 50.0
+        }
+    }
+}
+
+// The render camera, the cascades are fitted to its frustum.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ShadowCamera {
+    pub view: Float4x4,
+    pub fov: f32,
+    pub aspect_ratio: f32,
+    pub near: f32,
+    pub far: f32
+}
+
+struct ShadowView {
+    view_proj: Float4x4,
+    cascade: Option<usize>,
+    spot: Option<usize>
+}
+
+pub struct ShadowRenderer {
+    program: GLShaderProgram,
+    shadow_block: GLUniformBuffer<ShadowBlock>,
+    cascade_maps: GLTexture2DArray,
+    layer_target: GLRenderTarget,
+    // One layer per spot light, all at the largest resolution of this frame's spot lights.
+    spot_maps: GLTexture2DArray,
+    views: Vec<ShadowView>
+}
+
+impl ShadowRenderer {
+    pub fn new() -> Self {
+        let vertex_shader_src = app().resources().get_text(String::from("assets/shaders/shadow_vert.glsl"));
+        let vertex_shader = GLShader::new(GLShaderType::VERTEX, &vertex_shader_src.as_ref());
+        let fragment_shader_src = app().resources().get_text(String::from("assets/shaders/shadow_frag.glsl"));
+        let fragment_shader = GLShader::new(GLShaderType::FRAGMENT, &fragment_shader_src.as_ref());
+        let mut program = GLShaderProgram::new(&vertex_shader, &fragment_shader);
+        program.bind(); {
+            DeformationBuffers::setup_program(&mut program);
+            GLMaterial::setup_program(&mut program);
+        } program.unbind();
+
+        // Depth layers of the shadow map arrays are attached per view.
+        let mut layer_target_desc = GLRenderTargetDesc::new(1, 1);
+        layer_target_desc.depth = GLDepthAttachment::None;
+
+        ShadowRenderer {
+            program: program,
+            shadow_block: GLUniformBuffer::new(SHADOW_BLOCK_BINDING),
+            cascade_maps: Self::create_shadow_maps(1, 1),
+            layer_target: GLRenderTarget::from_desc(&layer_target_desc),
+            spot_maps: Self::create_shadow_maps(1, MAX_SPOT_SHADOWS as u32),
+            views: Vec::new()
+        }
+    }
+
+    fn create_shadow_maps(resolution: u32, layers: u32) -> GLTexture2DArray {
+        let mut desc = GLTextureDesc::new(GLTextureFormat::Depth32F);
+        desc.sampler.wrap_s = GLWrapMode::ClampToEdge;
+        desc.sampler.wrap_t = GLWrapMode::ClampToEdge;
+        desc.sampler.mip_filter = None;
+
+        let shadow_maps = GLTexture2DArray::new(resolution as i32, resolution as i32, layers as i32, &desc);
+        shadow_maps.tex().bind(); {
+            gl_tex_parami(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE);
+            gl_tex_parami(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL);
+        } shadow_maps.tex().unbind();
+
+        shadow_maps
+    }
+
+    pub fn setup_program(shader_program: &mut GLShaderProgram) {
+        shader_program.set_uniform_block_binding(&String::from("ShadowBlock"), SHADOW_BLOCK_BINDING);
+        shader_program.set_sampler_slot(&String::from("cascadeShadowMap"), CASCADE_SHADOW_SLOT as i32);
+        shader_program.set_sampler_slot(&String::from("spotShadowMap"), SPOT_SHADOW_SLOT as i32);
+    }
+
+    // Light data must already be in world space, one entry per light.
+    // Assigns shadow maps to the lights and computes their matrices, returns the number of shadow views to render.
+    pub fn prepare(&mut self, lights: &[Light], light_data: &mut [LightData], camera: &ShadowCamera) -> usize {
+        self.views.clear();
+
+        let mut block = ShadowBlock {
+            cascade_matrices: [Float4x4::identity(); MAX_SHADOW_CASCADES],
+            cascade_splits: Float4::default(),
+            spot_matrices: [Float4x4::identity(); MAX_SPOT_SHADOWS],
+            cascade_count: 0
+        };
+
+        let mut has_cascades = false;
+        let mut spot_count = 0;
+        let mut spot_resolution = 0;
+
+        for (light_idx, light) in lights.iter().enumerate() {
+            let settings = match light.shadow {
+                Some(settings) => settings,
+                None => continue
+            };
+            let data = &mut light_data[light_idx];
+
+            match light.kind {
+                LightKind::Directional if !has_cascades => {
+                    has_cascades = true;
+
+                    let cascade_count = settings.cascade_count.clamp(1, MAX_SHADOW_CASCADES as u32);
+                    if self.cascade_maps.width() != settings.resolution as i32 || self.cascade_maps.layers() != cascade_count as i32 {
+                        self.cascade_maps = Self::create_shadow_maps(settings.resolution, cascade_count);
+                        self.layer_target.resize(settings.resolution as usize, settings.resolution as usize);
+                    }
+
+                    let splits = Self::cascade_splits(camera, &settings, cascade_count as usize);
+                    let mut split_near = camera.near;
+                    for cascade in 0..cascade_count as usize {
+                        let view_proj = Self::cascade_matrix(camera, data.direction, split_near, splits[cascade], &settings);
+                        block.cascade_matrices[cascade] = view_proj;
+                        self.views.push(ShadowView { view_proj: view_proj, cascade: Some(cascade), spot: None });
+                        split_near = splits[cascade];
+                    }
+
+                    block.cascade_splits = Float4::new(splits[0], splits[1], splits[2], splits[3]);
+                    block.cascade_count = cascade_count as i32;
+                    data.shadow_index = 0;
+                },
+                LightKind::Spot { outer_cone_angle, .. } if spot_count < MAX_SPOT_SHADOWS => {
+                    spot_resolution = spot_resolution.max(settings.resolution);
+
+                    let far = light.range.unwrap_or(settings.max_distance);
+                    let proj = Float4x4::perspective((outer_cone_angle * 2.0).to_degrees().min(179.0), 1.0, 0.05, far);
+                    let view = Float4x4::look_at(data.position, data.position + data.direction, Self::up_vector(data.direction));
+                    let view_proj = proj * view;
+
+                    block.spot_matrices[spot_count] = view_proj;
+                    self.views.push(ShadowView { view_proj: view_proj, cascade: None, spot: Some(spot_count) });
+                    data.shadow_index = spot_count as i32;
+                    spot_count += 1;
+                },
+                _ => ()
+            }
+        }
+
+        if spot_count > 0 && self.spot_maps.width() != spot_resolution as i32 {
+            self.spot_maps = Self::create_shadow_maps(spot_resolution, MAX_SPOT_SHADOWS as u32);
+        }
+
+        self.shadow_block.set_data(&block);
+        self.views.len()
+    }
+
+    // Practical split scheme, blends logarithmic and uniform split distances.
+    fn cascade_splits(camera: &ShadowCamera, settings: &ShadowSettings, cascade_count: usize) -> [f32; MAX_SHADOW_CASCADES] {
+        let near = camera.near;
+        let far = camera.far.min(settings.max_distance).max(near + 0.001);
+
+        let mut splits = [far; MAX_SHADOW_CASCADES];
+        for cascade in 0..cascade_count {
+            let p = (cascade + 1) as f32 / cascade_count as f32;
+            let log = near * (far / near).powf(p);
+            let uniform = near + (far - near) * p;
+            splits[cascade] = settings.cascade_split_lambda * log + (1.0 - settings.cascade_split_lambda) * uniform;
+        }
+
+        splits
+    }
+
+    // Fits an orthographic projection around the bounding sphere of a camera frustum slice.
+    fn cascade_matrix(camera: &ShadowCamera, direction: Float3, near: f32, far: f32, settings: &ShadowSettings) -> Float4x4 {
+        let mut inv_view_proj = Float4x4::perspective(camera.fov, camera.aspect_ratio, near, far) * camera.view;
+        inv_view_proj.invert();
+
+        let mut corners = Vec::with_capacity(8);
+        for x in [-1.0f32, 1.0] {
+            for y in [-1.0f32, 1.0] {
+                for z in [-1.0f32, 1.0] {
+                    corners.push(transform_point(&inv_view_proj, Float3::new(x, y, z)));
+                }
+            }
+        }
+
+        let mut center = Float3::default();
+        for corner in corners.iter() {
+            center += *corner;
+        }
+        center = center * (1.0 / corners.len() as f32);
+
+        let mut radius: f32 = 0.0;
+        for corner in corners.iter() {
+            radius = radius.max((*corner - center).magnitude());
+        }
+        // Rounding keeps the projection size stable while the camera rotates.
+        radius = (radius * 16.0).ceil() / 16.0;
+
+        let eye = center - direction * (radius + settings.caster_distance);
+        let view = Float4x4::look_at(eye, center, Self::up_vector(direction));
+        let proj = Float4x4::orthographic(-radius, radius, -radius, radius, 0.0, radius * 2.0 + settings.caster_distance);
+        let mut view_proj = proj * view;
+
+        // Snaps the origin to whole texels to avoid shimmering edges when the camera moves.
+        let half_resolution = settings.resolution as f32 * 0.5;
+        let origin = transform_point(&view_proj, Float3::default());
+        let snapped_x = (origin.x * half_resolution).round() / half_resolution;
+        let snapped_y = (origin.y * half_resolution).round() / half_resolution;
+        *view_proj.at_mut(3, 0) += snapped_x - origin.x;
+        *view_proj.at_mut(3, 1) += snapped_y - origin.y;
+
+        view_proj
+    }
+
+    fn up_vector(direction: Float3) -> Float3 {
+        if direction.y.abs() > 0.99 { Float3::new(0.0, 0.0, 1.0) } else { Float3::up() }
+    }
+
+    pub fn view_frustum(&self, view: usize) -> Frustum {
+        Frustum::from_view_proj(&self.views[view].view_proj)
+    }
+
+    // Binds the target and program for a depth-only pass of the view.
+    pub fn begin_view(&mut self, view: usize) {
+        let shadow_view = &self.views[view];
+
+        let dimensions = match (shadow_view.cascade, shadow_view.spot) {
+            (Some(cascade), _) => {
+                self.layer_target.set_texture_layer(GLRenderAttachment::Depth, self.cascade_maps.tex(), cascade as i32, 0);
+                self.layer_target.bind();
+                Int2::new(self.cascade_maps.width(), self.cascade_maps.height())
+            },
+            (_, Some(spot)) => {
+                self.layer_target.set_texture_layer(GLRenderAttachment::Depth, self.spot_maps.tex(), spot as i32, 0);
+                self.layer_target.bind();
+                Int2::new(self.spot_maps.width(), self.spot_maps.height())
+            },
+            _ => unreachable!()
+        };
+
+        gl_viewport(dimensions);
+        gl_clear();
+
+        self.program.bind();
+        self.program.set_float4x4(&String::from("lightViewProj"), shadow_view.view_proj);
+        self.program.set_bool(&String::from("alphaTest"), false);
+    }
+
+    // Casters drawn after this bind their material and discard fragments below the alpha cutoff.
+    pub fn begin_alpha_tested(&mut self) {
+        self.program.set_bool(&String::from("alphaTest"), true);
+    }
+
+    pub fn end_view(&self) {
+        self.program.unbind();
+        self.layer_target.unbind();
+    }
+
+    pub fn bind(&self) {
+        self.shadow_block.bind();
+        self.cascade_maps.bind(CASCADE_SHADOW_SLOT);
+        self.spot_maps.bind(SPOT_SHADOW_SLOT);
+    }
+}
+
+pub(crate) fn transform_point(matrix: &Float4x4, point: Float3) -> Float3 {
+    let mut result = [0.0f32; 4];
+    for row in 0..4 {
+        result[row] = matrix.at(0, row) * point.x + matrix.at(1, row) * point.y + matrix.at(2, row) * point.z + matrix.at(3, row);
+    }
+
+    Float3::new(result[0], result[1], result[2]) * (1.0 / result[3])
+}
@@ -0,0 +1,309 @@
+use crate::gmaths::*;
+use crate::app;
+use crate::graphics::opengl::*;
+use crate::Timer;
+
+const HDR_COLOR_SLOT: u32 = 0;
+
+// Log2 luminance range covered by the histogram, darker and brighter pixels land in the outer bins.
+const HISTOGRAM_BINS: usize = 256;
+const HISTOGRAM_MIN_LOG_LUMINANCE: f32 = -12.0;
+const HISTOGRAM_MAX_LOG_LUMINANCE: f32 = 8.0;
+// The histogram is built from a grid of samples instead of every pixel.
+const HISTOGRAM_SAMPLE_SIZE: usize = 128;
+// Middle grey, auto exposure maps the average luminance to this value.
+const EXPOSURE_KEY: f32 = 0.18;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Tonemapper {
+    Aces,
+    Reinhard,
+    AgX
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ExposureMode {
+    Manual,
+    Auto
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct TonemapSettings {
+    pub tonemapper: Tonemapper,
+    pub exposure_mode: ExposureMode,
+    // In stops. The exposure in manual mode, a compensation on top of the metered exposure in auto mode.
+    pub exposure: f32,
+    // Auto exposure settings, exposures are in stops.
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    pub adaptation_speed: f32,
+    // Fractions of the histogram ignored at the dark and bright end.
+    pub low_percentile: f32,
+    pub high_percentile: f32,
+    // Disable when presenting to an sRGB frame buffer, which encodes by itself.
+    pub srgb_output: bool
+}
+
+impl Default for TonemapSettings {
+    fn default() -> Self {
+        TonemapSettings {
+            tonemapper: Tonemapper::Aces,
+            exposure_mode: ExposureMode::Manual,
+            exposure: 0.0,
+            min_exposure: -8.0,
+            max_exposure: 8.0,
+            adaptation_speed: 2.0,
+            low_percentile: 0.5,
+            high_percentile: 0.95,
+            srgb_output: true
+        }
+    }
+}
+
+impl Tonemapper {
+    const ALL: [Tonemapper; 3] = [Tonemapper::Aces, Tonemapper::Reinhard, Tonemapper::AgX];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tonemapper::Aces => "ACES",
+            Tonemapper::Reinhard => "Reinhard",
+            Tonemapper::AgX => "AgX"
+        }
+    }
+
+    // Matches the TONEMAPPER defines in tonemap_frag.glsl.
+    fn to_gl(&self) -> i32 {
+        match self {
+            Tonemapper::Aces => 0,
+            Tonemapper::Reinhard => 1,
+            Tonemapper::AgX => 2
+        }
+    }
+}
+
+impl TonemapSettings {
+    pub fn debug_ui(&mut self, ui: &DebugUI) {
+        let mut tonemapper = Tonemapper::ALL.iter().position(|tonemapper| *tonemapper == self.tonemapper).unwrap();
+        if ui.combo("Tonemapper", &mut tonemapper, &Tonemapper::ALL, |tonemapper| tonemapper.name().into()) {
+            self.tonemapper = Tonemapper::ALL[tonemapper];
+        }
+
+        let mut auto_exposure = self.exposure_mode == ExposureMode::Auto;
+        if ui.checkbox("Auto Exposure", &mut auto_exposure) {
+            self.exposure_mode = if auto_exposure { ExposureMode::Auto } else { ExposureMode::Manual };
+        }
+
+        match self.exposure_mode {
+            ExposureMode::Manual => {
+                ui.slider("Exposure", -10.0, 10.0, &mut self.exposure);
+            },
+            ExposureMode::Auto => {
+                ui.slider("Exposure Compensation", -5.0, 5.0, &mut self.exposure);
+                ui.slider("Min Exposure", -16.0, 16.0, &mut self.min_exposure);
+                ui.slider("Max Exposure", -16.0, 16.0, &mut self.max_exposure);
+                ui.slider("Adaptation Speed", 0.1, 10.0, &mut self.adaptation_speed);
+                ui.slider("Low Percentile", 0.0, 1.0, &mut self.low_percentile);
+                ui.slider("High Percentile", 0.0, 1.0, &mut self.high_percentile);
+            }
+        }
+
+        ui.checkbox("sRGB Output", &mut self.srgb_output);
+    }
+}
+
+// Owns the HDR scene targets, meters the exposure and tonemaps the scene color.
+pub struct HdrRenderer {
+    settings: TonemapSettings,
+    hdr_target: GLRenderTarget,
+    tonemap_program: GLShaderProgram,
+
+    histogram_program: GLShaderProgram,
+    histogram_target: GLRenderTarget,
+    histogram_readback: GLPixelReadback,
+    metered_exposure: Option<f32>,
+    exposure: f32,
+    adaptation_timer: Timer,
+
+    vao: GLVAO
+}
+
+impl HdrRenderer {
+    pub fn new(dimensions: Int2, samples: u32) -> Self {
+        let mut hdr_desc = GLRenderTargetDesc::new(dimensions.x as usize, dimensions.y as usize);
+        hdr_desc.samples = samples;
+        hdr_desc.color_formats = vec![GLTextureFormat::RGBA16F];
+
+        let mut histogram_desc = GLRenderTargetDesc::new(HISTOGRAM_BINS, 1);
+        histogram_desc.color_formats = vec![GLTextureFormat::R32F];
+        histogram_desc.depth = GLDepthAttachment::None;
+
+        let mut tonemap_program = Self::create_program("quad_vert.glsl", "tonemap_frag.glsl");
+        tonemap_program.bind(); {
+            tonemap_program.set_sampler_slot(&String::from("hdrColor"), HDR_COLOR_SLOT as i32);
+        } tonemap_program.unbind();
+
+        let mut histogram_program = Self::create_program("histogram_vert.glsl", "histogram_frag.glsl");
+        histogram_program.bind(); {
+            histogram_program.set_sampler_slot(&String::from("hdrColor"), HDR_COLOR_SLOT as i32);
+            histogram_program.set_int(&String::from("sampleSize"), HISTOGRAM_SAMPLE_SIZE as i32);
+            histogram_program.set_int(&String::from("binCount"), HISTOGRAM_BINS as i32);
+            histogram_program.set_float(&String::from("minLogLuminance"), HISTOGRAM_MIN_LOG_LUMINANCE);
+            histogram_program.set_float(&String::from("logLuminanceRange"), HISTOGRAM_MAX_LOG_LUMINANCE - HISTOGRAM_MIN_LOG_LUMINANCE);
+        } histogram_program.unbind();
+
+        HdrRenderer {
+            settings: TonemapSettings::default(),
+            hdr_target: GLRenderTarget::from_desc(&hdr_desc),
+            tonemap_program: tonemap_program,
+            histogram_program: histogram_program,
+            histogram_target: GLRenderTarget::from_desc(&histogram_desc),
+            histogram_readback: GLPixelReadback::new(Int2::new(HISTOGRAM_BINS as i32, 1), GLReadbackFormat::RGBA32F),
+            metered_exposure: None,
+            exposure: 0.0,
+            adaptation_timer: Timer::new(),
+            vao: GLVAO::new()
+        }
+    }
+
+    fn create_program(vertex_shader_name: &str, fragment_shader_name: &str) -> GLShaderProgram {
+        let vertex_shader_src = app().resources().get_text(format!("assets/shaders/{}", vertex_shader_name));
+        let vertex_shader = GLShader::new(GLShaderType::VERTEX, &vertex_shader_src.as_ref());
+        let fragment_shader_src = app().resources().get_text(format!("assets/shaders/{}", fragment_shader_name));
+        let fragment_shader = GLShader::new(GLShaderType::FRAGMENT, &fragment_shader_src.as_ref());
+        GLShaderProgram::new(&vertex_shader, &fragment_shader)
+    }
+
+    pub fn settings(&mut self) -> &mut TonemapSettings {
+        &mut self.settings
+    }
+
+    // Exposure in stops used for the last frame.
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    pub fn resize(&mut self, dimensions: Int2) {
+        self.hdr_target.resize(dimensions.x as usize, dimensions.y as usize);
+    }
+
+    pub fn dimensions(&self) -> Int2 {
+        self.hdr_target.dimensions()
+    }
+
+    // Binds and clears the scene target, everything drawn until resolve is in linear HDR.
+    pub fn begin(&self) {
+        self.bind();
+        gl_clear();
+    }
+
+    pub fn bind(&self) {
+        self.hdr_target.bind();
+        gl_viewport(self.hdr_target.dimensions());
+    }
+
+    pub fn unbind(&self) {
+        self.hdr_target.unbind();
+    }
+
+    // Resolves the multisampled scene target into a single sampled target with the scene color and depth, e.g. for screen space effects.
+    pub fn resolve(&self, target: &GLRenderTarget) {
+        self.hdr_target.resolve(target);
+    }
+
+    // Draws the exposed and tonemapped scene color into the bound target.
+    pub fn tonemap(&mut self, scene_color: &GLRenderTexture) {
+        self.tonemap_program.bind(); {
+            scene_color.bind(HDR_COLOR_SLOT);
+            self.tonemap_program.set_float(&String::from("exposure"), self.exposure.exp2());
+            self.tonemap_program.set_int(&String::from("tonemapper"), self.settings.tonemapper.to_gl());
+            self.tonemap_program.set_bool(&String::from("srgbOutput"), self.settings.srgb_output);
+
+            self.vao.bind(); {
+                gl_draw_arrays(gl::TRIANGLES, 0, 3);
+            } self.vao.unbind();
+        } self.tonemap_program.unbind();
+    }
+
+    pub fn update_exposure(&mut self, scene_color: &GLRenderTexture) {
+        let delta_time = self.adaptation_timer.elapsed() as f32;
+        self.adaptation_timer.reset();
+
+        if self.settings.exposure_mode == ExposureMode::Manual {
+            self.metered_exposure = None;
+            self.exposure = self.settings.exposure;
+            return;
+        }
+
+        // The histogram is read a few frames late so the pipeline never stalls.
+        if let Some(histogram) = self.histogram_readback.try_hdr_image() {
+            let bins: Vec<f32> = histogram.data.iter().step_by(4).cloned().collect();
+            self.metered_exposure = Some(self.meter_exposure(&bins));
+        }
+        if !self.histogram_readback.is_pending() {
+            self.build_histogram(scene_color);
+        }
+
+        let target = match self.metered_exposure {
+            Some(metered_exposure) => (metered_exposure + self.settings.exposure).clamp(self.settings.min_exposure, self.settings.max_exposure),
+            None => self.exposure
+        };
+        let blend = 1.0 - (-delta_time * self.settings.adaptation_speed).exp();
+        self.exposure += (target - self.exposure) * blend;
+    }
+
+    // Scatters a point per sample into the bin of its luminance, accumulated with additive blending.
+    fn build_histogram(&mut self, scene_color: &GLRenderTexture) {
+        self.histogram_target.bind(); {
+            gl_viewport(self.histogram_target.dimensions());
+            gl_clear();
+            gl_enable_blend(gl::ONE, gl::ONE);
+
+            self.histogram_program.bind(); {
+                scene_color.bind(HDR_COLOR_SLOT);
+                self.vao.bind(); {
+                    gl_draw_arrays(gl::POINTS, 0, HISTOGRAM_SAMPLE_SIZE * HISTOGRAM_SAMPLE_SIZE);
+                } self.vao.unbind();
+            } self.histogram_program.unbind();
+
+            gl_disable_blend();
+        } self.histogram_target.unbind();
+
+        self.histogram_target.read_pixels_async(GLRenderAttachment::Color(0), &mut self.histogram_readback);
+    }
+
+    // Exposure in stops that maps the average luminance between the percentiles to middle grey.
+    fn meter_exposure(&self, bins: &[f32]) -> f32 {
+        let total: f32 = bins.iter().sum();
+        if total <= 0.0 {
+            return self.exposure;
+        }
+
+        let low = total * self.settings.low_percentile.min(self.settings.high_percentile);
+        let high = total * self.settings.high_percentile.max(self.settings.low_percentile);
+        let bin_size = (HISTOGRAM_MAX_LOG_LUMINANCE - HISTOGRAM_MIN_LOG_LUMINANCE) / HISTOGRAM_BINS as f32;
+
+        let mut seen = 0.0;
+        let mut weighted_sum = 0.0;
+        let mut weight = 0.0;
+        for (bin, count) in bins.iter().enumerate() {
+            // Only the part of the bin that lies between the percentiles counts.
+            let start = seen;
+            seen += count;
+            let included = seen.min(high) - start.max(low);
+            if included <= 0.0 {
+                continue;
+            }
+
+            let log_luminance = HISTOGRAM_MIN_LOG_LUMINANCE + (bin as f32 + 0.5) * bin_size;
+            weighted_sum += log_luminance * included;
+            weight += included;
+        }
+
+        if weight <= 0.0 {
+            return self.exposure;
+        }
+
+        let average_luminance = (weighted_sum / weight).exp2();
+        (EXPOSURE_KEY / average_luminance).log2()
+    }
+}
@@ -5,4 +5,35 @@ pub struct Image {
     pub data: Vec<u8>,
     pub dimensions: Int2,
     pub channel_count: i32
-}
\ No newline at end of file
+}
+
+#[derive(Clone)]
+pub struct HdrImage {
+    pub data: Vec<f32>,
+    pub dimensions: Int2,
+    pub channel_count: i32
+}
+
+impl Image {
+    pub fn flip_vertical(&mut self) {
+        flip_rows(&mut self.data, (self.dimensions.x * self.channel_count) as usize);
+    }
+}
+
+impl HdrImage {
+    pub fn flip_vertical(&mut self) {
+        flip_rows(&mut self.data, (self.dimensions.x * self.channel_count) as usize);
+    }
+}
+
+fn flip_rows<T: Copy>(data: &mut Vec<T>, row_size: usize) {
+    if row_size == 0 {
+        return;
+    }
+
+    let rows = data.len() / row_size;
+    for y in 0..rows / 2 {
+        let (top, bottom) = data.split_at_mut((rows - y - 1) * row_size);
+        top[y * row_size..(y + 1) * row_size].swap_with_slice(&mut bottom[0..row_size]);
+    }
+}
@@ -0,0 +1,155 @@
+extern crate png;
+
+use std::fs::File;
+use std::io::{self, Write, BufWriter};
+use std::path::Path;
+
+use crate::resources::{Image, HdrImage};
+
+pub fn write_png(path: &Path, image: &Image) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    write_png_to(file, image)
+}
+
+// Png file contents, for embedding images in other files.
+pub fn encode_png(image: &Image) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    write_png_to(&mut data, image)?;
+    Ok(data)
+}
+
+fn write_png_to<W: Write>(writer: W, image: &Image) -> io::Result<()> {
+    let color = match image.channel_count {
+        1 => png::ColorType::Grayscale,
+        2 => png::ColorType::GrayscaleAlpha,
+        3 => png::ColorType::Rgb,
+        4 => png::ColorType::Rgba,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Failed to write png. (Unsupported channel count)"))
+    };
+
+    let mut encoder = png::Encoder::new(writer, image.dimensions.x as u32, image.dimensions.y as u32);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    writer.write_image_data(&image.data).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+// Radiance RGBE, written as flat (non run-length encoded) scanlines.
+pub fn write_hdr(path: &Path, image: &HdrImage) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", image.dimensions.y, image.dimensions.x)?;
+
+    let channel_count = image.channel_count as usize;
+    let mut scanline = Vec::with_capacity(image.dimensions.x as usize * 4);
+    for row in image.data.chunks(image.dimensions.x as usize * channel_count) {
+        scanline.clear();
+
+        for pixel in row.chunks(channel_count) {
+            let r = pixel[0];
+            let g = if channel_count > 1 { pixel[1] } else { r };
+            let b = if channel_count > 2 { pixel[2] } else { r };
+
+            let v = r.max(g).max(b);
+            if v < 1e-32 {
+                scanline.extend_from_slice(&[0, 0, 0, 0]);
+            } else {
+                let exponent = v.log2().floor() as i32 + 1;
+                let scale = 256.0 / 2.0f32.powi(exponent);
+                scanline.extend_from_slice(&[
+                    (r.max(0.0) * scale).min(255.0) as u8,
+                    (g.max(0.0) * scale).min(255.0) as u8,
+                    (b.max(0.0) * scale).min(255.0) as u8,
+                    (exponent + 128) as u8
+                ]);
+            }
+        }
+
+        file.write_all(&scanline)?;
+    }
+
+    file.flush()
+}
+
+// OpenEXR with uncompressed 32-bit float scanlines.
+pub fn write_exr(path: &Path, image: &HdrImage) -> io::Result<()> {
+    let width = image.dimensions.x;
+    let height = image.dimensions.y;
+    let channel_count = image.channel_count as usize;
+
+    // Channels are stored in alphabetical order.
+    let channels: Vec<(&str, usize)> = match channel_count {
+        1 => vec![("Y", 0)],
+        2 => vec![("G", 1), ("R", 0)],
+        3 => vec![("B", 2), ("G", 1), ("R", 0)],
+        4 => vec![("A", 3), ("B", 2), ("G", 1), ("R", 0)],
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Failed to write exr. (Unsupported channel count)"))
+    };
+
+    let mut header: Vec<u8> = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]);
+    header.extend_from_slice(&2u32.to_le_bytes());
+
+    let mut chlist: Vec<u8> = Vec::new();
+    for (name, _) in channels.iter() {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&2i32.to_le_bytes());
+        chlist.extend_from_slice(&[0, 0, 0, 0]);
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+    }
+    chlist.push(0);
+
+    let mut window: Vec<u8> = Vec::new();
+    for v in [0, 0, width - 1, height - 1] {
+        window.extend_from_slice(&(v as i32).to_le_bytes());
+    }
+
+    exr_attribute(&mut header, "channels", "chlist", &chlist);
+    exr_attribute(&mut header, "compression", "compression", &[0]);
+    exr_attribute(&mut header, "dataWindow", "box2i", &window);
+    exr_attribute(&mut header, "displayWindow", "box2i", &window);
+    exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    exr_attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    exr_attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+
+    let line_size = width as usize * channels.len() * 4;
+    let block_size = 8 + line_size;
+    let offsets_start = header.len() + height as usize * 8;
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&header)?;
+    for y in 0..height as usize {
+        file.write_all(&((offsets_start + y * block_size) as u64).to_le_bytes())?;
+    }
+
+    let mut block: Vec<u8> = Vec::with_capacity(block_size);
+    for y in 0..height as usize {
+        block.clear();
+        block.extend_from_slice(&(y as i32).to_le_bytes());
+        block.extend_from_slice(&(line_size as i32).to_le_bytes());
+
+        let row = &image.data[y * width as usize * channel_count..(y + 1) * width as usize * channel_count];
+        for (_, channel) in channels.iter() {
+            for pixel in row.chunks(channel_count) {
+                block.extend_from_slice(&pixel[*channel].to_le_bytes());
+            }
+        }
+
+        file.write_all(&block)?;
+    }
+
+    file.flush()
+}
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(attribute_type.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
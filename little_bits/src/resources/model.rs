@@ -3,6 +3,73 @@ use crate::gmaths::*;
 use crate::resources::Image;
 use crate::Shared;
 
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum FilterMode {
+    Nearest,
+    Linear
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Sampler {
+    pub wrap_s: WrapMode,
+    pub wrap_t: WrapMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mip_filter: Option<FilterMode>
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler {
+            wrap_s: WrapMode::Repeat,
+            wrap_t: WrapMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mip_filter: Some(FilterMode::Linear)
+        }
+    }
+}
+
+// KHR_texture_transform of a texture, along with the UV set it samples.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct TextureTransform {
+    pub offset: Float2,
+    // Radians, counter-clockwise in UV space.
+    pub rotation: f32,
+    pub scale: Float2,
+    pub tex_coord: u32
+}
+
+impl Default for TextureTransform {
+    fn default() -> Self {
+        TextureTransform {
+            offset: Float2::default(),
+            rotation: 0.0,
+            scale: Float2::new(1.0, 1.0),
+            tex_coord: 0
+        }
+    }
+}
+
+// How the base color alpha is used, see the glTF alphaMode.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum AlphaMode {
+    Opaque,
+    // Fragments below the alpha cutoff are discarded.
+    Mask,
+    Blend
+}
+
+// The renderer samples each pair of extension textures (clearcoat and clearcoat roughness, sheen color and roughness,
+// specular color and strength) through one texture. Pairs in different images are packed together when the material
+// is uploaded, but the second texture of a pair is ignored when its transform or UV set differs from the first's.
 #[derive(Clone)]
 pub struct Material {
     pub name: String,
@@ -10,19 +77,81 @@ pub struct Material {
 
     pub base_color_factor: Float4,
     pub base_color_texture: Shared<Image>,
+    pub base_color_sampler: Sampler,
+    pub base_color_transform: TextureTransform,
 
     pub normal_scale: f32,
     pub normal_texture: Shared<Image>,
+    pub normal_sampler: Sampler,
+    pub normal_transform: TextureTransform,
 
     pub metallic_factor: f32,
     pub roughness_factor: f32,
     pub metallic_roughness_texture: Shared<Image>,
+    pub metallic_roughness_sampler: Sampler,
+    pub metallic_roughness_transform: TextureTransform,
 
     pub occlusion_strength: f32,
     pub occlusion_texture: Shared<Image>,
+    pub occlusion_sampler: Sampler,
+    pub occlusion_transform: TextureTransform,
 
     pub emissive_factor: Float3,
+    // KHR_materials_emissive_strength
+    pub emissive_strength: f32,
     pub emissive_texture: Shared<Image>,
+    pub emissive_sampler: Sampler,
+    pub emissive_transform: TextureTransform,
+
+    // KHR_materials_unlit, the base color is output as is.
+    pub unlit: bool,
+    // KHR_materials_ior
+    pub ior: f32,
+
+    // KHR_materials_clearcoat, intensity is read from the red channel and roughness from the green channel.
+    pub clearcoat_factor: f32,
+    pub clearcoat_texture: Shared<Image>,
+    pub clearcoat_sampler: Sampler,
+    pub clearcoat_transform: TextureTransform,
+    pub clearcoat_roughness_factor: f32,
+    pub clearcoat_roughness_texture: Shared<Image>,
+    pub clearcoat_roughness_sampler: Sampler,
+    pub clearcoat_roughness_transform: TextureTransform,
+    pub clearcoat_normal_scale: f32,
+    pub clearcoat_normal_texture: Shared<Image>,
+    pub clearcoat_normal_sampler: Sampler,
+    pub clearcoat_normal_transform: TextureTransform,
+
+    // KHR_materials_transmission, read from the red channel.
+    pub transmission_factor: f32,
+    pub transmission_texture: Shared<Image>,
+    pub transmission_sampler: Sampler,
+    pub transmission_transform: TextureTransform,
+
+    // KHR_materials_sheen, color is read from rgb and roughness from the alpha channel.
+    pub sheen_color_factor: Float3,
+    pub sheen_color_texture: Shared<Image>,
+    pub sheen_color_sampler: Sampler,
+    pub sheen_color_transform: TextureTransform,
+    pub sheen_roughness_factor: f32,
+    pub sheen_roughness_texture: Shared<Image>,
+    pub sheen_roughness_sampler: Sampler,
+    pub sheen_roughness_transform: TextureTransform,
+
+    // KHR_materials_specular, strength is read from the alpha channel and color from rgb.
+    pub specular_factor: f32,
+    pub specular_texture: Shared<Image>,
+    pub specular_sampler: Sampler,
+    pub specular_transform: TextureTransform,
+    pub specular_color_factor: Float3,
+    pub specular_color_texture: Shared<Image>,
+    pub specular_color_sampler: Sampler,
+    pub specular_color_transform: TextureTransform,
+
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    // Back faces are drawn with flipped normals.
+    pub double_sided: bool
 }
 
 impl Default for Material {
@@ -32,15 +161,63 @@ impl Default for Material {
             index: None,
             base_color_factor: Float4::new(1.0, 1.0, 1.0, 1.0),
             base_color_texture: Shared::empty(),
+            base_color_sampler: Sampler::default(),
+            base_color_transform: TextureTransform::default(),
             normal_scale: 1.0,
             normal_texture: Shared::empty(),
+            normal_sampler: Sampler::default(),
+            normal_transform: TextureTransform::default(),
             metallic_factor: 0.0,
             roughness_factor: 1.0,
             metallic_roughness_texture: Shared::empty(),
+            metallic_roughness_sampler: Sampler::default(),
+            metallic_roughness_transform: TextureTransform::default(),
             occlusion_strength: 1.0,
             occlusion_texture: Shared::empty(),
+            occlusion_sampler: Sampler::default(),
+            occlusion_transform: TextureTransform::default(),
             emissive_factor: Float3::default(),
+            emissive_strength: 1.0,
             emissive_texture: Shared::empty(),
+            emissive_sampler: Sampler::default(),
+            emissive_transform: TextureTransform::default(),
+            unlit: false,
+            ior: 1.5,
+            clearcoat_factor: 0.0,
+            clearcoat_texture: Shared::empty(),
+            clearcoat_sampler: Sampler::default(),
+            clearcoat_transform: TextureTransform::default(),
+            clearcoat_roughness_factor: 0.0,
+            clearcoat_roughness_texture: Shared::empty(),
+            clearcoat_roughness_sampler: Sampler::default(),
+            clearcoat_roughness_transform: TextureTransform::default(),
+            clearcoat_normal_scale: 1.0,
+            clearcoat_normal_texture: Shared::empty(),
+            clearcoat_normal_sampler: Sampler::default(),
+            clearcoat_normal_transform: TextureTransform::default(),
+            transmission_factor: 0.0,
+            transmission_texture: Shared::empty(),
+            transmission_sampler: Sampler::default(),
+            transmission_transform: TextureTransform::default(),
+            sheen_color_factor: Float3::default(),
+            sheen_color_texture: Shared::empty(),
+            sheen_color_sampler: Sampler::default(),
+            sheen_color_transform: TextureTransform::default(),
+            sheen_roughness_factor: 0.0,
+            sheen_roughness_texture: Shared::empty(),
+            sheen_roughness_sampler: Sampler::default(),
+            sheen_roughness_transform: TextureTransform::default(),
+            specular_factor: 1.0,
+            specular_texture: Shared::empty(),
+            specular_sampler: Sampler::default(),
+            specular_transform: TextureTransform::default(),
+            specular_color_factor: Float3::new(1.0, 1.0, 1.0),
+            specular_color_texture: Shared::empty(),
+            specular_color_sampler: Sampler::default(),
+            specular_color_transform: TextureTransform::default(),
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false
         }
     }
 }
@@ -52,7 +229,10 @@ pub struct Vertex {
     pub tangent: Float4,
     pub tex_coord: Float2,
     pub tex_coord_1: Float2,
-    pub color: Float4
+    pub color: Float4,
+    // Indices into the skin's joints, only used when the mesh's node has a skin.
+    pub joints: [u16; 4],
+    pub weights: Float4
 }
 
 impl Default for Vertex {
@@ -63,15 +243,35 @@ impl Default for Vertex {
             tangent: Float4::default(),
             tex_coord: Float2::default(),
             tex_coord_1: Float2::default(),
-            color: Float4::default()
+            color: Float4::default(),
+            joints: [0; 4],
+            weights: Float4::default()
         }
     }
 }
 
+// How the indices of a mesh are assembled, strips, fans and loops are converted to lists on import.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PrimitiveType {
+    Triangles,
+    Lines,
+    Points
+}
+
+// Displacements of every vertex, attributes the target does not move are empty.
+#[derive(Clone, Default)]
+pub struct MorphTarget {
+    pub positions: Vec<Float3>,
+    pub normals: Vec<Float3>,
+    pub tangents: Vec<Float3>
+}
+
 #[derive(Clone)]
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
+    pub primitive_type: PrimitiveType,
+    pub morph_targets: Vec<MorphTarget>,
 
     pub min: Float3,
     pub max: Float3,
@@ -79,8 +279,160 @@ pub struct Mesh {
     pub material_idx: usize
 }
 
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum LightKind {
+    Directional,
+    Point,
+    // Cone angles in radians, measured from the light direction.
+    Spot { inner_cone_angle: f32, outer_cone_angle: f32 }
+}
+
+// Light imported from KHR_lights_punctual, it shines along the -Z axis of its node.
+#[derive(Clone, Debug)]
+pub struct PunctualLight {
+    pub name: String,
+    pub kind: LightKind,
+    pub color: Float3,
+    pub intensity: f32,
+    pub range: Option<f32>
+}
+
+#[derive(Clone)]
+pub struct Node {
+    pub name: String,
+    pub transform: Transform,
+    pub meshes: Vec<usize>,
+    pub light: Option<usize>,
+    // Meshes of the node are deformed by the skin, the node's own transform is ignored for them.
+    pub skin: Option<usize>,
+    // Default morph target weights of the node's meshes.
+    pub weights: Vec<f32>,
+    pub children: Vec<usize>,
+    pub parent: Option<usize>
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Joint {
+    pub node: usize,
+    // Moves model space bind pose vertices into the joint's space.
+    pub inverse_bind_matrix: Float4x4
+}
+
+#[derive(Clone, Debug)]
+pub struct Skin {
+    pub name: String,
+    pub joints: Vec<Joint>,
+    pub skeleton: Option<usize>
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum AnimationPath {
+    Translation,
+    // Stored like node rotations, see Resources::process_node.
+    Rotation,
+    Scale,
+    // Morph target weights, packed four to a value.
+    Weights
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    // Every keyframe stores an in-tangent, the value and an out-tangent.
+    CubicSpline
+}
+
+// Keyframes of one property of a node, translations and scales only use xyz.
+#[derive(Clone, Debug)]
+pub struct AnimationChannel {
+    pub node: usize,
+    pub path: AnimationPath,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    // Values per keyframe, only weights of more than four morph targets use several.
+    pub stride: usize,
+    pub values: Vec<Float4>
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationClip {
+    pub name: String,
+    // Time of the last keyframe in seconds.
+    pub duration: f32,
+    pub channels: Vec<AnimationChannel>
+}
+
 #[derive(Clone)]
 pub struct Model {
     pub meshes: Vec<Mesh>,
-    pub materials: Vec<Shared<Material>>
+    pub materials: Vec<Shared<Material>>,
+    pub lights: Vec<PunctualLight>,
+    pub nodes: Vec<Node>,
+    pub root_nodes: Vec<usize>,
+    pub skins: Vec<Skin>,
+    pub animations: Vec<AnimationClip>
+}
+
+impl Model {
+    // Model space matrix of every node.
+    pub fn node_matrices(&self) -> Vec<Float4x4> {
+        let mut matrices = vec![Float4x4::identity(); self.nodes.len()];
+        let mut stack: Vec<(usize, Float4x4)> = self.root_nodes.iter().map(|node_idx| (*node_idx, Float4x4::identity())).collect();
+
+        while let Some((node_idx, parent_matrix)) = stack.pop() {
+            let node = &self.nodes[node_idx];
+            let mut transform = node.transform;
+            matrices[node_idx] = parent_matrix * transform.get_matrix();
+
+            for child in node.children.iter() {
+                stack.push((*child, matrices[node_idx]));
+            }
+        }
+
+        matrices
+    }
+
+    pub fn animation_index(&self, name: &str) -> Option<usize> {
+        self.animations.iter().position(|animation| animation.name == name)
+    }
+
+    // Node every mesh is referenced by.
+    pub fn mesh_nodes(&self) -> Vec<Option<usize>> {
+        let mut nodes = vec![None; self.meshes.len()];
+        for (node_idx, node) in self.nodes.iter().enumerate() {
+            for mesh_idx in node.meshes.iter() {
+                nodes[*mesh_idx] = Some(node_idx);
+            }
+        }
+
+        nodes
+    }
+
+    // Skin deforming every mesh, from the node the mesh is referenced by.
+    pub fn mesh_skins(&self) -> Vec<Option<usize>> {
+        self.mesh_nodes().iter().map(|node_idx| node_idx.and_then(|node_idx| self.nodes[node_idx].skin)).collect()
+    }
+
+    // Default morph target weights of every mesh.
+    pub fn mesh_weights(&self) -> Vec<Vec<f32>> {
+        self.mesh_nodes().iter().map(|node_idx| match node_idx {
+            Some(node_idx) => self.nodes[*node_idx].weights.clone(),
+            None => Vec::new()
+        }).collect()
+    }
+
+    // Model space matrix of every mesh, meshes not referenced by a node keep the identity.
+    pub fn mesh_matrices(&self) -> Vec<Float4x4> {
+        let node_matrices = self.node_matrices();
+
+        let mut matrices = vec![Float4x4::identity(); self.meshes.len()];
+        for (node_idx, node) in self.nodes.iter().enumerate() {
+            for mesh_idx in node.meshes.iter() {
+                matrices[*mesh_idx] = node_matrices[node_idx];
+            }
+        }
+
+        matrices
+    }
 }
\ No newline at end of file
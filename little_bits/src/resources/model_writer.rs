@@ -0,0 +1,874 @@
+extern crate serde_json;
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bitmask_enum::bitmask;
+use serde_json::{json, Map, Value};
+
+use crate::gmaths::*;
+use crate::resources::*;
+use crate::Shared;
+
+#[bitmask(u8)]
+pub enum ModelExportSettings {
+    // Keeps the node tree, lights, skins and animations. Without it every node with meshes becomes a root node in model space.
+    SceneHierarchy
+}
+
+// glTF component types and buffer view targets.
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+const GLB_MAGIC: u32 = 0x46546C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F534A;
+const GLB_CHUNK_BIN: u32 = 0x004E4942;
+
+// Writes .gltf with a .bin next to it, or a single .glb. Images are embedded as png.
+pub fn write_gltf(path: &Path, model: &Model, export_settings: Option<ModelExportSettings>) -> io::Result<()> {
+    let hierarchy = export_settings.map_or(false, |settings| settings.contains(ModelExportSettings::SceneHierarchy));
+    let binary = path.extension().and_then(|extension| extension.to_str()).map_or(false, |extension| extension.eq_ignore_ascii_case("glb"));
+
+    let mut writer = GltfWriter::new();
+    writer.write_model(model, hierarchy)?;
+
+    if binary {
+        let root = writer.root(None);
+        fs::write(path, glb(&root, &writer.buffer)?)
+    } else {
+        let bin_path = path.with_extension("bin");
+        let bin_uri = bin_path.file_name().and_then(|name| name.to_str()).unwrap_or("model.bin").to_string();
+        let root = writer.root(Some(bin_uri));
+
+        fs::write(&bin_path, &writer.buffer)?;
+        fs::write(path, serde_json::to_vec_pretty(&root).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?)
+    }
+}
+
+fn glb(root: &Value, buffer: &Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut json = serde_json::to_vec(root).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    // Chunks are 4 byte aligned, json is padded with spaces and binary data with zeros.
+    json.resize((json.len() + 3) & !3, b' ');
+    let mut bin = buffer.clone();
+    bin.resize((bin.len() + 3) & !3, 0);
+
+    let length = 12 + 8 + json.len() + if bin.is_empty() { 0 } else { 8 + bin.len() };
+    let mut glb = Vec::with_capacity(length);
+    glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(length as u32).to_le_bytes());
+
+    glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+    glb.extend_from_slice(&GLB_CHUNK_JSON.to_le_bytes());
+    glb.extend_from_slice(&json);
+    if !bin.is_empty() {
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&GLB_CHUNK_BIN.to_le_bytes());
+        glb.extend_from_slice(&bin);
+    }
+
+    Ok(glb)
+}
+
+struct GltfWriter {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+
+    images: Vec<Value>,
+    samplers: Vec<Value>,
+    textures: Vec<Value>,
+    materials: Vec<Value>,
+    meshes: Vec<Value>,
+    nodes: Vec<Value>,
+    scene_nodes: Vec<usize>,
+    lights: Vec<Value>,
+    skins: Vec<Value>,
+    animations: Vec<Value>,
+    extensions_used: BTreeSet<&'static str>,
+
+    // Images are shared between materials, keyed by their address.
+    image_indices: HashMap<*const Image, usize>,
+    texture_indices: HashMap<(usize, usize), usize>
+}
+
+impl GltfWriter {
+    fn new() -> Self {
+        GltfWriter {
+            buffer: Vec::new(),
+            buffer_views: Vec::new(),
+            accessors: Vec::new(),
+            images: Vec::new(),
+            samplers: Vec::new(),
+            textures: Vec::new(),
+            materials: Vec::new(),
+            meshes: Vec::new(),
+            nodes: Vec::new(),
+            scene_nodes: Vec::new(),
+            lights: Vec::new(),
+            skins: Vec::new(),
+            animations: Vec::new(),
+            extensions_used: BTreeSet::new(),
+            image_indices: HashMap::new(),
+            texture_indices: HashMap::new()
+        }
+    }
+
+    fn root(&self, bin_uri: Option<String>) -> Value {
+        let mut root = Map::new();
+        root.insert("asset".into(), json!({ "version": "2.0", "generator": "little-bits" }));
+        root.insert("scene".into(), json!(0));
+        if self.scene_nodes.is_empty() {
+            root.insert("scenes".into(), json!([{}]));
+        } else {
+            root.insert("scenes".into(), json!([{ "nodes": self.scene_nodes }]));
+        }
+
+        if !self.buffer.is_empty() {
+            let mut buffer = json!({ "byteLength": self.buffer.len() });
+            if let Some(uri) = bin_uri {
+                buffer["uri"] = json!(uri);
+            }
+            root.insert("buffers".into(), json!([buffer]));
+        }
+
+        let arrays = [
+            ("bufferViews", &self.buffer_views),
+            ("accessors", &self.accessors),
+            ("images", &self.images),
+            ("samplers", &self.samplers),
+            ("textures", &self.textures),
+            ("materials", &self.materials),
+            ("meshes", &self.meshes),
+            ("nodes", &self.nodes),
+            ("skins", &self.skins),
+            ("animations", &self.animations)
+        ];
+        for (name, array) in arrays {
+            if !array.is_empty() {
+                root.insert(name.into(), json!(array));
+            }
+        }
+
+        if !self.lights.is_empty() {
+            root.insert("extensions".into(), json!({ "KHR_lights_punctual": { "lights": self.lights } }));
+        }
+        if !self.extensions_used.is_empty() {
+            root.insert("extensionsUsed".into(), json!(self.extensions_used));
+        }
+
+        Value::Object(root)
+    }
+
+    fn write_model(&mut self, model: &Model, hierarchy: bool) -> io::Result<()> {
+        for material in model.materials.iter() {
+            let material = self.write_material(&material.as_ref())?;
+            self.materials.push(material);
+        }
+
+        // Every node with meshes gets a glTF mesh holding them as primitives.
+        let mut node_meshes = vec![None; model.nodes.len()];
+        for (node_idx, node) in model.nodes.iter().enumerate() {
+            if !node.meshes.is_empty() {
+                node_meshes[node_idx] = Some(self.meshes.len());
+                let mesh = self.write_mesh(model, node, hierarchy);
+                self.meshes.push(mesh);
+            }
+        }
+
+        if hierarchy {
+            for (node_idx, node) in model.nodes.iter().enumerate() {
+                let (translation, rotation, scale) = (node.transform.get_translation(), node.transform.get_rotation(), node.transform.get_scale());
+
+                let mut gltf_node = json!({
+                    "name": node.name,
+                    // Imported rotations are conjugated.
+                    "rotation": [-rotation.x, -rotation.y, -rotation.z, rotation.w],
+                    "translation": [translation.x, translation.y, translation.z],
+                    "scale": [scale.x, scale.y, scale.z]
+                });
+                Self::write_node_contents(&mut gltf_node, node, node_meshes[node_idx]);
+                if !node.children.is_empty() {
+                    gltf_node["children"] = json!(node.children);
+                }
+                if let Some(light) = node.light {
+                    gltf_node["extensions"] = json!({ "KHR_lights_punctual": { "light": light } });
+                }
+                if let Some(skin) = node.skin {
+                    gltf_node["skin"] = json!(skin);
+                }
+                self.nodes.push(gltf_node);
+            }
+            self.scene_nodes = model.root_nodes.clone();
+
+            self.lights = model.lights.iter().map(Self::write_light).collect();
+            if !self.lights.is_empty() {
+                self.extensions_used.insert("KHR_lights_punctual");
+            }
+            for skin in model.skins.iter() {
+                let skin = self.write_skin(skin);
+                self.skins.push(skin);
+            }
+            for animation in model.animations.iter() {
+                let animation = self.write_animation(model, animation);
+                self.animations.push(animation);
+            }
+        } else {
+            // Skinned meshes are already in model space, like GLModel draws them.
+            let node_matrices = model.node_matrices();
+            for (node_idx, node) in model.nodes.iter().enumerate() {
+                if node_meshes[node_idx].is_none() {
+                    continue;
+                }
+
+                let matrix = if node.skin.is_some() { Float4x4::identity() } else { node_matrices[node_idx] };
+                let mut gltf_node = json!({ "name": node.name, "matrix": matrix.elems });
+                Self::write_node_contents(&mut gltf_node, node, node_meshes[node_idx]);
+
+                self.scene_nodes.push(self.nodes.len());
+                self.nodes.push(gltf_node);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_node_contents(gltf_node: &mut Value, node: &Node, mesh: Option<usize>) {
+        if let Some(mesh) = mesh {
+            gltf_node["mesh"] = json!(mesh);
+            if !node.weights.is_empty() {
+                gltf_node["weights"] = json!(node.weights);
+            }
+        }
+    }
+
+    fn write_mesh(&mut self, model: &Model, node: &Node, hierarchy: bool) -> Value {
+        // Joints only mean something with the skin they index.
+        let skinned = hierarchy && node.skin.is_some();
+
+        let primitives: Vec<Value> = node.meshes.iter().map(|mesh_idx| {
+            let mesh = &model.meshes[*mesh_idx];
+            let vertices = &mesh.vertices;
+
+            let positions: Vec<f32> = vertices.iter().flat_map(|v| [v.position.x, v.position.y, v.position.z]).collect();
+            let normals: Vec<f32> = vertices.iter().flat_map(|v| [v.normal.x, v.normal.y, v.normal.z]).collect();
+            let tangents: Vec<f32> = vertices.iter().flat_map(|v| [v.tangent.x, v.tangent.y, v.tangent.z, v.tangent.w]).collect();
+            let tex_coords: Vec<f32> = vertices.iter().flat_map(|v| [v.tex_coord.x, v.tex_coord.y]).collect();
+            let tex_coords_1: Vec<f32> = vertices.iter().flat_map(|v| [v.tex_coord_1.x, v.tex_coord_1.y]).collect();
+
+            let mut attributes = Map::new();
+            attributes.insert("POSITION".into(), json!(self.push_attribute(&positions, "VEC3", true)));
+            attributes.insert("NORMAL".into(), json!(self.push_attribute(&normals, "VEC3", false)));
+            // Tangents are only known for triangles, lines and points keep zeroed ones.
+            if mesh.primitive_type == PrimitiveType::Triangles {
+                attributes.insert("TANGENT".into(), json!(self.push_attribute(&tangents, "VEC4", false)));
+            }
+            attributes.insert("TEXCOORD_0".into(), json!(self.push_attribute(&tex_coords, "VEC2", false)));
+            if vertices.iter().any(|v| v.tex_coord_1 != Float2::default()) {
+                attributes.insert("TEXCOORD_1".into(), json!(self.push_attribute(&tex_coords_1, "VEC2", false)));
+            }
+            // Colors default to zero on import, only meshes that had them write them.
+            if vertices.iter().any(|v| v.color != Float4::default()) {
+                let colors: Vec<f32> = vertices.iter().flat_map(|v| [v.color.x, v.color.y, v.color.z, v.color.w]).collect();
+                attributes.insert("COLOR_0".into(), json!(self.push_attribute(&colors, "VEC4", false)));
+            }
+            if skinned {
+                let joints: Vec<u16> = vertices.iter().flat_map(|v| v.joints).collect();
+                let weights: Vec<f32> = vertices.iter().flat_map(|v| [v.weights.x, v.weights.y, v.weights.z, v.weights.w]).collect();
+                attributes.insert("JOINTS_0".into(), json!(self.push_joints(&joints)));
+                attributes.insert("WEIGHTS_0".into(), json!(self.push_attribute(&weights, "VEC4", false)));
+            }
+
+            let mode = match mesh.primitive_type {
+                PrimitiveType::Points => 0,
+                PrimitiveType::Lines => 1,
+                PrimitiveType::Triangles => 4
+            };
+            let mut primitive = json!({
+                "attributes": attributes,
+                "indices": self.push_indices(&mesh.indices),
+                "mode": mode
+            });
+            if mesh.material_idx < model.materials.len() {
+                primitive["material"] = json!(mesh.material_idx);
+            }
+
+            if !mesh.morph_targets.is_empty() {
+                let targets: Vec<Value> = mesh.morph_targets.iter().map(|target| {
+                    let mut attributes = Map::new();
+                    for (name, displacements) in [("POSITION", &target.positions), ("NORMAL", &target.normals), ("TANGENT", &target.tangents)] {
+                        if !displacements.is_empty() {
+                            let values: Vec<f32> = displacements.iter().flat_map(|d| [d.x, d.y, d.z]).collect();
+                            attributes.insert(name.into(), json!(self.push_attribute(&values, "VEC3", name == "POSITION")));
+                        }
+                    }
+                    Value::Object(attributes)
+                }).collect();
+                primitive["targets"] = json!(targets);
+            }
+
+            primitive
+        }).collect();
+
+        json!({ "name": node.name, "primitives": primitives })
+    }
+
+    fn write_light(light: &PunctualLight) -> Value {
+        let mut gltf_light = json!({
+            "name": light.name,
+            "color": [light.color.x, light.color.y, light.color.z],
+            "intensity": light.intensity
+        });
+        match light.kind {
+            LightKind::Directional => gltf_light["type"] = json!("directional"),
+            LightKind::Point => gltf_light["type"] = json!("point"),
+            LightKind::Spot { inner_cone_angle, outer_cone_angle } => {
+                gltf_light["type"] = json!("spot");
+                gltf_light["spot"] = json!({ "innerConeAngle": inner_cone_angle, "outerConeAngle": outer_cone_angle });
+            }
+        }
+        if let Some(range) = light.range {
+            gltf_light["range"] = json!(range);
+        }
+
+        gltf_light
+    }
+
+    fn write_skin(&mut self, skin: &Skin) -> Value {
+        let matrices: Vec<f32> = skin.joints.iter().flat_map(|joint| joint.inverse_bind_matrix.elems).collect();
+        let mut gltf_skin = json!({
+            "name": skin.name,
+            "joints": skin.joints.iter().map(|joint| joint.node).collect::<Vec<usize>>(),
+            "inverseBindMatrices": self.push_floats(&matrices, "MAT4", false)
+        });
+        if let Some(skeleton) = skin.skeleton {
+            gltf_skin["skeleton"] = json!(skeleton);
+        }
+
+        gltf_skin
+    }
+
+    fn write_animation(&mut self, model: &Model, animation: &AnimationClip) -> Value {
+        let mut samplers = Vec::new();
+        let mut channels = Vec::new();
+
+        for channel in animation.channels.iter() {
+            let (path, values, kind): (&str, Vec<f32>, &str) = match channel.path {
+                AnimationPath::Translation => ("translation", channel.values.iter().flat_map(|v| [v.x, v.y, v.z]).collect(), "VEC3"),
+                // Conjugated back, like node rotations.
+                AnimationPath::Rotation => ("rotation", channel.values.iter().flat_map(|v| [-v.x, -v.y, -v.z, v.w]).collect(), "VEC4"),
+                AnimationPath::Scale => ("scale", channel.values.iter().flat_map(|v| [v.x, v.y, v.z]).collect(), "VEC3"),
+                AnimationPath::Weights => {
+                    // Outputs are padded to whole Float4s on import, the node knows the actual target count.
+                    let target_count = model.nodes[channel.node].weights.len();
+                    let values = channel.values.chunks(channel.stride)
+                        .flat_map(|output| output.iter().flat_map(|v| [v.x, v.y, v.z, v.w]).take(target_count).collect::<Vec<f32>>())
+                        .collect();
+                    ("weights", values, "SCALAR")
+                }
+            };
+            let interpolation = match channel.interpolation {
+                Interpolation::Step => "STEP",
+                Interpolation::Linear => "LINEAR",
+                Interpolation::CubicSpline => "CUBICSPLINE"
+            };
+
+            channels.push(json!({ "sampler": samplers.len(), "target": { "node": channel.node, "path": path } }));
+            samplers.push(json!({
+                "input": self.push_floats(&channel.times, "SCALAR", true),
+                "output": self.push_floats(&values, kind, false),
+                "interpolation": interpolation
+            }));
+        }
+
+        json!({ "name": animation.name, "samplers": samplers, "channels": channels })
+    }
+
+    fn write_material(&mut self, material: &Material) -> io::Result<Value> {
+        let mut pbr = json!({
+            "baseColorFactor": [material.base_color_factor.x, material.base_color_factor.y, material.base_color_factor.z, material.base_color_factor.w],
+            "metallicFactor": material.metallic_factor,
+            "roughnessFactor": material.roughness_factor
+        });
+        if let Some(info) = self.write_texture_info(&material.base_color_texture, &material.base_color_sampler, &material.base_color_transform)? {
+            pbr["baseColorTexture"] = info;
+        }
+        if let Some(info) = self.write_texture_info(&material.metallic_roughness_texture, &material.metallic_roughness_sampler, &material.metallic_roughness_transform)? {
+            pbr["metallicRoughnessTexture"] = info;
+        }
+
+        let mut gltf_material = json!({
+            "name": material.name,
+            "pbrMetallicRoughness": pbr,
+            "emissiveFactor": [material.emissive_factor.x, material.emissive_factor.y, material.emissive_factor.z],
+            "doubleSided": material.double_sided
+        });
+        if let Some(mut info) = self.write_texture_info(&material.normal_texture, &material.normal_sampler, &material.normal_transform)? {
+            info["scale"] = json!(material.normal_scale);
+            gltf_material["normalTexture"] = info;
+        }
+        if let Some(mut info) = self.write_texture_info(&material.occlusion_texture, &material.occlusion_sampler, &material.occlusion_transform)? {
+            info["strength"] = json!(material.occlusion_strength);
+            gltf_material["occlusionTexture"] = info;
+        }
+        if let Some(info) = self.write_texture_info(&material.emissive_texture, &material.emissive_sampler, &material.emissive_transform)? {
+            gltf_material["emissiveTexture"] = info;
+        }
+        match material.alpha_mode {
+            AlphaMode::Opaque => gltf_material["alphaMode"] = json!("OPAQUE"),
+            AlphaMode::Mask => {
+                gltf_material["alphaMode"] = json!("MASK");
+                gltf_material["alphaCutoff"] = json!(material.alpha_cutoff);
+            },
+            AlphaMode::Blend => gltf_material["alphaMode"] = json!("BLEND")
+        }
+
+        // Extensions are only written when they change the default look.
+        let mut extensions = Map::new();
+        if material.emissive_strength != 1.0 {
+            extensions.insert("KHR_materials_emissive_strength".into(), json!({ "emissiveStrength": material.emissive_strength }));
+        }
+        if material.unlit {
+            extensions.insert("KHR_materials_unlit".into(), json!({}));
+        }
+        if material.ior != 1.5 {
+            extensions.insert("KHR_materials_ior".into(), json!({ "ior": material.ior }));
+        }
+
+        let mut clearcoat = json!({
+            "clearcoatFactor": material.clearcoat_factor,
+            "clearcoatRoughnessFactor": material.clearcoat_roughness_factor
+        });
+        let mut has_clearcoat_texture = false;
+        if let Some(info) = self.write_texture_info(&material.clearcoat_texture, &material.clearcoat_sampler, &material.clearcoat_transform)? {
+            clearcoat["clearcoatTexture"] = info;
+            has_clearcoat_texture = true;
+        }
+        if let Some(info) = self.write_texture_info(&material.clearcoat_roughness_texture, &material.clearcoat_roughness_sampler, &material.clearcoat_roughness_transform)? {
+            clearcoat["clearcoatRoughnessTexture"] = info;
+            has_clearcoat_texture = true;
+        }
+        if let Some(mut info) = self.write_texture_info(&material.clearcoat_normal_texture, &material.clearcoat_normal_sampler, &material.clearcoat_normal_transform)? {
+            info["scale"] = json!(material.clearcoat_normal_scale);
+            clearcoat["clearcoatNormalTexture"] = info;
+            has_clearcoat_texture = true;
+        }
+        if material.clearcoat_factor != 0.0 || has_clearcoat_texture {
+            extensions.insert("KHR_materials_clearcoat".into(), clearcoat);
+        }
+
+        let mut transmission = json!({ "transmissionFactor": material.transmission_factor });
+        let transmission_info = self.write_texture_info(&material.transmission_texture, &material.transmission_sampler, &material.transmission_transform)?;
+        if material.transmission_factor != 0.0 || transmission_info.is_some() {
+            if let Some(info) = transmission_info {
+                transmission["transmissionTexture"] = info;
+            }
+            extensions.insert("KHR_materials_transmission".into(), transmission);
+        }
+
+        let mut sheen = json!({
+            "sheenColorFactor": [material.sheen_color_factor.x, material.sheen_color_factor.y, material.sheen_color_factor.z],
+            "sheenRoughnessFactor": material.sheen_roughness_factor
+        });
+        let mut has_sheen_texture = false;
+        if let Some(info) = self.write_texture_info(&material.sheen_color_texture, &material.sheen_color_sampler, &material.sheen_color_transform)? {
+            sheen["sheenColorTexture"] = info;
+            has_sheen_texture = true;
+        }
+        if let Some(info) = self.write_texture_info(&material.sheen_roughness_texture, &material.sheen_roughness_sampler, &material.sheen_roughness_transform)? {
+            sheen["sheenRoughnessTexture"] = info;
+            has_sheen_texture = true;
+        }
+        if material.sheen_color_factor != Float3::default() || has_sheen_texture {
+            extensions.insert("KHR_materials_sheen".into(), sheen);
+        }
+
+        let mut specular = json!({
+            "specularFactor": material.specular_factor,
+            "specularColorFactor": [material.specular_color_factor.x, material.specular_color_factor.y, material.specular_color_factor.z]
+        });
+        let mut has_specular_texture = false;
+        if let Some(info) = self.write_texture_info(&material.specular_texture, &material.specular_sampler, &material.specular_transform)? {
+            specular["specularTexture"] = info;
+            has_specular_texture = true;
+        }
+        if let Some(info) = self.write_texture_info(&material.specular_color_texture, &material.specular_color_sampler, &material.specular_color_transform)? {
+            specular["specularColorTexture"] = info;
+            has_specular_texture = true;
+        }
+        if material.specular_factor != 1.0 || material.specular_color_factor != Float3::new(1.0, 1.0, 1.0) || has_specular_texture {
+            extensions.insert("KHR_materials_specular".into(), specular);
+        }
+
+        for name in [
+            "KHR_materials_emissive_strength", "KHR_materials_unlit", "KHR_materials_ior", "KHR_materials_clearcoat",
+            "KHR_materials_transmission", "KHR_materials_sheen", "KHR_materials_specular"
+        ] {
+            if extensions.contains_key(name) {
+                self.extensions_used.insert(name);
+            }
+        }
+        if !extensions.is_empty() {
+            gltf_material["extensions"] = Value::Object(extensions);
+        }
+
+        Ok(gltf_material)
+    }
+
+    // Returns None for materials without the texture.
+    fn write_texture_info(&mut self, image: &Shared<Image>, sampler: &Sampler, transform: &TextureTransform) -> io::Result<Option<Value>> {
+        if image.is_empty() {
+            return Ok(None);
+        }
+
+        let image_idx = match self.image_indices.get(&image.as_ptr()) {
+            Some(image_idx) => *image_idx,
+            None => {
+                let png = encode_png(&image.as_ref())?;
+                let buffer_view = self.push_buffer_view(&png, None);
+
+                let image_idx = self.images.len();
+                self.images.push(json!({ "bufferView": buffer_view, "mimeType": "image/png" }));
+                self.image_indices.insert(image.as_ptr(), image_idx);
+                image_idx
+            }
+        };
+
+        let sampler_json = Self::write_sampler(sampler);
+        let sampler_idx = match self.samplers.iter().position(|existing| *existing == sampler_json) {
+            Some(sampler_idx) => sampler_idx,
+            None => {
+                self.samplers.push(sampler_json);
+                self.samplers.len() - 1
+            }
+        };
+
+        let texture_idx = match self.texture_indices.get(&(image_idx, sampler_idx)) {
+            Some(texture_idx) => *texture_idx,
+            None => {
+                let texture_idx = self.textures.len();
+                self.textures.push(json!({ "source": image_idx, "sampler": sampler_idx }));
+                self.texture_indices.insert((image_idx, sampler_idx), texture_idx);
+                texture_idx
+            }
+        };
+
+        let mut info = json!({ "index": texture_idx, "texCoord": transform.tex_coord });
+        let identity = TextureTransform { tex_coord: transform.tex_coord, ..TextureTransform::default() };
+        if *transform != identity {
+            info["extensions"] = json!({
+                "KHR_texture_transform": {
+                    "offset": [transform.offset.x, transform.offset.y],
+                    "rotation": transform.rotation,
+                    "scale": [transform.scale.x, transform.scale.y]
+                }
+            });
+            self.extensions_used.insert("KHR_texture_transform");
+        }
+
+        Ok(Some(info))
+    }
+
+    fn write_sampler(sampler: &Sampler) -> Value {
+        let wrap_mode = |mode: WrapMode| -> u32 {
+            match mode {
+                WrapMode::Repeat => 10497,
+                WrapMode::MirroredRepeat => 33648,
+                WrapMode::ClampToEdge => 33071
+            }
+        };
+        let mag_filter = match sampler.mag_filter {
+            FilterMode::Nearest => 9728,
+            FilterMode::Linear => 9729
+        };
+        let min_filter = match (sampler.min_filter, sampler.mip_filter) {
+            (FilterMode::Nearest, None) => 9728,
+            (FilterMode::Linear, None) => 9729,
+            (FilterMode::Nearest, Some(FilterMode::Nearest)) => 9984,
+            (FilterMode::Linear, Some(FilterMode::Nearest)) => 9985,
+            (FilterMode::Nearest, Some(FilterMode::Linear)) => 9986,
+            (FilterMode::Linear, Some(FilterMode::Linear)) => 9987
+        };
+
+        json!({
+            "magFilter": mag_filter,
+            "minFilter": min_filter,
+            "wrapS": wrap_mode(sampler.wrap_s),
+            "wrapT": wrap_mode(sampler.wrap_t)
+        })
+    }
+
+    // Views start 4 byte aligned, which every component type needs.
+    fn push_buffer_view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        self.buffer.resize((self.buffer.len() + 3) & !3, 0);
+
+        let mut view = json!({ "buffer": 0, "byteOffset": self.buffer.len(), "byteLength": bytes.len() });
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+        self.buffer.extend_from_slice(bytes);
+
+        self.buffer_views.push(view);
+        self.buffer_views.len() - 1
+    }
+
+    fn push_accessor(&mut self, bytes: &[u8], target: Option<u32>, component_type: u32, count: usize, kind: &str) -> Value {
+        let buffer_view = self.push_buffer_view(bytes, target);
+        json!({ "bufferView": buffer_view, "componentType": component_type, "count": count, "type": kind })
+    }
+
+    fn push_attribute(&mut self, values: &[f32], kind: &str, bounds: bool) -> usize {
+        self.push_float_accessor(values, kind, Some(ARRAY_BUFFER), bounds)
+    }
+
+    fn push_floats(&mut self, values: &[f32], kind: &str, bounds: bool) -> usize {
+        self.push_float_accessor(values, kind, None, bounds)
+    }
+
+    // Bounds are required for positions and animation inputs.
+    fn push_float_accessor(&mut self, values: &[f32], kind: &str, target: Option<u32>, bounds: bool) -> usize {
+        let components = match kind {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            _ => 16
+        };
+        let count = values.len() / components;
+        let bytes: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+
+        let mut accessor = self.push_accessor(&bytes, target, FLOAT, count, kind);
+        if bounds && count > 0 {
+            let mut min = vec![f32::MAX; components];
+            let mut max = vec![f32::MIN; components];
+            for element in values.chunks(components) {
+                for (i, value) in element.iter().enumerate() {
+                    min[i] = min[i].min(*value);
+                    max[i] = max[i].max(*value);
+                }
+            }
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let bytes: Vec<u8> = indices.iter().flat_map(|index| index.to_le_bytes()).collect();
+        let accessor = self.push_accessor(&bytes, Some(ELEMENT_ARRAY_BUFFER), UNSIGNED_INT, indices.len(), "SCALAR");
+
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_joints(&mut self, joints: &[u16]) -> usize {
+        let bytes: Vec<u8> = joints.iter().flat_map(|joint| joint.to_le_bytes()).collect();
+        let accessor = self.push_accessor(&bytes, Some(ARRAY_BUFFER), UNSIGNED_SHORT, joints.len() / 4, "VEC4");
+
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use crate::system::System;
+
+    use super::*;
+
+    // Two nodes with a mesh each, the root's mesh has a morph target animated by a weights channel.
+    fn test_model() -> Model {
+        let image = Image {
+            data: (0..16).map(|i| i as u8 * 16).collect(),
+            dimensions: Int2::new(2, 2),
+            channel_count: 4
+        };
+        let material = Material {
+            name: String::from("Textured"),
+            base_color_factor: Float4::new(0.5, 0.25, 1.0, 1.0),
+            base_color_texture: Shared::new(image),
+            base_color_transform: TextureTransform {
+                offset: Float2::new(0.25, 0.5),
+                rotation: 0.5,
+                scale: Float2::new(2.0, 3.0),
+                tex_coord: 1
+            },
+            ..Material::default()
+        };
+
+        let vertex = |x: f32, y: f32| Vertex {
+            position: Float3::new(x, y, 0.0),
+            normal: Float3::new(0.0, 0.0, 1.0),
+            tangent: Float4::new(1.0, 0.0, 0.0, 1.0),
+            tex_coord: Float2::new(x, y),
+            tex_coord_1: Float2::new(y, x),
+            ..Vertex::default()
+        };
+        let quad = Mesh {
+            vertices: vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(1.0, 1.0), vertex(0.0, 1.0)],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            primitive_type: PrimitiveType::Triangles,
+            morph_targets: vec![MorphTarget {
+                positions: vec![Float3::new(0.0, 0.0, 1.0); 4],
+                normals: Vec::new(),
+                tangents: Vec::new()
+            }],
+            min: Float3::new(0.0, 0.0, 0.0),
+            max: Float3::new(1.0, 1.0, 0.0),
+            material_idx: 0
+        };
+        let triangle = Mesh {
+            vertices: vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0)],
+            indices: vec![0, 1, 2],
+            primitive_type: PrimitiveType::Triangles,
+            morph_targets: Vec::new(),
+            min: Float3::new(0.0, 0.0, 0.0),
+            max: Float3::new(1.0, 1.0, 0.0),
+            material_idx: 0
+        };
+
+        let mut root_transform = Transform::new();
+        root_transform.set_translation(Float3::new(1.0, 2.0, 3.0));
+        root_transform.set_rotation(Quat::new(0.0, 0.6, 0.0, 0.8));
+        root_transform.set_scale(Float3::new(2.0, 2.0, 2.0));
+        let mut child_transform = Transform::new();
+        child_transform.set_translation(Float3::new(0.0, -1.0, 0.5));
+
+        Model {
+            meshes: vec![quad, triangle],
+            materials: vec![Shared::new(material)],
+            lights: Vec::new(),
+            nodes: vec![
+                Node {
+                    name: String::from("Root"),
+                    transform: root_transform,
+                    meshes: vec![0],
+                    light: None,
+                    skin: None,
+                    weights: vec![0.5],
+                    children: vec![1],
+                    parent: None
+                },
+                Node {
+                    name: String::from("Child"),
+                    transform: child_transform,
+                    meshes: vec![1],
+                    light: None,
+                    skin: None,
+                    weights: Vec::new(),
+                    children: Vec::new(),
+                    parent: Some(0)
+                }
+            ],
+            root_nodes: vec![0],
+            skins: Vec::new(),
+            animations: vec![AnimationClip {
+                name: String::from("Morph"),
+                duration: 1.0,
+                channels: vec![AnimationChannel {
+                    node: 0,
+                    path: AnimationPath::Weights,
+                    interpolation: Interpolation::Linear,
+                    times: vec![0.0, 1.0],
+                    stride: 1,
+                    values: vec![Float4::new(0.0, 0.0, 0.0, 0.0), Float4::new(1.0, 0.0, 0.0, 0.0)]
+                }]
+            }]
+        }
+    }
+
+    fn assert_equivalent(written: &Model, imported: &Model) {
+        assert_eq!(imported.meshes.len(), written.meshes.len());
+        for (expected, mesh) in written.meshes.iter().zip(imported.meshes.iter()) {
+            assert_eq!(mesh.indices, expected.indices);
+            assert_eq!(mesh.primitive_type, expected.primitive_type);
+            assert_eq!(mesh.material_idx, expected.material_idx);
+            assert_eq!((mesh.min, mesh.max), (expected.min, expected.max));
+
+            assert_eq!(mesh.vertices.len(), expected.vertices.len());
+            for (expected, vertex) in expected.vertices.iter().zip(mesh.vertices.iter()) {
+                assert_eq!(vertex.position, expected.position);
+                assert_eq!(vertex.normal, expected.normal);
+                assert_eq!(vertex.tangent, expected.tangent);
+                assert_eq!(vertex.tex_coord, expected.tex_coord);
+                assert_eq!(vertex.tex_coord_1, expected.tex_coord_1);
+            }
+
+            assert_eq!(mesh.morph_targets.len(), expected.morph_targets.len());
+            for (expected, target) in expected.morph_targets.iter().zip(mesh.morph_targets.iter()) {
+                assert_eq!(target.positions, expected.positions);
+                assert_eq!(target.normals, expected.normals);
+                assert_eq!(target.tangents, expected.tangents);
+            }
+        }
+
+        assert_eq!(imported.materials.len(), written.materials.len());
+        for (expected, material) in written.materials.iter().zip(imported.materials.iter()) {
+            let (expected, material) = (expected.as_ref(), material.as_ref());
+            assert_eq!(material.name, expected.name);
+            assert_eq!(material.base_color_factor, expected.base_color_factor);
+            assert_eq!(material.base_color_transform, expected.base_color_transform);
+
+            let (expected_image, image) = (expected.base_color_texture.as_ref(), material.base_color_texture.as_ref());
+            assert_eq!(image.dimensions, expected_image.dimensions);
+            assert_eq!(image.channel_count, expected_image.channel_count);
+            assert_eq!(image.data, expected_image.data);
+        }
+
+        assert_eq!(imported.root_nodes, written.root_nodes);
+        assert_eq!(imported.nodes.len(), written.nodes.len());
+        for (expected, node) in written.nodes.iter().zip(imported.nodes.iter()) {
+            assert_eq!(node.name, expected.name);
+            assert_eq!(node.meshes, expected.meshes);
+            assert_eq!(node.weights, expected.weights);
+            assert_eq!(node.children, expected.children);
+            assert_eq!(node.parent, expected.parent);
+            assert_eq!(node.transform.get_translation(), expected.transform.get_translation());
+            assert_eq!(node.transform.get_rotation(), expected.transform.get_rotation());
+            assert_eq!(node.transform.get_scale(), expected.transform.get_scale());
+        }
+
+        assert_eq!(imported.animations.len(), written.animations.len());
+        for (expected, animation) in written.animations.iter().zip(imported.animations.iter()) {
+            assert_eq!(animation.name, expected.name);
+            assert_eq!(animation.duration, expected.duration);
+            assert_eq!(animation.channels.len(), expected.channels.len());
+            for (expected, channel) in expected.channels.iter().zip(animation.channels.iter()) {
+                assert_eq!(channel.node, expected.node);
+                assert_eq!(channel.path, expected.path);
+                assert_eq!(channel.interpolation, expected.interpolation);
+                assert_eq!(channel.times, expected.times);
+                assert_eq!(channel.stride, expected.stride);
+                assert_eq!(channel.values, expected.values);
+            }
+        }
+    }
+
+    #[test]
+    fn reimports_written_model() {
+        let directory = env::temp_dir().join(format!("little_bits_model_writer_{}", process::id()));
+        fs::create_dir_all(&directory).expect("Failed to create the test directory.");
+
+        let model = test_model();
+        let mut resources = Resources::init();
+        for file_name in ["model.gltf", "model.glb"] {
+            let path = directory.join(file_name);
+            write_gltf(&path, &model, Some(ModelExportSettings::SceneHierarchy)).expect("Failed to write model.");
+
+            let imported = resources.get_model(path.to_str().unwrap().to_string());
+            assert_equivalent(&model, &imported.as_ref());
+        }
+
+        fs::remove_dir_all(&directory).ok();
+    }
+}
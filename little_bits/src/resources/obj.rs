@@ -0,0 +1,510 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::gmaths::*;
+use crate::Shared;
+use crate::resources::*;
+
+// Meshes of an object are split by material and primitive type, vertices are shared per (v, vt, vn) triple.
+struct ObjMesh {
+    material: Option<usize>,
+    primitive_type: PrimitiveType,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+    vertex_map: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    has_normals: bool
+}
+
+struct ObjObject {
+    name: String,
+    meshes: Vec<ObjMesh>
+}
+
+// Vertex data shared by every object of the file.
+#[derive(Default)]
+struct ObjData {
+    positions: Vec<Float3>,
+    colors: Vec<Option<Float4>>,
+    tex_coords: Vec<Float2>,
+    normals: Vec<Float3>
+}
+
+impl ObjObject {
+    fn new(name: String) -> Self {
+        ObjObject {
+            name: name,
+            meshes: Vec::new()
+        }
+    }
+
+    fn mesh(&mut self, material: Option<usize>, primitive_type: PrimitiveType) -> &mut ObjMesh {
+        let mesh_idx = match self.meshes.iter().position(|mesh| mesh.material == material && mesh.primitive_type == primitive_type) {
+            Some(mesh_idx) => mesh_idx,
+            None => {
+                self.meshes.push(ObjMesh {
+                    material: material,
+                    primitive_type: primitive_type,
+                    vertices: Vec::new(),
+                    indices: Vec::new(),
+                    vertex_map: HashMap::new(),
+                    has_normals: true
+                });
+                self.meshes.len() - 1
+            }
+        };
+        &mut self.meshes[mesh_idx]
+    }
+}
+
+impl ObjMesh {
+    fn vertex(&mut self, data: &ObjData, key: (usize, Option<usize>, Option<usize>)) -> u32 {
+        if let Some(index) = self.vertex_map.get(&key) {
+            return *index;
+        }
+
+        let (position_idx, tex_coord_idx, normal_idx) = key;
+        let mut vertex = Vertex {
+            position: data.positions[position_idx],
+            ..Vertex::default()
+        };
+        if let Some(color) = data.colors[position_idx] {
+            vertex.color = color;
+        }
+        if let Some(tex_coord_idx) = tex_coord_idx {
+            vertex.tex_coord = data.tex_coords[tex_coord_idx];
+        }
+        match normal_idx {
+            Some(normal_idx) => vertex.normal = data.normals[normal_idx],
+            None => self.has_normals = false
+        }
+
+        let index = self.vertices.len() as u32;
+        self.vertices.push(vertex);
+        self.vertex_map.insert(key, index);
+        index
+    }
+}
+
+// Resolves a 1-based or negative (relative to the end) OBJ index.
+fn obj_index(token: &str, count: usize, line_number: usize) -> Result<usize, String> {
+    let index: i64 = token.parse().map_err(|_| format!("Invalid index '{}' on line {}", token, line_number))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("Index {} out of range on line {}", index, line_number));
+    }
+    Ok(resolved as usize)
+}
+
+fn obj_floats(tokens: &[&str], line_number: usize) -> Result<Vec<f32>, String> {
+    tokens.iter()
+        .map(|token| token.parse::<f32>().map_err(|_| format!("Invalid number '{}' on line {}", token, line_number)))
+        .collect()
+}
+
+// Parses the "v", "v/vt", "v//vn" and "v/vt/vn" forms of a face or line element.
+fn obj_vertex_key(token: &str, data: &ObjData, line_number: usize) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut parts = token.split('/');
+    let position_idx = obj_index(parts.next().unwrap_or(""), data.positions.len(), line_number)?;
+    let tex_coord_idx = match parts.next() {
+        Some(part) if !part.is_empty() => Some(obj_index(part, data.tex_coords.len(), line_number)?),
+        _ => None
+    };
+    let normal_idx = match parts.next() {
+        Some(part) if !part.is_empty() => Some(obj_index(part, data.normals.len(), line_number)?),
+        _ => None
+    };
+    Ok((position_idx, tex_coord_idx, normal_idx))
+}
+
+impl Resources {
+    // Wavefront .obj files, faces are fan triangulated and "o"/"g" statements start new nodes.
+    pub(super) fn load_obj(&mut self, asset_path: &String) -> Result<Model, String> {
+        let contents = fs::read_to_string(asset_path).map_err(|error| error.to_string())?;
+        let base_path = Path::new(asset_path).parent().unwrap_or_else(|| Path::new("./"));
+
+        let mut data = ObjData::default();
+        let mut materials: Vec<Material> = Vec::new();
+        let mut material_indices: HashMap<String, usize> = HashMap::new();
+        let mut material: Option<usize> = None;
+
+        let mut objects: Vec<ObjObject> = Vec::new();
+        let mut object = ObjObject::new(Path::new(asset_path).file_stem().and_then(|stem| stem.to_str()).unwrap_or("Object").into());
+
+        // Lines ending in a backslash continue on the next line.
+        let contents = contents.replace("\\\r\n", " ").replace("\\\n", " ");
+        for (line_idx, line) in contents.lines().enumerate() {
+            let line_number = line_idx + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.is_empty() {
+                continue;
+            }
+
+            match tokens[0] {
+                "v" => {
+                    let values = obj_floats(&tokens[1..], line_number)?;
+                    if values.len() < 3 {
+                        return Err(format!("Vertex position needs 3 components on line {}", line_number));
+                    }
+                    data.positions.push(Float3::new(values[0], values[1], values[2]));
+                    // Vertex colors follow the position, w is only present without them.
+                    data.colors.push(match values.len() {
+                        6 => Some(Float4::new(values[3], values[4], values[5], 1.0)),
+                        _ => None
+                    });
+                }
+                "vt" => {
+                    let values = obj_floats(&tokens[1..], line_number)?;
+                    let u = values.first().cloned().unwrap_or(0.0);
+                    let v = values.get(1).cloned().unwrap_or(0.0);
+                    // OBJ has V pointing up, images are stored top to bottom.
+                    data.tex_coords.push(Float2::new(u, 1.0 - v));
+                }
+                "vn" => {
+                    let values = obj_floats(&tokens[1..], line_number)?;
+                    if values.len() < 3 {
+                        return Err(format!("Vertex normal needs 3 components on line {}", line_number));
+                    }
+                    let mut normal = Float3::new(values[0], values[1], values[2]);
+                    if normal.magnitude() != 0.0 {
+                        normal.normalize();
+                    }
+                    data.normals.push(normal);
+                }
+                "f" | "l" | "p" => {
+                    let keys = tokens[1..].iter()
+                        .map(|token| obj_vertex_key(token, &data, line_number))
+                        .collect::<Result<Vec<_>, String>>()?;
+
+                    let primitive_type = match tokens[0] {
+                        "f" => PrimitiveType::Triangles,
+                        "l" => PrimitiveType::Lines,
+                        _ => PrimitiveType::Points
+                    };
+                    let mesh = object.mesh(material, primitive_type);
+                    let indices: Vec<u32> = keys.into_iter().map(|key| mesh.vertex(&data, key)).collect();
+                    match primitive_type {
+                        PrimitiveType::Triangles => {
+                            for i in 1..indices.len().saturating_sub(1) {
+                                mesh.indices.extend_from_slice(&[indices[0], indices[i], indices[i + 1]]);
+                            }
+                        }
+                        PrimitiveType::Lines => {
+                            for i in 0..indices.len().saturating_sub(1) {
+                                mesh.indices.extend_from_slice(&[indices[i], indices[i + 1]]);
+                            }
+                        }
+                        PrimitiveType::Points => mesh.indices.extend(indices)
+                    }
+                }
+                "o" | "g" => {
+                    let name = match tokens.len() {
+                        1 => String::from("Unnamed"),
+                        _ => tokens[1..].join(" ")
+                    };
+                    // Statements before the first object or group only get their own node when they made geometry.
+                    let previous = std::mem::replace(&mut object, ObjObject::new(name));
+                    if !previous.meshes.is_empty() {
+                        objects.push(previous);
+                    }
+                }
+                "usemtl" => {
+                    let name = tokens[1..].join(" ");
+                    material = material_indices.get(&name).cloned();
+                    if material.is_none() {
+                        eprintln!("Failed to find material. ('{}' on line {} of '{}')", name, line_number, asset_path);
+                    }
+                }
+                "mtllib" => {
+                    // Library names can contain spaces, try the whole remainder before splitting it.
+                    let whole = line[tokens[0].len()..].trim();
+                    let libraries: Vec<&str> = match base_path.join(whole).exists() {
+                        true => vec![whole],
+                        false => tokens[1..].to_vec()
+                    };
+                    for library in libraries {
+                        let library_path = base_path.join(library);
+                        match self.process_mtl(&library_path, &mut materials) {
+                            Ok(names) => {
+                                for (name, material_idx) in names {
+                                    material_indices.insert(name, material_idx);
+                                }
+                            }
+                            Err(error) => eprintln!("Failed to load material library. ('{}': {})", library_path.display(), error)
+                        }
+                    }
+                }
+                // Smoothing groups, curves and surfaces are not supported.
+                _ => {}
+            }
+        }
+        if !object.meshes.is_empty() {
+            objects.push(object);
+        }
+
+        // Faces without a known material use a default one.
+        let mut default_material = None;
+        let objects = objects.into_iter().map(|object| {
+            let meshes = object.meshes.into_iter().filter(|mesh| !mesh.indices.is_empty()).map(|mesh| {
+                let material_idx = match mesh.material {
+                    Some(material_idx) => material_idx,
+                    None => *default_material.get_or_insert_with(|| {
+                        materials.push(Material::default());
+                        materials.len() - 1
+                    })
+                };
+                let mesh_name = format!("{}#{}", object.name, material_idx);
+                self.process_mesh(mesh.vertices, mesh.indices, mesh.primitive_type, mesh.has_normals, material_idx, &mesh_name)
+            }).collect();
+            (object.name, meshes)
+        }).collect();
+
+        Ok(Self::process_objects(objects, materials))
+    }
+
+    // Appends the materials of a .mtl file, returning their names and indices.
+    fn process_mtl(&mut self, path: &Path, materials: &mut Vec<Material>) -> Result<Vec<(String, usize)>, String> {
+        let contents = fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let base_path = path.parent().unwrap_or_else(|| Path::new("./"));
+
+        let mut names = Vec::new();
+        // Roughness and metallic maps are merged into one image once the material is complete.
+        let mut roughness_maps: Vec<Option<String>> = Vec::new();
+        let mut metallic_maps: Vec<Option<String>> = Vec::new();
+        let mut has_roughness: Vec<bool> = Vec::new();
+
+        for (line_idx, line) in contents.lines().enumerate() {
+            let line_number = line_idx + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.is_empty() {
+                continue;
+            }
+
+            if tokens[0] == "newmtl" {
+                let name = tokens[1..].join(" ");
+                materials.push(Material {
+                    name: name.clone(),
+                    index: Some(materials.len()),
+                    ..Material::default()
+                });
+                names.push((name, materials.len() - 1));
+                roughness_maps.push(None);
+                metallic_maps.push(None);
+                has_roughness.push(false);
+                continue;
+            }
+
+            let material = match names.last() {
+                Some((_, material_idx)) => &mut materials[*material_idx],
+                None => continue
+            };
+            let values = || obj_floats(&tokens[1..], line_number);
+
+            match tokens[0] {
+                "Kd" => {
+                    let values = values()?;
+                    if values.len() >= 3 {
+                        material.base_color_factor = Float4::new(values[0], values[1], values[2], material.base_color_factor.w);
+                    }
+                }
+                "d" => {
+                    // "-halo" is not supported, only the factor is read.
+                    if let Some(alpha) = tokens.last().and_then(|token| token.parse::<f32>().ok()) {
+                        material.base_color_factor.w = alpha;
+                    }
+                }
+                "Tr" => {
+                    if let Some(transparency) = tokens.last().and_then(|token| token.parse::<f32>().ok()) {
+                        material.base_color_factor.w = 1.0 - transparency;
+                    }
+                }
+                "Ns" => {
+                    let values = values()?;
+                    // Blinn-Phong exponent to roughness, an explicit "Pr" takes precedence.
+                    if let (Some(exponent), false) = (values.first(), has_roughness[names.len() - 1]) {
+                        material.roughness_factor = (2.0 / (exponent.max(0.0) + 2.0)).sqrt();
+                    }
+                }
+                "Pr" => {
+                    if let Some(roughness) = values()?.first() {
+                        material.roughness_factor = *roughness;
+                        has_roughness[names.len() - 1] = true;
+                    }
+                }
+                "Pm" => {
+                    if let Some(metallic) = values()?.first() {
+                        material.metallic_factor = *metallic;
+                    }
+                }
+                "Ke" => {
+                    let values = values()?;
+                    if values.len() >= 3 {
+                        material.emissive_factor = Float3::new(values[0], values[1], values[2]);
+                    }
+                }
+                "Ni" => {
+                    if let Some(ior) = values()?.first() {
+                        material.ior = *ior;
+                    }
+                }
+                "map_Kd" | "map_Ke" | "norm" | "map_Bump" | "map_bump" | "bump" => {
+                    let (file, sampler, transform, bump_multiplier) = Self::process_mtl_map(&tokens[1..], line_number)?;
+                    let image = match self.process_mtl_image(base_path, &file) {
+                        Some(image) => image,
+                        None => continue
+                    };
+                    match tokens[0] {
+                        "map_Kd" => {
+                            material.base_color_texture = image;
+                            material.base_color_sampler = sampler;
+                            material.base_color_transform = transform;
+                        }
+                        "map_Ke" => {
+                            material.emissive_texture = image;
+                            material.emissive_sampler = sampler;
+                            material.emissive_transform = transform;
+                            // A black factor would hide the map.
+                            if material.emissive_factor == Float3::default() {
+                                material.emissive_factor = Float3::new(1.0, 1.0, 1.0);
+                            }
+                        }
+                        _ => {
+                            material.normal_texture = image;
+                            material.normal_sampler = sampler;
+                            material.normal_transform = transform;
+                            material.normal_scale = bump_multiplier;
+                        }
+                    }
+                }
+                "map_Pr" | "map_Pm" => {
+                    let (file, sampler, transform, _) = Self::process_mtl_map(&tokens[1..], line_number)?;
+                    material.metallic_roughness_sampler = sampler;
+                    material.metallic_roughness_transform = transform;
+                    match tokens[0] {
+                        "map_Pr" => roughness_maps[names.len() - 1] = Some(file),
+                        _ => metallic_maps[names.len() - 1] = Some(file)
+                    }
+                }
+                // Phong ambient and specular terms have no metallic-roughness equivalent.
+                _ => {}
+            }
+        }
+
+        for (i, (_, material_idx)) in names.iter().enumerate() {
+            let roughness = roughness_maps[i].as_ref().and_then(|file| self.process_mtl_image(base_path, file));
+            let metallic = metallic_maps[i].as_ref().and_then(|file| self.process_mtl_image(base_path, file));
+            if roughness.is_some() || metallic.is_some() {
+                match Self::combine_metallic_roughness(roughness, metallic) {
+                    Ok(image) => materials[*material_idx].metallic_roughness_texture = Shared::new(image),
+                    Err(error) => eprintln!("Failed to combine metallic and roughness maps. ({} in '{}')", error, materials[*material_idx].name)
+                }
+            }
+        }
+
+        for (_, material_idx) in names.iter() {
+            if materials[*material_idx].base_color_factor.w < 1.0 {
+                materials[*material_idx].alpha_mode = AlphaMode::Blend;
+            }
+        }
+
+        Ok(names)
+    }
+
+    // Reads the options of a texture statement, only "-o", "-s", "-clamp" and "-bm" are used.
+    fn process_mtl_map(tokens: &[&str], line_number: usize) -> Result<(String, Sampler, TextureTransform, f32), String> {
+        let mut sampler = Sampler::default();
+        let mut offset = Float2::new(0.0, 0.0);
+        let mut scale = Float2::new(1.0, 1.0);
+        let mut bump_multiplier = 1.0;
+
+        let mut i = 0;
+        while i < tokens.len() && tokens[i].starts_with('-') {
+            // Reads up to "max" numbers, options like "-o u v w" may omit trailing components.
+            let numbers = |max: usize| -> Vec<f32> {
+                tokens[i + 1..].iter().take(max).map_while(|token| token.parse::<f32>().ok()).collect()
+            };
+            let consumed = match tokens[i] {
+                "-o" | "-s" => {
+                    let values = numbers(3);
+                    let default = if tokens[i] == "-o" { 0.0 } else { 1.0 };
+                    let value = Float2::new(values.first().cloned().unwrap_or(default), values.get(1).cloned().unwrap_or(default));
+                    match tokens[i] {
+                        "-o" => offset = value,
+                        _ => scale = value
+                    }
+                    values.len()
+                }
+                "-bm" => {
+                    let values = numbers(1);
+                    bump_multiplier = values.first().cloned().unwrap_or(1.0);
+                    values.len()
+                }
+                "-clamp" => {
+                    if tokens.get(i + 1) == Some(&"on") {
+                        sampler.wrap_s = WrapMode::ClampToEdge;
+                        sampler.wrap_t = WrapMode::ClampToEdge;
+                    }
+                    1
+                }
+                "-blendu" | "-blendv" | "-cc" | "-imfchan" | "-type" => 1,
+                "-mm" => numbers(2).len(),
+                "-t" => numbers(3).len(),
+                "-texres" | "-boost" => numbers(1).len(),
+                option => return Err(format!("Unknown texture option '{}' on line {}", option, line_number))
+            };
+            i += 1 + consumed;
+        }
+
+        if i >= tokens.len() {
+            return Err(format!("Missing texture file on line {}", line_number));
+        }
+
+        // OBJ coordinates have V flipped, so the transform is applied around the flipped origin.
+        let transform = TextureTransform {
+            offset: Float2::new(offset.x, 1.0 - scale.y - offset.y),
+            scale: scale,
+            ..TextureTransform::default()
+        };
+        Ok((tokens[i..].join(" "), sampler, transform, bump_multiplier))
+    }
+
+    fn process_mtl_image(&mut self, base_path: &Path, file: &str) -> Option<Shared<Image>> {
+        // Exporters on Windows write backslashes.
+        let path = base_path.join(file.replace('\\', "/"));
+        if !path.exists() {
+            eprintln!("Failed to find texture. ('{}')", path.display());
+            return None;
+        }
+        Some(self.get_image(path.into_os_string().into_string().unwrap(), Some(ImageImportSettings::FlipVertical)))
+    }
+
+    // Packs roughness into green and metallic into blue like glTF, missing maps are white so the factors apply as is.
+    fn combine_metallic_roughness(roughness: Option<Shared<Image>>, metallic: Option<Shared<Image>>) -> Result<Image, String> {
+        let dimensions = match (&roughness, &metallic) {
+            (Some(roughness), Some(metallic)) if roughness.as_ref().dimensions != metallic.as_ref().dimensions => return Err(String::from("Dimensions differ")),
+            (Some(image), _) | (_, Some(image)) => image.as_ref().dimensions,
+            (None, None) => return Err(String::from("No maps"))
+        };
+
+        let pixel_count = (dimensions.x * dimensions.y) as usize;
+        let channel = |image: &Option<Shared<Image>>, i: usize| -> u8 {
+            match image {
+                Some(image) => image.as_ref().data[i * image.as_ref().channel_count as usize],
+                None => 255
+            }
+        };
+
+        let mut data = Vec::with_capacity(pixel_count * 3);
+        for i in 0..pixel_count {
+            data.extend_from_slice(&[0, channel(&roughness, i), channel(&metallic, i)]);
+        }
+
+        Ok(Image {
+            data: data,
+            dimensions: dimensions,
+            channel_count: 3
+        })
+    }
+}
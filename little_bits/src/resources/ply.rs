@@ -0,0 +1,305 @@
+use std::fs;
+use std::path::Path;
+
+use crate::gmaths::*;
+use crate::resources::*;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum PlyScalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double
+}
+
+#[derive(Clone, Debug)]
+struct PlyProperty {
+    name: String,
+    scalar: PlyScalar,
+    // Type of the list's count, None for a single value.
+    list_count: Option<PlyScalar>
+}
+
+#[derive(Clone, Debug)]
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>
+}
+
+impl PlyScalar {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "char" | "int8" => Ok(PlyScalar::Char),
+            "uchar" | "uint8" => Ok(PlyScalar::UChar),
+            "short" | "int16" => Ok(PlyScalar::Short),
+            "ushort" | "uint16" => Ok(PlyScalar::UShort),
+            "int" | "int32" => Ok(PlyScalar::Int),
+            "uint" | "uint32" => Ok(PlyScalar::UInt),
+            "float" | "float32" => Ok(PlyScalar::Float),
+            "double" | "float64" => Ok(PlyScalar::Double),
+            _ => Err(format!("Unknown property type '{}'", name))
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            PlyScalar::Char | PlyScalar::UChar => 1,
+            PlyScalar::Short | PlyScalar::UShort => 2,
+            PlyScalar::Int | PlyScalar::UInt | PlyScalar::Float => 4,
+            PlyScalar::Double => 8
+        }
+    }
+
+    // Integer colors are normalized by the type's maximum, float colors are used as is.
+    fn normalize(&self, value: f64) -> f32 {
+        match self {
+            PlyScalar::UChar => (value / u8::MAX as f64) as f32,
+            PlyScalar::UShort => (value / u16::MAX as f64) as f32,
+            PlyScalar::UInt => (value / u32::MAX as f64) as f32,
+            PlyScalar::Char => (value / i8::MAX as f64) as f32,
+            PlyScalar::Short => (value / i16::MAX as f64) as f32,
+            PlyScalar::Int => (value / i32::MAX as f64) as f32,
+            PlyScalar::Float | PlyScalar::Double => value as f32
+        }
+    }
+}
+
+// Reads values of either encoding from the body of the file.
+struct PlyReader<'a> {
+    format: PlyFormat,
+    data: &'a [u8],
+    position: usize,
+    // Remaining tokens of the current ASCII line.
+    tokens: Vec<&'a str>
+}
+
+impl<'a> PlyReader<'a> {
+    fn read(&mut self, scalar: PlyScalar) -> Result<f64, String> {
+        if self.format == PlyFormat::Ascii {
+            let token = self.tokens.pop().ok_or_else(|| String::from("Unexpected end of element"))?;
+            return token.parse::<f64>().map_err(|_| format!("Invalid number '{}'", token));
+        }
+
+        let size = scalar.size();
+        if self.position + size > self.data.len() {
+            return Err(String::from("Unexpected end of file"));
+        }
+        let mut bytes = [0u8; 8];
+        bytes[..size].copy_from_slice(&self.data[self.position..self.position + size]);
+        if self.format == PlyFormat::BinaryBigEndian {
+            bytes[..size].reverse();
+        }
+        self.position += size;
+
+        Ok(match scalar {
+            PlyScalar::Char => bytes[0] as i8 as f64,
+            PlyScalar::UChar => bytes[0] as f64,
+            PlyScalar::Short => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            PlyScalar::UShort => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            PlyScalar::Int => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            PlyScalar::UInt => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            PlyScalar::Float => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            PlyScalar::Double => f64::from_le_bytes(bytes)
+        })
+    }
+
+    // ASCII elements are one per line, the tokens are stored reversed so they pop in order.
+    fn begin_element(&mut self) -> Result<(), String> {
+        if self.format != PlyFormat::Ascii {
+            return Ok(());
+        }
+
+        loop {
+            if self.position >= self.data.len() {
+                return Err(String::from("Unexpected end of file"));
+            }
+            let end = self.data[self.position..].iter().position(|byte| *byte == b'\n').map(|end| self.position + end).unwrap_or(self.data.len());
+            let line = std::str::from_utf8(&self.data[self.position..end]).map_err(|error| error.to_string())?;
+            self.position = end + 1;
+
+            self.tokens = line.split_whitespace().rev().collect();
+            if !self.tokens.is_empty() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read_property(&mut self, property: &PlyProperty) -> Result<Vec<f64>, String> {
+        match property.list_count {
+            Some(count_scalar) => {
+                let count = self.read(count_scalar)? as usize;
+                (0..count).map(|_| self.read(property.scalar)).collect()
+            }
+            None => Ok(vec![self.read(property.scalar)?])
+        }
+    }
+}
+
+// Parses the header, returning the format, the elements and where the body starts.
+fn ply_header(data: &[u8]) -> Result<(PlyFormat, Vec<PlyElement>, usize), String> {
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    let mut position = 0;
+
+    loop {
+        let end = data[position..].iter().position(|byte| *byte == b'\n').map(|end| position + end).ok_or_else(|| String::from("Missing end_header"))?;
+        let line = std::str::from_utf8(&data[position..end]).map_err(|error| error.to_string())?.trim();
+        position = end + 1;
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["ply"] if elements.is_empty() && format.is_none() => {}
+            ["format", name, _version] => format = Some(match *name {
+                "ascii" => PlyFormat::Ascii,
+                "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                _ => return Err(format!("Unknown format '{}'", name))
+            }),
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| format!("Invalid element count '{}'", count))?,
+                properties: Vec::new()
+            }),
+            ["property", "list", count_type, scalar_type, name] => elements.last_mut().ok_or_else(|| String::from("Property outside of an element"))?.properties.push(PlyProperty {
+                name: name.to_string(),
+                scalar: PlyScalar::parse(scalar_type)?,
+                list_count: Some(PlyScalar::parse(count_type)?)
+            }),
+            ["property", scalar_type, name] => elements.last_mut().ok_or_else(|| String::from("Property outside of an element"))?.properties.push(PlyProperty {
+                name: name.to_string(),
+                scalar: PlyScalar::parse(scalar_type)?,
+                list_count: None
+            }),
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(format!("Invalid header line '{}'", line))
+        }
+    }
+
+    match format {
+        Some(format) => Ok((format, elements, position)),
+        None => Err(String::from("Missing format"))
+    }
+}
+
+impl Resources {
+    // ASCII and binary .ply files, polygons are fan triangulated and files without faces become points.
+    pub(super) fn load_ply(&self, asset_path: &String) -> Result<Model, String> {
+        let data = fs::read(asset_path).map_err(|error| error.to_string())?;
+        if !data.starts_with(b"ply") {
+            return Err(String::from("Not a PLY file"));
+        }
+        let (format, elements, body) = ply_header(&data)?;
+
+        let mut reader = PlyReader {
+            format: format,
+            data: &data,
+            position: body,
+            tokens: Vec::new()
+        };
+
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        let mut has_normals = false;
+        let mut has_faces = false;
+
+        for element in elements.iter() {
+            let property_idx = |names: &[&str]| element.properties.iter().position(|property| names.contains(&property.name.as_str()));
+
+            match element.name.as_str() {
+                "vertex" => {
+                    let position = [property_idx(&["x"]), property_idx(&["y"]), property_idx(&["z"])];
+                    let normal = [property_idx(&["nx"]), property_idx(&["ny"]), property_idx(&["nz"])];
+                    let tex_coord = [property_idx(&["u", "s", "texture_u", "texture_s"]), property_idx(&["v", "t", "texture_v", "texture_t"])];
+                    let color = [property_idx(&["red", "r", "diffuse_red"]), property_idx(&["green", "g", "diffuse_green"]), property_idx(&["blue", "b", "diffuse_blue"]), property_idx(&["alpha", "a"])];
+                    has_normals = normal.iter().all(|idx| idx.is_some());
+
+                    for _ in 0..element.count {
+                        reader.begin_element()?;
+                        let values = element.properties.iter()
+                            .map(|property| reader.read_property(property))
+                            .collect::<Result<Vec<_>, String>>()?;
+                        let value = |idx: Option<usize>| -> Option<f64> { idx.and_then(|idx| values[idx].first().cloned()) };
+                        let normalized = |idx: Option<usize>, default: f32| -> f32 {
+                            match (idx, value(idx)) {
+                                (Some(idx), Some(value)) => element.properties[idx].scalar.normalize(value),
+                                _ => default
+                            }
+                        };
+
+                        let mut vertex = Vertex {
+                            position: Float3::new(value(position[0]).unwrap_or(0.0) as f32, value(position[1]).unwrap_or(0.0) as f32, value(position[2]).unwrap_or(0.0) as f32),
+                            ..Vertex::default()
+                        };
+                        if has_normals {
+                            vertex.normal = Float3::new(value(normal[0]).unwrap_or(0.0) as f32, value(normal[1]).unwrap_or(0.0) as f32, value(normal[2]).unwrap_or(0.0) as f32);
+                        }
+                        if tex_coord.iter().all(|idx| idx.is_some()) {
+                            // PLY has V pointing up, images are stored top to bottom.
+                            vertex.tex_coord = Float2::new(value(tex_coord[0]).unwrap_or(0.0) as f32, 1.0 - value(tex_coord[1]).unwrap_or(0.0) as f32);
+                        }
+                        if color[..3].iter().all(|idx| idx.is_some()) {
+                            vertex.color = Float4::new(normalized(color[0], 0.0), normalized(color[1], 0.0), normalized(color[2], 0.0), normalized(color[3], 1.0));
+                        }
+                        vertices.push(vertex);
+                    }
+                }
+                "face" => {
+                    has_faces = true;
+                    let polygon = property_idx(&["vertex_indices", "vertex_index"]).ok_or_else(|| String::from("Faces must have vertex indices"))?;
+
+                    for _ in 0..element.count {
+                        reader.begin_element()?;
+                        let mut polygon_indices = Vec::new();
+                        for (idx, property) in element.properties.iter().enumerate() {
+                            let values = reader.read_property(property)?;
+                            if idx == polygon {
+                                polygon_indices = values.into_iter().map(|index| index as u32).collect();
+                            }
+                        }
+                        for i in 1..polygon_indices.len().saturating_sub(1) {
+                            indices.extend_from_slice(&[polygon_indices[0], polygon_indices[i], polygon_indices[i + 1]]);
+                        }
+                    }
+                }
+                // Elements like edges or materials are skipped, they still have to be read to reach the ones after them.
+                _ => {
+                    for _ in 0..element.count {
+                        reader.begin_element()?;
+                        for property in element.properties.iter() {
+                            reader.read_property(property)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        if indices.iter().any(|index| *index as usize >= vertices.len()) {
+            return Err(String::from("Index out of range"));
+        }
+
+        let primitive_type = match has_faces {
+            true => PrimitiveType::Triangles,
+            false => {
+                indices = (0..vertices.len() as u32).collect();
+                PrimitiveType::Points
+            }
+        };
+
+        let name = Path::new(asset_path).file_stem().and_then(|stem| stem.to_str()).unwrap_or("Object").to_string();
+        let mesh = self.process_mesh(vertices, indices, primitive_type, has_normals, 0, &name);
+        Ok(Self::process_objects(vec![(name, vec![mesh])], vec![Material::default()]))
+    }
+}
@@ -20,6 +20,19 @@ pub use model::*;
 #[path = "image.rs"] pub mod image;
 pub use image::*;
 
+#[path = "image_writer.rs"] pub mod image_writer;
+pub use image_writer::*;
+
+#[path = "model_writer.rs"] pub mod model_writer;
+pub use model_writer::*;
+
+#[path = "tangents.rs"] mod tangents;
+use tangents::generate_tangents;
+
+#[path = "obj.rs"] mod obj;
+#[path = "ply.rs"] mod ply;
+#[path = "stl.rs"] mod stl;
+
 #[bitmask(u8)]
 pub enum ImageImportSettings {
     FlipVertical
@@ -29,8 +42,11 @@ pub struct Resources {
     model_manager: ResourceManager<Model>,
     text_manager: ResourceManager<String>,
     image_manager: ResourceManager<Image>,
+    hdr_image_manager: ResourceManager<HdrImage>,
 
-    pub kill_time: f32
+    pub kill_time: f32,
+    // Meshes without normals get flat normals unless set, models that are already loaded keep theirs.
+    pub smooth_normals: bool
 }
 
 impl System for Resources {
@@ -39,7 +55,9 @@ impl System for Resources {
             model_manager: ResourceManager::new(5.0),
             text_manager: ResourceManager::new(5.0),
             image_manager: ResourceManager::new(5.0),
-            kill_time: 5.0
+            hdr_image_manager: ResourceManager::new(5.0),
+            kill_time: 5.0,
+            smooth_normals: false
         })
     }
 
@@ -47,249 +65,711 @@ impl System for Resources {
         self.model_manager.update();
         self.text_manager.update();
         self.image_manager.update();
+        self.hdr_image_manager.update();
     }
 }
 
 impl Resources {
-    fn process_tex(&mut self, texture: &gltf::Texture, base_path: &String) -> Shared<Image> {
+    // External images are shared with get_image, embedded ones are cached under "<model path>#image<index>".
+    fn process_tex(&mut self, images: &Vec<gltf::image::Data>, texture: &gltf::Texture, base_path: &String) -> Shared<Image> {
         let img = texture.source();
         let img = match img.source() {
-            gltf::image::Source::Uri { uri, .. } => {
+            gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
                 let base_path = Path::new(base_path);
                 let path = base_path.parent().unwrap_or_else(|| Path::new("./")).join(uri);
                 self.get_image(path.into_os_string().into_string().unwrap(), Some(ImageImportSettings::FlipVertical))
             }
-            _ => panic!("Failed to process tex. (Only uri support)")
+            _ => {
+                let asset_path = format!("{}#image{}", base_path, img.index());
+                match self.image_manager.get(&asset_path) {
+                    Some(resource) => resource,
+                    None => {
+                        let resource = Shared::new(Self::process_image_data(&images[img.index()]));
+                        self.image_manager.insert(resource.clone(), asset_path);
+                        resource
+                    }
+                }
+            }
         };
         img
     }
 
-    fn process_node(&mut self, node: &gltf::Node, buffers: &Vec<gltf::buffer::Data>, _images: &Vec<gltf::image::Data>, base_path: &String, meshes: &mut Vec<Mesh>, materials: &mut Vec<Material>) {
+    // Converts decoded glTF image data to 8 bits per channel, rows stay top to bottom like stb_image's.
+    fn process_image_data(data: &gltf::image::Data) -> Image {
+        let (channel_count, component_size) = match data.format {
+            gltf::image::Format::R8 => (1, 1),
+            gltf::image::Format::R8G8 => (2, 1),
+            gltf::image::Format::R8G8B8 => (3, 1),
+            gltf::image::Format::R8G8B8A8 => (4, 1),
+            gltf::image::Format::R16 => (1, 2),
+            gltf::image::Format::R16G16 => (2, 2),
+            gltf::image::Format::R16G16B16 => (3, 2),
+            gltf::image::Format::R16G16B16A16 => (4, 2),
+            gltf::image::Format::R32G32B32FLOAT => (3, 4),
+            gltf::image::Format::R32G32B32A32FLOAT => (4, 4)
+        };
+
+        let pixels: Vec<u8> = match component_size {
+            1 => data.pixels.clone(),
+            2 => data.pixels.chunks_exact(2).map(|c| (u16::from_ne_bytes([c[0], c[1]]) >> 8) as u8).collect(),
+            _ => data.pixels.chunks_exact(4).map(|c| (f32::from_ne_bytes([c[0], c[1], c[2], c[3]]).clamp(0.0, 1.0) * 255.0).round() as u8).collect()
+        };
+
+        Image {
+            data: pixels,
+            dimensions: Int2::new(data.width as i32, data.height as i32),
+            channel_count: channel_count
+        }
+    }
+
+    fn process_info_transform(info: &gltf::texture::Info) -> TextureTransform {
+        match info.texture_transform() {
+            Some(transform) => TextureTransform {
+                offset: Float2::from(&transform.offset()),
+                rotation: transform.rotation(),
+                scale: Float2::from(&transform.scale()),
+                tex_coord: transform.tex_coord().unwrap_or(info.tex_coord())
+            },
+            None => TextureTransform { tex_coord: info.tex_coord(), ..TextureTransform::default() }
+        }
+    }
+
+    // Reads a KHR_texture_transform the gltf crate left unparsed.
+    fn process_texture_transform(tex_coord: u32, transform: Option<&gltf::json::Value>) -> TextureTransform {
+        let transform = match transform {
+            Some(transform) => transform,
+            None => return TextureTransform { tex_coord: tex_coord, ..TextureTransform::default() }
+        };
+        let float2 = |key: &str, default: Float2| -> Float2 {
+            match transform.get(key).and_then(|value| value.as_array()) {
+                Some(value) if value.len() == 2 => Float2::new(value[0].as_f64().unwrap_or(0.0) as f32, value[1].as_f64().unwrap_or(0.0) as f32),
+                _ => default
+            }
+        };
+
+        TextureTransform {
+            offset: float2("offset", Float2::default()),
+            rotation: Self::json_f32(transform, "rotation", 0.0),
+            scale: float2("scale", Float2::new(1.0, 1.0)),
+            tex_coord: transform.get("texCoord").and_then(|value| value.as_u64()).map(|value| value as u32).unwrap_or(tex_coord)
+        }
+    }
+
+    // Loads a textureInfo of an extension the gltf crate left unparsed.
+    fn process_json_tex(&mut self, document: &gltf::Document, images: &Vec<gltf::image::Data>, info: Option<&gltf::json::Value>, base_path: &String) -> Option<(Shared<Image>, Sampler, TextureTransform)> {
+        let info = info?;
+        let texture = match info.get("index").and_then(|index| index.as_u64()).and_then(|index| document.textures().nth(index as usize)) {
+            Some(texture) => texture,
+            None => {
+                eprintln!("Failed to load extension texture. (Invalid texture index)");
+                return None;
+            }
+        };
+        let tex_coord = info.get("texCoord").and_then(|value| value.as_u64()).unwrap_or(0) as u32;
+        let transform = Self::process_texture_transform(tex_coord, info.get("extensions").and_then(|extensions| extensions.get("KHR_texture_transform")));
+
+        Some((self.process_tex(images, &texture, base_path), Self::process_sampler(&texture.sampler()), transform))
+    }
+
+    fn json_f32(value: &gltf::json::Value, key: &str, default: f32) -> f32 {
+        value.get(key).and_then(|value| value.as_f64()).map(|value| value as f32).unwrap_or(default)
+    }
+
+    fn process_sampler(sampler: &gltf::texture::Sampler) -> Sampler {
+        let wrap_mode = |mode: gltf::texture::WrappingMode| -> WrapMode {
+            match mode {
+                gltf::texture::WrappingMode::Repeat => WrapMode::Repeat,
+                gltf::texture::WrappingMode::MirroredRepeat => WrapMode::MirroredRepeat,
+                gltf::texture::WrappingMode::ClampToEdge => WrapMode::ClampToEdge
+            }
+        };
+
+        let mag_filter = match sampler.mag_filter() {
+            Some(gltf::texture::MagFilter::Nearest) => FilterMode::Nearest,
+            _ => FilterMode::Linear
+        };
+
+        let (min_filter, mip_filter) = match sampler.min_filter() {
+            Some(gltf::texture::MinFilter::Nearest) => (FilterMode::Nearest, None),
+            Some(gltf::texture::MinFilter::Linear) => (FilterMode::Linear, None),
+            Some(gltf::texture::MinFilter::NearestMipmapNearest) => (FilterMode::Nearest, Some(FilterMode::Nearest)),
+            Some(gltf::texture::MinFilter::LinearMipmapNearest) => (FilterMode::Linear, Some(FilterMode::Nearest)),
+            Some(gltf::texture::MinFilter::NearestMipmapLinear) => (FilterMode::Nearest, Some(FilterMode::Linear)),
+            Some(gltf::texture::MinFilter::LinearMipmapLinear) | None => (FilterMode::Linear, Some(FilterMode::Linear))
+        };
+
+        Sampler {
+            wrap_s: wrap_mode(sampler.wrap_s()),
+            wrap_t: wrap_mode(sampler.wrap_t()),
+            mag_filter: mag_filter,
+            min_filter: min_filter,
+            mip_filter: mip_filter
+        }
+    }
+
+    fn process_light(light: &gltf::khr_lights_punctual::Light) -> PunctualLight {
+        let kind = match light.kind() {
+            gltf::khr_lights_punctual::Kind::Directional => LightKind::Directional,
+            gltf::khr_lights_punctual::Kind::Point => LightKind::Point,
+            gltf::khr_lights_punctual::Kind::Spot { inner_cone_angle, outer_cone_angle } => LightKind::Spot {
+                inner_cone_angle: inner_cone_angle,
+                outer_cone_angle: outer_cone_angle
+            }
+        };
+
+        PunctualLight {
+            name: light.name().map(|s| s.into()).unwrap_or(format!("Light {}", light.index())),
+            kind: kind,
+            color: Float3::from(&light.color()),
+            intensity: light.intensity(),
+            range: light.range()
+        }
+    }
+
+    fn process_node(&mut self, document: &gltf::Document, node: &gltf::Node, buffers: &Vec<gltf::buffer::Data>, images: &Vec<gltf::image::Data>, base_path: &String, meshes: &mut Vec<Mesh>, materials: &mut Vec<Material>) -> Node {
         let (translation, rotation, scale) = node.transform().decomposed();
-        let _translation = Float3::new(translation[0], translation[1], translation[2]);
-        let _rotation = Quaternion::new(rotation[3], rotation[0], rotation[1], rotation[2]); // Correct order?!?!?!?
-        let _scale = Float3::new(scale[0], scale[1], scale[2]);
+        let mut transform = Transform::new();
+        transform.set_translation(Float3::new(translation[0], translation[1], translation[2]));
+        // Matrix4::from(Quat) builds the transposed rotation, conjugating keeps the node matrix identical to glTF's.
+        transform.set_rotation(Quaternion::new(-rotation[0], -rotation[1], -rotation[2], rotation[3]));
+        transform.set_scale(Float3::new(scale[0], scale[1], scale[2]));
+
+        let first_mesh = meshes.len();
 
         match node.mesh() {
             Some(mesh) => {
                 for primitive in mesh.primitives() {
-                    if primitive.mode() == gltf::mesh::Mode::Triangles {
-                        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-
-                        let bounds = primitive.bounding_box();
-                        let min = Float3::from(&bounds.min);
-                        let max = Float3::from(&bounds.max);
-
-                        let positions = {
-                            let iter = reader
-                                .read_positions()
-                                .expect("Failed to process mesh node. (Vertices must have positions)");
-
-                            iter.map(|arr| -> Float3 { Float3::from(&arr) }).collect::<Vec<_>>()
-                        };
+                    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let mesh_name = mesh.name().unwrap_or("Unnamed");
+
+                    let primitive_type = match primitive.mode() {
+                        gltf::mesh::Mode::Points => PrimitiveType::Points,
+                        gltf::mesh::Mode::Lines | gltf::mesh::Mode::LineLoop | gltf::mesh::Mode::LineStrip => PrimitiveType::Lines,
+                        gltf::mesh::Mode::Triangles | gltf::mesh::Mode::TriangleStrip | gltf::mesh::Mode::TriangleFan => PrimitiveType::Triangles
+                    };
+
+                    let bounds = primitive.bounding_box();
+                    let min = Float3::from(&bounds.min);
+                    let max = Float3::from(&bounds.max);
+
+                    let positions = match reader.read_positions() {
+                        Some(iter) => iter.map(|arr| -> Float3 { Float3::from(&arr) }).collect::<Vec<_>>(),
+                        None => {
+                            eprintln!("Failed to process mesh primitive. (Vertices must have positions, skipping primitive {} of '{}')", primitive.index(), mesh_name);
+                            continue;
+                        }
+                    };
+
+                    let mut vertices: Vec<Vertex> = positions
+                        .into_iter()
+                        .map(|position| {
+                            Vertex {
+                                position: Vector3::from(position),
+                                ..Vertex::default()
+                            }
+                    }).collect();
+
+                    // Non-indexed primitives use their vertices in order.
+                    let indices = reader
+                        .read_indices()
+                        .map(|read_indices| {
+                            read_indices.into_u32().collect::<Vec<_>>()
+                        }).unwrap_or_else(|| (0..vertices.len() as u32).collect());
+                    let mut indices = Self::process_indices(primitive.mode(), indices);
+
+                    if indices.is_empty() {
+                        eprintln!("Failed to process mesh primitive. (No complete primitives, skipping primitive {} of '{}')", primitive.index(), mesh_name);
+                        continue;
+                    }
+                    if indices.iter().any(|index| *index as usize >= vertices.len()) {
+                        eprintln!("Failed to process mesh primitive. (Index out of range, skipping primitive {} of '{}')", primitive.index(), mesh_name);
+                        continue;
+                    }
 
-                        let mut vertices: Vec<Vertex> = positions
-                            .into_iter()
-                            .map(|position| {
-                                Vertex {
-                                    position: Vector3::from(position),
-                                    ..Vertex::default()
-                                }
-                        }).collect();
-
-                        let indices = reader
-                            .read_indices()
-                            .map(|read_indices| {
-                                read_indices.into_u32().collect::<Vec<_>>()
-                            }).expect("Failed to process mesh node. (Indices are required)");
-
-                        if let Some(normals) = reader.read_normals() {
+                    let has_normals = match reader.read_normals() {
+                        Some(normals) => {
                             for (i, normal) in normals.enumerate() {
                                 vertices[i].normal = Float3::from(&normal);
                             }
+                            true
+                        },
+                        None => false
+                    };
+
+                    if let Some(joints) = reader.read_joints(0) {
+                        for (i, joints) in joints.into_u16().enumerate() {
+                            vertices[i].joints = joints;
                         }
+                    }
 
-                        let mut tex_coord_channel = 0;
-                        while let Some(tex_coords) = reader.read_tex_coords(tex_coord_channel) {
-                            for (i, tex_coord) in tex_coords.into_f32().enumerate() {
-                                match tex_coord_channel {
-                                    0 => vertices[i].tex_coord = Float2::from(&tex_coord),
-                                    1 => vertices[i].tex_coord_1 = Float2::from(&tex_coord),
-                                    _ => {}
-                                }
+                    if let Some(weights) = reader.read_weights(0) {
+                        for (i, weights) in weights.into_f32().enumerate() {
+                            vertices[i].weights = Float4::from(&weights);
+                        }
+                    }
+
+                    let mut tex_coord_channel = 0;
+                    while let Some(tex_coords) = reader.read_tex_coords(tex_coord_channel) {
+                        for (i, tex_coord) in tex_coords.into_f32().enumerate() {
+                            match tex_coord_channel {
+                                0 => vertices[i].tex_coord = Float2::from(&tex_coord),
+                                1 => vertices[i].tex_coord_1 = Float2::from(&tex_coord),
+                                _ => {}
                             }
+                        }
+
+                        tex_coord_channel += 1;
+                    }
 
-                            tex_coord_channel += 1;
+                    if let Some(colors) = reader.read_colors(0) {
+                        let colors = colors.into_rgba_f32();
+                        for (i, color) in colors.enumerate() {
+                            vertices[i].color = Float4::from(&color);
                         }
+                    }
+
+                    let mut morph_targets: Vec<MorphTarget> = reader.read_morph_targets().map(|(positions, normals, tangents)| MorphTarget {
+                        positions: positions.map(|iter| iter.map(|p| Float3::from(&p)).collect()).unwrap_or_default(),
+                        normals: normals.map(|iter| iter.map(|n| Float3::from(&n)).collect()).unwrap_or_default(),
+                        tangents: tangents.map(|iter| iter.map(|t| Float3::from(&t)).collect()).unwrap_or_default()
+                    }).collect();
+
+                    // Generated after every vertex attribute is read, flat normals split the vertices.
+                    if !has_normals && primitive_type == PrimitiveType::Triangles {
+                        Self::generate_normals(&mut vertices, &mut indices, &mut morph_targets, self.smooth_normals);
+                    }
 
-                        if let Some(tangents) = reader.read_tangents() {
+                    // Authored tangents are ignored without authored normals, as glTF requires.
+                    match reader.read_tangents() {
+                        Some(tangents) if has_normals => {
                             for (i, tangent) in tangents.enumerate() {
                                 vertices[i].tangent = Float4::from(&tangent);
                             }
-                        } else {
-                            // Source: 2001. http://www.terathon.com/code/tangent.html
-                            let mut tan1 = vec![Float3::default(); vertices.len()];
-                            let mut tan2 = vec![Float3::default(); vertices.len()];
-
-                            for i in (0..indices.len()).step_by(3) {
-                                let i1 = indices[i + 0] as usize;
-                                let i2 = indices[i + 1] as usize;
-                                let i3 = indices[i + 2] as usize;
-                            
-                                let v1 = vertices[i1].position;
-                                let v2 = vertices[i2].position;
-                                let v3 = vertices[i3].position;
-                            
-                                let w1 = vertices[i1].tex_coord;
-                                let w2 = vertices[i2].tex_coord;
-                                let w3 = vertices[i3].tex_coord;
-                            
-                                let x1 = v2.x - v1.x;
-                                let x2 = v3.x - v1.x;
-                                let y1 = v2.y - v1.y;
-                                let y2 = v3.y - v1.y;
-                                let z1 = v2.z - v1.z;
-                                let z2 = v3.z - v1.z;
-
-                                let s1 = w2.x - w1.x;
-                                let s2 = w3.x - w1.x;
-                                let t1 = w2.y - w1.y;
-                                let t2 = w3.y - w1.y;
-
-                                let rdiv = s1 * t2 - s2 * t1;
-                                let r;
-                                if rdiv == 0.0 {
-                                    r = 0.0;
-                                } else {
-                                    r = 1.0 / rdiv;
-                                }
-
-                                let sdir = Float3::new(
-                                    (t2 * x1 - t1 * x2) * r,
-                                    (t2 * y1 - t1 * y2) * r,
-                                    (t2 * z1 - t1 * z2) * r
-                                );
-
-                                let tdir = Float3::new(
-                                    (s1 * x2 - s2 * x1) * r,
-                                    (s1 * y2 - s2 * y1) * r,
-                                    (s1 * z2 - s2 * z1) * r
-                                );
-                            
-                                tan1[i1] += sdir;
-                                tan1[i2] += sdir;
-                                tan1[i3] += sdir;
-                            
-                                tan2[i1] += tdir;
-                                tan2[i2] += tdir;
-                                tan2[i3] += tdir;
+                        },
+                        _ if primitive_type == PrimitiveType::Triangles => {
+                            if !generate_tangents(&mut vertices, &mut indices, &mut morph_targets) {
+                                eprintln!("Failed to generate tangents. (Unsuitable geometry in primitive {} of '{}')", primitive.index(), mesh_name);
                             }
-                        
-                            for i in 0..vertices.len() {
-                                let n = vertices[i].normal;
-                                let t = tan1[i];
-                            
-                                let mut xyz = t - (n * dot(n, t));
-                                if xyz.magnitude() != 0.0 {
-                                    xyz.normalize();
-                                }
-                            
-                                let w;
-                                if dot(cross(n, t), tan2[i]) < 0.0 {
-                                    w = -1.0;
-                                } else {
-                                    w = 1.0;
-                                }
-
-                                if xyz.x.is_nan() {
-                                    println!("REEE");
-                                }
-
-                                vertices[i].tangent = Float4::new(xyz.x, xyz.y, xyz.z, w);
+                        },
+                        _ => {}
+                    }
+                    
+                    let prim_material = primitive.material();
+                    let pbr = prim_material.pbr_metallic_roughness();
+                    // Primitives without a material share a default one, appended after the document's materials.
+                    let material_idx = match prim_material.index() {
+                        Some(material_idx) => material_idx,
+                        None => {
+                            let default_idx = document.materials().len();
+                            if materials.len() == default_idx {
+                                materials.push(Material::default());
                             }
+                            default_idx
                         }
+                    };
+
+                    let material = &mut materials[material_idx];
+                    if material.index == None {
+                        material.index = Some(material_idx);
+                        material.name = prim_material.name().map(|s| s.into()).unwrap_or(String::from("Unnamed"));
+                        material.base_color_factor = Float4::from(&pbr.base_color_factor());
+                        material.metallic_factor = pbr.metallic_factor();
+                        material.roughness_factor = pbr.roughness_factor();
+                        material.emissive_factor = Float3::from(&prim_material.emissive_factor());
+                        material.emissive_strength = prim_material.emissive_strength().unwrap_or(1.0);
+                        material.unlit = prim_material.unlit();
+                        material.ior = prim_material.ior().unwrap_or(1.5);
+                        material.alpha_mode = match prim_material.alpha_mode() {
+                            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+                            gltf::material::AlphaMode::Blend => AlphaMode::Blend
+                        };
+                        material.alpha_cutoff = prim_material.alpha_cutoff().unwrap_or(0.5);
+                        material.double_sided = prim_material.double_sided();
 
-                        if let Some(colors) = reader.read_colors(0) {
-                            let colors = colors.into_rgba_f32();
-                            for (i, color) in colors.enumerate() {
-                                vertices[i].color = Float4::from(&color);
-                            }
+                        if let Some(color_tex) = pbr.base_color_texture() {
+                            material.base_color_texture = self.process_tex(images, &color_tex.texture(), base_path);
+                            material.base_color_sampler = Self::process_sampler(&color_tex.texture().sampler());
+                            material.base_color_transform = Self::process_info_transform(&color_tex);
                         }
-                        
-                        let prim_material = primitive.material();
-                        let pbr = prim_material.pbr_metallic_roughness();
-                        let material_idx = primitive.material().index().unwrap_or(0);
-
-                        let material = &mut materials[material_idx];
-                        if material.index == None {
-                            material.index = Some(material_idx);
-                            material.name = prim_material.name().map(|s| s.into()).unwrap_or(String::from("Unnamed"));
-                            material.base_color_factor = Float4::from(&pbr.base_color_factor());
-                            material.metallic_factor = pbr.metallic_factor();
-                            material.roughness_factor = pbr.roughness_factor();
-                            material.emissive_factor = Float3::from(&prim_material.emissive_factor());
-
-                            if let Some(color_tex) = pbr.base_color_texture() {
-                                material.base_color_texture = self.process_tex(&color_tex.texture(), base_path);
-                            }
 
-                            if let Some(normal_tex) = prim_material.normal_texture() {
-                                material.normal_texture = self.process_tex(&normal_tex.texture(), base_path);
-                                material.normal_scale = normal_tex.scale();
-                            }
+                        if let Some(normal_tex) = prim_material.normal_texture() {
+                            material.normal_texture = self.process_tex(images, &normal_tex.texture(), base_path);
+                            material.normal_sampler = Self::process_sampler(&normal_tex.texture().sampler());
+                            material.normal_transform = Self::process_texture_transform(normal_tex.tex_coord(), normal_tex.extension_value("KHR_texture_transform"));
+                            material.normal_scale = normal_tex.scale();
+                        }
 
-                            if let Some(mr_tex) = pbr.metallic_roughness_texture() {
-                                material.metallic_roughness_texture = self.process_tex(&mr_tex.texture(), base_path);
+                        if let Some(mr_tex) = pbr.metallic_roughness_texture() {
+                            material.metallic_roughness_texture = self.process_tex(images, &mr_tex.texture(), base_path);
+                            material.metallic_roughness_sampler = Self::process_sampler(&mr_tex.texture().sampler());
+                            material.metallic_roughness_transform = Self::process_info_transform(&mr_tex);
+                        }
+
+                        if let Some(occlusion_tex) = prim_material.occlusion_texture() {
+                            material.occlusion_texture = self.process_tex(images, &occlusion_tex.texture(), base_path);
+                            material.occlusion_sampler = Self::process_sampler(&occlusion_tex.texture().sampler());
+                            material.occlusion_transform = Self::process_texture_transform(occlusion_tex.tex_coord(), occlusion_tex.extension_value("KHR_texture_transform"));
+                            material.occlusion_strength = occlusion_tex.strength();
+                        }
+
+                        if let Some(emissive_tex) = prim_material.emissive_texture() {
+                            material.emissive_texture = self.process_tex(images, &emissive_tex.texture(), base_path);
+                            material.emissive_sampler = Self::process_sampler(&emissive_tex.texture().sampler());
+                            material.emissive_transform = Self::process_info_transform(&emissive_tex);
+                        }
+
+                        if let Some(transmission) = prim_material.transmission() {
+                            material.transmission_factor = transmission.transmission_factor();
+                            if let Some(transmission_tex) = transmission.transmission_texture() {
+                                material.transmission_texture = self.process_tex(images, &transmission_tex.texture(), base_path);
+                                material.transmission_sampler = Self::process_sampler(&transmission_tex.texture().sampler());
+                                material.transmission_transform = Self::process_info_transform(&transmission_tex);
                             }
+                        }
 
-                            if let Some(occlusion_tex) = prim_material.occlusion_texture() {
-                                material.occlusion_texture = self.process_tex(&occlusion_tex.texture(), base_path);
-                                material.occlusion_strength = occlusion_tex.strength();
+                        if let Some(specular) = prim_material.specular() {
+                            material.specular_factor = specular.specular_factor();
+                            material.specular_color_factor = Float3::from(&specular.specular_color_factor());
+                            if let Some(specular_tex) = specular.specular_texture() {
+                                material.specular_texture = self.process_tex(images, &specular_tex.texture(), base_path);
+                                material.specular_sampler = Self::process_sampler(&specular_tex.texture().sampler());
+                                material.specular_transform = Self::process_info_transform(&specular_tex);
+                            }
+                            if let Some(specular_color_tex) = specular.specular_color_texture() {
+                                material.specular_color_texture = self.process_tex(images, &specular_color_tex.texture(), base_path);
+                                material.specular_color_sampler = Self::process_sampler(&specular_color_tex.texture().sampler());
+                                material.specular_color_transform = Self::process_info_transform(&specular_color_tex);
                             }
+                        }
 
-                            if let Some(emissive_tex) = prim_material.emissive_texture() {
-                                material.emissive_texture = self.process_tex(&emissive_tex.texture(), base_path);
+                        // The gltf crate doesn't parse clearcoat and sheen, they're read from the raw extension json.
+                        if let Some(clearcoat) = prim_material.extension_value("KHR_materials_clearcoat") {
+                            material.clearcoat_factor = Self::json_f32(clearcoat, "clearcoatFactor", 0.0);
+                            material.clearcoat_roughness_factor = Self::json_f32(clearcoat, "clearcoatRoughnessFactor", 0.0);
+                            if let Some((image, sampler, transform)) = self.process_json_tex(document, images, clearcoat.get("clearcoatTexture"), base_path) {
+                                (material.clearcoat_texture, material.clearcoat_sampler, material.clearcoat_transform) = (image, sampler, transform);
+                            }
+                            if let Some((image, sampler, transform)) = self.process_json_tex(document, images, clearcoat.get("clearcoatRoughnessTexture"), base_path) {
+                                (material.clearcoat_roughness_texture, material.clearcoat_roughness_sampler, material.clearcoat_roughness_transform) = (image, sampler, transform);
+                            }
+                            if let Some((image, sampler, transform)) = self.process_json_tex(document, images, clearcoat.get("clearcoatNormalTexture"), base_path) {
+                                (material.clearcoat_normal_texture, material.clearcoat_normal_sampler, material.clearcoat_normal_transform) = (image, sampler, transform);
+                                material.clearcoat_normal_scale = Self::json_f32(&clearcoat["clearcoatNormalTexture"], "scale", 1.0);
                             }
                         }
 
-                        meshes.push(Mesh {
-                            vertices: vertices,
-                            indices: indices,
-                            min: min,
-                            max: max,
-                            material_idx: material_idx
-                        });
-                    } else {
-                        panic!("Failed to process mesh node. (Trying to parse a non-triangle)");
+                        if let Some(sheen) = prim_material.extension_value("KHR_materials_sheen") {
+                            material.sheen_color_factor = match sheen.get("sheenColorFactor").and_then(|value| value.as_array()) {
+                                Some(color) if color.len() == 3 => Float3::new(
+                                    color[0].as_f64().unwrap_or(0.0) as f32,
+                                    color[1].as_f64().unwrap_or(0.0) as f32,
+                                    color[2].as_f64().unwrap_or(0.0) as f32
+                                ),
+                                _ => Float3::default()
+                            };
+                            material.sheen_roughness_factor = Self::json_f32(sheen, "sheenRoughnessFactor", 0.0);
+                            if let Some((image, sampler, transform)) = self.process_json_tex(document, images, sheen.get("sheenColorTexture"), base_path) {
+                                (material.sheen_color_texture, material.sheen_color_sampler, material.sheen_color_transform) = (image, sampler, transform);
+                            }
+                            if let Some((image, sampler, transform)) = self.process_json_tex(document, images, sheen.get("sheenRoughnessTexture"), base_path) {
+                                (material.sheen_roughness_texture, material.sheen_roughness_sampler, material.sheen_roughness_transform) = (image, sampler, transform);
+                            }
+                        }
                     }
+
+                    meshes.push(Mesh {
+                        vertices: vertices,
+                        indices: indices,
+                        primitive_type: primitive_type,
+                        morph_targets: morph_targets,
+                        min: min,
+                        max: max,
+                        material_idx: material_idx
+                    });
                 }
             },
             None => {}
         };
+
+        // Node weights override the mesh's, weights default to zero.
+        let target_count = meshes[first_mesh..].iter().map(|mesh| mesh.morph_targets.len()).max().unwrap_or(0);
+        let mut weights: Vec<f32> = node.weights()
+            .or_else(|| node.mesh().and_then(|mesh| mesh.weights()))
+            .map(|weights| weights.to_vec())
+            .unwrap_or_default();
+        weights.resize(target_count, 0.0);
+
+        Node {
+            name: node.name().map(|s| s.into()).unwrap_or(format!("Node {}", node.index())),
+            transform: transform,
+            meshes: (first_mesh..meshes.len()).collect(),
+            light: node.light().map(|light| light.index()),
+            skin: node.skin().map(|skin| skin.index()),
+            weights: weights,
+            children: node.children().map(|child| child.index()).collect(),
+            parent: None
+        }
+    }
+
+    // Converts strips, fans and loops to lists, trailing indices of incomplete primitives are dropped.
+    fn process_indices(mode: gltf::mesh::Mode, indices: Vec<u32>) -> Vec<u32> {
+        let count = indices.len();
+        match mode {
+            gltf::mesh::Mode::Triangles => indices[..count - count % 3].to_vec(),
+            gltf::mesh::Mode::Lines => indices[..count - count % 2].to_vec(),
+            gltf::mesh::Mode::Points => indices,
+            // Every other triangle is flipped to keep the winding.
+            gltf::mesh::Mode::TriangleStrip => (0..count.saturating_sub(2)).flat_map(|i| match i % 2 {
+                0 => [indices[i], indices[i + 1], indices[i + 2]],
+                _ => [indices[i + 1], indices[i], indices[i + 2]]
+            }).collect(),
+            gltf::mesh::Mode::TriangleFan => (1..count.saturating_sub(1)).flat_map(|i| [indices[i], indices[i + 1], indices[0]]).collect(),
+            gltf::mesh::Mode::LineStrip => (0..count.saturating_sub(1)).flat_map(|i| [indices[i], indices[i + 1]]).collect(),
+            gltf::mesh::Mode::LineLoop => match count {
+                0 | 1 => Vec::new(),
+                _ => (0..count).flat_map(|i| [indices[i], indices[(i + 1) % count]]).collect()
+            }
+        }
+    }
+
+    // Area weighted face normals, vertices shared between triangles are smoothed and unshared ones come out flat.
+    // Flat normals first split the vertices and morph target rows per triangle, as glTF requires without normals.
+    fn generate_normals(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, morph_targets: &mut Vec<MorphTarget>, smooth: bool) {
+        if !smooth {
+            *vertices = indices.iter().map(|index| vertices[*index as usize].clone()).collect();
+            for morph_target in morph_targets.iter_mut() {
+                for rows in [&mut morph_target.positions, &mut morph_target.normals, &mut morph_target.tangents] {
+                    if !rows.is_empty() {
+                        *rows = indices.iter().map(|index| rows[*index as usize]).collect();
+                    }
+                }
+            }
+            *indices = (0..vertices.len() as u32).collect();
+        }
+
+        let mut normals = vec![Float3::default(); vertices.len()];
+        for triangle in indices.chunks_exact(3) {
+            let (i1, i2, i3) = (triangle[0] as usize, triangle[1] as usize, triangle[2] as usize);
+            let face_normal = cross(vertices[i2].position - vertices[i1].position, vertices[i3].position - vertices[i1].position);
+
+            normals[i1] += face_normal;
+            normals[i2] += face_normal;
+            normals[i3] += face_normal;
+        }
+
+        for (vertex, mut normal) in vertices.iter_mut().zip(normals.into_iter()) {
+            if normal.magnitude() != 0.0 {
+                normal.normalize();
+            }
+            vertex.normal = normal;
+        }
+    }
+
+    fn process_skin(skin: &gltf::Skin, buffers: &Vec<gltf::buffer::Data>) -> Skin {
+        let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+        // Missing inverse bind matrices are identities.
+        let mut inverse_bind_matrices: Vec<Float4x4> = match reader.read_inverse_bind_matrices() {
+            Some(matrices) => matrices.map(|matrix| Float4x4::new(matrix.iter().flatten().cloned().collect())).collect(),
+            None => Vec::new()
+        };
+        inverse_bind_matrices.resize(skin.joints().count(), Float4x4::identity());
+
+        Skin {
+            name: skin.name().map(|s| s.into()).unwrap_or(format!("Skin {}", skin.index())),
+            joints: skin.joints().zip(inverse_bind_matrices).map(|(joint, inverse_bind_matrix)| Joint {
+                node: joint.index(),
+                inverse_bind_matrix: inverse_bind_matrix
+            }).collect(),
+            skeleton: skin.skeleton().map(|node| node.index())
+        }
+    }
+
+    fn process_animation(animation: &gltf::Animation, buffers: &Vec<gltf::buffer::Data>) -> AnimationClip {
+        let mut channels = Vec::new();
+        for channel in animation.channels() {
+            let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+            let times: Vec<f32> = match reader.read_inputs() {
+                Some(inputs) => inputs.collect(),
+                None => continue
+            };
+
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline
+            };
+
+            let (path, stride, values) = match reader.read_outputs() {
+                Some(gltf::animation::util::ReadOutputs::Translations(translations)) => {
+                    (AnimationPath::Translation, 1, translations.map(|t| Float4::new(t[0], t[1], t[2], 0.0)).collect())
+                },
+                Some(gltf::animation::util::ReadOutputs::Rotations(rotations)) => {
+                    // Conjugated like node rotations.
+                    (AnimationPath::Rotation, 1, rotations.into_f32().map(|r| Float4::new(-r[0], -r[1], -r[2], r[3])).collect())
+                },
+                Some(gltf::animation::util::ReadOutputs::Scales(scales)) => {
+                    (AnimationPath::Scale, 1, scales.map(|s| Float4::new(s[0], s[1], s[2], 0.0)).collect())
+                },
+                Some(gltf::animation::util::ReadOutputs::MorphTargetWeights(weights)) => {
+                    let weights: Vec<f32> = weights.into_f32().collect();
+                    let output_count = match interpolation {
+                        Interpolation::CubicSpline => times.len() * 3,
+                        _ => times.len()
+                    };
+                    if output_count == 0 || weights.len() < output_count {
+                        continue;
+                    }
+
+                    // Every output is padded to whole Float4s.
+                    let target_count = weights.len() / output_count;
+                    let stride = (target_count + 3) / 4;
+                    let values = weights.chunks(target_count).flat_map(|output| {
+                        let mut padded = output.to_vec();
+                        padded.resize(stride * 4, 0.0);
+                        padded.chunks(4).map(|w| Float4::new(w[0], w[1], w[2], w[3])).collect::<Vec<_>>()
+                    }).collect();
+
+                    (AnimationPath::Weights, stride, values)
+                },
+                None => continue
+            };
+
+            channels.push(AnimationChannel {
+                node: channel.target().node().index(),
+                path: path,
+                interpolation: interpolation,
+                times: times,
+                stride: stride,
+                values: values
+            });
+        }
+
+        AnimationClip {
+            name: animation.name().map(|s| s.into()).unwrap_or(format!("Animation {}", animation.index())),
+            duration: channels.iter().filter_map(|channel| channel.times.last()).fold(0.0, |duration: f32, time| duration.max(*time)),
+            channels: channels
+        }
+    }
+
+    // Finishes a mesh of a format without tangents, normals are generated for triangles when the format has none.
+    fn process_mesh(&self, mut vertices: Vec<Vertex>, mut indices: Vec<u32>, primitive_type: PrimitiveType, has_normals: bool, material_idx: usize, mesh_name: &str) -> Mesh {
+        let mut morph_targets = Vec::new();
+        if !has_normals && primitive_type == PrimitiveType::Triangles {
+            Self::generate_normals(&mut vertices, &mut indices, &mut morph_targets, self.smooth_normals);
+        }
+
+        if primitive_type == PrimitiveType::Triangles && !generate_tangents(&mut vertices, &mut indices, &mut morph_targets) {
+            eprintln!("Failed to generate tangents. (Unsuitable geometry in '{}')", mesh_name);
+        }
+
+        let mut min = Float3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Float3::new(f32::MIN, f32::MIN, f32::MIN);
+        for vertex in vertices.iter() {
+            let p = vertex.position;
+            min = Float3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Float3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+
+        Mesh {
+            vertices: vertices,
+            indices: indices,
+            primitive_type: primitive_type,
+            morph_targets: morph_targets,
+            min: min,
+            max: max,
+            material_idx: material_idx
+        }
     }
 
+    // Formats without a scene graph get a root node per object, holding the object's meshes.
+    fn process_objects(objects: Vec<(String, Vec<Mesh>)>, materials: Vec<Material>) -> Model {
+        let mut meshes = Vec::new();
+        let mut nodes = Vec::new();
+        for (name, object_meshes) in objects {
+            let first_mesh = meshes.len();
+            meshes.extend(object_meshes);
+
+            nodes.push(Node {
+                name: name,
+                transform: Transform::new(),
+                meshes: (first_mesh..meshes.len()).collect(),
+                light: None,
+                skin: None,
+                weights: Vec::new(),
+                children: Vec::new(),
+                parent: None
+            });
+        }
+
+        Model {
+            meshes: meshes,
+            materials: materials.into_iter().map(|m| Shared::new(m)).collect(),
+            lights: Vec::new(),
+            root_nodes: (0..nodes.len()).collect(),
+            nodes: nodes,
+            skins: Vec::new(),
+            animations: Vec::new()
+        }
+    }
+
+    // Imports .gltf and .glb, Wavefront .obj with .mtl materials, .ply and .stl files, picked by extension.
     pub fn get_model(&mut self, asset_path: String) -> Shared<Model> {
         match self.model_manager.get(&asset_path) {
             Some(resource) => resource,
             None => {
-                let (document, buffers, images) = gltf::import(asset_path.clone()).expect("Failed to get model.");
+                let extension = Path::new(&asset_path).extension().and_then(|extension| extension.to_str()).unwrap_or("").to_lowercase();
+                let model = match extension.as_str() {
+                    "obj" => self.load_obj(&asset_path),
+                    "ply" => self.load_ply(&asset_path),
+                    "stl" => self.load_stl(&asset_path),
+                    _ => Ok(self.load_gltf(&asset_path))
+                };
+                let model = model.unwrap_or_else(|error| panic!("Failed to get model. ({})", error));
+
+                let resource = Shared::new(model);
+                self.model_manager.insert(resource.clone(), asset_path);
+                resource
+            }
+        }
+    }
 
-                let mut meshes = Vec::new();
-                let mut materials = vec![Material::default(); document.materials().len()];
+    // glTF files and binary .glb files, with external, data URI or buffer view images.
+    fn load_gltf(&mut self, asset_path: &String) -> Model {
+        let (document, buffers, images) = gltf::import(asset_path.clone()).expect("Failed to get model.");
 
-                for node in document.nodes() {
-                    self.process_node(&node, &buffers, &images, &asset_path, &mut meshes, &mut materials);
-                }
+        let mut meshes = Vec::new();
+        let mut materials = vec![Material::default(); document.materials().len()];
 
-                let resource = Shared::new(Model {
-                    meshes: meshes,
-                    materials: materials.into_iter().map(|m| Shared::new(m)).collect()
-                });
+        let mut nodes: Vec<Node> = document.nodes()
+            .map(|node| self.process_node(&document, &node, &buffers, &images, asset_path, &mut meshes, &mut materials))
+            .collect();
 
-                self.model_manager.insert(resource.clone(), asset_path);
-                resource
+        for node_idx in 0..nodes.len() {
+            for child in nodes[node_idx].children.clone() {
+                nodes[child].parent = Some(node_idx);
             }
         }
+
+        let root_nodes = match document.default_scene().or_else(|| document.scenes().next()) {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            None => (0..nodes.len()).filter(|node_idx| nodes[*node_idx].parent.is_none()).collect()
+        };
+
+        let lights = match document.lights() {
+            Some(lights) => lights.map(|light| Self::process_light(&light)).collect(),
+            None => Vec::new()
+        };
+
+        Model {
+            meshes: meshes,
+            materials: materials.into_iter().map(|m| Shared::new(m)).collect(),
+            lights: lights,
+            nodes: nodes,
+            root_nodes: root_nodes,
+            skins: document.skins().map(|skin| Self::process_skin(&skin, &buffers)).collect(),
+            animations: document.animations().map(|animation| Self::process_animation(&animation, &buffers)).collect()
+        }
     }
 
     pub fn get_text(&mut self, asset_path: String) -> Shared<String> {
@@ -343,4 +823,41 @@ impl Resources {
             }
         }
     }
+
+    // Loads Radiance RGBE (.hdr) images as linear RGB floats, rows are kept top to bottom.
+    pub fn get_hdr_image(&mut self, asset_path: String) -> Shared<HdrImage> {
+        match self.hdr_image_manager.get(&asset_path) {
+            Some(resource) => resource,
+            None => {
+                let c_asset_path = CString::new(asset_path.as_bytes()).unwrap();
+
+                unsafe {
+                    stb_image::stb_image::bindgen::stbi_set_flip_vertically_on_load(0);
+
+                    let mut width = 0;
+                    let mut height = 0;
+                    let mut channels = 0;
+                    let data = stb_image::stb_image::bindgen::stbi_loadf(
+                        c_asset_path.as_ptr(),
+                        &mut width,
+                        &mut height,
+                        &mut channels,
+                        3,
+                    );
+                    assert!(!data.is_null(), "Failed to read hdr image.");
+                    let pixels: Vec<f32> = std::slice::from_raw_parts(data, (width * height * 3) as usize).to_vec();
+                    stb_image::stb_image::bindgen::stbi_image_free(data as *mut std::ffi::c_void);
+
+                    let resource = Shared::new(HdrImage {
+                        data: pixels,
+                        dimensions: Int2::new(width, height),
+                        channel_count: 3
+                    });
+
+                    self.hdr_image_manager.insert(resource.clone(), asset_path);
+                    resource
+                }
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::Path;
+
+use crate::gmaths::*;
+use crate::resources::*;
+
+// Facet normals that are missing (zero) come from the winding order.
+fn stl_facet(vertices: &mut Vec<Vertex>, normal: Float3, positions: [Float3; 3]) {
+    let mut normal = match normal.magnitude() != 0.0 {
+        true => normal,
+        false => cross(positions[1] - positions[0], positions[2] - positions[0])
+    };
+    if normal.magnitude() != 0.0 {
+        normal.normalize();
+    }
+
+    for position in positions {
+        vertices.push(Vertex {
+            position: position,
+            normal: normal,
+            ..Vertex::default()
+        });
+    }
+}
+
+fn stl_float3(tokens: &[&str]) -> Result<Float3, String> {
+    let values = tokens.iter()
+        .map(|token| token.parse::<f32>().map_err(|_| format!("Invalid number '{}'", token)))
+        .collect::<Result<Vec<_>, String>>()?;
+    match values.as_slice() {
+        [x, y, z] => Ok(Float3::new(*x, *y, *z)),
+        _ => Err(String::from("Expected 3 components"))
+    }
+}
+
+impl Resources {
+    // ASCII and binary .stl files, every facet gets its own flat shaded vertices.
+    pub(super) fn load_stl(&self, asset_path: &String) -> Result<Model, String> {
+        let data = fs::read(asset_path).map_err(|error| error.to_string())?;
+
+        let mut name = Path::new(asset_path).file_stem().and_then(|stem| stem.to_str()).unwrap_or("Object").to_string();
+        let mut vertices: Vec<Vertex> = Vec::new();
+
+        // Binary files may also start with "solid", their size decides.
+        let binary_count = match data.len() >= 84 {
+            true => Some(u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize),
+            false => None
+        };
+        let is_binary = match binary_count {
+            Some(count) => data.len() == 84 + 50 * count || !data.starts_with(b"solid"),
+            None => false
+        };
+
+        if is_binary {
+            let count = binary_count.unwrap();
+            if data.len() < 84 + 50 * count {
+                return Err(String::from("Unexpected end of file"));
+            }
+
+            let read_float3 = |offset: usize| -> Float3 {
+                let value = |i: usize| f32::from_le_bytes([data[offset + i], data[offset + i + 1], data[offset + i + 2], data[offset + i + 3]]);
+                Float3::new(value(0), value(4), value(8))
+            };
+            for facet in 0..count {
+                let offset = 84 + 50 * facet;
+                stl_facet(&mut vertices, read_float3(offset), [read_float3(offset + 12), read_float3(offset + 24), read_float3(offset + 36)]);
+            }
+        } else {
+            let contents = std::str::from_utf8(&data).map_err(|error| error.to_string())?;
+            let mut normal = Float3::default();
+            let mut positions = Vec::new();
+
+            for line in contents.lines() {
+                let tokens: Vec<&str> = line.split_whitespace().collect();
+                match tokens.as_slice() {
+                    ["solid", solid_name @ ..] if !solid_name.is_empty() => name = solid_name.join(" "),
+                    ["facet", "normal", values @ ..] => {
+                        normal = stl_float3(values)?;
+                        positions.clear();
+                    }
+                    ["vertex", values @ ..] => positions.push(stl_float3(values)?),
+                    ["endfacet"] => {
+                        if positions.len() != 3 {
+                            return Err(format!("Facet has {} vertices instead of 3", positions.len()));
+                        }
+                        stl_facet(&mut vertices, normal, [positions[0], positions[1], positions[2]]);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let indices = (0..vertices.len() as u32).collect();
+        let mesh = self.process_mesh(vertices, indices, PrimitiveType::Triangles, true, 0, &name);
+        Ok(Self::process_objects(vec![(name, vec![mesh])], vec![Material::default()]))
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use crate::gmaths::*;
+use crate::resources::{Vertex, MorphTarget};
+
+// Triangle corners as MikkTSpace sees them, it writes a tangent per corner.
+struct TangentGeometry<'a> {
+    vertices: &'a Vec<Vertex>,
+    indices: &'a Vec<u32>,
+    tangents: Vec<Float4>
+}
+
+impl TangentGeometry<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> &Vertex {
+        &self.vertices[self.indices[face * 3 + vert] as usize]
+    }
+}
+
+impl mikktspace::Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        let position = self.vertex(face, vert).position;
+        [position.x, position.y, position.z]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        let normal = self.vertex(face, vert).normal;
+        [normal.x, normal.y, normal.z]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let tex_coord = self.vertex(face, vert).tex_coord;
+        [tex_coord.x, tex_coord.y]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = Float4::from(&tangent);
+    }
+}
+
+// Generates MikkTSpace tangents for a triangle list, matching the tangent space most normal maps are baked in.
+// Vertices whose triangles disagree on the tangent are split, the copies are appended along with their morph target displacements.
+pub(crate) fn generate_tangents(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, morph_targets: &mut Vec<MorphTarget>) -> bool {
+    let mut geometry = TangentGeometry {
+        vertices: vertices,
+        indices: indices,
+        tangents: vec![Float4::default(); indices.len()]
+    };
+    if !mikktspace::generate_tangents(&mut geometry) {
+        return false;
+    }
+    let tangents = geometry.tangents;
+
+    let mut assigned: Vec<Option<Float4>> = vec![None; vertices.len()];
+    let mut splits: HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+    for (corner, tangent) in tangents.into_iter().enumerate() {
+        let vertex_idx = indices[corner] as usize;
+        match assigned[vertex_idx] {
+            None => {
+                assigned[vertex_idx] = Some(tangent);
+                vertices[vertex_idx].tangent = tangent;
+            },
+            Some(assigned_tangent) if assigned_tangent == tangent => {},
+            Some(_) => {
+                let key = (vertex_idx as u32, [tangent.x.to_bits(), tangent.y.to_bits(), tangent.z.to_bits(), tangent.w.to_bits()]);
+                let split_idx = *splits.entry(key).or_insert_with(|| {
+                    let mut vertex = vertices[vertex_idx].clone();
+                    vertex.tangent = tangent;
+                    vertices.push(vertex);
+
+                    for target in morph_targets.iter_mut() {
+                        for displacements in [&mut target.positions, &mut target.normals, &mut target.tangents] {
+                            if let Some(displacement) = displacements.get(vertex_idx).copied() {
+                                displacements.push(displacement);
+                            }
+                        }
+                    }
+
+                    (vertices.len() - 1) as u32
+                });
+
+                indices[corner] = split_idx;
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // Cosine between generated and authored tangents that still counts as matching.
+    const TANGENT_TOLERANCE: f32 = 0.99;
+    // Authored tangents were not all generated with MikkTSpace, degenerate UVs may differ.
+    const MATCHING_CORNERS: f32 = 0.99;
+
+    // Cube faces as (corner positions, corner UVs, normal, MikkTSpace tangent). Every face maps its UVs affinely, so the
+    // tangent is the normalized position derivative along U and w is the sign of the bitangent against cross(N, T).
+    const CUBE_FACES: [([[f32; 3]; 4], [[f32; 2]; 4], [f32; 3], [f32; 4]); 6] = [
+        // +Z, plain UVs.
+        ([[-1.0, -1.0, 1.0], [1.0, -1.0, 1.0], [1.0, 1.0, 1.0], [-1.0, 1.0, 1.0]], [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]),
+        // -Z, U mirrored.
+        ([[1.0, -1.0, -1.0], [-1.0, -1.0, -1.0], [-1.0, 1.0, -1.0], [1.0, 1.0, -1.0]], [[1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [1.0, 1.0]], [0.0, 0.0, -1.0], [1.0, 0.0, 0.0, -1.0]),
+        // +X, UVs rotated by 90 degrees.
+        ([[1.0, -1.0, 1.0], [1.0, -1.0, -1.0], [1.0, 1.0, -1.0], [1.0, 1.0, 1.0]], [[0.0, 1.0], [0.0, 0.0], [1.0, 0.0], [1.0, 1.0]], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0, 1.0]),
+        // -X, UVs scaled unevenly.
+        ([[-1.0, -1.0, -1.0], [-1.0, -1.0, 1.0], [-1.0, 1.0, 1.0], [-1.0, 1.0, -1.0]], [[0.0, 0.0], [4.0, 0.0], [4.0, 0.5], [0.0, 0.5]], [-1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 1.0]),
+        // +Y, UVs sheared so U runs diagonally across the face.
+        ([[-1.0, 1.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, -1.0], [-1.0, 1.0, -1.0]], [[0.0, 0.0], [1.0, -1.0], [1.0, 0.0], [0.0, 1.0]], [0.0, 1.0, 0.0], [0.70710677, 0.0, -0.70710677, 1.0]),
+        // -Y, V mirrored.
+        ([[-1.0, -1.0, -1.0], [1.0, -1.0, -1.0], [1.0, -1.0, 1.0], [-1.0, -1.0, 1.0]], [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]], [0.0, -1.0, 0.0], [1.0, 0.0, 0.0, -1.0])
+    ];
+
+    // Primitives of a sample model as vertices with their authored tangents and triangle list indices.
+    fn load_primitives(model_path: &str) -> Vec<(Vec<Vertex>, Vec<u32>)> {
+        let path = format!("{}/../game/assets/test_models/{}", env!("CARGO_MANIFEST_DIR"), model_path);
+        // Checkouts without Git LFS only have pointer files.
+        if fs::read(&path).map_or(true, |data| data.starts_with(b"version https://git-lfs")) {
+            panic!("Failed to load '{}'. (Fetch the sample models with Git LFS)", path);
+        }
+
+        let (document, buffers, _) = gltf::import(&path).expect("Failed to import model.");
+        let mut primitives = Vec::new();
+        for mesh in document.meshes() {
+            for primitive in mesh.primitives() {
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let positions = reader.read_positions().expect("Failed to read positions.");
+                let normals = reader.read_normals().expect("Failed to read normals.");
+                let tex_coords = reader.read_tex_coords(0).expect("Failed to read tex coords.").into_f32();
+                let tangents = reader.read_tangents().expect("Failed to read tangents.");
+
+                let vertices = positions.zip(normals).zip(tex_coords).zip(tangents).map(|(((position, normal), tex_coord), tangent)| Vertex {
+                    position: Float3::from(&position),
+                    normal: Float3::from(&normal),
+                    tex_coord: Float2::from(&tex_coord),
+                    tangent: Float4::from(&tangent),
+                    ..Vertex::default()
+                }).collect::<Vec<_>>();
+                let indices = reader.read_indices().map(|indices| indices.into_u32().collect()).unwrap_or_else(|| (0..vertices.len() as u32).collect());
+
+                primitives.push((vertices, indices));
+            }
+        }
+
+        primitives
+    }
+
+    // Strips the tangents, regenerates them and compares every triangle corner with the authored tangent.
+    fn compare_with_authored(model_path: &str) {
+        for (authored, indices) in load_primitives(model_path) {
+            let mut vertices: Vec<Vertex> = authored.iter().map(|vertex| Vertex { tangent: Float4::default(), ..vertex.clone() }).collect();
+            let mut generated_indices = indices.clone();
+            assert!(generate_tangents(&mut vertices, &mut generated_indices, &mut Vec::new()), "Failed to generate tangents.");
+            assert_eq!(generated_indices.len(), indices.len());
+
+            let mut matching = 0;
+            for (corner, index) in indices.iter().enumerate() {
+                let expected = &authored[*index as usize];
+                let generated = &vertices[generated_indices[corner] as usize];
+                // Split vertices are copies of the original.
+                assert_eq!(generated.position, expected.position);
+                assert_eq!(generated.tex_coord, expected.tex_coord);
+
+                let cos = dot(Float3::new(generated.tangent.x, generated.tangent.y, generated.tangent.z), Float3::new(expected.tangent.x, expected.tangent.y, expected.tangent.z));
+                if cos >= TANGENT_TOLERANCE && generated.tangent.w.signum() == expected.tangent.w.signum() {
+                    matching += 1;
+                }
+            }
+
+            let ratio = matching as f32 / indices.len() as f32;
+            assert!(ratio >= MATCHING_CORNERS, "Only {:.1}% of the corners of '{}' match the authored tangents.", ratio * 100.0, model_path);
+        }
+    }
+
+    #[test]
+    fn matches_cube_tangents() {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (positions, tex_coords, normal, _) in CUBE_FACES.iter() {
+            let first = vertices.len() as u32;
+            for corner in 0..4 {
+                vertices.push(Vertex {
+                    position: Float3::from(&positions[corner]),
+                    normal: Float3::from(normal),
+                    tex_coord: Float2::from(&tex_coords[corner]),
+                    ..Vertex::default()
+                });
+            }
+            indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+
+        assert!(generate_tangents(&mut vertices, &mut indices, &mut Vec::new()));
+        // The faces share no vertices, so nothing is split.
+        assert_eq!(vertices.len(), CUBE_FACES.len() * 4);
+
+        for (vertex_idx, vertex) in vertices.iter().enumerate() {
+            let expected = Float4::from(&CUBE_FACES[vertex_idx / 4].3);
+            let cos = dot(Float3::new(vertex.tangent.x, vertex.tangent.y, vertex.tangent.z), Float3::new(expected.x, expected.y, expected.z));
+            assert!(cos > 0.9999, "Tangent {:?} of face {} does not match {:?}.", vertex.tangent, vertex_idx / 4, expected);
+            assert_eq!(vertex.tangent.w, expected.w, "Handedness of face {} does not match.", vertex_idx / 4);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs git lfs"]
+    fn matches_avocado_tangents() {
+        compare_with_authored("Avocado/glTF/Avocado.gltf");
+    }
+
+    #[test]
+    #[ignore = "needs git lfs"]
+    fn matches_lantern_tangents() {
+        compare_with_authored("Lantern/glTF/Lantern.gltf");
+    }
+
+    // A quad whose second triangle has mirrored UVs, so the shared corners need both handednesses.
+    #[test]
+    fn split_vertices_keep_morph_targets_aligned() {
+        let corners = [(Float3::new(0.0, 0.0, 0.0), Float2::new(0.0, 0.0)), (Float3::new(1.0, 0.0, 0.0), Float2::new(1.0, 0.0)), (Float3::new(1.0, 1.0, 0.0), Float2::new(1.0, 1.0)), (Float3::new(0.0, 1.0, 0.0), Float2::new(2.0, 0.0))];
+        let mut vertices: Vec<Vertex> = corners.iter().map(|(position, tex_coord)| Vertex {
+            position: *position,
+            normal: Float3::new(0.0, 0.0, 1.0),
+            tex_coord: *tex_coord,
+            ..Vertex::default()
+        }).collect();
+        let original_indices = vec![0, 1, 2, 0, 2, 3];
+        let mut indices = original_indices.clone();
+
+        let displacements: Vec<Float3> = (0..4).map(|i| Float3::new(0.0, 0.0, i as f32 + 1.0)).collect();
+        let mut morph_targets = vec![MorphTarget {
+            positions: displacements.clone(),
+            normals: Vec::new(),
+            tangents: displacements.iter().map(|displacement| *displacement * 2.0).collect()
+        }];
+
+        assert!(generate_tangents(&mut vertices, &mut indices, &mut morph_targets));
+        assert!(vertices.len() > 4, "Expected the mirrored corners to be split.");
+
+        let target = &morph_targets[0];
+        assert_eq!(target.positions.len(), vertices.len());
+        assert_eq!(target.tangents.len(), vertices.len());
+        // Attributes the target does not move stay empty.
+        assert!(target.normals.is_empty());
+
+        for (corner, index) in indices.iter().enumerate() {
+            let original = original_indices[corner] as usize;
+            assert_eq!(vertices[*index as usize].position, corners[original].0);
+            assert_eq!(target.positions[*index as usize], displacements[original]);
+            assert_eq!(target.tangents[*index as usize], displacements[original] * 2.0);
+        }
+
+        // The triangles disagree on handedness.
+        assert_ne!(vertices[indices[0] as usize].tangent.w.signum(), vertices[indices[3] as usize].tangent.w.signum());
+    }
+}